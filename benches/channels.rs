@@ -0,0 +1,60 @@
+// Compares the lock-free bounded channel with the Mutex + Condvar channel as the number of threads goes up.
+// Run with `cargo bench --bench channels`
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_atomic_locks::boundedchannel::sync_channel;
+use rust_atomic_locks::mutexchannel::MutexChannel;
+
+const MESSAGES: usize = 200_000;
+
+// Every producer sends its share of MESSAGES, and every consumer receives its share
+fn bounded(threads: usize) -> Duration {
+    let (sender, receiver) = sync_channel(1024);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            s.spawn(move || {
+                for i in 0..MESSAGES / threads {
+                    sender.send(i).unwrap();
+                }
+            });
+            let receiver = receiver.clone();
+            s.spawn(move || {
+                for _ in 0..MESSAGES / threads {
+                    receiver.receive().unwrap();
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn mutex(threads: usize) -> Duration {
+    let channel = MutexChannel::new();
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                for i in 0..MESSAGES / threads {
+                    channel.send(i);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..MESSAGES / threads {
+                    channel.receive();
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    println!("{:>8} {:>16} {:>16}", "threads", "bounded (msg/s)", "mutex (msg/s)");
+    for threads in [1, 2, 4, 8] {
+        let rate = |d: Duration| (MESSAGES as f64 / d.as_secs_f64()) as u64;
+        println!("{:>8} {:>16} {:>16}", threads * 2, rate(bounded(threads)), rate(mutex(threads)));
+    }
+}
@@ -0,0 +1,107 @@
+#![no_main]
+// Drives sync_channel through an arbitrary sequence of operations from up to four senders and four receivers,
+// and checks every result against a plain VecDeque model.
+// The interleaving is decided by the input rather than by real threads: everything runs on one thread, one
+// operation at a time. A blocking operation only runs when the model says it would return straight away -
+// otherwise the actor counts as blocked and the operation is skipped, as a scheduler would run someone else
+use std::collections::VecDeque;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rust_atomic_locks::boundedchannel::{sync_channel, RecvError, SendError, TryRecvError, TrySendError};
+
+const ACTORS: usize = 4;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Send { actor: u8, value: u8 },
+    TrySend { actor: u8, value: u8 },
+    Receive { actor: u8 },
+    TryReceive { actor: u8 },
+    CloneSender { from: u8, to: u8 },
+    CloneReceiver { from: u8, to: u8 },
+    // closing a side of the channel is dropping its handle
+    CloseSender { actor: u8 },
+    CloseReceiver { actor: u8 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    capacity: u8,
+    ops: Vec<Op>,
+}
+
+fuzz_target!(|input: Input| {
+    let capacity = input.capacity as usize % 8 + 1;
+    let (sender, receiver) = sync_channel(capacity);
+    let mut senders: Vec<_> = (0..ACTORS).map(|_| None).collect();
+    let mut receivers: Vec<_> = (0..ACTORS).map(|_| None).collect();
+    senders[0] = Some(sender);
+    receivers[0] = Some(receiver);
+    let mut model = VecDeque::new();
+
+    for op in input.ops {
+        let live_senders = senders.iter().flatten().count();
+        let live_receivers = receivers.iter().flatten().count();
+        match op {
+            Op::Send { actor, value } => {
+                let Some(sender) = &senders[actor as usize % ACTORS] else { continue };
+                if live_receivers == 0 {
+                    assert_eq!(sender.send(value), Err(SendError(value)));
+                } else if model.len() < capacity {
+                    assert_eq!(sender.send(value), Ok(()));
+                    model.push_back(value);
+                }
+                // otherwise it would block until a receiver makes room
+            }
+            Op::TrySend { actor, value } => {
+                let Some(sender) = &senders[actor as usize % ACTORS] else { continue };
+                let expected = if live_receivers == 0 {
+                    Err(TrySendError::Disconnected(value))
+                } else if model.len() == capacity {
+                    Err(TrySendError::Full(value))
+                } else {
+                    model.push_back(value);
+                    Ok(())
+                };
+                assert_eq!(sender.try_send(value), expected);
+            }
+            Op::Receive { actor } => {
+                let Some(receiver) = &receivers[actor as usize % ACTORS] else { continue };
+                if let Some(value) = model.pop_front() {
+                    assert_eq!(receiver.receive(), Ok(value));
+                } else if live_senders == 0 {
+                    assert_eq!(receiver.receive(), Err(RecvError));
+                }
+                // otherwise it would block until something is sent
+            }
+            Op::TryReceive { actor } => {
+                let Some(receiver) = &receivers[actor as usize % ACTORS] else { continue };
+                // a message that's already in the channel is still handed out after the senders have gone
+                let expected = match model.pop_front() {
+                    Some(value) => Ok(value),
+                    None if live_senders == 0 => Err(TryRecvError::Disconnected),
+                    None => Err(TryRecvError::Empty),
+                };
+                assert_eq!(receiver.try_receive(), expected);
+            }
+            Op::CloneSender { from, to } => {
+                if let Some(sender) = &senders[from as usize % ACTORS] {
+                    senders[to as usize % ACTORS] = Some(sender.clone());
+                }
+            }
+            Op::CloneReceiver { from, to } => {
+                if let Some(receiver) = &receivers[from as usize % ACTORS] {
+                    receivers[to as usize % ACTORS] = Some(receiver.clone());
+                }
+            }
+            Op::CloseSender { actor } => senders[actor as usize % ACTORS] = None,
+            Op::CloseReceiver { actor } => receivers[actor as usize % ACTORS] = None,
+        }
+
+        // every handle sees the same length, and it matches the model
+        for len in senders.iter().flatten().map(|s| s.len()).chain(receivers.iter().flatten().map(|r| r.len())) {
+            assert_eq!(len, model.len());
+        }
+    }
+});
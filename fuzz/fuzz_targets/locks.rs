@@ -0,0 +1,138 @@
+#![no_main]
+// Drives an RwSpinLock and a RawSpinLock through arbitrary lock/try_lock/unlock sequences from four actors,
+// checking each result against a model of who holds what. As in the channel targets everything runs on one
+// thread, and a blocking lock only runs when the model says it's free - otherwise the actor is blocked and
+// the operation is skipped. The RwSpinLock's value is bumped by every writer, so readers can check they see
+// the latest write
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rust_atomic_locks::rawspinlock::{RawGuard, RawSpinLock};
+use rust_atomic_locks::rwspinlock::{ReadGuard, RwSpinLock, UpgradableGuard, WriteGuard};
+
+const ACTORS: usize = 4;
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Op {
+    Read(u8),
+    TryRead(u8),
+    Write(u8),
+    TryWrite(u8),
+    UpgradableRead(u8),
+    TryUpgradableRead(u8),
+    Upgrade(u8),
+    Downgrade(u8),
+    Unlock(u8),
+    RawLock(u8),
+    RawTryLock(u8),
+    RawUnlock(u8),
+}
+
+enum Held<'a> {
+    Read(ReadGuard<'a, u64>),
+    Write(WriteGuard<'a, u64>),
+    Upgradable(UpgradableGuard<'a, u64>),
+}
+
+// What the RwSpinLock should look like, worked out from the guards the actors hold
+fn counts(held: &[Option<Held>]) -> (usize, bool, bool) {
+    let readers = held.iter().filter(|h| matches!(h, Some(Held::Read(_)))).count();
+    let writer = held.iter().any(|h| matches!(h, Some(Held::Write(_))));
+    let upgradable = held.iter().any(|h| matches!(h, Some(Held::Upgradable(_))));
+    (readers, writer, upgradable)
+}
+
+// try_read and try_upgradable_read use a weak compare exchange, which is allowed to fail now and then
+// even when the lock is free, so a failure only counts after a few tries
+fn retry<T>(mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    (0..100).find_map(|_| f())
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let lock = RwSpinLock::new(0u64);
+    let raw = RawSpinLock::new();
+    let mut held: Vec<Option<Held>> = (0..ACTORS).map(|_| None).collect();
+    let mut raw_held: Vec<Option<RawGuard>> = (0..ACTORS).map(|_| None).collect();
+    let mut writes = 0u64;
+
+    for op in ops {
+        let (readers, writer, upgradable) = counts(&held);
+        let can_read = !writer;
+        let can_write = !writer && !upgradable && readers == 0;
+        let can_upgradable = !writer && !upgradable;
+        // the actors all share the one thread, and a blocking read on a lock the thread is already reading
+        // panics in debug builds (it could deadlock against a waiting writer), so those only happen on their own
+        let reading = readers > 0 || upgradable;
+        match op {
+            Op::Read(a) | Op::TryRead(a) | Op::Write(a) | Op::TryWrite(a) | Op::UpgradableRead(a)
+            | Op::TryUpgradableRead(a)
+                if held[a as usize % ACTORS].is_some() =>
+            {
+                // an actor only holds one guard at a time - taking a second one is how a thread deadlocks itself
+            }
+            Op::Read(a) if can_read && !reading => held[a as usize % ACTORS] = Some(Held::Read(lock.read())),
+            Op::TryRead(a) => {
+                let guard = retry(|| lock.try_read());
+                assert_eq!(guard.is_some(), can_read);
+                held[a as usize % ACTORS] = guard.map(Held::Read);
+            }
+            Op::Write(a) if can_write => {
+                let mut guard = lock.write();
+                writes += 1;
+                *guard = writes;
+                held[a as usize % ACTORS] = Some(Held::Write(guard));
+            }
+            Op::TryWrite(a) => {
+                let guard = lock.try_write();
+                assert_eq!(guard.is_some(), can_write);
+                held[a as usize % ACTORS] = guard.map(|mut guard| {
+                    writes += 1;
+                    *guard = writes;
+                    Held::Write(guard)
+                });
+            }
+            Op::UpgradableRead(a) if can_upgradable && !reading => {
+                held[a as usize % ACTORS] = Some(Held::Upgradable(lock.upgradable_read()))
+            }
+            Op::TryUpgradableRead(a) => {
+                let guard = retry(|| lock.try_upgradable_read());
+                assert_eq!(guard.is_some(), can_upgradable);
+                held[a as usize % ACTORS] = guard.map(Held::Upgradable);
+            }
+            // upgrading waits for the plain readers to leave
+            Op::Upgrade(a) if readers == 0 && matches!(held[a as usize % ACTORS], Some(Held::Upgradable(_))) => {
+                let Some(Held::Upgradable(guard)) = held[a as usize % ACTORS].take() else { unreachable!() };
+                let mut guard = guard.upgrade();
+                writes += 1;
+                *guard = writes;
+                held[a as usize % ACTORS] = Some(Held::Write(guard));
+            }
+            Op::Downgrade(a) if matches!(held[a as usize % ACTORS], Some(Held::Upgradable(_))) => {
+                let Some(Held::Upgradable(guard)) = held[a as usize % ACTORS].take() else { unreachable!() };
+                held[a as usize % ACTORS] = Some(Held::Read(guard.downgrade()));
+            }
+            Op::Unlock(a) => held[a as usize % ACTORS] = None,
+            Op::RawLock(a) if raw_held.iter().all(Option::is_none) => raw_held[a as usize % ACTORS] = Some(raw.lock()),
+            Op::RawTryLock(a) if raw_held[a as usize % ACTORS].is_none() => {
+                let guard = raw.try_lock();
+                assert_eq!(guard.is_some(), raw_held.iter().all(Option::is_none));
+                raw_held[a as usize % ACTORS] = guard;
+            }
+            Op::RawUnlock(a) => raw_held[a as usize % ACTORS] = None,
+            // a blocking operation the model says would wait
+            _ => {}
+        }
+
+        // every guard reads the last value written
+        for h in held.iter().flatten() {
+            let value = match h {
+                Held::Read(g) => **g,
+                Held::Write(g) => **g,
+                Held::Upgradable(g) => **g,
+            };
+            assert_eq!(value, writes);
+        }
+        let raw_locked = raw_held.iter().any(Option::is_some);
+        assert_eq!(raw.is_locked(), raw_locked);
+        assert_eq!(raw.owner(), raw_locked.then(std::process::id));
+    }
+});
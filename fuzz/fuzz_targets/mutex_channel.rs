@@ -0,0 +1,68 @@
+#![no_main]
+// Drives a MutexChannel through an arbitrary sequence of operations and checks every result against a plain
+// VecDeque model. Like the bounded_channel target, everything runs on one thread and a blocking operation
+// only runs when the model says it would return straight away, otherwise it's skipped
+use std::collections::VecDeque;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rust_atomic_locks::mutexchannel::MutexChannel;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Send(u8),
+    SendAll(Vec<u8>),
+    Receive,
+    // takes the first message with value % modulus == remainder
+    ReceiveIf { modulus: u8, remainder: u8 },
+    ReceiveUpTo(u8),
+    Drain,
+    Peek,
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let channel = MutexChannel::new();
+    let mut model = VecDeque::new();
+
+    for op in ops {
+        match op {
+            Op::Send(value) => {
+                channel.send(value);
+                model.push_back(value);
+            }
+            Op::SendAll(values) => {
+                channel.send_all(values.iter().copied());
+                model.extend(values);
+            }
+            Op::Receive => {
+                if let Some(value) = model.pop_front() {
+                    assert_eq!(channel.receive(), value);
+                }
+            }
+            Op::ReceiveIf { modulus, remainder } => {
+                let modulus = modulus.max(1);
+                let matches = |v: &u8| v % modulus == remainder % modulus;
+                if let Some(i) = model.iter().position(matches) {
+                    let expected = model.remove(i).unwrap();
+                    assert_eq!(channel.receive_if(matches), expected);
+                }
+            }
+            Op::ReceiveUpTo(n) => {
+                if !model.is_empty() {
+                    let n = (n as usize).min(model.len());
+                    let expected: Vec<_> = model.drain(..n).collect();
+                    assert_eq!(channel.receive_up_to(n), expected);
+                }
+            }
+            Op::Drain => {
+                let expected: Vec<_> = model.drain(..).collect();
+                assert_eq!(channel.drain(), expected);
+            }
+            Op::Peek => {
+                assert_eq!(channel.peek_with(|v| *v), model.front().copied());
+                assert_eq!(channel.peek().as_deref(), model.front());
+            }
+        }
+        assert_eq!(channel.len(), model.len());
+    }
+});
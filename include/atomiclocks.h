@@ -0,0 +1,58 @@
+#ifndef ATOMICLOCKS_H
+#define ATOMICLOCKS_H
+
+/* The C interface to rust-atomic-locks' SpinLock and bounded channel, for the crate built with the ffi feature.
+   Kept in step with src/ffi.rs - cbindgen --config cbindgen.toml --output include/atomiclocks.h regenerates it */
+
+#include <stdbool.h>
+#include <stddef.h>
+
+// A SpinLock with nothing in it - the C side keeps whatever it protects itself
+typedef struct AtomicLocksSpinLock AtomicLocksSpinLock;
+
+// A bounded channel of void pointers, both ends in one. What the pointers point at is up to the C side: the
+// channel only moves them, and never frees them
+typedef struct AtomicLocksChannel AtomicLocksChannel;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+AtomicLocksSpinLock *atomiclocks_spinlock_new(void);
+
+// lock has to have come from atomiclocks_spinlock_new and not been freed
+void atomiclocks_spinlock_lock(const AtomicLocksSpinLock *lock);
+
+// Returns whether it locked it
+bool atomiclocks_spinlock_try_lock(const AtomicLocksSpinLock *lock);
+
+// The lock has to be locked by the caller
+void atomiclocks_spinlock_unlock(const AtomicLocksSpinLock *lock);
+
+// Nothing can use the lock afterwards. Null is fine
+void atomiclocks_spinlock_free(AtomicLocksSpinLock *lock);
+
+// A channel that holds up to capacity messages before send blocks. NULL if capacity is 0
+AtomicLocksChannel *atomiclocks_channel_new(size_t capacity);
+
+// Blocks while the channel's full
+void atomiclocks_channel_send(const AtomicLocksChannel *channel, void *message);
+
+// Returns false, and leaves the message with the caller, if the channel's full
+bool atomiclocks_channel_try_send(const AtomicLocksChannel *channel, void *message);
+
+// Blocks while the channel's empty
+void *atomiclocks_channel_recv(const AtomicLocksChannel *channel);
+
+// Returns false if the channel's empty, and otherwise writes the message to *message
+bool atomiclocks_channel_try_recv(const AtomicLocksChannel *channel, void **message);
+
+// Messages left in it are dropped, which for a pointer means forgotten - free what they point at first.
+// Nothing can use the channel afterwards. Null is fine
+void atomiclocks_channel_free(AtomicLocksChannel *channel);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ATOMICLOCKS_H */
@@ -0,0 +1,131 @@
+use std::sync::atomic::AtomicBool;
+use std::thread::{self, JoinHandle};
+
+use crate::arc::Arc;
+use crate::boundedchannel::{sync_channel, SendError, TrySendError, Sender};
+use crate::mutex::Mutex;
+use crate::ordering::Relaxed;
+
+// How many messages an actor's mailbox holds before send blocks, for spawn_actor
+pub const DEFAULT_MAILBOX: usize = 1024;
+
+// An actor: some state owned by a dedicated thread, which handles the messages sent to its mailbox one at a
+// time. Nothing else can touch the state, so there are no locks to think about - it's the common "one thread
+// owns the resource, everyone else sends it requests" pattern, packaged up.
+//
+//     let counter = spawn_actor(0, |total: &mut u64, n: u64| *total += n);
+//     counter.send(5).unwrap();
+//     counter.join().unwrap();
+//
+// The actor runs until it's stopped or every Addr for it is dropped, handling whatever's already in its
+// mailbox first either way. A reply can go back through a oneshot or rendezvous sent in the message
+pub fn spawn_actor<S, M>(state: S, handler: impl FnMut(&mut S, M) + Send + 'static) -> Addr<M>
+where
+    S: Send + 'static,
+    M: Send + 'static,
+{
+    spawn_actor_with_capacity(DEFAULT_MAILBOX, state, handler)
+}
+
+// Same as spawn_actor, with a mailbox of the given size. Senders block while it's full, which slows them down
+// to the actor's pace rather than letting the mailbox grow without limit
+pub fn spawn_actor_with_capacity<S, M>(
+    capacity: usize,
+    mut state: S,
+    mut handler: impl FnMut(&mut S, M) + Send + 'static,
+) -> Addr<M>
+where
+    S: Send + 'static,
+    M: Send + 'static,
+{
+    let (sender, receiver) = sync_channel(capacity);
+    let thread = thread::spawn(move || {
+        // ends on a Stop, or once every Addr is gone and the mailbox is empty
+        while let Ok(Envelope::Message(message)) = receiver.receive() {
+            handler(&mut state, message);
+        }
+    });
+    Addr {
+        sender,
+        shared: Arc::new(Shared { stopped: AtomicBool::new(false), thread: Mutex::new(Some(thread)) }),
+    }
+}
+
+enum Envelope<M> {
+    Message(M),
+    Stop,
+}
+
+struct Shared {
+    stopped: AtomicBool,
+    // taken by whichever Addr joins first
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+// The address of an actor, for sending it messages. Clone it for every thread that needs to talk to the actor
+pub struct Addr<M> {
+    sender: Sender<Envelope<M>>,
+    shared: Arc<Shared>,
+}
+
+impl<M> Addr<M> {
+    // Queues a message for the actor, blocking while its mailbox is full. The message comes back if the actor
+    // has stopped (or panicked)
+    pub fn send(&self, message: M) -> Result<(), SendError<M>> {
+        if self.shared.stopped.load(Relaxed) {
+            return Err(SendError(message));
+        }
+        self.sender.send(Envelope::Message(message)).map_err(|SendError(envelope)| SendError(unwrap(envelope)))
+    }
+
+    // Same as send, but fails rather than waiting if the mailbox is full
+    pub fn try_send(&self, message: M) -> Result<(), TrySendError<M>> {
+        if self.shared.stopped.load(Relaxed) {
+            return Err(TrySendError::Disconnected(message));
+        }
+        self.sender.try_send(Envelope::Message(message)).map_err(|e| match e {
+            TrySendError::Full(envelope) => TrySendError::Full(unwrap(envelope)),
+            TrySendError::Disconnected(envelope) => TrySendError::Disconnected(unwrap(envelope)),
+        })
+    }
+
+    // Tells the actor to stop once it's handled the messages already in its mailbox. Sends from then on fail,
+    // apart from ones racing with the stop - those can get in ahead of it, or be dropped unhandled behind it
+    pub fn stop(&self) {
+        if !self.shared.stopped.swap(true, Relaxed) {
+            // an Err means the actor is already gone, which is just as good
+            let _ = self.sender.send(Envelope::Stop);
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.shared.stopped.load(Relaxed)
+    }
+
+    // Stops the actor and waits for its thread to finish. Err if the handler panicked, with the panic - only
+    // the first join finds out about that, ones after it (from clones of this Addr) just wait for the thread
+    pub fn join(self) -> thread::Result<()> {
+        self.stop();
+        // the lock is held while joining, so a second join waits for the first to finish rather than
+        // returning while the actor's still running
+        let mut thread = self.shared.thread.lock();
+        match thread.take() {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<M> Clone for Addr<M> {
+    fn clone(&self) -> Self {
+        Self { sender: self.sender.clone(), shared: self.shared.clone() }
+    }
+}
+
+// Only ever called on envelopes that came from send, which are all messages
+fn unwrap<M>(envelope: Envelope<M>) -> M {
+    match envelope {
+        Envelope::Message(message) => message,
+        Envelope::Stop => unreachable!("only messages come back from a failed send"),
+    }
+}
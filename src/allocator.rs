@@ -0,0 +1,49 @@
+// The allocator Arc is generic over. With the nightly `allocator_api` feature this is std's Allocator,
+// so any allocator written for std works. On stable std's trait doesn't exist yet, so there's a stand-in here
+// with the same method signatures (just the two Arc needs), which only the global allocator implements
+#[cfg(feature = "allocator_api")]
+pub use std::alloc::{AllocError, Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+use std::alloc::Layout;
+#[cfg(not(feature = "allocator_api"))]
+use std::ptr::NonNull;
+
+#[cfg(not(feature = "allocator_api"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+/// # Safety
+/// allocate must return memory that fits the layout, and stays valid until it's passed to deallocate
+#[cfg(not(feature = "allocator_api"))]
+pub unsafe trait Allocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+    /// # Safety
+    /// ptr must have come from allocate on this allocator, with the same layout
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+// The global allocator, which is what Box and Vec use
+#[cfg(not(feature = "allocator_api"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+#[cfg(not(feature = "allocator_api"))]
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        // the global allocator can't be asked for zero bytes, but any well aligned pointer will do for those
+        let ptr = if layout.size() == 0 {
+            NonNull::new(layout.align() as *mut u8)
+        } else {
+            NonNull::new(unsafe { std::alloc::alloc(layout) })
+        };
+        ptr.map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size())).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            std::alloc::dealloc(ptr.as_ptr(), layout);
+        }
+    }
+}
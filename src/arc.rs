@@ -0,0 +1,408 @@
+use std::ptr::{self, NonNull};
+use std::mem::{self, ManuallyDrop};
+use std::pin::Pin;
+use std::alloc::{Layout, handle_alloc_error};
+use std::cell::UnsafeCell;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, fence};
+
+use crate::allocator::{Allocator, Global};
+use crate::atomicupdate::AtomicUpdate;
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::relax::cpu_relax;
+use crate::sched::pause;
+
+// Once the last Arc is dropped the data is dropped straight away, but the allocation (and the counters in it)
+// lives on until the last Weak is gone too. In that state upgrade always returns None and
+// Weak::strong_count is 0, and the data is never touched again
+struct ArcData<T, A: Allocator> {
+    // Number of Arcs
+    data_ref_count: AtomicUsize,
+    // Number of Weaks, plus one if there are any Arcs
+    alloc_ref_count: AtomicUsize,
+    // The data. Dropped (but not deallocated) if there's only weak pointers left
+    data: UnsafeCell<ManuallyDrop<T>>,
+    // The allocator the ArcData came from, which it's handed back to by the last Weak
+    alloc: A,
+}
+
+pub struct Arc<T, A: Allocator = Global> {
+    ptr: NonNull<ArcData<T, A>>
+}
+
+// The last Arc or Weak to be dropped hands the memory back to the allocator, on whatever thread that is
+unsafe impl<T: Sync + Send, A: Allocator + Send> Send for Arc<T, A> {}
+unsafe impl<T: Sync + Send, A: Allocator + Send> Sync for Arc<T, A> {}
+
+pub struct Weak<T, A: Allocator = Global> {
+    ptr: NonNull<ArcData<T, A>>
+}
+
+unsafe impl<T: Sync + Send, A: Allocator + Send> Send for Weak<T, A> {}
+unsafe impl<T: Sync + Send, A: Allocator + Send> Sync for Weak<T, A> {}
+
+impl<T> Arc<T> {
+    pub fn new(data: T) -> Arc<T> {
+        Arc::new_in(data, Global)
+    }
+
+    pub fn pin(data: T) -> Pin<Arc<T>> {
+        Arc::pin_in(data, Global)
+    }
+
+    pub fn new_cyclic(f: impl FnOnce(&Weak<T>) -> T) -> Arc<T> {
+        Arc::new_cyclic_in(f, Global)
+    }
+}
+
+impl<T, A: Allocator> Arc<T, A> {
+    // to be able to create a new Arc, we have to create a new allocation with an ArcData<T> with a ref count of 1.
+    // The allocator hands back raw memory, the ArcData is written into it, and the pointer is kept as a NonNull
+    // as it can never be null. The allocator itself is moved into the ArcData so the last Weak can free the memory
+    pub fn new_in(data: T, alloc: A) -> Arc<T, A> {
+        let layout = Layout::new::<ArcData<T, A>>();
+        let ptr = match alloc.allocate(layout) {
+            Ok(ptr) => ptr.cast::<ArcData<T, A>>(),
+            Err(_) => handle_alloc_error(layout),
+        };
+        // Safety: the memory is freshly allocated for exactly this layout
+        unsafe {
+            ptr.as_ptr().write(ArcData {
+                alloc_ref_count: AtomicUsize::new(1),
+                data_ref_count: AtomicUsize::new(1),
+                data: UnsafeCell::new(ManuallyDrop::new(data)),
+                alloc,
+            });
+        }
+        Arc { ptr }
+    }
+
+    // The data never moves once it's in the ArcData, and there's no way to get it back out by value
+    // (no DerefMut, and get_mut is only for Unpin data through the Pin), so pinning it is always fine
+    pub fn pin_in(data: T, alloc: A) -> Pin<Arc<T, A>> {
+        unsafe { Pin::new_unchecked(Arc::new_in(data, alloc)) }
+    }
+
+    // Builds data that holds a Weak pointing back at itself (like a tree node pointing at its owner).
+    // f gets the Weak before the data exists, so upgrading it inside f returns None
+    pub fn new_cyclic_in(f: impl FnOnce(&Weak<T, A>) -> T, alloc: A) -> Arc<T, A> {
+        let layout = Layout::new::<ArcData<T, A>>();
+        let ptr = match alloc.allocate(layout) {
+            Ok(ptr) => ptr.cast::<ArcData<T, A>>(),
+            Err(_) => handle_alloc_error(layout),
+        };
+        // Everything but the data is filled in. data_ref_count starts at 0 so the Weak can't be upgraded,
+        // and alloc_ref_count is 1 for the Weak handed to f
+        unsafe {
+            let p = ptr.as_ptr();
+            ptr::addr_of_mut!((*p).data_ref_count).write(AtomicUsize::new(0));
+            ptr::addr_of_mut!((*p).alloc_ref_count).write(AtomicUsize::new(1));
+            ptr::addr_of_mut!((*p).alloc).write(alloc);
+        }
+        let weak = Weak { ptr };
+        // If f panics, dropping the Weak frees the allocation. The data was never written so nothing else is dropped
+        let data = f(&weak);
+        unsafe {
+            ptr::addr_of_mut!((*ptr.as_ptr()).data).write(UnsafeCell::new(ManuallyDrop::new(data)));
+        }
+        // Release matches the Acquire in upgrade, so whoever upgrades a copy of the Weak sees the data written
+        weak.data().data_ref_count.store(1, Release);
+        // The Weak's count becomes the one all of the Arcs share, the same as in new_in
+        mem::forget(weak);
+        Arc { ptr }
+    }
+
+    // As long as Arc exists, the pointer will always ref a valid ArcData<T>
+    // However, the compiler can't know this so we have to wrap this in an unsafe 
+    fn data(&self) -> &ArcData<T, A> {
+        unsafe { self.ptr.as_ref()}
+    }
+
+    pub fn get_mut(arc: &mut Self) -> Option<&mut T> {
+        // Acquire matches Weak::drop's Release decrement, to make sure any
+        // upgraded pointers are visible in the next data_ref_count.load.
+        if arc.data().alloc_ref_count.compare_exchange(
+            1, usize::MAX, Acquire, Relaxed
+        ).is_err() {
+            return None;
+        }
+        pause!("Arc::get_mut locked");
+        let is_unique = arc.data().data_ref_count.load(Relaxed) == 1;
+        // Release matches Acquire increment in `downgrade`, to make sure any
+        // changes to the data_ref_count that come after `downgrade` don't
+        // change the is_unique result above.
+        arc.data().alloc_ref_count.store(1, Release);
+        if !is_unique {
+            return None;
+        }
+        // Acquire to match Arc::drop's Release decrement, to make sure nothing
+        // else is accessing the data.
+        fence(Acquire);
+        unsafe { Some(&mut *arc.data().data.get()) }
+    }
+
+    pub fn downgrade(arc: &Self) -> Weak<T, A> {
+        let mut n = arc.data().alloc_ref_count.load(Relaxed);
+        loop {
+            if n == usize::MAX {
+                cpu_relax();
+                n = arc.data().alloc_ref_count.load(Relaxed);
+                continue;
+            }
+            assert!(n < usize::MAX - 1);
+            pause!("Arc::downgrade loaded");
+            // Acquire synchronises with get_mut's release-store.
+            if let Err(e) =
+                arc.data()
+                    .alloc_ref_count
+                    .compare_exchange_weak(n, n + 1, Acquire, Relaxed)
+            {
+                n = e;
+                continue;
+            }
+            return Weak { ptr: arc.ptr };
+        }
+    }
+
+    // A pointer to the data, for as long as there's an Arc to it. It's worked out from the pointer to the whole
+    // allocation without going through a reference, so it keeps that pointer's provenance - which is what lets
+    // from_raw get back from it to the counters in front of the data
+    pub fn as_ptr(arc: &Self) -> *const T {
+        // Safety: the ArcData is alive while arc is, and addr_of! doesn't make a reference to it. ManuallyDrop and
+        // UnsafeCell are both repr(transparent), so the data's address is T's
+        unsafe { UnsafeCell::raw_get(ptr::addr_of!((*arc.ptr.as_ptr()).data)).cast() }
+    }
+
+    // Gives up the Arc for a raw pointer to the data, which still counts as an Arc until it's turned back into
+    // one with from_raw (or decrement_strong_count). For handing to C as a callback's user data, say
+    pub fn into_raw(arc: Self) -> *const T {
+        let ptr = Arc::as_ptr(&arc);
+        mem::forget(arc);
+        ptr
+    }
+
+    // Turns a pointer from into_raw back into the Arc it was
+    /// # Safety
+    /// ptr has to have come from into_raw on an Arc<T, A> with the same T and A, and each pointer from into_raw
+    /// can only be turned back into an Arc once (or counted off once by decrement_strong_count)
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        // The allocator's kept in the ArcData, so unlike std there's no from_raw_in needed to hand it back.
+        // byte_sub keeps ptr's provenance, which covers the whole allocation
+        let data = ptr.byte_sub(mem::offset_of!(ArcData<T, A>, data)).cast::<ArcData<T, A>>();
+        Arc { ptr: NonNull::new_unchecked(data.cast_mut()) }
+    }
+
+    // Adds one to the count behind a pointer from into_raw, as if the Arc it came from had been cloned - so it
+    // takes one more from_raw or decrement_strong_count to let it go
+    /// # Safety
+    /// ptr has to have come from into_raw, and still count as an Arc (not been given back with from_raw or
+    /// decrement_strong_count)
+    pub unsafe fn increment_strong_count(ptr: *const T) {
+        let arc = ManuallyDrop::new(Arc::<T, A>::from_raw(ptr));
+        mem::forget(Arc::clone(&arc));
+    }
+
+    // Takes one off the count behind a pointer from into_raw, as if an Arc had been dropped - dropping the data if
+    // it was the last one
+    /// # Safety
+    /// Same as increment_strong_count, and afterwards the pointer counts as one Arc fewer: if this was its last,
+    /// it can't be used again
+    pub unsafe fn decrement_strong_count(ptr: *const T) {
+        drop(Arc::<T, A>::from_raw(ptr));
+    }
+}
+
+impl<T, A: Allocator> Weak<T, A> {
+    fn data(&self) -> &ArcData<T, A> {
+        unsafe {self.ptr.as_ref()}
+    }
+
+    pub fn upgrade(&self) -> Option<Arc<T, A>> {
+        // If there's no arcs, return Nothing - once it's hit 0 it never goes back up
+        // Acquire matches the Release store in new_cyclic_in, for Weaks that were shared before the data existed
+        self.data().data_ref_count.try_cas_update(Acquire, Relaxed, |n| {
+            if n == 0 {
+                return None;
+            }
+            assert!(n < usize::MAX);
+            pause!("Weak::upgrade loaded");
+            Some(n + 1)
+        }).ok()?;
+        Some(Arc { ptr: self.ptr })
+    }
+
+    // The number of Arcs pointing at the data. 0 means the data has been dropped and upgrade will fail
+    pub fn strong_count(&self) -> usize {
+        self.data().data_ref_count.load(Relaxed)
+    }
+
+    // The number of Weaks pointing at the allocation, this one included
+    pub fn weak_count(&self) -> usize {
+        // alloc_ref_count counts all of the Arcs together as one extra weak pointer. It can't be usize::MAX
+        // (locked by get_mut) here, as get_mut only locks it when there are no Weaks at all
+        let n = self.data().alloc_ref_count.load(Relaxed);
+        if self.strong_count() > 0 {
+            n - 1
+        } else {
+            n
+        }
+    }
+}
+
+impl<T, A: Allocator> Deref for Arc<T, A> {
+    type Target = T;
+
+    // deref allows Arc<T> to transparently behave as reference to T
+    // Because Arc<T> represents shared ownership, DerefMut cannot be implemented
+    fn deref(&self) -> &T {
+        // Since there's an Arc to the data, it exists and can therefore be shared safely
+        unsafe { &*self.data().data.get() }
+    }
+}
+
+
+impl<T: Default> Default for Arc<T> {
+    fn default() -> Self {
+        Arc::new(T::default())
+    }
+}
+
+impl<T> From<T> for Arc<T> {
+    fn from(data: T) -> Self {
+        Arc::new(data)
+    }
+}
+
+// Debug and Display look straight through to T, same as std's Arc
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for Arc<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display, A: Allocator> fmt::Display for Arc<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+// Comparisons and hashing are T's too, so an Arc can be a HashMap key or go in a BTreeSet like the T inside it.
+// Two Arcs are equal when their values are, whether or not they point to the same allocation
+impl<T: PartialEq, A: Allocator> PartialEq for Arc<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for Arc<T, A> {}
+
+impl<T: PartialOrd, A: Allocator> PartialOrd for Arc<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, A: Allocator> Ord for Arc<T, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, A: Allocator> Hash for Arc<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+// Borrow is what lets a HashMap<Arc<T>, V> be looked up with a &T. It's only sound alongside the impls above
+// hashing and comparing the same way T does
+impl<T, A: Allocator> Borrow<T> for Arc<T, A> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, A: Allocator> AsRef<T> for Arc<T, A> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+// Serialized as just the value, like std's Arc with serde's rc feature. Every Arc to the same value serializes
+// its own copy, and each one deserializes into a separate allocation - sharing isn't kept across a round trip
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, A: Allocator> serde::Serialize for Arc<T, A> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Arc<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Arc::new)
+    }
+}
+
+// The value could be gone (or get dropped halfway through formatting it), so a Weak doesn't show it
+impl<T, A: Allocator> fmt::Debug for Weak<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(Weak)")
+    }
+}
+
+impl<T, A: Allocator> Clone for Arc<T, A> {
+    fn clone (&self) -> Self {
+        if self.data().data_ref_count.fetch_add(1, Relaxed) > usize::MAX / 2 {
+            std::process::abort()
+        }
+        Arc {
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<T, A: Allocator> Clone for Weak<T, A> {
+    fn clone(&self) -> Self {
+        // If the reference counter is about to overflow, abort
+        if self.data().alloc_ref_count.fetch_add(1, Relaxed) > usize::MAX / 2 {
+            std::process::abort();
+        }
+        Weak { ptr: self.ptr }
+    }
+}
+
+impl<T, A: Allocator> Drop for Weak<T, A> {
+    fn drop(&mut self) {
+        // Decrement the Arc counter and de-allocate the ArcData when the counter hits 0
+        pause!("Weak::drop");
+        if self.data().alloc_ref_count.fetch_sub(1, Release) == 1 {
+            fence(Acquire);
+            unsafe {
+                // The data was already dropped by the last Arc and the counters don't need dropping, so all that's
+                // left is the allocator. It's moved out first, as the memory it lives in is about to be freed
+                let alloc = ptr::read(&self.data().alloc);
+                alloc.deallocate(self.ptr.cast(), Layout::new::<ArcData<T, A>>());
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator> Drop for Arc<T, A> {
+    fn drop(&mut self) {
+        // When the last Arc is dropped, drop the data and then the one Weak that all of the Arcs share
+        pause!("Arc::drop");
+        if self.data().data_ref_count.fetch_sub(1, Release) == 1 {
+            fence(Acquire);
+            // The reference counter is 0, so nothing is going to access the data and it's therefore safe
+            unsafe {
+                ManuallyDrop::drop(&mut *self.data().data.get());
+            }
+            drop(Weak {ptr: self.ptr});
+        }
+    }
+}
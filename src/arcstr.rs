@@ -0,0 +1,221 @@
+use std::alloc::{handle_alloc_error, Layout};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::str;
+use std::sync::atomic::{fence, AtomicUsize};
+
+use crate::allocator::{Allocator, Global};
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::sched::pause;
+
+// An immutable string shared between threads, like Arc<str> but in one word: the reference count, the length
+// and the bytes are all in one allocation, header first,
+//
+//     [ count | len | h e l l o ]
+//       ^ ptr
+//
+// so a clone is one fetch_add and getting at the str is a bit of pointer arithmetic - no second allocation for
+// a Box<str> or String inside an Arc, and no fat pointer either. The header's size is a multiple of its
+// alignment, so the bytes always start straight after it. There's no Weak, as nothing in a string can point
+// back at itself - which is what lets it get by with the one count
+pub struct ArcStr {
+    ptr: NonNull<Header>,
+}
+
+struct Header {
+    // Number of ArcStrs
+    count: AtomicUsize,
+    len: usize,
+}
+
+// The str is never changed after it's written, and the last ArcStr frees it on whatever thread that is
+unsafe impl Send for ArcStr {}
+unsafe impl Sync for ArcStr {}
+
+impl ArcStr {
+    pub fn new(s: &str) -> Self {
+        let layout = Self::layout(s.len());
+        let ptr = match Global.allocate(layout) {
+            Ok(ptr) => ptr.cast::<Header>(),
+            Err(_) => handle_alloc_error(layout),
+        };
+        // Safety: the memory is freshly allocated with room for the header and len bytes after it
+        unsafe {
+            ptr.as_ptr().write(Header { count: AtomicUsize::new(1), len: s.len() });
+            ptr::copy_nonoverlapping(s.as_ptr(), Self::bytes(ptr), s.len());
+        }
+        Self { ptr }
+    }
+
+    fn layout(len: usize) -> Layout {
+        let (layout, offset) = Layout::new::<Header>()
+            .extend(Layout::array::<u8>(len).expect("string too long"))
+            .expect("string too long");
+        debug_assert_eq!(offset, size_of::<Header>());
+        layout
+    }
+
+    // Where the bytes start, straight after the header
+    fn bytes(ptr: NonNull<Header>) -> *mut u8 {
+        // Safety: every allocation has room for the header, so one past it is in bounds (or one past the end)
+        unsafe { ptr.as_ptr().add(1).cast() }
+    }
+
+    fn header(&self) -> &Header {
+        // Safety: the header's valid for as long as there's an ArcStr pointing at it
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn as_str(&self) -> &str {
+        let len = self.header().len;
+        // Safety: the len bytes after the header were copied from a str and are never written again
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(Self::bytes(self.ptr), len)) }
+    }
+
+    // How many ArcStrs share this string, this one included. Another thread can change it straight after
+    pub fn strong_count(this: &Self) -> usize {
+        this.header().count.load(Relaxed)
+    }
+
+    // Whether the two are clones of each other, rather than just equal strings
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+}
+
+impl Clone for ArcStr {
+    fn clone(&self) -> Self {
+        // If the reference counter is about to overflow, abort
+        if self.header().count.fetch_add(1, Relaxed) > usize::MAX / 2 {
+            std::process::abort();
+        }
+        Self { ptr: self.ptr }
+    }
+}
+
+impl Drop for ArcStr {
+    fn drop(&mut self) {
+        pause!("ArcStr::drop");
+        if self.header().count.fetch_sub(1, Release) == 1 {
+            fence(Acquire);
+            // Safety: it was the last one, so nothing else can be looking at it. A str needs no dropping, so
+            // it's just handed back with the layout it was allocated with
+            unsafe { Global.deallocate(self.ptr.cast(), Self::layout(self.header().len)) };
+        }
+    }
+}
+
+impl Deref for ArcStr {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Default for ArcStr {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl From<&str> for ArcStr {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for ArcStr {
+    fn from(s: String) -> Self {
+        Self::new(&s)
+    }
+}
+
+impl From<&ArcStr> for String {
+    fn from(s: &ArcStr) -> Self {
+        s.as_str().to_owned()
+    }
+}
+
+impl fmt::Debug for ArcStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ArcStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+// Compared, ordered and hashed as the str, same as Arc. Clones are checked first, as they're equal without
+// looking at the bytes
+impl PartialEq for ArcStr {
+    fn eq(&self, other: &Self) -> bool {
+        Self::ptr_eq(self, other) || self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ArcStr {}
+
+impl PartialEq<str> for ArcStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ArcStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for ArcStr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ArcStr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for ArcStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+// So a HashMap<ArcStr, V> can be looked up with a &str
+impl Borrow<str> for ArcStr {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl AsRef<str> for ArcStr {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ArcStr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ArcStr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::atomic::AtomicU64;
+
+use crate::atomicupdate::AtomicUpdate;
+use crate::ordering::{Relaxed, Acquire, AcqRel};
+
+// A fixed size set of bits that any thread can set and clear, packed 64 to an AtomicU64.
+// Handy for handing out slots: find_and_set_first_zero claims a free slot and clear gives it back
+pub struct AtomicBitSet {
+    words: Box<[AtomicU64]>,
+    len: usize,
+}
+
+// Which word bit i is in, and the mask for it within that word
+fn position(i: usize) -> (usize, u64) {
+    (i / 64, 1 << (i % 64))
+}
+
+impl AtomicBitSet {
+    // A set of len bits, all clear
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: (0..len.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn word(&self, i: usize) -> (&AtomicU64, u64) {
+        assert!(i < self.len, "bit {i} is out of range for a set of {} bits", self.len);
+        let (word, mask) = position(i);
+        (&self.words[word], mask)
+    }
+
+    // Sets bit i and returns whether it was already set. AcqRel so that a thread that claims a bit sees what
+    // the thread that cleared it did beforehand (when the bits guard slots, say)
+    pub fn set(&self, i: usize) -> bool {
+        let (word, mask) = self.word(i);
+        word.fetch_or(mask, AcqRel) & mask != 0
+    }
+
+    // Clears bit i and returns whether it was set
+    pub fn clear(&self, i: usize) -> bool {
+        let (word, mask) = self.word(i);
+        word.fetch_and(!mask, AcqRel) & mask != 0
+    }
+
+    pub fn test(&self, i: usize) -> bool {
+        let (word, mask) = self.word(i);
+        word.load(Acquire) & mask != 0
+    }
+
+    // Finds a clear bit and sets it, returning its index, or None if every bit is set.
+    // Two threads can never get the same bit, as the bit is claimed with a compare exchange
+    pub fn find_and_set_first_zero(&self) -> Option<usize> {
+        for (w, word) in self.words.iter().enumerate() {
+            let first_zero = |current: u64| {
+                let bit = (!current).trailing_zeros() as usize;
+                // trailing_zeros is 64 when the word is full, and the last word may have bits past len
+                (bit < 64 && w * 64 + bit < self.len).then_some(bit)
+            };
+            // the bit that was set is the first zero in the word it replaced
+            let claimed = word.try_cas_update(AcqRel, Relaxed, |current| Some(current | 1 << first_zero(current)?));
+            if let Ok(previous) = claimed {
+                return first_zero(previous).map(|bit| w * 64 + bit);
+            }
+        }
+        None
+    }
+
+    // The indexes of the bits that are set. Bits changed by other threads while this runs may or may not show up
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, word)| {
+            let mut bits = word.load(Acquire);
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                // clear the lowest set bit
+                bits &= bits - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|word| word.load(Relaxed).count_ones() as usize).sum()
+    }
+}
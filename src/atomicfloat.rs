@@ -0,0 +1,133 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::ordering::Relaxed;
+
+// f32 and f64 that can be shared between threads, for things like summing up latencies or amounts from many
+// threads at once. There's no atomic float in the hardware (or std), so each one is its integer of the same
+// size holding the float's bits - to_bits going in and from_bits coming out, which is exact. load, store and
+// swap are single atomic operations like the integer's; the arithmetic is a compare_exchange loop that
+// retries when another thread got in first.
+//
+// Under a lot of contention that loop can go round many times. For a hot counter, giving each thread its own
+// (like ShardedCounter does) and adding them up on read scales much better
+macro_rules! atomic_float {
+    ($name:ident, $float:ty, $atomic:ty) => {
+        pub struct $name {
+            bits: $atomic,
+        }
+
+        impl $name {
+            pub const fn new(value: $float) -> Self {
+                Self { bits: <$atomic>::new(value.to_bits()) }
+            }
+
+            pub fn load(&self, order: Ordering) -> $float {
+                <$float>::from_bits(self.bits.load(order))
+            }
+
+            pub fn store(&self, value: $float, order: Ordering) {
+                self.bits.store(value.to_bits(), order);
+            }
+
+            pub fn swap(&self, value: $float, order: Ordering) -> $float {
+                <$float>::from_bits(self.bits.swap(value.to_bits(), order))
+            }
+
+            // Compares the bits, not the values: 0.0 and -0.0 don't match, and a NaN matches the same NaN.
+            // That's what makes it usable in a loop - with ==, a NaN would never match and the loop would
+            // never end
+            pub fn compare_exchange(
+                &self,
+                current: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                self.bits
+                    .compare_exchange(current.to_bits(), new.to_bits(), success, failure)
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            pub fn compare_exchange_weak(
+                &self,
+                current: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                self.bits
+                    .compare_exchange_weak(current.to_bits(), new.to_bits(), success, failure)
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            // Same as the integers' fetch_update: f is called with the current value until the swap to what
+            // it returns goes through, or it returns None. It can be called more than once, so it shouldn't
+            // have side effects
+            pub fn fetch_update(
+                &self,
+                set_order: Ordering,
+                fetch_order: Ordering,
+                mut f: impl FnMut($float) -> Option<$float>,
+            ) -> Result<$float, $float> {
+                self.bits
+                    .fetch_update(set_order, fetch_order, |bits| f(<$float>::from_bits(bits)).map(<$float>::to_bits))
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            // The arithmetic ones return the value from before, like the integers' do
+            pub fn fetch_add(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_with(order, |current| current + value)
+            }
+
+            pub fn fetch_sub(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_with(order, |current| current - value)
+            }
+
+            // max and min go by the float's own max and min, so a NaN on either side is ignored
+            pub fn fetch_max(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_with(order, |current| current.max(value))
+            }
+
+            pub fn fetch_min(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_with(order, |current| current.min(value))
+            }
+
+            pub fn into_inner(self) -> $float {
+                <$float>::from_bits(self.bits.into_inner())
+            }
+
+            // fetch_update that always goes through. The load after a failed swap only has to see the
+            // latest value, so it's Relaxed whatever order the swap is
+            fn fetch_with(&self, order: Ordering, mut f: impl FnMut($float) -> $float) -> $float {
+                match self.fetch_update(order, Relaxed, |current| Some(f(current))) {
+                    Ok(previous) | Err(previous) => previous,
+                }
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new(0.0)
+            }
+        }
+
+        impl From<$float> for $name {
+            fn from(value: $float) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.load(Relaxed), f)
+            }
+        }
+    };
+}
+
+atomic_float!(AtomicF32, f32, AtomicU32);
+atomic_float!(AtomicF64, f64, AtomicU64);
@@ -0,0 +1,97 @@
+use std::ptr;
+use std::sync::atomic::AtomicPtr;
+
+use crate::ordering::{Relaxed, Release, Acquire, AcqRel};
+
+// An Option<Box<T>> that threads can swap values in and out of without a lock, for handing a value from one
+// thread to another exactly once (a result, a shutdown signal with a payload, a lazily made value) without
+// setting up a whole channel. It's one AtomicPtr: null for None, or a pointer from Box::into_raw.
+//
+// The box only ever changes hands whole - nothing dereferences the pointer while it's in here - so there's no
+// reclamation to worry about: whoever swaps a pointer out owns it
+pub struct AtomicOption<T> {
+    ptr: AtomicPtr<T>,
+}
+
+// Values move between threads through it, so T only has to be Send (it's never shared)
+unsafe impl<T: Send> Sync for AtomicOption<T> {}
+unsafe impl<T: Send> Send for AtomicOption<T> {}
+
+fn into_ptr<T>(value: Option<Box<T>>) -> *mut T {
+    value.map_or(ptr::null_mut(), Box::into_raw)
+}
+
+// Safety: ptr has to be null or from Box::into_raw, and owned by the caller
+unsafe fn from_ptr<T>(ptr: *mut T) -> Option<Box<T>> {
+    (!ptr.is_null()).then(|| unsafe { Box::from_raw(ptr) })
+}
+
+impl<T> AtomicOption<T> {
+    pub const fn none() -> Self {
+        Self { ptr: AtomicPtr::new(ptr::null_mut()) }
+    }
+
+    pub fn new(value: Option<Box<T>>) -> Self {
+        Self { ptr: AtomicPtr::new(into_ptr(value)) }
+    }
+
+    // Takes the value out, leaving None. Only one thread can get any given value
+    pub fn take(&self) -> Option<Box<T>> {
+        // Acquire so the value's contents, written before it was put in, are visible
+        unsafe { from_ptr(self.ptr.swap(ptr::null_mut(), Acquire)) }
+    }
+
+    // Puts value in and hands back what was there
+    pub fn swap(&self, value: Option<Box<T>>) -> Option<Box<T>> {
+        // Release for the value going in, Acquire for the one coming out
+        unsafe { from_ptr(self.ptr.swap(into_ptr(value), AcqRel)) }
+    }
+
+    // Puts value in only if it's empty, otherwise hands it straight back. The first thread to try wins, which
+    // makes it a lock-free "init once" - the losers drop what they made (or keep it for something else)
+    pub fn try_insert(&self, value: Box<T>) -> Result<(), Box<T>> {
+        let new = Box::into_raw(value);
+        match self.ptr.compare_exchange(ptr::null_mut(), new, Release, Relaxed) {
+            Ok(_) => Ok(()),
+            // Safety: it didn't go in, so it's still ours
+            Err(_) => Err(unsafe { Box::from_raw(new) }),
+        }
+    }
+
+    // Only a snapshot, another thread can take or insert straight after
+    pub fn is_some(&self) -> bool {
+        !self.ptr.load(Relaxed).is_null()
+    }
+
+    pub fn is_none(&self) -> bool {
+        !self.is_some()
+    }
+
+    // No atomics needed, the &mut means nobody else can get at it
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.ptr.get_mut().as_mut() }
+    }
+
+    pub fn into_inner(mut self) -> Option<Box<T>> {
+        // swapped out, so drop doesn't free it as well
+        unsafe { from_ptr(std::mem::replace(self.ptr.get_mut(), ptr::null_mut())) }
+    }
+}
+
+impl<T> Default for AtomicOption<T> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<T> From<Box<T>> for AtomicOption<T> {
+    fn from(value: Box<T>) -> Self {
+        Self::new(Some(value))
+    }
+}
+
+impl<T> Drop for AtomicOption<T> {
+    fn drop(&mut self) {
+        unsafe { drop(from_ptr(*self.ptr.get_mut())) }
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::atomic::{
+    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize,
+    Ordering,
+};
+use std::thread;
+
+use crate::atomicfloat::{AtomicF32, AtomicF64};
+use crate::ordering::{AcqRel, Acquire};
+use crate::relax::cpu_relax;
+use crate::waitstrategy::cpu_budget;
+
+// Most of the lock-free code in the crate is a load, a new value worked out from the old one, and a
+// compare_exchange_weak that goes round again if another thread got in first. The loop's easy to get subtly
+// wrong - a lone compare_exchange_weak that gives up on its first (possibly spurious) failure, or a retry that
+// hammers the cache line every other thread is fighting over too - so it lives here once.
+//
+//     use rust_atomic_locks::atomicupdate::AtomicUpdate;
+//     let high_water = AtomicUsize::new(0);
+//     high_water.cas_update(Relaxed, Relaxed, |n| n.max(len));
+//     let taken = tokens.try_cas_update(Acquire, Relaxed, |n| n.checked_sub(1)).is_ok();
+//
+// Every failed swap backs off before the next try, so under contention the threads spread out rather than
+// all retrying in lockstep
+
+// Exponential backoff for retrying after losing a race: spins twice as long each time, up to a limit, then
+// yields. It yields straight away when spinning won't help (see cpu_budget), as then the thread that won is
+// likely waiting for this one's core
+#[derive(Debug, Clone, Default)]
+pub struct Backoff {
+    step: u32,
+}
+
+// 2^6 = 64 spins at the most before it starts yielding
+const SPIN_LIMIT: u32 = 6;
+
+impl Backoff {
+    pub const fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    pub fn spin(&mut self) {
+        if self.step > SPIN_LIMIT || !cpu_budget().spinning_helps() {
+            thread::yield_now();
+        } else {
+            for _ in 0..1 << self.step {
+                cpu_relax();
+            }
+        }
+        self.step = self.step.saturating_add(1);
+    }
+
+    // Whether it's spun as long as it's going to and has moved on to yielding
+    pub fn is_yielding(&self) -> bool {
+        self.step > SPIN_LIMIT
+    }
+
+    pub fn reset(&mut self) {
+        self.step = 0;
+    }
+}
+
+// The CAS loop for every integer atomic (and the floats). They're not called update and try_update as std
+// has those on its atomics behind a nightly feature, and a trait method with the same name would be a warning
+pub trait AtomicUpdate {
+    type Value: Copy;
+
+    // f is called with the current value until the swap to what it returns goes through, or until it returns
+    // None - Ok with the value it replaced, or Err with the value f turned down. Like std's fetch_update, f
+    // can be called more than once so it shouldn't have side effects (other than noting what it saw), and
+    // fetch_order is for the loads, set_order for the swap that goes through
+    fn try_cas_update(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        f: impl FnMut(Self::Value) -> Option<Self::Value>,
+    ) -> Result<Self::Value, Self::Value>;
+
+    // Same as try_cas_update for an f that always has a new value, returning the value it replaced
+    fn cas_update(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: impl FnMut(Self::Value) -> Self::Value,
+    ) -> Self::Value {
+        match self.try_cas_update(set_order, fetch_order, |current| Some(f(current))) {
+            Ok(previous) | Err(previous) => previous,
+        }
+    }
+}
+
+macro_rules! atomic_update {
+    ($($atomic:ty => $value:ty),* $(,)?) => {$(
+        impl AtomicUpdate for $atomic {
+            type Value = $value;
+
+            fn try_cas_update(
+                &self,
+                set_order: Ordering,
+                fetch_order: Ordering,
+                mut f: impl FnMut($value) -> Option<$value>,
+            ) -> Result<$value, $value> {
+                let mut backoff = Backoff::new();
+                let mut current = self.load(fetch_order);
+                while let Some(new) = f(current) {
+                    match self.compare_exchange_weak(current, new, set_order, fetch_order) {
+                        Ok(previous) => return Ok(previous),
+                        Err(actual) => {
+                            // a spurious failure leaves the value as it was, so there's no one to back off from
+                            if !actual.same_as(current) {
+                                backoff.spin();
+                            }
+                            current = actual;
+                        }
+                    }
+                }
+                Err(current)
+            }
+        }
+    )*};
+}
+
+atomic_update!(
+    AtomicU8 => u8, AtomicU16 => u16, AtomicU32 => u32, AtomicU64 => u64, AtomicUsize => usize,
+    AtomicI8 => i8, AtomicI16 => i16, AtomicI32 => i32, AtomicI64 => i64, AtomicIsize => isize,
+    AtomicF32 => f32, AtomicF64 => f64,
+);
+
+// Whether the value's the one the swap compared against. The floats go by their bits like their
+// compare_exchange does, as a NaN is never == itself
+trait SameAs: Copy {
+    fn same_as(self, other: Self) -> bool;
+}
+
+macro_rules! same_as {
+    ($($value:ty),*; $($float:ty),*) => {
+        $(impl SameAs for $value {
+            fn same_as(self, other: Self) -> bool {
+                self == other
+            }
+        })*
+        $(impl SameAs for $float {
+            fn same_as(self, other: Self) -> bool {
+                self.to_bits() == other.to_bits()
+            }
+        })*
+    };
+}
+
+same_as!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize; f32, f64);
+
+// cas_update with orderings that are always right, if stronger than they need to be: the swap acquires what
+// the thread that stored the old value released, and releases this thread's writes to the next. Returns the
+// value it replaced
+pub fn atomic_update<A: AtomicUpdate>(atomic: &A, f: impl FnMut(A::Value) -> A::Value) -> A::Value {
+    atomic.cas_update(AcqRel, Acquire, f)
+}
+
+// try_cas_update with the same orderings as atomic_update
+pub fn try_atomic_update<A: AtomicUpdate>(
+    atomic: &A,
+    f: impl FnMut(A::Value) -> Option<A::Value>,
+) -> Result<A::Value, A::Value> {
+    atomic.try_cas_update(AcqRel, Acquire, f)
+}
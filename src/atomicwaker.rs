@@ -0,0 +1,93 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::AtomicUsize;
+use std::task::Waker;
+
+use crate::ordering::{Release, Acquire, AcqRel};
+
+// Nobody is registering or waking
+const WAITING: usize = 0;
+// A task is storing its Waker
+const REGISTERING: usize = 1;
+// A wake is taking the Waker out. Can be set alongside REGISTERING, which means a wake came in partway through
+// a register, and the registering task has to do the waking
+const WAKING: usize = 2;
+
+// The slot a future leaves its Waker in while it's pending, for whatever finishes the work (on any thread) to
+// wake it with - the building block for a leaf future, like a channel receive that has nothing to return yet.
+// The same design as futures' AtomicWaker: there's no lock, and a wake that comes in while the Waker is being
+// swapped is never lost, it's handed to the registering side to do instead.
+//
+// Only one task should register at a time (it's one slot), but any number of threads can wake
+pub struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// The waker is only touched by whoever moved the state out of WAITING, so one thread at a time
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    pub const fn new() -> Self {
+        Self { state: AtomicUsize::new(WAITING), waker: UnsafeCell::new(None) }
+    }
+
+    // Stores the Waker to wake on the next wake, replacing the last one. Called from poll, before checking
+    // whether the work is done, so a wake that happens in between still wakes the task
+    pub fn register(&self, waker: &Waker) {
+        match self.state.compare_exchange(WAITING, REGISTERING, Acquire, Acquire).unwrap_or_else(|s| s) {
+            WAITING => {
+                // Safety: REGISTERING keeps wakes from touching the slot
+                unsafe {
+                    let slot = &mut *self.waker.get();
+                    // no clone when the task's Waker hasn't changed since last time, which it usually hasn't
+                    if !slot.as_ref().is_some_and(|old| old.will_wake(waker)) {
+                        *slot = Some(waker.clone());
+                    }
+                }
+                if self.state.compare_exchange(REGISTERING, WAITING, AcqRel, Acquire).is_err() {
+                    // a wake came in while the Waker was going in, and left it to us. The state is
+                    // REGISTERING | WAKING, so nothing else touches the slot until it's back to WAITING
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.swap(WAITING, AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            // a wake is taking the old Waker out right now, so it can't be replaced - but the task can just be
+            // woken straight away, which is what the wake was going to do anyway
+            WAKING => waker.wake_by_ref(),
+            // another register at the same time, which is misuse. One of them wins, there's nothing unsafe
+            _ => {}
+        }
+    }
+
+    // Wakes the registered task, if there is one. The Waker is taken out, so it's one wake per register
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    // Takes the registered Waker out without waking it
+    pub fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, AcqRel) {
+            WAITING => {
+                // Safety: WAKING keeps a register from touching the slot
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!WAKING, Release);
+                waker
+            }
+            // either a register is in progress, which sees WAKING and wakes the task itself, or another wake
+            // already has the Waker
+            _ => None,
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::panic::Location;
+use std::sync::{PoisonError, RwLock};
+
+// Catches blocking calls made from async code. A task that blocks holds up its executor's thread, and with it
+// every other task on that thread - including, often enough, the one it's waiting for, which makes it a
+// deadlock. It's an easy mistake to make with a crate that has both: a Mutex::lock or receive where the async
+// version was meant, or a sync helper called from a task that turns out to wait on something.
+//
+// With the feature on, the blocking calls (Mutex, SpinLock and RwSpinLock locks, channel receives and full
+// channel sends, Semaphore::acquire, and executor::block_on) panic when they're about to wait on a thread
+// that's running async tasks. Only when they'd wait: an uncontended lock in async code is fine, as long as it's
+// not held across an await, so a call that returns straight away is let through. Which also means a test only
+// catches it when there's contention, so it's worth running the tests a few times with it on.
+//
+// The crate's own executor marks its thread while it's polling. Other runtimes can be told about with
+// set_detector, or by calling enter in their worker threads
+thread_local! {
+    static RUNNING_TASKS: Cell<usize> = const { Cell::new(0) };
+}
+
+static DETECTOR: RwLock<Option<fn() -> bool>> = RwLock::new(None);
+
+// Marks the thread as running async tasks until the returned guard is dropped. They nest
+pub fn enter() -> Entered {
+    RUNNING_TASKS.with(|n| n.set(n.get() + 1));
+    Entered { _not_send: PhantomData }
+}
+
+// Marks the thread as running async tasks for as long as it's held. It's tied to the thread it was made on
+pub struct Entered {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        RUNNING_TASKS.with(|n| n.set(n.get() - 1));
+    }
+}
+
+// A check for runtimes the crate doesn't know about, for whether the current thread's one of theirs - for tokio
+// it's || tokio::runtime::Handle::try_current().is_ok(). Replaces the last one
+pub fn set_detector(detector: fn() -> bool) {
+    *DETECTOR.write().unwrap_or_else(PoisonError::into_inner) = Some(detector);
+}
+
+pub fn clear_detector() {
+    *DETECTOR.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+// Whether the current thread's running async tasks, going by enter and the detector
+pub fn in_async_context() -> bool {
+    RUNNING_TASKS.with(Cell::get) > 0
+        || DETECTOR.read().unwrap_or_else(PoisonError::into_inner).is_some_and(|detector| detector())
+}
+
+// Called by a blocking call that's about to wait, with what it is. The blocking calls are all #[track_caller]
+// with the feature on, so the location here is where the user called them. The crate's own code uses them too
+// (a Mutex inside a channel, say), but only for short waits that aren't the user's mistake, so calls from
+// inside the crate are left alone
+#[track_caller]
+pub(crate) fn check(what: &str) {
+    let caller = Location::caller();
+    if is_in_crate(caller) || !in_async_context() {
+        return;
+    }
+    panic!(
+        "{what} would block an async executor's thread. Blocking in a task holds up every other task on the \
+         thread, and deadlocks if the one it's waiting for is among them - use the async version if there is \
+         one (Semaphore::acquire_async, RwSpinLock::read_async and write_async), or move the call to a thread of \
+         its own"
+    );
+}
+
+// This file's path with its name taken off is the crate's src directory, however it's been built - relative for
+// the crate itself, a path into the registry or a git checkout when it's a dependency
+fn is_in_crate(location: &Location<'_>) -> bool {
+    let src = file!().trim_end_matches("blockingcheck.rs");
+    location.file().starts_with(src)
+}
@@ -0,0 +1,641 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::time::Instant;
+
+use crate::arc::Arc;
+use crate::boundedqueue::BoundedQueue;
+use crate::deadline::Deadline;
+#[cfg(feature = "metrics")]
+use crate::metrics::{ChannelCounters, ChannelStats};
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::sched::pause;
+use crate::trace::check_blocking;
+use crate::waitqueue::WaitQueue;
+
+// The threads parked waiting for a channel to have room (senders) or messages (receivers)
+struct Waiters {
+    queue: WaitQueue,
+}
+
+impl Waiters {
+    const fn new() -> Self {
+        Self { queue: WaitQueue::new() }
+    }
+
+    fn wake_one(&self) {
+        self.queue.wake_one();
+    }
+
+    fn wake_all(&self) {
+        self.queue.wake_all();
+    }
+
+    // Calls attempt until it returns Some, parking in between.
+    // The thread joins the queue before the last attempt before parking, so anything that would make attempt
+    // succeed and then wakes a waiter can't slip in unnoticed between the attempt and the park
+    fn block<R>(&self, attempt: impl FnMut() -> Option<R>) -> R {
+        self.block_until(None, attempt).expect("without a deadline it only returns once attempt succeeds")
+    }
+
+    // Same as block, but gives up and returns None once the deadline has passed
+    fn block_until<R>(&self, deadline: Option<Instant>, mut attempt: impl FnMut() -> Option<R>) -> Option<R> {
+        loop {
+            if let Some(r) = attempt() {
+                return Some(r);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            let mut r = None;
+            self.queue.wait_until(deadline, || {
+                pause!("Waiters::block registered");
+                r = attempt();
+                r.is_none()
+            });
+            if r.is_some() {
+                return r;
+            }
+        }
+    }
+}
+
+// What send does when the channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    // Waits for a receiver to make room
+    #[default]
+    Block,
+    // Throws the new message away (send still returns Ok)
+    DropNewest,
+    // Throws the oldest message in the channel away to make room for the new one
+    DropOldest,
+    // Hands the message straight back, as SendError - use try_send to tell that apart from a disconnect
+    Fail,
+}
+
+struct Chan<T> {
+    queue: BoundedQueue<Envelope<T>>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    waiting_senders: Waiters,
+    waiting_receivers: Waiters,
+    overflow: Overflow,
+    // made with sync_channel_fair, so every Sender has a Share
+    fair: bool,
+    // messages thrown away by DropNewest or DropOldest
+    dropped: AtomicU64,
+    #[cfg(feature = "metrics")]
+    stats: ChannelCounters,
+}
+
+// A message in the channel, along with the share of the Sender that sent it on a fair channel
+struct Envelope<T> {
+    message: T,
+    share: Option<Arc<Share>>,
+}
+
+// One Sender's part of a fair channel: how many of its messages are in there, and the threads sending on it
+// that are waiting for one of them to be received
+struct Share {
+    queued: AtomicUsize,
+    peak: AtomicUsize,
+    waiting: WaitQueue,
+}
+
+impl Share {
+    fn new() -> Self {
+        Self { queued: AtomicUsize::new(0), peak: AtomicUsize::new(0), waiting: WaitQueue::new() }
+    }
+}
+
+// A Sender's place in a fair channel, a snapshot. quota goes down as Senders are cloned and up as they're dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SenderOccupancy {
+    // its messages in the channel right now
+    pub queued: usize,
+    // how many it's allowed at once
+    pub quota: usize,
+    // the most it's had at once
+    pub peak: usize,
+}
+
+impl<T> Chan<T> {
+    // Every receive comes through here, so a fair channel's sender gets its share back (and its blocked threads
+    // woken - all of them, as one that times out mustn't take the wake with it while another's still waiting)
+    fn pop(&self) -> Option<T> {
+        let Envelope { message, share } = self.queue.pop()?;
+        if let Some(share) = share {
+            share.queued.fetch_sub(1, Release);
+            share.waiting.wake_all();
+        }
+        Some(message)
+    }
+
+    // A wake up can go to a thread that then got what it wanted without parking, or was about to give up.
+    // So whoever succeeds passes a wake up on to the next waiter of the same kind while there's still something
+    // for them, so no waiter sleeps through a message (or a free slot) that nobody is going to take
+    fn after_send(&self) {
+        self.waiting_receivers.wake_one();
+        if !self.queue.is_full() {
+            self.waiting_senders.wake_one();
+        }
+    }
+
+    fn after_receive(&self) {
+        self.waiting_senders.wake_one();
+        if !self.queue.is_empty() {
+            self.waiting_receivers.wake_one();
+        }
+    }
+}
+
+// A bounded multi-producer multi-consumer channel on top of BoundedQueue, meant as a drop in for
+// std's sync_channel. Senders block while the channel is full, receivers block while it's empty, and both
+// find out when everyone on the other side has gone
+pub fn sync_channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    sync_channel_with(capacity, Overflow::Block)
+}
+
+// Same as sync_channel, but send does what overflow says when the channel is full instead of always blocking.
+// Dropping is often better than holding up the senders - for telemetry, say, where the newest (or oldest)
+// data can go missing but the producer mustn't stall
+pub fn sync_channel_with<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    new_channel(capacity, overflow, false)
+}
+
+// Same as sync_channel, but no one Sender can fill the channel up on its own: each one (every clone included) gets
+// an equal share of the capacity, and once it has that many messages waiting to be received its sends block (or
+// try_send returns Full) even if there's room left for the others. That way one chatty producer can't starve the
+// rest, as there's always room kept for them. Each share is capacity / senders, at least one, so it shrinks as
+// Senders are cloned - one that's already over its new share just waits until it's back under.
+// occupancy says where each Sender is
+pub fn sync_channel_fair<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    new_channel(capacity, Overflow::Block, true)
+}
+
+fn new_channel<T>(capacity: usize, overflow: Overflow, fair: bool) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        queue: BoundedQueue::new(capacity),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        waiting_senders: Waiters::new(),
+        waiting_receivers: Waiters::new(),
+        overflow,
+        fair,
+        dropped: AtomicU64::new(0),
+        #[cfg(feature = "metrics")]
+        stats: ChannelCounters::new(),
+    });
+    let share = fair.then(|| Arc::new(Share::new()));
+    (Sender { chan: chan.clone(), share }, Receiver { chan })
+}
+
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+    // on a fair channel, this Sender's share of it
+    share: Option<Arc<Share>>,
+}
+
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+// The message comes back when every receiver has been dropped, so it isn't lost
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Disconnected(T),
+}
+
+// What send_timeout hands back: the message, and whether it ran out of time or the receivers are gone
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendTimeoutError<T> {
+    Timeout(T),
+    Disconnected(T),
+}
+
+impl<T> SendTimeoutError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            SendTimeoutError::Timeout(message) | SendTimeoutError::Disconnected(message) => message,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Disconnected,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+impl<T> Sender<T> {
+    // On a fair channel it's also Full when this Sender has its whole share in the channel already
+    pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
+        if self.chan.receivers.load(Acquire) == 0 {
+            return Err(TrySendError::Disconnected(message));
+        }
+        // takes a place in the share first, and gives it back if the channel turns out to be full
+        if let Some(share) = &self.share {
+            let quota = self.quota();
+            if share.queued.fetch_update(Relaxed, Acquire, |n| (n < quota).then_some(n + 1)).is_err() {
+                return Err(TrySendError::Full(message));
+            }
+        }
+        match self.chan.queue.push(Envelope { message, share: self.share.clone() }) {
+            Ok(()) => {
+                if let Some(share) = &self.share {
+                    share.peak.fetch_max(share.queued.load(Relaxed), Relaxed);
+                }
+                #[cfg(feature = "metrics")]
+                self.chan.stats.record_send(1, self.chan.queue.len());
+                self.chan.after_send();
+                Ok(())
+            }
+            Err(Envelope { message, share }) => {
+                if let Some(share) = share {
+                    share.queued.fetch_sub(1, Relaxed);
+                }
+                Err(TrySendError::Full(message))
+            }
+        }
+    }
+
+    // Blocks while the channel is full, unless the channel was made with a different Overflow. try_send
+    // always leaves a full channel alone and returns Full, whatever the policy
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+        match self.chan.overflow {
+            Overflow::Block => {
+                check_blocking!(when self.chan.queue.is_full(), "Sender::send");
+                self.send_until(message, None).map_err(|e| SendError(e.into_inner()))
+            }
+            Overflow::Fail => {
+                self.try_send(message).map_err(|(TrySendError::Full(m) | TrySendError::Disconnected(m))| SendError(m))
+            }
+            Overflow::DropNewest => match self.try_send(message) {
+                Err(TrySendError::Full(_)) => {
+                    self.chan.dropped.fetch_add(1, Relaxed);
+                    Ok(())
+                }
+                Err(TrySendError::Disconnected(m)) => Err(SendError(m)),
+                Ok(()) => Ok(()),
+            },
+            Overflow::DropOldest => {
+                let mut message = message;
+                loop {
+                    match self.try_send(message) {
+                        Err(TrySendError::Full(m)) => {
+                            // a receiver may take the oldest one first, which makes room just the same
+                            if self.chan.pop().is_some() {
+                                self.chan.dropped.fetch_add(1, Relaxed);
+                            }
+                            message = m;
+                        }
+                        Err(TrySendError::Disconnected(m)) => return Err(SendError(m)),
+                        Ok(()) => return Ok(()),
+                    }
+                }
+            }
+        }
+    }
+
+    // Same as send, but only waits for room until the deadline, and then hands the message back as Timeout.
+    // The overflow policies other than Block never wait, so for those it's just send - apart from Fail, whose
+    // full channel counts as a timeout rather than a disconnect
+    pub fn send_timeout(&self, message: T, timeout: impl Into<Deadline>) -> Result<(), SendTimeoutError<T>> {
+        match self.chan.overflow {
+            Overflow::Block => self.send_until(message, timeout.into().instant()),
+            Overflow::Fail => self.try_send(message).map_err(|e| match e {
+                TrySendError::Full(m) => SendTimeoutError::Timeout(m),
+                TrySendError::Disconnected(m) => SendTimeoutError::Disconnected(m),
+            }),
+            _ => self.send(message).map_err(|SendError(m)| SendTimeoutError::Disconnected(m)),
+        }
+    }
+
+    fn send_until(&self, message: T, deadline: Option<Instant>) -> Result<(), SendTimeoutError<T>> {
+        let mut message = Some(message);
+        // only set once the channel turns out to be full, so sends that go straight in don't read the clock
+        #[cfg(feature = "metrics")]
+        let mut blocked_since = None;
+        let attempt = || match self.try_send(message.take().unwrap()) {
+            Ok(()) => Some(Ok(())),
+            Err(TrySendError::Disconnected(m)) => Some(Err(SendTimeoutError::Disconnected(m))),
+            Err(TrySendError::Full(m)) => {
+                #[cfg(feature = "metrics")]
+                blocked_since.get_or_insert_with(Instant::now);
+                message = Some(m);
+                None
+            }
+        };
+        let result = match &self.share {
+            None => self.chan.waiting_senders.block_until(deadline, attempt),
+            Some(share) => self.block_fair_until(share, deadline, attempt),
+        };
+        #[cfg(feature = "metrics")]
+        if let Some(since) = blocked_since {
+            self.chan.stats.record_blocked_send(since.elapsed());
+        }
+        // timed out, with the message put back by the last attempt
+        result.unwrap_or_else(|| Err(SendTimeoutError::Timeout(message.take().unwrap())))
+    }
+
+    // Waiters::block_until for a fair channel's sender, which can be blocked for two reasons: it's over its share,
+    // and waits for one of its own messages to be received, or the channel's full, and it waits for any to be. It
+    // only parks in a queue if that's still why it's blocked once it's in it, so whatever unblocks it has to wake it
+    fn block_fair_until<R>(
+        &self,
+        share: &Share,
+        deadline: Option<Instant>,
+        mut attempt: impl FnMut() -> Option<R>,
+    ) -> Option<R> {
+        let over_quota = || share.queued.load(Acquire) >= self.quota();
+        let full = || self.chan.queue.is_full();
+        loop {
+            if let Some(r) = attempt() {
+                return Some(r);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            let waiting_for_room = !over_quota();
+            let (queue, blocked): (&WaitQueue, &dyn Fn() -> bool) = if waiting_for_room {
+                (&self.chan.waiting_senders.queue, &full)
+            } else {
+                (&share.waiting, &over_quota)
+            };
+            let mut r = None;
+            let woken = queue.wait_until(deadline, || {
+                pause!("Sender::block_fair_until registered");
+                r = attempt();
+                r.is_none() && blocked()
+            });
+            if r.is_some() {
+                return r;
+            }
+            // woken for room it turned out it couldn't use, as it had gone over its share in the meantime - so the
+            // room goes to the next sender instead
+            if woken && waiting_for_room && !full() {
+                self.chan.waiting_senders.wake_one();
+            }
+        }
+    }
+
+    // This Sender's share of a fair channel
+    fn quota(&self) -> usize {
+        (self.chan.queue.capacity() / self.chan.senders.load(Relaxed)).max(1)
+    }
+
+    // Where this Sender is in its share of the channel, or None if it wasn't made with sync_channel_fair
+    pub fn occupancy(&self) -> Option<SenderOccupancy> {
+        let share = self.share.as_ref()?;
+        Some(SenderOccupancy {
+            queued: share.queued.load(Relaxed),
+            quota: self.quota(),
+            peak: share.peak.load(Relaxed),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.chan.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        Some(self.chan.queue.capacity())
+    }
+
+    // How many messages the overflow policy has thrown away so far, across every sender
+    pub fn dropped(&self) -> u64 {
+        self.chan.dropped.load(Relaxed)
+    }
+
+    // Counts for the whole channel, every sender and receiver included - the same from either end
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> ChannelStats {
+        self.chan.stats.snapshot()
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn try_receive(&self) -> Result<T, TryRecvError> {
+        if let Some(message) = self.chan.pop() {
+            #[cfg(feature = "metrics")]
+            self.chan.stats.record_receive(1);
+            self.chan.after_receive();
+            return Ok(message);
+        }
+        if self.chan.senders.load(Acquire) == 0 {
+            // a sender could have sent one last message between the pop and the load, so look once more
+            let message = self.chan.pop().ok_or(TryRecvError::Disconnected)?;
+            #[cfg(feature = "metrics")]
+            self.chan.stats.record_receive(1);
+            return Ok(message);
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    // Blocks while the channel is empty. Once every sender is gone and the channel is empty, returns RecvError
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn receive(&self) -> Result<T, RecvError> {
+        check_blocking!(when self.is_empty() && self.chan.senders.load(Relaxed) != 0, "Receiver::receive");
+        self.chan.waiting_receivers.block(|| match self.try_receive() {
+            Ok(message) => Some(Ok(message)),
+            Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+            Err(TryRecvError::Empty) => None,
+        })
+    }
+
+    // Same as receive, but gives up with None as soon as stop returns true, for ShutdownSignal::receive. Whatever
+    // makes stop true has to wake the blocked receivers afterwards, with a waker from receive_waker
+    pub(crate) fn receive_unless(&self, stop: impl Fn() -> bool) -> Option<Result<T, RecvError>> {
+        self.chan.waiting_receivers.block(|| {
+            if stop() {
+                return Some(None);
+            }
+            match self.try_receive() {
+                Ok(message) => Some(Some(Ok(message))),
+                Err(TryRecvError::Disconnected) => Some(Some(Err(RecvError))),
+                Err(TryRecvError::Empty) => None,
+            }
+        })
+    }
+
+    // Wakes every receiver blocked on the channel, so they look at their stop again
+    pub(crate) fn receive_waker(&self) -> impl Fn() + Send + Sync + 'static
+    where
+        T: Send + 'static,
+    {
+        let chan = self.chan.clone();
+        move || chan.waiting_receivers.wake_all()
+    }
+
+    // Same as receive, but gives up once the deadline has passed
+    pub fn receive_timeout(&self, timeout: impl Into<Deadline>) -> Result<T, RecvTimeoutError> {
+        let deadline = timeout.into().instant();
+        let received = self.chan.waiting_receivers.block_until(deadline, || match self.try_receive() {
+            Ok(message) => Some(Ok(message)),
+            Err(TryRecvError::Disconnected) => Some(Err(RecvTimeoutError::Disconnected)),
+            Err(TryRecvError::Empty) => None,
+        });
+        received.unwrap_or(Err(RecvTimeoutError::Timeout))
+    }
+
+    // Collects up to max messages, waiting until there are that many or the timeout runs out, and returns
+    // whatever arrived by then - which can be nothing. Handy for batching writes: a busy channel fills the
+    // batch straight away, and a quiet one still gets flushed every timeout. It also returns early once every
+    // sender is gone and the channel is empty, as nothing more is coming
+    pub fn receive_batch(&self, max: usize, timeout: impl Into<Deadline>) -> Vec<T> {
+        let deadline = timeout.into().instant();
+        let mut batch = Vec::with_capacity(max.min(self.chan.queue.capacity()));
+        while batch.len() < max {
+            let next = self.chan.waiting_receivers.block_until(deadline, || match self.try_receive() {
+                Ok(message) => Some(Some(message)),
+                Err(TryRecvError::Disconnected) => Some(None),
+                Err(TryRecvError::Empty) => None,
+            });
+            match next {
+                Some(Some(message)) => batch.push(message),
+                // timed out, or disconnected
+                _ => break,
+            }
+        }
+        batch
+    }
+
+    // The messages left in the channel, ending once every sender is gone
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.receive().ok())
+    }
+
+    pub fn len(&self) -> usize {
+        self.chan.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        Some(self.chan.queue.capacity())
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.chan.dropped.load(Relaxed)
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> ChannelStats {
+        self.chan.stats.snapshot()
+    }
+
+    // Where receive waits, for the receive module's adapters to wait in alongside other channels'
+    pub(crate) fn receive_queue(&self) -> &WaitQueue {
+        &self.chan.waiting_receivers.queue
+    }
+}
+
+// Both ends can be cloned, for any number of senders and receivers
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, Relaxed);
+        // a share of its own, not a part of this one's
+        let share = self.chan.fair.then(|| Arc::new(Share::new()));
+        Self { chan: self.chan.clone(), share }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.receivers.fetch_add(1, Relaxed);
+        Self { chan: self.chan.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // the last sender wakes every receiver so they can find out the channel is disconnected
+        if self.chan.senders.fetch_sub(1, Release) == 1 {
+            self.chan.waiting_receivers.wake_all();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.chan.receivers.fetch_sub(1, Release) == 1 {
+            self.chan.waiting_senders.wake_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("len", &self.len())
+            .field("capacity", &self.chan.queue.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("len", &self.len())
+            .field("capacity", &self.chan.queue.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a channel with no receivers")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on an empty channel with no senders")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => f.write_str("timed out waiting for room in the channel"),
+            SendTimeoutError::Disconnected(_) => f.write_str("sending on a channel with no receivers"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendTimeoutError<T> {}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => f.write_str("timed out waiting for a message"),
+            RecvTimeoutError::Disconnected => f.write_str("receiving on an empty channel with no senders"),
+        }
+    }
+}
+
+impl std::error::Error for RecvTimeoutError {}
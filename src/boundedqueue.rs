@@ -0,0 +1,136 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicUsize;
+
+use crate::cachepadded::CachePadded;
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::sched::pause;
+
+struct Slot<T> {
+    // Says whose turn it is for this slot. When it's 2 * the push position that lands here the slot is free to
+    // write, and when it's 2 * that position + 1 it holds a value ready to pop. Popping moves it a whole lap ahead.
+    // Doubling keeps "ready to pop" odd and "free" even - otherwise with a capacity of 1 the free marker for the
+    // next lap would be the same number as the ready marker for this one
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// A bounded multi-producer multi-consumer queue, using Dmitry Vyukov's per-slot sequence numbers. There's no lock:
+// a thread claims a position with a compare exchange on the head or tail, and the slot's sequence number tells
+// it whether the slot is ready for it, so pushers and poppers only ever touch their own slot's data
+pub struct BoundedQueue<T> {
+    slots: Box<[Slot<T>]>,
+    // the next positions to push to and pop from. They only ever go up, and the slot is position % capacity.
+    // They're on their own cache lines as pushers and poppers hammer them from different threads
+    tail: CachePadded<AtomicUsize>,
+    head: CachePadded<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Sync for BoundedQueue<T> {}
+unsafe impl<T: Send> Send for BoundedQueue<T> {}
+
+impl<T> BoundedQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "a BoundedQueue needs room for at least one value");
+        Self {
+            slots: (0..capacity)
+                .map(|i| Slot { sequence: AtomicUsize::new(2 * i), value: UnsafeCell::new(MaybeUninit::uninit()) })
+                .collect(),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            head: CachePadded::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Pushes the value onto the back of the queue, or hands it back if the queue is full.
+    // A slot only counts as free once the pop emptying it has finished, so the queue can look full for a
+    // moment while a pop is part way through, even though len is below the capacity
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Relaxed);
+        loop {
+            let slot = &self.slots[pos % self.capacity()];
+            // Acquire matches the Release in pop, so the old value is completely read before we overwrite it
+            let seq = slot.sequence.load(Acquire);
+            if seq == 2 * pos {
+                // the slot is free, try to claim the position
+                match self.tail.compare_exchange_weak(pos, pos + 1, Relaxed, Relaxed) {
+                    Ok(_) => {
+                        pause!("BoundedQueue::push claimed");
+                        unsafe { (*slot.value.get()).write(value) };
+                        // Release matches the Acquire in pop, publishing the value
+                        slot.sequence.store(2 * pos + 1, Release);
+                        return Ok(());
+                    }
+                    Err(actual) => pos = actual,
+                }
+            } else if seq < 2 * pos {
+                // the slot still holds the value from a lap ago, so the queue is full
+                return Err(value);
+            } else {
+                // another pusher claimed this position already, catch up
+                pos = self.tail.load(Relaxed);
+            }
+        }
+    }
+
+    // Pops the value at the front of the queue, or None if it's empty
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.head.load(Relaxed);
+        loop {
+            let slot = &self.slots[pos % self.capacity()];
+            let seq = slot.sequence.load(Acquire);
+            if seq == 2 * pos + 1 {
+                match self.head.compare_exchange_weak(pos, pos + 1, Relaxed, Relaxed) {
+                    Ok(_) => {
+                        pause!("BoundedQueue::pop claimed");
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        // the slot is free for the push one lap ahead
+                        slot.sequence.store(2 * (pos + self.capacity()), Release);
+                        return Some(value);
+                    }
+                    Err(actual) => pos = actual,
+                }
+            } else if seq < 2 * pos + 1 {
+                // nothing has been pushed here yet, so the queue is empty
+                return None;
+            } else {
+                pos = self.head.load(Relaxed);
+            }
+        }
+    }
+
+    // The number of values in the queue. The head and tail are read one after the other, so with pushes and pops
+    // going on this is a rough snapshot, clamped to the capacity
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Relaxed);
+        let tail = self.tail.load(Relaxed);
+        tail.saturating_sub(head).min(self.capacity())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl<T> fmt::Debug for BoundedQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundedQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for BoundedQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
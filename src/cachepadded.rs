@@ -0,0 +1,31 @@
+use std::ops::{Deref, DerefMut};
+
+// Pads and aligns a value to 128 bytes so it sits on its own cache line(s). Without this, atomics that are
+// written by different threads but happen to share a cache line keep stealing the line from each other
+// (false sharing). 128 rather than 64 because some CPUs fetch cache lines in pairs
+#[repr(align(128))]
+#[derive(Default)]
+pub struct CachePadded<T>(pub T);
+
+impl<T> CachePadded<T> {
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
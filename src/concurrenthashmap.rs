@@ -0,0 +1,102 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::striped::Striped;
+
+const DEFAULT_STRIPES: usize = 16;
+
+// A hash map that can be shared between threads, built from a HashMap per stripe of a Striped lock.
+// Each operation only locks the stripe its key hashes to, so threads using different keys mostly don't contend
+pub struct ConcurrentHashMap<K, V> {
+    shards: Striped<HashMap<K, V>>,
+}
+
+impl<K: Hash + Eq, V> ConcurrentHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_stripes(DEFAULT_STRIPES)
+    }
+
+    pub fn with_stripes(stripes: usize) -> Self {
+        Self {
+            shards: Striped::new(stripes, HashMap::new),
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shards.lock(&key).insert(key, value)
+    }
+
+    // References into the map can't outlive the stripe's lock, so instead of returning one
+    // the value is handed to f while the lock is held
+    pub fn get<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.lock(key).get(key).map(f)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.lock(key).contains_key(key)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.lock(key).remove(key)
+    }
+
+    // Locks all of the stripes so the count is exact at the time it was taken
+    pub fn len(&self) -> usize {
+        self.shards.lock_all().iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Calls f on every entry, one stripe at a time. Other threads can change stripes that
+    // haven't been visited yet, so this isn't a snapshot of the whole map - use snapshot for that.
+    // Each stripe's entries are copied out and f is called with the stripe unlocked, so f can use the map
+    // itself - a get or insert on a stripe that was still locked would spin forever
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V))
+    where
+        K: Clone,
+        V: Clone,
+    {
+        for i in 0..self.shards.stripes() {
+            // lock each stripe through its index rather than a key
+            let entries: Vec<(K, V)> =
+                self.shards.lock_index(i).iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            for (k, v) in &entries {
+                f(k, v);
+            }
+        }
+    }
+
+    // Copies every entry out while holding all of the stripes at once
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.shards
+            .lock_all()
+            .iter()
+            .flat_map(|shard| shard.iter().map(|(k, v)| (k.clone(), v.clone())))
+            .collect()
+    }
+}
+
+impl<K: Hash + Eq, V> Default for ConcurrentHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,263 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+use crate::striped::Striped;
+
+const DEFAULT_STRIPES: usize = 16;
+// the end of a recency list
+const NIL: usize = usize::MAX;
+
+// A cache of up to capacity entries that can be shared between threads, throwing out the least recently used
+// entry to make room for a new one. Like ConcurrentHashMap it's split over the stripes of a Striped lock, and
+// each stripe is an LRU of its own with its share of the capacity - so a get or put only locks one stripe,
+// but what gets evicted is the least recently used entry in that stripe, not in the whole cache. With keys
+// that hash evenly that's close enough, and it's what keeps the threads from all fighting over one list
+pub struct ConcurrentLru<K, V> {
+    shards: Striped<Shard<K, V>>,
+    capacity: usize,
+}
+
+// How the cache has been doing, added up over every stripe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LruStats {
+    pub hits: u64,
+    pub misses: u64,
+    // entries thrown out to make room, not the ones taken out with remove
+    pub evictions: u64,
+}
+
+impl LruStats {
+    // The share of gets that found their key, 0 before there have been any
+    pub fn hit_ratio(&self) -> f64 {
+        let gets = self.hits + self.misses;
+        if gets == 0 { 0.0 } else { self.hits as f64 / gets as f64 }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> ConcurrentLru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_stripes(capacity, DEFAULT_STRIPES)
+    }
+
+    // Never more stripes than entries, as a stripe with no room in it couldn't hold anything
+    pub fn with_stripes(capacity: usize, stripes: usize) -> Self {
+        assert!(capacity > 0, "an LRU cache needs room for at least one entry");
+        let stripes = stripes.min(capacity);
+        // capacity split as evenly as it goes, the first few stripes taking one more
+        let mut i = 0;
+        let shards = Striped::new(stripes, || {
+            let share = capacity / stripes + usize::from(i < capacity % stripes);
+            i += 1;
+            Shard::new(share)
+        });
+        Self { shards, capacity }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Calls f on the value while the stripe is locked (a reference couldn't outlive the lock), and makes it the
+    // most recently used. Counts as a hit or a miss
+    pub fn get<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut shard = self.shards.lock(key);
+        match shard.map.get(key).copied() {
+            Some(i) => {
+                shard.hits += 1;
+                shard.touch(i);
+                Some(f(&shard.entry(i).value))
+            }
+            None => {
+                shard.misses += 1;
+                None
+            }
+        }
+    }
+
+    // Adds or replaces the value for key as the most recently used, evicting the least recently used entry in
+    // its stripe if that's full. Returns the value it replaced
+    pub fn put(&self, key: K, value: V) -> Option<V> {
+        let mut shard = self.shards.lock(&key);
+        if let Some(&i) = shard.map.get(&key) {
+            shard.touch(i);
+            return Some(mem::replace(&mut shard.entry_mut(i).value, value));
+        }
+        let evicted = if shard.map.len() == shard.capacity { shard.pop_back() } else { None };
+        shard.push_front(key, value);
+        // the evicted value can be big, so it's let go of outside the lock
+        drop(shard);
+        drop(evicted);
+        None
+    }
+
+    // Whether key is cached, without counting as a use of it (or as a hit or miss)
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.lock(key).map.contains_key(key)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut shard = self.shards.lock(key);
+        let i = shard.map.remove(key)?;
+        Some(shard.unlink(i).value)
+    }
+
+    // Locks all of the stripes so the count is exact at the time it was taken
+    pub fn len(&self) -> usize {
+        self.shards.lock_all().iter().map(|shard| shard.map.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        for i in 0..self.shards.stripes() {
+            let mut shard = self.shards.lock_index(i);
+            let capacity = shard.capacity;
+            let (hits, misses, evictions) = (shard.hits, shard.misses, shard.evictions);
+            *shard = Shard { hits, misses, evictions, ..Shard::new(capacity) };
+        }
+    }
+
+    // Each stripe keeps its own counts under its lock, so counting never adds any contention of its own. They're
+    // added up one stripe at a time, which is near enough while other threads carry on
+    pub fn stats(&self) -> LruStats {
+        let mut stats = LruStats::default();
+        for i in 0..self.shards.stripes() {
+            let shard = self.shards.lock_index(i);
+            stats.hits += shard.hits;
+            stats.misses += shard.misses;
+            stats.evictions += shard.evictions;
+        }
+        stats
+    }
+}
+
+// One stripe's LRU: the map finds an entry's slot, and the slots are linked into a list from the most
+// recently used (head) to the least (tail). The slots are reused as entries come and go, so a full stripe
+// doesn't allocate
+struct Shard<K, V> {
+    map: HashMap<K, usize>,
+    slots: Vec<Option<Entry<K, V>>>,
+    free: Vec<usize>,
+    head: usize,
+    tail: usize,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+struct Entry<K, V> {
+    key: K,
+    value: V,
+    prev: usize,
+    next: usize,
+}
+
+impl<K: Hash + Eq + Clone, V> Shard<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            map: HashMap::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    // Only ever called with slots the map points at, which are always full
+    fn entry(&self, i: usize) -> &Entry<K, V> {
+        self.slots[i].as_ref().expect("linked slots are full")
+    }
+
+    fn entry_mut(&mut self, i: usize) -> &mut Entry<K, V> {
+        self.slots[i].as_mut().expect("linked slots are full")
+    }
+
+    fn push_front(&mut self, key: K, value: V) {
+        let entry = Entry { key: key.clone(), value, prev: NIL, next: self.head };
+        let i = match self.free.pop() {
+            Some(i) => {
+                self.slots[i] = Some(entry);
+                i
+            }
+            None => {
+                self.slots.push(Some(entry));
+                self.slots.len() - 1
+            }
+        };
+        self.link_front(i);
+        self.map.insert(key, i);
+    }
+
+    fn pop_back(&mut self) -> Option<Entry<K, V>> {
+        if self.tail == NIL {
+            return None;
+        }
+        let entry = self.unlink(self.tail);
+        self.map.remove(&entry.key);
+        self.evictions += 1;
+        Some(entry)
+    }
+
+    // Moves an entry to the front, as the most recently used
+    fn touch(&mut self, i: usize) {
+        if self.head != i {
+            self.detach(i);
+            self.link_front(i);
+        }
+    }
+
+    // Takes an entry out of the list and frees its slot. The map is left to the caller
+    fn unlink(&mut self, i: usize) -> Entry<K, V> {
+        self.detach(i);
+        self.free.push(i);
+        self.slots[i].take().expect("linked slots are full")
+    }
+
+    fn detach(&mut self, i: usize) {
+        let (prev, next) = {
+            let entry = self.entry(i);
+            (entry.prev, entry.next)
+        };
+        match prev {
+            NIL => self.head = next,
+            prev => self.entry_mut(prev).next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.entry_mut(next).prev = prev,
+        }
+    }
+
+    fn link_front(&mut self, i: usize) {
+        let head = self.head;
+        let entry = self.entry_mut(i);
+        entry.prev = NIL;
+        entry.next = head;
+        match head {
+            NIL => self.tail = i,
+            head => self.entry_mut(head).prev = i,
+        }
+        self.head = i;
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize};
+use std::time::Instant;
+
+use crate::deadline::Deadline;
+use crate::futex::{wait, wait_timeout, wake_all, wake_one};
+use crate::mutex::MutexGuard;
+use crate::ordering::Relaxed;
+use crate::sched::pause;
+use crate::trace::trace_event;
+
+// A condition variable for the crate's Mutex, sleeping on the futex module the same way.
+// counter goes up with every notify, and a waiter sleeps on the value it read before unlocking the mutex -
+// so a notify that comes in between unlocking and going to sleep changes the value, and the wait returns
+// straight away instead of sleeping through it
+pub struct Condvar {
+    counter: AtomicU32,
+    // lets the notify functions skip the syscall when nobody's waiting
+    num_waiters: AtomicUsize,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            counter: AtomicU32::new(0),
+            num_waiters: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn notify_one(&self) {
+        if self.num_waiters.load(Relaxed) > 0 {
+            self.counter.fetch_add(1, Relaxed);
+            wake_one(&self.counter);
+        }
+    }
+
+    pub fn notify_all(&self) {
+        if self.num_waiters.load(Relaxed) > 0 {
+            self.counter.fetch_add(1, Relaxed);
+            wake_all(&self.counter);
+        }
+    }
+
+    // Unlocks the mutex, sleeps until notified, and locks the mutex again before handing the guard back.
+    // It can wake up without a notify (and another thread can get the lock first anyway), so it's always
+    // called in a loop that checks what it's waiting for
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.sleep(guard, wait)
+    }
+
+    // Same as wait, but gives up once timeout has passed. Whether it timed out is only a hint, as it can be
+    // notified (or wake up for no reason) right at the end - what's being waited for still has to be checked
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: impl Into<Deadline>,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        let deadline = timeout.into().instant();
+        let guard = self.sleep(guard, |counter, expected| match deadline {
+            Some(deadline) => wait_timeout(counter, expected, deadline.saturating_duration_since(Instant::now())),
+            None => wait(counter, expected),
+        });
+        (guard, WaitTimeoutResult(deadline.is_some_and(|deadline| Instant::now() >= deadline)))
+    }
+
+    // Waits for as long as condition returns true, with the loop around wait done here - it's checked before
+    // the first wait and after every wake up, always with the mutex locked, so no notify can slip through
+    // between checking and going to sleep
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    // Same as wait_while, but gives up once timeout has passed in total, however many times it woke up in
+    // between. Timed out means condition was still true at the end
+    pub fn wait_timeout_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        timeout: impl Into<Deadline>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        // worked out once, so every wait counts down to the same point
+        let deadline = timeout.into().instant();
+        while condition(&mut guard) {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return (guard, WaitTimeoutResult(true));
+            }
+            guard = self.wait_timeout(guard, deadline).0;
+        }
+        (guard, WaitTimeoutResult(false))
+    }
+
+    // Everything a wait does apart from the sleep itself, which is sleep(counter, value it was read as)
+    fn sleep<'a, T>(&self, guard: MutexGuard<'a, T>, sleep: impl FnOnce(&AtomicU32, u32)) -> MutexGuard<'a, T> {
+        // Relaxed is enough for both: the waiter is counted while the mutex is locked, so a thread that locks
+        // the mutex to change something and then notifies sees it. And the counter is read before unlocking,
+        // so any notify after the unlock has bumped it past this value
+        self.num_waiters.fetch_add(1, Relaxed);
+        let counter = self.counter.load(Relaxed);
+
+        let mutex = guard.mutex;
+        drop(guard);
+        pause!("Condvar::wait unlocked");
+
+        trace_event!("condvar waiter sleeping");
+        sleep(&self.counter, counter);
+
+        self.num_waiters.fetch_sub(1, Relaxed);
+        mutex.lock()
+    }
+}
+
+// What wait_timeout and wait_timeout_while return alongside the guard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// How long a blocking call is allowed to wait. Every timed operation in the crate (the _timeout locks,
+// acquires, waits and receives) takes an impl Into<Deadline>, so a Duration, an Instant or Deadline::Never
+// all work in the same places:
+//
+//     mutex.lock_timeout(Duration::from_millis(10))
+//     receiver.receive_timeout(frame_started + FRAME)
+//
+// An Instant is the one to pass down through several calls that share one budget - with a Duration each of
+// them would get the whole of it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Deadline {
+    // this long from when the call starts
+    After(Duration),
+    At(Instant),
+    Never,
+}
+
+impl Deadline {
+    // When it runs out, working an After out from now - so it's called once, at the start of the wait. None for
+    // Never, and for an After too long to add to now, which is as good as never
+    pub fn instant(self) -> Option<Instant> {
+        match self {
+            Deadline::After(timeout) => Instant::now().checked_add(timeout),
+            Deadline::At(deadline) => Some(deadline),
+            Deadline::Never => None,
+        }
+    }
+
+    // How long there is left from now (zero once it's passed), or None for no limit
+    pub fn remaining(self) -> Option<Duration> {
+        match self {
+            Deadline::After(timeout) => Some(timeout),
+            Deadline::At(deadline) => Some(deadline.saturating_duration_since(Instant::now())),
+            Deadline::Never => None,
+        }
+    }
+}
+
+impl From<Duration> for Deadline {
+    fn from(timeout: Duration) -> Self {
+        Deadline::After(timeout)
+    }
+}
+
+impl From<Instant> for Deadline {
+    fn from(deadline: Instant) -> Self {
+        Deadline::At(deadline)
+    }
+}
+
+// None is no deadline, which is what the crate's own wait loops keep it as
+impl From<Option<Instant>> for Deadline {
+    fn from(deadline: Option<Instant>) -> Self {
+        deadline.map_or(Deadline::Never, Deadline::At)
+    }
+}
+
+// What the timed locks (Mutex::lock_timeout and SpinLock::lock_timeout, and their _map versions) return when
+// the lock couldn't be had in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("timed out waiting for the lock")
+    }
+}
+
+impl std::error::Error for Timeout {}
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::boundedchannel::{
+    sync_channel, Receiver, RecvError, RecvTimeoutError, SendError, SendTimeoutError, Sender, TryRecvError,
+    TrySendError,
+};
+use crate::deadline::Deadline;
+use crate::receive::Receive;
+use crate::rendezvous::CallError;
+use crate::waitqueue::WaitQueue;
+
+// Two threads talking back and forth, like a pair of coroutines handing control to each other: each end sends
+// one type and receives the other. It's two bounded channels, one each way, packaged up so they can't be mixed
+// up or only half handed over to the worker.
+//
+//     let (main, worker) = duplex::<Job, Report>();
+//     thread::spawn(move || {
+//         while let Ok(job) = worker.receive() {
+//             let _ = worker.send(run(job));
+//         }
+//     });
+//     let report = main.call(job)?;
+//
+// call is send then receive, for when the two take strict turns. It returns the next message from the other end,
+// whatever it was sent for, so it's only the answer to the call if nothing else is in flight in that direction
+pub fn duplex<A, B>() -> (Endpoint<A, B>, Endpoint<B, A>) {
+    duplex_with_capacity(1)
+}
+
+// Same as duplex, but each way holds up to capacity messages before send blocks, for ends that don't take turns
+pub fn duplex_with_capacity<A, B>(capacity: usize) -> (Endpoint<A, B>, Endpoint<B, A>) {
+    let (a_sender, a_receiver) = sync_channel(capacity);
+    let (b_sender, b_receiver) = sync_channel(capacity);
+    (Endpoint { sender: a_sender, receiver: b_receiver }, Endpoint { sender: b_sender, receiver: a_receiver })
+}
+
+// One end of a duplex: sends Out and receives In. Either end finds out when the other one's gone - sends come
+// back as errors, and receives do once whatever was already sent has been received
+pub struct Endpoint<Out, In> {
+    sender: Sender<Out>,
+    receiver: Receiver<In>,
+}
+
+impl<Out, In> Endpoint<Out, In> {
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn send(&self, message: Out) -> Result<(), SendError<Out>> {
+        self.sender.send(message)
+    }
+
+    pub fn try_send(&self, message: Out) -> Result<(), TrySendError<Out>> {
+        self.sender.try_send(message)
+    }
+
+    pub fn send_timeout(&self, message: Out, timeout: impl Into<Deadline>) -> Result<(), SendTimeoutError<Out>> {
+        self.sender.send_timeout(message, timeout)
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn receive(&self) -> Result<In, RecvError> {
+        self.receiver.receive()
+    }
+
+    pub fn try_receive(&self) -> Result<In, TryRecvError> {
+        self.receiver.try_receive()
+    }
+
+    pub fn receive_timeout(&self, timeout: impl Into<Deadline>) -> Result<In, RecvTimeoutError> {
+        self.receiver.receive_timeout(timeout)
+    }
+
+    // Sends message and blocks until the other end sends something back
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn call(&self, message: Out) -> Result<In, CallError> {
+        self.send(message).map_err(|_| CallError::Disconnected)?;
+        self.receive().map_err(|_| CallError::Disconnected)
+    }
+
+    // Same as call, with the timeout covering both the send and waiting for the answer
+    pub fn call_timeout(&self, message: Out, timeout: impl Into<Deadline>) -> Result<In, CallError> {
+        // as an Instant, so the receive gets what's left rather than all of it again
+        let deadline = timeout.into().instant();
+        self.send_timeout(message, deadline).map_err(|e| match e {
+            SendTimeoutError::Timeout(_) => CallError::Timeout,
+            SendTimeoutError::Disconnected(_) => CallError::Disconnected,
+        })?;
+        self.receive_timeout(deadline).map_err(|e| match e {
+            RecvTimeoutError::Timeout => CallError::Timeout,
+            RecvTimeoutError::Disconnected => CallError::Disconnected,
+        })
+    }
+
+    // The two channels, for sending and receiving on different threads
+    pub fn split(self) -> (Sender<Out>, Receiver<In>) {
+        (self.sender, self.receiver)
+    }
+}
+
+impl<Out, In> fmt::Debug for Endpoint<Out, In> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("sending", &self.sender.len())
+            .field("receiving", &self.receiver.len())
+            .finish_non_exhaustive()
+    }
+}
+
+// So the receive adapters work on an end too
+impl<Out, In> Receive for Endpoint<Out, In> {
+    type Item = In;
+
+    fn try_receive(&self) -> Result<In, TryRecvError> {
+        self.receiver.try_receive()
+    }
+
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>) {
+        self.receiver.wait_queues(queues)
+    }
+}
@@ -0,0 +1,289 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::boundedchannel::{sync_channel, Receiver, Sender, TrySendError};
+use crate::hybridlock::HybridLock;
+use crate::mutex::Mutex;
+use crate::mutexchannel::MutexChannel;
+use crate::rwspinlock::RwSpinLock;
+use crate::spinlock::SpinLock;
+
+// Locks and channels picked at runtime. Which lock suits a piece of shared state, or which channel suits a
+// queue, often comes down to measuring it in production - but with the type fixed at compile time, trying a
+// different one means changing every struct and function it passes through. DynLock and DynChannel are the
+// operations they all have in common, as traits that work as trait objects, so the choice can come from a
+// config flag instead:
+//
+//     let lock: BoxedLock<Stats> = new_lock(config.lock.parse()?, Stats::default());
+//     lock.with(|stats| stats.requests += 1);
+//     let jobs: BoxedChannel<Job> = new_channel(config.queue.parse()?);
+//
+// A lock's guard is a different type for every lock, so the traits work with closures instead: the locking
+// calls run the closure with the lock held, and take it as a &mut dyn FnMut so the traits stay object safe.
+// The with, try_with and read on dyn DynLock wrap that back up with an ordinary FnOnce and a return value.
+// It's a virtual call on top of the lock, which is nothing next to a lock that's contended
+
+// Which lock new_lock makes. Parses from "spin", "mutex", "rwlock" or "hybrid", for config files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Spin,
+    Mutex,
+    // RwSpinLock, the only one of them that lets readers in together
+    RwLock,
+    Hybrid,
+}
+
+// Which channel new_channel makes. Parses from "mutex", for an unbounded MutexChannel, or "bounded:N", for a
+// bounded channel with room for N messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Mutex,
+    Bounded(usize),
+}
+
+// What parsing a LockKind or ChannelKind fails with: the string that wasn't one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKindError(pub String);
+
+pub type BoxedLock<T> = Box<dyn DynLock<T>>;
+pub type BoxedChannel<T> = Box<dyn DynChannel<T>>;
+
+// A lock around a T, whichever kind it is
+pub trait DynLock<T>: Send + Sync {
+    // Runs f with the lock held
+    fn with_dyn(&self, f: &mut dyn FnMut(&mut T));
+
+    // Same as with_dyn, but if the lock is taken already f isn't run, and it's false
+    fn try_with_dyn(&self, f: &mut dyn FnMut(&mut T)) -> bool;
+
+    // Runs f with the value shared: alongside other readers on a lock that has a read mode, and holding it like
+    // with_dyn on one that doesn't
+    fn read_dyn(&self, f: &mut dyn FnMut(&T)) {
+        self.with_dyn(&mut |value| f(value));
+    }
+
+    fn kind(&self) -> LockKind;
+}
+
+// A channel, whichever kind it is. Senders and receivers share the one object (an Arc of a BoxedChannel, say)
+// rather than having ends of their own, so it can't be disconnected - a receive waits for as long as it takes
+pub trait DynChannel<T>: Send + Sync {
+    // Blocks while the channel's full, on the kinds that can be
+    fn send(&self, message: T);
+
+    // Hands the message back if the channel's full
+    fn try_send(&self, message: T) -> Result<(), T>;
+
+    fn receive(&self) -> T;
+
+    fn try_receive(&self) -> Option<T>;
+
+    // How many messages are waiting, a snapshot
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn capacity(&self) -> Option<usize>;
+}
+
+// A lock of the given kind around value. Sync as well as Send, for the RwLock's readers to share it
+pub fn new_lock<T: Send + Sync + 'static>(kind: LockKind, value: T) -> BoxedLock<T> {
+    match kind {
+        LockKind::Spin => Box::new(SpinLock::new(value)),
+        LockKind::Mutex => Box::new(Mutex::new(value)),
+        LockKind::RwLock => Box::new(RwSpinLock::new(value)),
+        LockKind::Hybrid => Box::new(HybridLock::new(value)),
+    }
+}
+
+pub fn new_channel<T: Send + 'static>(kind: ChannelKind) -> BoxedChannel<T> {
+    match kind {
+        ChannelKind::Mutex => Box::new(MutexChannel::new()),
+        ChannelKind::Bounded(capacity) => {
+            let (sender, receiver) = sync_channel(capacity);
+            Box::new(Bounded { sender, receiver })
+        }
+    }
+}
+
+impl<T> dyn DynLock<T> + '_ {
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut f = Some(f);
+        let mut result = None;
+        self.with_dyn(&mut |value| result = f.take().map(|f| f(value)));
+        result.expect("with_dyn runs the closure")
+    }
+
+    pub fn try_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut f = Some(f);
+        let mut result = None;
+        self.try_with_dyn(&mut |value| result = f.take().map(|f| f(value)));
+        result
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let mut f = Some(f);
+        let mut result = None;
+        self.read_dyn(&mut |value| result = f.take().map(|f| f(value)));
+        result.expect("read_dyn runs the closure")
+    }
+}
+
+impl<T: Send> DynLock<T> for SpinLock<T> {
+    fn with_dyn(&self, f: &mut dyn FnMut(&mut T)) {
+        self.with(f)
+    }
+
+    fn try_with_dyn(&self, f: &mut dyn FnMut(&mut T)) -> bool {
+        self.try_with(f).is_some()
+    }
+
+    fn kind(&self) -> LockKind {
+        LockKind::Spin
+    }
+}
+
+impl<T: Send> DynLock<T> for Mutex<T> {
+    fn with_dyn(&self, f: &mut dyn FnMut(&mut T)) {
+        f(&mut self.lock())
+    }
+
+    fn try_with_dyn(&self, f: &mut dyn FnMut(&mut T)) -> bool {
+        self.try_lock().map(|mut guard| f(&mut guard)).is_some()
+    }
+
+    fn kind(&self) -> LockKind {
+        LockKind::Mutex
+    }
+}
+
+impl<T: Send + Sync> DynLock<T> for RwSpinLock<T> {
+    fn with_dyn(&self, f: &mut dyn FnMut(&mut T)) {
+        f(&mut self.write())
+    }
+
+    fn try_with_dyn(&self, f: &mut dyn FnMut(&mut T)) -> bool {
+        self.try_write().map(|mut guard| f(&mut guard)).is_some()
+    }
+
+    fn read_dyn(&self, f: &mut dyn FnMut(&T)) {
+        f(&self.read())
+    }
+
+    fn kind(&self) -> LockKind {
+        LockKind::RwLock
+    }
+}
+
+impl<T: Send> DynLock<T> for HybridLock<T> {
+    fn with_dyn(&self, f: &mut dyn FnMut(&mut T)) {
+        f(&mut self.lock())
+    }
+
+    fn try_with_dyn(&self, f: &mut dyn FnMut(&mut T)) -> bool {
+        self.try_lock().map(|mut guard| f(&mut guard)).is_some()
+    }
+
+    fn kind(&self) -> LockKind {
+        LockKind::Hybrid
+    }
+}
+
+impl<T: Send> DynChannel<T> for MutexChannel<T> {
+    fn send(&self, message: T) {
+        MutexChannel::send(self, message)
+    }
+
+    // Only full with a byte budget
+    fn try_send(&self, message: T) -> Result<(), T> {
+        MutexChannel::try_send(self, message)
+    }
+
+    fn receive(&self) -> T {
+        MutexChannel::receive(self)
+    }
+
+    fn try_receive(&self) -> Option<T> {
+        MutexChannel::try_receive(self)
+    }
+
+    fn len(&self) -> usize {
+        MutexChannel::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        MutexChannel::capacity(self)
+    }
+}
+
+// Both ends of a bounded channel, which is what keeps it from ever disconnecting
+struct Bounded<T> {
+    sender: Sender<T>,
+    receiver: Receiver<T>,
+}
+
+impl<T: Send> DynChannel<T> for Bounded<T> {
+    fn send(&self, message: T) {
+        let _ = self.sender.send(message);
+    }
+
+    fn try_send(&self, message: T) -> Result<(), T> {
+        self.sender.try_send(message).map_err(|(TrySendError::Full(m) | TrySendError::Disconnected(m))| m)
+    }
+
+    fn receive(&self) -> T {
+        self.receiver.receive().expect("a channel with its own Sender can't be disconnected")
+    }
+
+    fn try_receive(&self) -> Option<T> {
+        self.receiver.try_receive().ok()
+    }
+
+    fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.receiver.capacity()
+    }
+}
+
+impl FromStr for LockKind {
+    type Err = ParseKindError;
+
+    fn from_str(s: &str) -> Result<Self, ParseKindError> {
+        match s {
+            "spin" => Ok(LockKind::Spin),
+            "mutex" => Ok(LockKind::Mutex),
+            "rwlock" => Ok(LockKind::RwLock),
+            "hybrid" => Ok(LockKind::Hybrid),
+            _ => Err(ParseKindError(s.to_owned())),
+        }
+    }
+}
+
+impl FromStr for ChannelKind {
+    type Err = ParseKindError;
+
+    fn from_str(s: &str) -> Result<Self, ParseKindError> {
+        if s == "mutex" {
+            return Ok(ChannelKind::Mutex);
+        }
+        s.strip_prefix("bounded:")
+            .and_then(|capacity| capacity.parse().ok())
+            .filter(|&capacity| capacity > 0)
+            .map(ChannelKind::Bounded)
+            .ok_or_else(|| ParseKindError(s.to_owned()))
+    }
+}
+
+impl fmt::Display for ParseKindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown lock or channel kind {:?}", self.0)
+    }
+}
+
+impl StdError for ParseKindError {}
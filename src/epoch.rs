@@ -0,0 +1,261 @@
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize};
+
+use crate::mutex::Mutex;
+use crate::ordering::{Relaxed, Release, Acquire, SeqCst};
+
+// Epoch based memory reclamation, for lock-free structures that unlink memory other threads might still be
+// reading. Freeing it straight away is a use after free waiting to happen, so instead it's handed over here
+// and freed once no thread can possibly still have a pointer to it.
+//
+// Every operation on the structure pins the current thread first. There's a global epoch, and a pinned thread
+// records the epoch it saw when it pinned. The epoch only moves forward when every pinned thread has caught up
+// with it, so once it's moved on twice since something was unlinked, every thread that was pinned back then has
+// unpinned since - and a thread that pinned later can't have found the unlinked memory. Garbage is tagged with
+// the epoch it was retired in and freed two epochs later.
+//
+// Freeing happens a bit at a time as threads pin, out of each thread's own bag of garbage, so there's no
+// background thread and no lock on the way in or out (a thread that exits with garbage left passes it to a
+// shared list, which is the one place there's a lock)
+
+// How many pins between a thread trying to move the epoch on and freeing what it can
+const PINS_BETWEEN_COLLECTS: usize = 64;
+
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+// Every thread that's ever pinned, as a linked list that's only ever pushed onto. A thread's entry is marked
+// free when it exits and reused by the next new thread, so the list only grows as far as the most threads
+// there have been at once
+static PARTICIPANTS: AtomicPtr<Participant> = AtomicPtr::new(ptr::null_mut());
+// Garbage from threads that exited before it could be freed
+static ORPHANS: Mutex<Vec<Deferred>> = Mutex::new(Vec::new());
+
+const PINNED: usize = 1;
+
+struct Participant {
+    // the epoch the thread pinned in, shifted up one, with PINNED in the lowest bit while it's pinned
+    state: AtomicUsize,
+    in_use: AtomicBool,
+    next: *const Participant,
+}
+
+// the list hands out &'static Participants to any thread, and everything in one is atomic
+unsafe impl Sync for Participant {}
+
+impl Participant {
+    // Takes a free entry from the list, or adds a new one
+    fn acquire() -> &'static Participant {
+        let mut p = PARTICIPANTS.load(Acquire);
+        while let Some(participant) = unsafe { p.as_ref() } {
+            if participant.in_use.compare_exchange(false, true, Acquire, Relaxed).is_ok() {
+                return participant;
+            }
+            p = participant.next.cast_mut();
+        }
+        // leaked on purpose, the list lives as long as the program
+        let new = Box::leak(Box::new(Participant {
+            state: AtomicUsize::new(0),
+            in_use: AtomicBool::new(true),
+            next: ptr::null(),
+        }));
+        let mut head = PARTICIPANTS.load(Relaxed);
+        loop {
+            new.next = head;
+            match PARTICIPANTS.compare_exchange_weak(head, new, Release, Relaxed) {
+                Ok(_) => return new,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn release(&self) {
+        self.state.store(0, Release);
+        self.in_use.store(false, Release);
+    }
+
+    fn pin(&self) {
+        self.state.store(EPOCH.load(Relaxed) << 1 | PINNED, Relaxed);
+        // the pin has to be visible to a thread trying to move the epoch on before this thread goes on to read
+        // anything from the structure, which takes a full fence
+        fence(SeqCst);
+    }
+
+    fn unpin(&self) {
+        // Release so everything this thread read while pinned happens before the epoch can move past it
+        self.state.store(0, Release);
+    }
+}
+
+fn participants() -> impl Iterator<Item = &'static Participant> {
+    let mut p = PARTICIPANTS.load(Acquire);
+    std::iter::from_fn(move || {
+        let participant = unsafe { p.as_ref()? };
+        p = participant.next.cast_mut();
+        Some(participant)
+    })
+}
+
+// Moves the epoch on if every pinned thread has seen the current one, and returns the epoch as it is now
+fn try_advance() -> usize {
+    let epoch = EPOCH.load(Relaxed);
+    fence(SeqCst);
+    for participant in participants() {
+        let state = participant.state.load(Relaxed);
+        if state & PINNED != 0 && state >> 1 != epoch {
+            return epoch;
+        }
+    }
+    fence(Acquire);
+    match EPOCH.compare_exchange(epoch, epoch + 1, Release, Relaxed) {
+        Ok(_) => epoch + 1,
+        Err(actual) => actual,
+    }
+}
+
+// Something to free later. It's a pointer and the function that frees it rather than a boxed closure, so it
+// doesn't need an allocation of its own or a 'static type
+struct Deferred {
+    epoch: usize,
+    ptr: *mut u8,
+    destroy: unsafe fn(*mut u8),
+}
+
+// Only ever run once, by whichever thread finds it's safe to
+unsafe impl Send for Deferred {}
+
+impl Deferred {
+    fn run(self) {
+        unsafe { (self.destroy)(self.ptr) }
+    }
+}
+
+unsafe fn drop_box<T>(ptr: *mut u8) {
+    drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+}
+
+// Takes out the garbage that's at least two epochs old, which is safe to free
+fn collect_from(garbage: &mut Vec<Deferred>, epoch: usize) -> Vec<Deferred> {
+    let (ripe, rest) = mem::take(garbage).into_iter().partition(|d| d.epoch + 2 <= epoch);
+    *garbage = rest;
+    ripe
+}
+
+struct Local {
+    participant: &'static Participant,
+    // pins nest, and only the outermost one pins the participant
+    guards: Cell<usize>,
+    pins: Cell<usize>,
+    bag: RefCell<Vec<Deferred>>,
+}
+
+impl Local {
+    fn new() -> Self {
+        Self {
+            participant: Participant::acquire(),
+            guards: Cell::new(0),
+            pins: Cell::new(0),
+            bag: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn pin(&self) {
+        let guards = self.guards.get();
+        self.guards.set(guards + 1);
+        if guards > 0 {
+            return;
+        }
+        self.participant.pin();
+        let pins = self.pins.get().wrapping_add(1);
+        self.pins.set(pins);
+        if pins.is_multiple_of(PINS_BETWEEN_COLLECTS) {
+            self.collect();
+        }
+    }
+
+    fn unpin(&self) {
+        let guards = self.guards.get() - 1;
+        self.guards.set(guards);
+        if guards == 0 {
+            self.participant.unpin();
+        }
+    }
+
+    fn collect(&self) {
+        let epoch = try_advance();
+        // freed after the borrow ends, as dropping the garbage could (in theory) defer more
+        let ripe = collect_from(&mut self.bag.borrow_mut(), epoch);
+        ripe.into_iter().for_each(Deferred::run);
+        // the orphans are nobody's in particular, so only take them if nobody else is already
+        let orphans = ORPHANS.try_lock().map(|mut orphans| collect_from(&mut orphans, epoch));
+        orphans.into_iter().flatten().for_each(Deferred::run);
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        let bag = mem::take(self.bag.get_mut());
+        if !bag.is_empty() {
+            ORPHANS.lock().extend(bag);
+        }
+        self.participant.release();
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local::new();
+}
+
+// Pins the current thread until the guard is dropped. Nothing retired while it's pinned is freed until after
+pub fn pin() -> Guard {
+    match LOCAL.try_with(Local::pin) {
+        Ok(()) => Guard { participant: None, _not_send: PhantomData },
+        // the thread local is gone (this is some other thread local's destructor), so pin with an entry of
+        // our own for the guard's lifetime
+        Err(_) => {
+            let participant = Participant::acquire();
+            participant.pin();
+            Guard { participant: Some(participant), _not_send: PhantomData }
+        }
+    }
+}
+
+pub struct Guard {
+    // only set when the thread local couldn't be used
+    participant: Option<&'static Participant>,
+    // the pin belongs to this thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Guard {
+    // Frees the Box at ptr once no thread can still be reading it.
+    /// # Safety
+    /// ptr has to come from Box::into_raw and be unreachable for any thread that pins from now on (unlinked
+    /// from the structure), and it mustn't be retired twice. Dropping T later than now, on whichever thread
+    /// gets round to it, has to be fine
+    pub unsafe fn defer_destroy<T>(&self, ptr: *mut T) {
+        let mut deferred = Some(Deferred { epoch: EPOCH.load(SeqCst), ptr: ptr.cast(), destroy: drop_box::<T> });
+        if self.participant.is_none() {
+            let _ = LOCAL.try_with(|local| local.bag.borrow_mut().extend(deferred.take()));
+        }
+        // no bag of our own to put it in
+        if let Some(deferred) = deferred {
+            ORPHANS.lock().push(deferred);
+        }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        match self.participant {
+            None => {
+                let _ = LOCAL.try_with(Local::unpin);
+            }
+            Some(participant) => {
+                participant.unpin();
+                participant.release();
+            }
+        }
+    }
+}
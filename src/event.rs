@@ -0,0 +1,87 @@
+use std::sync::atomic::AtomicUsize;
+use std::time::Instant;
+
+use crate::deadline::Deadline;
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::trace::trace_event;
+use crate::waitqueue::WaitQueue;
+
+// A manual-reset event (like the Windows one): once set, every waiting thread is released and any thread
+// that calls wait afterwards returns straight away, until the event is reset again
+pub struct Event {
+    // The lowest bit says whether the event is set, the rest of the bits count how many times it's been set.
+    // The count means a waiter that misses a quick set + reset still notices that it should wake up
+    state: AtomicUsize,
+    // The threads currently parked in wait, so set knows who to unpark
+    waiters: WaitQueue,
+}
+
+impl Event {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn set(&self) {
+        // Only bump the count if the event wasn't already set - setting twice shouldn't look like two sets
+        // Release matches the Acquire load in wait, so anything written before set is visible to the waiters
+        if self.state.fetch_update(Release, Relaxed, |s| (s & 1 == 0).then_some(s + 3)).is_err() {
+            return;
+        }
+        let woken = self.waiters.wake_all();
+        trace_event!(waiters = woken, "event set, unparked waiters");
+        #[cfg(not(feature = "tracing"))]
+        let _ = woken;
+    }
+
+    pub fn reset(&self) {
+        self.state.fetch_and(!1, Relaxed);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.state.load(Acquire) & 1 == 1
+    }
+
+    // Blocks until the event is set
+    pub fn wait(&self) {
+        self.wait_until(None);
+    }
+
+    // Blocks until the event is set or the timeout runs out. Returns whether the event was set
+    pub fn wait_timeout(&self, timeout: impl Into<Deadline>) -> bool {
+        self.wait_until(timeout.into().instant())
+    }
+
+    fn wait_until(&self, deadline: Option<Instant>) -> bool {
+        let s = self.state.load(Acquire);
+        if s & 1 == 1 {
+            return true;
+        }
+        loop {
+            // Checked after joining the queue, otherwise a set in between the first load and joining would be
+            // missed. Any change to the state at all means a set happened, as reset on an unset event changes
+            // nothing
+            self.waiters.wait_until(deadline, || {
+                let unset = self.state.load(Acquire) == s;
+                if unset {
+                    trace_event!("event waiter parking");
+                }
+                unset
+            });
+            if self.state.load(Acquire) != s {
+                return true;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
+        }
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+#[cfg(feature = "blocking_check")]
+use crate::blockingcheck;
+use crate::mutex::Mutex;
+use crate::ordering::Relaxed;
+use crate::parker::Parker;
+use crate::trace::check_blocking;
+
+// A tiny single threaded async runtime, for running futures without pulling in tokio: block_on for one future,
+// and Executor for a handful of tasks taking turns on the current thread. Neither has any IO or timers of its
+// own - a future gets woken by whatever it's waiting for calling its Waker, usually through an AtomicWaker.
+//
+// Built out of the crate's own parts: the thread sleeps on a Parker while nothing's ready, the Wakers unpark it,
+// and the queue of woken tasks is a Mutex
+
+// Runs the future to completion on the current thread, sleeping whenever it's pending until it's woken
+#[cfg_attr(feature = "blocking_check", track_caller)]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    // a block_on inside a task blocks the outer executor's thread like anything else
+    check_blocking!("block_on");
+    #[cfg(feature = "blocking_check")]
+    let _running = blockingcheck::enter();
+    let parker = Arc::new(Parker::new());
+    let waker = Waker::from(parker.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        parker.park();
+    }
+}
+
+// A set of tasks that run on the thread that calls run, polled one at a time as they're woken. They're never
+// moved to another thread, so they don't have to be Send
+pub struct Executor {
+    tasks: Vec<Task>,
+    shared: Arc<Shared>,
+}
+
+struct Task {
+    future: Option<Pin<Box<dyn Future<Output = ()>>>>,
+    waker: Arc<TaskWaker>,
+}
+
+struct Shared {
+    // tasks that have been woken and are waiting for a poll, in the order they were woken
+    ready: Mutex<VecDeque<usize>>,
+    parker: Parker,
+}
+
+struct TaskWaker {
+    id: usize,
+    // set while the task is in the ready queue, so waking it again before it's polled doesn't queue it twice
+    queued: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    // Wakers can be called from any thread, which is why the queue is behind a lock
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.queued.swap(true, Relaxed) {
+            self.shared.ready.lock().push_back(self.id);
+            self.shared.parker.unpark();
+        }
+    }
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        let shared = Shared { ready: Mutex::new(VecDeque::new()), parker: Parker::new() };
+        Self { tasks: Vec::new(), shared: Arc::new(shared) }
+    }
+
+    // Adds a task, which gets its first poll once run is called
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'static) {
+        let id = self.tasks.len();
+        let waker = Arc::new(TaskWaker { id, queued: AtomicBool::new(false), shared: self.shared.clone() });
+        waker.wake_by_ref();
+        self.tasks.push(Task { future: Some(Box::pin(future)), waker });
+    }
+
+    // Polls tasks as they're woken until every one of them has finished, sleeping while none are ready
+    pub fn run(mut self) {
+        #[cfg(feature = "blocking_check")]
+        let _running = blockingcheck::enter();
+        let mut remaining = self.tasks.len();
+        while remaining > 0 {
+            let Some(id) = self.shared.ready.lock().pop_front() else {
+                self.shared.parker.park();
+                continue;
+            };
+            let task = &mut self.tasks[id];
+            // a task can be woken again after it's finished, by a Waker it left somewhere
+            let Some(future) = task.future.as_mut() else { continue };
+            // cleared before the poll, so a wake during the poll queues it up again
+            task.waker.queued.store(false, Relaxed);
+            let waker = Waker::from(task.waker.clone());
+            if future.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                task.future = None;
+                remaining -= 1;
+            }
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
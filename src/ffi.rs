@@ -0,0 +1,143 @@
+use std::ffi::c_void;
+use std::mem;
+use std::ptr;
+use std::time::Duration;
+
+use crate::boundedchannel::{sync_channel, Receiver, Sender, TryRecvError};
+use crate::spinlock::SpinLock;
+
+// extern "C" functions for the SpinLock and the bounded channel, so C and C++ code in the same program can use
+// the very same implementations as the Rust side (and share them with it). include/atomiclocks.h declares them;
+// cbindgen.toml is the config to regenerate it with after changing anything here:
+//
+//     cbindgen --config cbindgen.toml --output include/atomiclocks.h
+//
+// Build the crate as a staticlib or cdylib to link it in, e.g.
+// cargo rustc --release --features ffi --crate-type staticlib.
+//
+// Everything's handed out as an opaque pointer from a _new function and given back with the _free one. C has no
+// guards, so unlocking's a call of its own, and it's up to the caller to pair them up
+
+// A SpinLock with nothing in it - the C side keeps whatever it protects itself
+pub struct AtomicLocksSpinLock {
+    lock: SpinLock<()>,
+}
+
+// A bounded channel of void pointers, both ends in one. What the pointers point at is up to the C side: the
+// channel only moves them, and never frees them
+pub struct AtomicLocksChannel {
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
+}
+
+struct Message(*mut c_void);
+
+// Whoever sends a pointer is handing over whatever it points at, which is the C side's lookout
+unsafe impl Send for Message {}
+
+#[no_mangle]
+pub extern "C" fn atomiclocks_spinlock_new() -> *mut AtomicLocksSpinLock {
+    Box::into_raw(Box::new(AtomicLocksSpinLock { lock: SpinLock::new(()) }))
+}
+
+/// # Safety
+/// lock has to have come from atomiclocks_spinlock_new and not been freed
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_spinlock_lock(lock: *const AtomicLocksSpinLock) {
+    // the guard's forgotten, atomiclocks_spinlock_unlock is what lets go
+    mem::forget((*lock).lock.lock());
+}
+
+// Returns whether it locked it
+/// # Safety
+/// Same as atomiclocks_spinlock_lock
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_spinlock_try_lock(lock: *const AtomicLocksSpinLock) -> bool {
+    // a timeout that's already up still gets one go at it
+    (*lock).lock.lock_timeout(Duration::ZERO).map(mem::forget).is_ok()
+}
+
+/// # Safety
+/// Same as atomiclocks_spinlock_lock, and the lock has to be locked by the caller
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_spinlock_unlock(lock: *const AtomicLocksSpinLock) {
+    (*lock).lock.force_unlock();
+}
+
+/// # Safety
+/// lock has to have come from atomiclocks_spinlock_new, and nothing can use it afterwards. Null is fine
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_spinlock_free(lock: *mut AtomicLocksSpinLock) {
+    if !lock.is_null() {
+        drop(Box::from_raw(lock));
+    }
+}
+
+// A channel that holds up to capacity messages before send blocks. Null for a capacity of 0, which the
+// bounded channel would panic on - and a panic can't unwind into C, it'd abort the whole process
+#[no_mangle]
+pub extern "C" fn atomiclocks_channel_new(capacity: usize) -> *mut AtomicLocksChannel {
+    if capacity == 0 {
+        return ptr::null_mut();
+    }
+    let (sender, receiver) = sync_channel(capacity);
+    Box::into_raw(Box::new(AtomicLocksChannel { sender, receiver }))
+}
+
+// Blocks while the channel's full
+/// # Safety
+/// channel has to have come from atomiclocks_channel_new and not been freed
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_channel_send(channel: *const AtomicLocksChannel, message: *mut c_void) {
+    // both ends are in the channel, so it can't be disconnected while there's a pointer to it
+    let _ = (*channel).sender.send(Message(message));
+}
+
+// Returns false, and leaves the message with the caller, if the channel's full
+/// # Safety
+/// Same as atomiclocks_channel_send
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_channel_try_send(
+    channel: *const AtomicLocksChannel,
+    message: *mut c_void,
+) -> bool {
+    (*channel).sender.try_send(Message(message)).is_ok()
+}
+
+// Blocks while the channel's empty
+/// # Safety
+/// Same as atomiclocks_channel_send
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_channel_recv(channel: *const AtomicLocksChannel) -> *mut c_void {
+    match (*channel).receiver.receive() {
+        Ok(Message(message)) => message,
+        Err(_) => unreachable!("the channel holds a sender of its own"),
+    }
+}
+
+// Returns false if the channel's empty, and otherwise writes the message to *message
+/// # Safety
+/// Same as atomiclocks_channel_send, and message has to be valid to write a pointer to
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_channel_try_recv(
+    channel: *const AtomicLocksChannel,
+    message: *mut *mut c_void,
+) -> bool {
+    match (*channel).receiver.try_receive() {
+        Ok(Message(received)) => {
+            message.write(received);
+            true
+        }
+        Err(TryRecvError::Empty | TryRecvError::Disconnected) => false,
+    }
+}
+
+// Messages left in it are dropped, which for a pointer means forgotten - free what they point at first
+/// # Safety
+/// channel has to have come from atomiclocks_channel_new, and nothing can use it afterwards. Null is fine
+#[no_mangle]
+pub unsafe extern "C" fn atomiclocks_channel_free(channel: *mut AtomicLocksChannel) {
+    if !channel.is_null() {
+        drop(Box::from_raw(channel));
+    }
+}
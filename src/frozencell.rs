@@ -0,0 +1,166 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::AtomicU8;
+
+use crate::atomicupdate::Backoff;
+use crate::ordering::{AcqRel, Acquire, Relaxed, Release};
+use crate::sched::pause;
+
+const WRITABLE: u8 = 0;
+// a write guard has it
+const WRITING: u8 = 1;
+const FROZEN: u8 = 2;
+
+// A value that's written while the program starts up and never again - config, lookup tables, a registry of
+// handlers. Until it's frozen it's behind a lock (a small spinlock, as it's only for startup); freeze turns it
+// read-only for good, and from then on reading it needs no lock at all:
+//
+//     static CONFIG: FrozenCell<Config> = FrozenCell::new(Config::DEFAULT);
+//     CONFIG.write()?.port = args.port;
+//     let config = CONFIG.freeze();
+//
+// The &T freeze hands back can be read from forever with nothing but plain loads. get gets at it again from
+// anywhere else, at the cost of one Acquire load of the state (a plain load on x86 too), and get_unchecked skips
+// even that, with the state only checked in debug builds
+pub struct FrozenCell<T> {
+    state: AtomicU8,
+    value: UnsafeCell<T>,
+}
+
+// Written from whichever thread has the write guard (so T: Send), then read from every thread at once (T: Sync)
+unsafe impl<T> Sync for FrozenCell<T> where T: Send + Sync {}
+
+// What write returns once the cell's been frozen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frozen;
+
+impl fmt::Display for Frozen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the cell has been frozen")
+    }
+}
+
+impl std::error::Error for Frozen {}
+
+impl<T> FrozenCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self { state: AtomicU8::new(WRITABLE), value: UnsafeCell::new(value) }
+    }
+
+    // Locks it for writing, waiting for another writer to finish first. Err once it's frozen
+    pub fn write(&self) -> Result<WriteGuard<'_, T>, Frozen> {
+        let mut backoff = Backoff::new();
+        loop {
+            match self.state.compare_exchange_weak(WRITABLE, WRITING, Acquire, Relaxed) {
+                Ok(_) => return Ok(WriteGuard { cell: self }),
+                Err(FROZEN) => return Err(Frozen),
+                // another writer, or a spurious failure
+                Err(_) => backoff.spin(),
+            }
+        }
+    }
+
+    // Makes it read-only for good, once any write in progress is done, and hands back the value. Freezing it
+    // again is fine, and just hands it back again
+    pub fn freeze(&self) -> &T {
+        let mut backoff = Backoff::new();
+        loop {
+            // Acquire, so the last writer's writes are all visible to this thread, and everyone who sees FROZEN
+            // after it gets them from the Release half
+            match self.state.compare_exchange_weak(WRITABLE, FROZEN, AcqRel, Acquire) {
+                Ok(_) | Err(FROZEN) => break,
+                Err(_) => backoff.spin(),
+            }
+        }
+        pause!("FrozenCell::freeze frozen");
+        // Safety: it's frozen, so nothing will ever write to it again
+        unsafe { &*self.value.get() }
+    }
+
+    // The value, panicking if it hasn't been frozen yet - reading it before then would race with the writers
+    pub fn get(&self) -> &T {
+        self.try_get().expect("FrozenCell read before it was frozen")
+    }
+
+    // The value if it's been frozen
+    pub fn try_get(&self) -> Option<&T> {
+        if self.state.load(Acquire) != FROZEN {
+            return None;
+        }
+        // Safety: frozen, and the Acquire pairs with freeze, so the writes before it are all visible
+        Some(unsafe { &*self.value.get() })
+    }
+
+    // The value with no check at all in release builds, for the hottest reads
+    /// # Safety
+    /// The cell has to have been frozen, and this thread has to have seen it frozen - by calling freeze or get
+    /// itself, or through something that synchronizes with a thread that did (the spawn of a thread after
+    /// freezing, say)
+    pub unsafe fn get_unchecked(&self) -> &T {
+        debug_assert_eq!(self.state.load(Relaxed), FROZEN, "FrozenCell read before it was frozen");
+        &*self.value.get()
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.state.load(Relaxed) == FROZEN
+    }
+
+    // The &mut means nobody else can be reading it, so this works frozen or not
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for FrozenCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for FrozenCell<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// Shows the value once it's frozen, but doesn't wait for a writer to get at it before then
+impl<T: fmt::Debug> fmt::Debug for FrozenCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_tuple("FrozenCell").field(value).finish(),
+            None => f.write_str("FrozenCell(<not frozen>)"),
+        }
+    }
+}
+
+pub struct WriteGuard<'a, T> {
+    cell: &'a FrozenCell<T>,
+}
+
+unsafe impl<T: Send> Send for WriteGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for WriteGuard<'_, T> {}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: the guard has the cell to itself, and it can't be frozen until the guard goes
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.cell.state.store(WRITABLE, Release);
+    }
+}
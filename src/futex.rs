@@ -0,0 +1,241 @@
+use std::cell::Cell;
+use std::sync::atomic::AtomicU32;
+use std::thread;
+use std::time::Duration;
+
+// Waiting on an atomic directly, like a futex: a thread goes to sleep until another thread changes the
+// atomic and wakes it up. It's what thread::park is built on, but without having to keep a list of Thread
+// handles around - the address of the atomic is the queue. This is the one place the crate talks to the OS
+// about parking, so the locks built on it all park the same way.
+//
+// Linux uses the futex syscall, Windows uses WaitOnAddress and macOS uses __ulock_wait. wasm32 built with the
+// atomics target feature uses memory.atomic.wait32, the instruction behind JS's Atomics.wait (which needs
+// nightly, but so does building std for threaded wasm). Anywhere else wait just yields, which is allowed as
+// waits can always wake up spuriously.
+//
+// The primitives that keep their own list of waiters (the bounded channel, the oneshot channel, the rendezvous
+// channel) park with thread::park instead, but they go through park below so that set_spin_hook covers them too
+
+thread_local! {
+    static SPIN_HOOK: Cell<Option<fn()>> = const { Cell::new(None) };
+}
+
+// Makes every wait on the calling thread spin instead of sleeping, calling hook each time round the loop -
+// for the browser's main thread, where Atomics.wait throws rather than blocking. The hook's whatever lets the
+// rest of the program get on with it (or just spin_loop). The wakes are still sent, so threads that do sleep
+// aren't affected. None goes back to sleeping
+pub fn set_spin_hook(hook: Option<fn()>) {
+    SPIN_HOOK.with(|h| h.set(hook));
+}
+
+fn spin_hook() -> Option<fn()> {
+    SPIN_HOOK.with(Cell::get)
+}
+
+// Blocks while the atomic still holds expected. The check and going to sleep happen as one step in the
+// kernel, so a wake that comes after the value changed can't be missed. It can return spuriously (or
+// straight away, if the value is already different), so callers always check the value again in a loop
+pub fn wait(a: &AtomicU32, expected: u32) {
+    match spin_hook() {
+        Some(hook) => spin(a, expected, hook),
+        None => imp::wait(a, expected),
+    }
+}
+
+// Same as wait, but gives up after timeout. Returning says nothing about whether it timed out - like wait it can
+// return early for no reason - so callers keep their own deadline
+pub fn wait_timeout(a: &AtomicU32, expected: u32, timeout: Duration) {
+    match spin_hook() {
+        Some(hook) => spin(a, expected, hook),
+        None => imp::wait_timeout(a, expected, timeout),
+    }
+}
+
+// One go round a caller's wait loop, for a thread with a spin hook
+fn spin(a: &AtomicU32, expected: u32, hook: fn()) {
+    if a.load(crate::ordering::Relaxed) == expected {
+        hook();
+    }
+}
+
+// thread::park, or thread::park_timeout with a timeout, unless the thread has a spin hook, in which case it's
+// one call of the hook. Both can return for no reason, so it's the same for the callers' loops
+pub(crate) fn park(timeout: Option<Duration>) {
+    match (spin_hook(), timeout) {
+        (Some(hook), _) => hook(),
+        (None, None) => thread::park(),
+        (None, Some(timeout)) => thread::park_timeout(timeout),
+    }
+}
+
+// Wakes one thread waiting on the atomic, if there are any
+pub fn wake_one(a: &AtomicU32) {
+    imp::wake_one(a)
+}
+
+// Wakes every thread waiting on the atomic
+pub fn wake_all(a: &AtomicU32) {
+    imp::wake_all(a)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::sync::atomic::AtomicU32;
+    use std::time::Duration;
+
+    fn futex_wait(a: &AtomicU32, expected: u32, timeout: *const libc::timespec) {
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                a as *const AtomicU32,
+                libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                expected,
+                timeout,
+            );
+        }
+    }
+
+    pub fn wait(a: &AtomicU32, expected: u32) {
+        futex_wait(a, expected, std::ptr::null())
+    }
+
+    // FUTEX_WAIT's timeout is relative. A timeout too long for a timespec is as good as none
+    pub fn wait_timeout(a: &AtomicU32, expected: u32, timeout: Duration) {
+        match libc::time_t::try_from(timeout.as_secs()) {
+            Ok(secs) => {
+                let ts = libc::timespec { tv_sec: secs, tv_nsec: timeout.subsec_nanos() as _ };
+                futex_wait(a, expected, &ts)
+            }
+            Err(_) => wait(a, expected),
+        }
+    }
+
+    fn wake(a: &AtomicU32, n: i32) {
+        unsafe {
+            libc::syscall(libc::SYS_futex, a as *const AtomicU32, libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG, n);
+        }
+    }
+
+    pub fn wake_one(a: &AtomicU32) {
+        wake(a, 1)
+    }
+
+    pub fn wake_all(a: &AtomicU32) {
+        wake(a, i32::MAX)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::ffi::{c_int, c_void};
+    use std::sync::atomic::AtomicU32;
+    use std::time::Duration;
+
+    // Not in libc, but it's what libc++ uses for std::atomic::wait and it's been stable since macOS 10.12
+    extern "C" {
+        fn __ulock_wait(operation: u32, addr: *mut c_void, value: u64, timeout_us: u32) -> c_int;
+        fn __ulock_wake(operation: u32, addr: *mut c_void, wake_value: u64) -> c_int;
+    }
+
+    const UL_COMPARE_AND_WAIT: u32 = 1;
+    const ULF_WAKE_ALL: u32 = 0x100;
+    const ULF_NO_ERRNO: u32 = 0x0100_0000;
+
+    pub fn wait(a: &AtomicU32, expected: u32) {
+        // a timeout of 0 waits forever
+        unsafe { __ulock_wait(UL_COMPARE_AND_WAIT | ULF_NO_ERRNO, a.as_ptr().cast(), expected as u64, 0) };
+    }
+
+    // In microseconds, rounded up so a short timeout doesn't turn into 0 (forever), and capped at the longest
+    // a u32 holds - the caller's deadline takes care of anything longer
+    pub fn wait_timeout(a: &AtomicU32, expected: u32, timeout: Duration) {
+        let us = timeout.as_nanos().div_ceil(1000).clamp(1, u32::MAX as u128) as u32;
+        unsafe { __ulock_wait(UL_COMPARE_AND_WAIT | ULF_NO_ERRNO, a.as_ptr().cast(), expected as u64, us) };
+    }
+
+    pub fn wake_one(a: &AtomicU32) {
+        unsafe { __ulock_wake(UL_COMPARE_AND_WAIT | ULF_NO_ERRNO, a.as_ptr().cast(), 0) };
+    }
+
+    pub fn wake_all(a: &AtomicU32) {
+        unsafe { __ulock_wake(UL_COMPARE_AND_WAIT | ULF_WAKE_ALL | ULF_NO_ERRNO, a.as_ptr().cast(), 0) };
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::sync::atomic::AtomicU32;
+    use std::time::Duration;
+    use windows_sys::Win32::System::Threading::{WaitOnAddress, WakeByAddressAll, WakeByAddressSingle, INFINITE};
+
+    pub fn wait(a: &AtomicU32, expected: u32) {
+        let expected_ptr: *const u32 = &expected;
+        unsafe { WaitOnAddress(a.as_ptr().cast(), expected_ptr.cast(), 4, INFINITE) };
+    }
+
+    // In milliseconds, rounded up, and kept under INFINITE so a long timeout still times out
+    pub fn wait_timeout(a: &AtomicU32, expected: u32, timeout: Duration) {
+        let ms = timeout.as_nanos().div_ceil(1_000_000).min(INFINITE as u128 - 1) as u32;
+        let expected_ptr: *const u32 = &expected;
+        unsafe { WaitOnAddress(a.as_ptr().cast(), expected_ptr.cast(), 4, ms) };
+    }
+
+    pub fn wake_one(a: &AtomicU32) {
+        unsafe { WakeByAddressSingle(a.as_ptr().cast()) };
+    }
+
+    pub fn wake_all(a: &AtomicU32) {
+        unsafe { WakeByAddressAll(a.as_ptr().cast()) };
+    }
+}
+
+// memory.atomic.wait32 compares and sleeps as one step, like the futex syscall
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+mod imp {
+    use std::arch::wasm32::{memory_atomic_notify, memory_atomic_wait32};
+    use std::sync::atomic::AtomicU32;
+    use std::time::Duration;
+
+    pub fn wait(a: &AtomicU32, expected: u32) {
+        // a negative timeout waits forever
+        unsafe { memory_atomic_wait32(a.as_ptr().cast(), expected as i32, -1) };
+    }
+
+    // In nanoseconds, capped at the longest an i64 holds - which is a few hundred years
+    pub fn wait_timeout(a: &AtomicU32, expected: u32, timeout: Duration) {
+        let ns = timeout.as_nanos().min(i64::MAX as u128) as i64;
+        unsafe { memory_atomic_wait32(a.as_ptr().cast(), expected as i32, ns) };
+    }
+
+    pub fn wake_one(a: &AtomicU32) {
+        unsafe { memory_atomic_notify(a.as_ptr().cast(), 1) };
+    }
+
+    pub fn wake_all(a: &AtomicU32) {
+        unsafe { memory_atomic_notify(a.as_ptr().cast(), u32::MAX) };
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    windows,
+    all(target_arch = "wasm32", target_feature = "atomics")
+)))]
+mod imp {
+    use std::sync::atomic::AtomicU32;
+
+    pub fn wait(a: &AtomicU32, expected: u32) {
+        if a.load(crate::ordering::Relaxed) == expected {
+            std::thread::yield_now();
+        }
+    }
+
+    pub fn wait_timeout(a: &AtomicU32, expected: u32, _timeout: std::time::Duration) {
+        wait(a, expected)
+    }
+
+    pub fn wake_one(_a: &AtomicU32) {}
+
+    pub fn wake_all(_a: &AtomicU32) {}
+}
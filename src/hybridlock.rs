@@ -0,0 +1,199 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU32};
+
+use crate::futex::{wait, wake_one};
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::relax::cpu_relax;
+use crate::sched::pause;
+use crate::trace::{check_blocking, trace_event};
+use crate::waitstrategy::cpu_budget;
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+// Locked, and there may be threads asleep waiting for it
+const CONTENDED: u32 = 2;
+
+// How long a waiter spins before it sleeps, in cpu_relaxes: long enough to outlast most short critical sections
+// while spinning, and only long enough to catch the lock being let go right away while parking
+const SPINNING_BUDGET: u32 = 1 << 12;
+const PARKING_BUDGET: u32 = 64;
+
+// Contended locks are looked at in windows of this many, and the one that fills a window decides on the mode
+const WINDOW: u32 = 64;
+// Failures (waits that outlasted the spin) are counted in the top half of the window's atomic
+const FAILURE: u32 = 1 << 16;
+
+// A lock that picks between being a spinlock and a Mutex by itself, going by how it's used. A spinlock is the
+// fastest thing there is for a critical section of a few instructions and the worst for one that takes a while,
+// which is the Mutex's strength - and which one a lock needs can change as the program runs.
+//
+// It's the futex Mutex underneath, so a waiter can always go to sleep. What changes is how long waiters spin first:
+// a long while in the spinning mode, barely at all in the parking one. Each contended lock notes whether the spin
+// got it, and every WINDOW contended locks it looks at how many didn't - if more than a quarter of the waits while
+// spinning outlasted the spin, the critical sections are too long for spinning and it switches to parking; if
+// nearly all of them while parking were over within the short spin, they're short again and it switches back.
+// Uncontended locks don't count, as they're the same either way. Spinning never helps on a single core, so there
+// it soon settles on parking
+pub struct HybridLock<T> {
+    state: AtomicU32,
+    parking: AtomicBool,
+    // contended locks in the current window in the bottom half, and the ones the spin didn't get in the top half
+    window: AtomicU32,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T> Sync for HybridLock<T> where T: Send {}
+
+// Which way waiters are waiting at the moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Spinning,
+    Parking,
+}
+
+impl<T> HybridLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            parking: AtomicBool::new(false),
+            window: AtomicU32::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn lock(&self) -> HybridGuard<'_, T> {
+        if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_err() {
+            check_blocking!("HybridLock::lock");
+            self.lock_contended();
+        }
+        HybridGuard { lock: self }
+    }
+
+    pub fn try_lock(&self) -> Option<HybridGuard<'_, T>> {
+        self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok().then(|| HybridGuard { lock: self })
+    }
+
+    pub fn mode(&self) -> LockMode {
+        if self.parking.load(Relaxed) {
+            LockMode::Parking
+        } else {
+            LockMode::Spinning
+        }
+    }
+
+    // No locking needed, the &mut means nobody else can have the lock
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        let spun = cpu_budget().spinning_helps() && self.spin();
+        self.record(spun);
+        if spun {
+            return;
+        }
+        // the same as the Mutex from here on: marked contended, so whoever unlocks it wakes someone up
+        while self.state.swap(CONTENDED, Acquire) != UNLOCKED {
+            trace_event!("hybrid lock waiter sleeping");
+            wait(&self.state, CONTENDED);
+        }
+    }
+
+    // Spins for as long as the mode says, taking the lock if it's let go in the meantime
+    fn spin(&self) -> bool {
+        let budget = if self.parking.load(Relaxed) { PARKING_BUDGET } else { SPINNING_BUDGET };
+        for _ in 0..budget {
+            // a plain load while it's held, so the spin doesn't keep taking the cache line off the holder
+            if self.state.load(Relaxed) == UNLOCKED
+                && self.state.compare_exchange_weak(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok()
+            {
+                return true;
+            }
+            cpu_relax();
+        }
+        false
+    }
+
+    // Counts a contended lock into the window, and if it's the last one in it, picks the mode for the next one
+    fn record(&self, spun: bool) {
+        let count = if spun { 1 } else { 1 + FAILURE };
+        let counted = self.window.fetch_add(count, Relaxed) + count;
+        if counted % FAILURE != WINDOW {
+            return;
+        }
+        // locks counted between the fetch_add and here are lost, which only makes the next window a little longer
+        self.window.store(0, Relaxed);
+        let failures = counted / FAILURE;
+        let parking = self.parking.load(Relaxed);
+        if !parking && failures * 4 > WINDOW {
+            trace_event!("hybrid lock switching to parking");
+            self.parking.store(true, Relaxed);
+        } else if parking && failures * 8 < WINDOW {
+            trace_event!("hybrid lock switching to spinning");
+            self.parking.store(false, Relaxed);
+        }
+    }
+
+    fn unlock(&self) {
+        pause!("HybridLock::unlock");
+        if self.state.swap(UNLOCKED, Release) == CONTENDED {
+            wake_one(&self.state);
+        }
+    }
+}
+
+impl<T: Default> Default for HybridLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for HybridLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for HybridLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HybridLock")
+            .field("locked", &(self.state.load(Relaxed) != UNLOCKED))
+            .field("mode", &self.mode())
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct HybridGuard<'a, T> {
+    lock: &'a HybridLock<T>,
+}
+
+unsafe impl<T> Send for HybridGuard<'_, T> where T: Send {}
+unsafe impl<T> Sync for HybridGuard<'_, T> where T: Sync {}
+
+impl<T> Deref for HybridGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: the guard means the lock is held
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for HybridGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for HybridGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::mpsc;
+use std::thread;
+
+use crate::boundedchannel::{sync_channel, Receiver, Sender};
+
+// Conversions between the bounded channel's ends and std's mpsc ones (and crossbeam-channel's, with the
+// crossbeam feature), for moving a codebase over a piece at a time. A consumer can switch to this crate's
+// Receiver while its producers still send on an mpsc::Sender, or the other way around:
+//
+//     let (sender, receiver) = mpsc::channel();
+//     spawn_legacy_producers(sender);
+//     let receiver: Receiver<Event> = receiver.into();
+//
+// There's no way to turn one channel into the other, so each conversion makes a new channel on the converted
+// side and a thread to move the messages across from one to the other. That thread keeps one message in hand
+// while it waits to send it on, so there's room for one more message in between than either channel holds.
+// It stops (and drops its ends, so the rest see the disconnect) once the side it takes from has been closed by
+// every sender, or once a send fails because the other side has no receiver - which it only finds out at the
+// next message, so a bridge into a dropped receiver can sit there until one comes along.
+//
+// The crate's side of each bridge is a bounded channel of capacity 1, so what backpressure there is carries
+// across: a sender converted from a SyncSender still blocks while the channel behind it is full
+
+// Moves messages from receive to send on a thread of its own, until receive runs out or send fails
+fn bridge<T: Send + 'static>(
+    mut receive: impl FnMut() -> Option<T> + Send + 'static,
+    mut send: impl FnMut(T) -> bool + Send + 'static,
+) {
+    thread::Builder::new()
+        .name("channel-bridge".into())
+        .spawn(move || {
+            while let Some(message) = receive() {
+                if !send(message) {
+                    break;
+                }
+            }
+        })
+        .expect("couldn't spawn the channel bridge thread");
+}
+
+// A Receiver for messages sent on the mpsc channel
+impl<T: Send + 'static> From<mpsc::Receiver<T>> for Receiver<T> {
+    fn from(from: mpsc::Receiver<T>) -> Self {
+        let (sender, receiver) = sync_channel(1);
+        bridge(move || from.recv().ok(), move |message| sender.send(message).is_ok());
+        receiver
+    }
+}
+
+// An mpsc Receiver for messages sent on the bounded channel
+impl<T: Send + 'static> From<Receiver<T>> for mpsc::Receiver<T> {
+    fn from(from: Receiver<T>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        bridge(move || from.receive().ok(), move |message| sender.send(message).is_ok());
+        receiver
+    }
+}
+
+// A Sender whose messages go on to the mpsc channel
+impl<T: Send + 'static> From<mpsc::Sender<T>> for Sender<T> {
+    fn from(to: mpsc::Sender<T>) -> Self {
+        let (sender, receiver) = sync_channel(1);
+        bridge(move || receiver.receive().ok(), move |message| to.send(message).is_ok());
+        sender
+    }
+}
+
+impl<T: Send + 'static> From<mpsc::SyncSender<T>> for Sender<T> {
+    fn from(to: mpsc::SyncSender<T>) -> Self {
+        let (sender, receiver) = sync_channel(1);
+        bridge(move || receiver.receive().ok(), move |message| to.send(message).is_ok());
+        sender
+    }
+}
+
+// An mpsc Sender whose messages go on to the bounded channel. It's unbounded like any mpsc Sender, so sends on
+// it never block however full the bounded channel is - convert to a SyncSender to keep that
+impl<T: Send + 'static> From<Sender<T>> for mpsc::Sender<T> {
+    fn from(to: Sender<T>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        bridge(move || receiver.recv().ok(), move |message| to.send(message).is_ok());
+        sender
+    }
+}
+
+impl<T: Send + 'static> From<Sender<T>> for mpsc::SyncSender<T> {
+    fn from(to: Sender<T>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(0);
+        bridge(move || receiver.recv().ok(), move |message| to.send(message).is_ok());
+        sender
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+mod crossbeam {
+    use super::bridge;
+    use crate::boundedchannel::{sync_channel, Receiver, Sender};
+
+    impl<T: Send + 'static> From<crossbeam_channel::Receiver<T>> for Receiver<T> {
+        fn from(from: crossbeam_channel::Receiver<T>) -> Self {
+            let (sender, receiver) = sync_channel(1);
+            bridge(move || from.recv().ok(), move |message| sender.send(message).is_ok());
+            receiver
+        }
+    }
+
+    impl<T: Send + 'static> From<Receiver<T>> for crossbeam_channel::Receiver<T> {
+        fn from(from: Receiver<T>) -> Self {
+            let (sender, receiver) = crossbeam_channel::bounded(1);
+            bridge(move || from.receive().ok(), move |message| sender.send(message).is_ok());
+            receiver
+        }
+    }
+
+    impl<T: Send + 'static> From<crossbeam_channel::Sender<T>> for Sender<T> {
+        fn from(to: crossbeam_channel::Sender<T>) -> Self {
+            let (sender, receiver) = sync_channel(1);
+            bridge(move || receiver.receive().ok(), move |message| to.send(message).is_ok());
+            sender
+        }
+    }
+
+    // Bounded like the channel it sends on, with none of its own so the bridge is all that's in between
+    impl<T: Send + 'static> From<Sender<T>> for crossbeam_channel::Sender<T> {
+        fn from(to: Sender<T>) -> Self {
+            let (sender, receiver) = crossbeam_channel::bounded(0);
+            bridge(move || receiver.recv().ok(), move |message| to.send(message).is_ok());
+            sender
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// The lock only uses core, so it's built without the std feature too, for no_std targets
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::AtomicBool;
+
+use crate::ordering::{Relaxed, Acquire, Release};
+use crate::relax::cpu_relax;
+
+// How to turn interrupts off and back on, for whatever chip the code runs on (cpsid/cpsie on a Cortex-M,
+// the mstatus MIE bit on RISC-V, cli/sti on x86...).
+// disable hands back what the interrupt state was before, and restore puts it back - restoring rather than
+// just enabling means nested locks (or a lock taken with interrupts already off) leave things as they were
+/// # Safety
+/// While the State from disable hasn't been given to restore yet, no interrupt handler can run on this core
+pub unsafe trait InterruptController {
+    type State;
+
+    fn disable() -> Self::State;
+
+    /// # Safety
+    /// state has to have come from a call to disable on this core
+    unsafe fn restore(state: Self::State);
+}
+
+// A spinlock for data shared between normal code and interrupt handlers. Interrupts are turned off before
+// the lock is taken and only turned back on after it's unlocked: if an interrupt came in while normal code
+// held the lock, and the handler tried to take it, the handler would spin forever as the code holding the
+// lock can't run again until the handler returns.
+// On a multi-core chip this only turns off interrupts on the current core - the spinning covers the others.
+// With more than one held at once, unlock them in the opposite order to locking them, otherwise interrupts
+// come back on when the first one is unlocked
+pub struct IrqSpinLock<T, I: InterruptController> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+    _controller: PhantomData<fn() -> I>,
+}
+
+unsafe impl<T: Send, I: InterruptController> Sync for IrqSpinLock<T, I> {}
+
+impl<T: Default, I: InterruptController> Default for IrqSpinLock<T, I> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, I: InterruptController> From<T> for IrqSpinLock<T, I> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T, I: InterruptController> fmt::Debug for IrqSpinLock<T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IrqSpinLock").field("locked", &self.locked.load(Relaxed)).finish_non_exhaustive()
+    }
+}
+
+impl<T, I: InterruptController> IrqSpinLock<T, I> {
+    pub const fn new(value: T) -> Self {
+        Self { locked: AtomicBool::new(false), value: UnsafeCell::new(value), _controller: PhantomData }
+    }
+
+    pub fn lock(&self) -> IrqGuard<'_, T, I> {
+        // interrupts go off first, so nothing can get in between taking the lock and them going off
+        let state = I::disable();
+        while self.locked.swap(true, Acquire) {
+            while self.locked.load(Relaxed) {
+                cpu_relax();
+            }
+        }
+        IrqGuard { lock: self, state: Some(state), _not_send: PhantomData }
+    }
+
+    pub fn try_lock(&self) -> Option<IrqGuard<'_, T, I>> {
+        let state = I::disable();
+        if self.locked.swap(true, Acquire) {
+            // Safety: the state came from the disable just above, and nothing was disabled since
+            unsafe { I::restore(state) };
+            return None;
+        }
+        Some(IrqGuard { lock: self, state: Some(state), _not_send: PhantomData })
+    }
+
+    // Doesn't touch interrupts either: with &mut self nothing, a handler included, can be holding the lock
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+pub struct IrqGuard<'a, T, I: InterruptController> {
+    lock: &'a IrqSpinLock<T, I>,
+    // only None while it's being dropped
+    state: Option<I::State>,
+    // the interrupt state is this CPU's, so it has to be put back on the thread that took the lock
+    _not_send: PhantomData<*const ()>,
+}
+
+// Sharing the guard only hands out &T, and the state isn't reachable through it
+unsafe impl<T: Sync, I: InterruptController> Sync for IrqGuard<'_, T, I> {}
+
+impl<T, I: InterruptController> Deref for IrqGuard<'_, T, I> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: the guard means the lock is held
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T, I: InterruptController> DerefMut for IrqGuard<'_, T, I> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T, I: InterruptController> Drop for IrqGuard<'_, T, I> {
+    fn drop(&mut self) {
+        // unlock before interrupts come back on, for the same reason they went off before locking
+        self.lock.locked.store(false, Release);
+        if let Some(state) = self.state.take() {
+            // Safety: the state came from the disable in lock or try_lock
+            unsafe { I::restore(state) };
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::atomic::AtomicUsize;
+
+use crate::deadline::Deadline;
+use crate::event::Event;
+use crate::ordering::{Relaxed, Acquire, AcqRel};
+
+// A count down latch: it starts closed with a count of n, and once count_down has been called n times
+// every waiting thread is released. Unlike a barrier it can't be reused - once open it stays open
+pub struct Latch {
+    count: AtomicUsize,
+    open: Event,
+}
+
+impl Latch {
+    pub const fn new(n: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(n),
+            open: Event::new(),
+        }
+    }
+
+    pub fn count_down(&self) {
+        // AcqRel so the thread that takes the count to zero has seen everything the other threads did
+        // before their count_down, and the Event passes that on to the waiters
+        let n = self.count.fetch_update(AcqRel, Relaxed, |n| n.checked_sub(1));
+        if n == Ok(1) {
+            self.open.set();
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Relaxed)
+    }
+
+    // Blocks until the count reaches zero
+    pub fn wait(&self) {
+        // a latch created with a count of zero never sets its event, so check the count first
+        if self.count.load(Acquire) == 0 {
+            return;
+        }
+        self.open.wait();
+    }
+
+    // Blocks until the count reaches zero or the timeout runs out. Returns whether the latch is open
+    pub fn wait_timeout(&self, timeout: impl Into<Deadline>) -> bool {
+        self.count.load(Acquire) == 0 || self.open.wait_timeout(timeout)
+    }
+}
@@ -0,0 +1,104 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+// the futex module's memory.atomic.wait32 - threaded wasm needs a nightly build of std anyway
+#![cfg_attr(all(target_arch = "wasm32", target_feature = "atomics"), feature(stdarch_wasm_atomic_wait))]
+
+// Without the std feature it's only what runs on core alone, for no_std targets: the IrqSpinLock and the
+// spin hint it spins with. Everything else parks threads, allocates or asks the OS for something
+macro_rules! with_std {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "std")]
+            $item
+        )*
+    };
+}
+
+mod ordering;
+
+pub mod irqspinlock;
+pub mod relax;
+
+with_std! {
+    mod trace;
+
+    pub mod spinlock;
+    pub mod arc;
+    pub mod allocator;
+    pub mod oneshotchannel;
+    pub mod mutexchannel;
+    pub mod waitstrategy;
+    pub mod event;
+    pub mod latch;
+    pub mod striped;
+    pub mod concurrenthashmap;
+    pub mod objectpool;
+    pub mod cachepadded;
+    pub mod shardedcounter;
+    #[cfg(feature = "metrics")]
+    pub mod metrics;
+    pub mod rwspinlock;
+    pub mod threadlocal;
+    pub mod triplebuffer;
+    pub mod atomicbitset;
+    pub mod boundedqueue;
+    pub mod boundedchannel;
+    pub mod futex;
+    pub mod rawspinlock;
+    pub mod sharedmem;
+    pub mod shmring;
+    #[cfg(all(target_os = "linux", feature = "pi_mutex"))]
+    pub mod pimutex;
+    pub mod sched;
+    pub mod mutex;
+    pub mod condvar;
+    pub mod semaphore;
+    pub mod ratelimiter;
+    pub mod shardedrwlock;
+    pub mod weakregistry;
+    #[cfg(feature = "watchdog")]
+    pub mod watchdog;
+    pub mod rendezvous;
+    pub mod actor;
+    pub mod pipeline;
+    pub mod epoch;
+    pub mod segqueue;
+    pub mod atomicoption;
+    pub mod registry;
+    #[cfg(feature = "serde")]
+    pub mod serialized;
+    pub mod parker;
+    pub mod atomicwaker;
+    pub mod executor;
+    pub mod sequencer;
+    pub mod waitqueue;
+    pub mod atomicfloat;
+    pub mod stampedlock;
+    pub mod versioned;
+    pub mod deadline;
+    pub mod concurrentlru;
+    pub mod racecell;
+    pub mod shutdown;
+    #[cfg(feature = "timed_guard")]
+    pub mod timedguard;
+    pub mod arcstr;
+    pub mod router;
+    pub mod atomicupdate;
+    pub mod pubsub;
+    #[cfg(feature = "async")]
+    mod wakerlist;
+    #[cfg(feature = "blocking_check")]
+    pub mod blockingcheck;
+    pub mod threadpool;
+    pub mod frozencell;
+    #[cfg(feature = "ffi")]
+    pub mod ffi;
+    pub mod receive;
+    pub mod duplex;
+    pub mod waitmap;
+    pub mod hybridlock;
+    pub mod prioritychannel;
+    pub mod taskgroup;
+    pub mod interop;
+    pub mod dynamic;
+}
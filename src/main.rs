@@ -1,15 +1,83 @@
-mod spinlock;
-use spinlock::simulate_spinlock;
+use std::process;
+use std::time::Duration;
 
-mod oneshotchannel;
-use oneshotchannel::simulate_oneshot_channel;
+mod stress;
 
-use crate::oneshotchannel::simulate_oneshot_channel_with_sender_and_receiver;
+use stress::{Config, PRIMITIVES, REPORT_HEADER};
 
-fn main() {    
-    simulate_spinlock();
-    simulate_oneshot_channel();
-    simulate_oneshot_channel_with_sender_and_receiver();
-    println!("Hello world");
+const USAGE: &str = "\
+Runs contention scenarios against the crate's primitives and reports throughput and latency percentiles.
+
+usage: rust-atomic-locks [options]
+
+  --primitive NAME   the primitive to run, or all (the default) for every one of them
+  --threads N        threads hammering the primitive (default: one per core)
+  --iters N          operations per thread, like 100000, 100k or 1M (default: 100k)
+  --duration TIME    stop early after this long, like 500ms, 10s or 2m
+  --list             list the primitives
+  --help             show this";
+
+// 1M, 100k, 1_000 and so on
+fn parse_count(s: &str) -> Option<u64> {
+    let s = s.replace('_', "");
+    let (digits, multiplier) = match s.chars().last()? {
+        'k' | 'K' => (&s[..s.len() - 1], 1_000),
+        'm' | 'M' => (&s[..s.len() - 1], 1_000_000),
+        'g' | 'G' => (&s[..s.len() - 1], 1_000_000_000),
+        _ => (&s[..], 1),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+// 500ms, 10s, 2m, or a bare number of seconds
+fn parse_duration(s: &str) -> Option<Duration> {
+    if let Some(ms) = s.strip_suffix("ms") {
+        return ms.parse().ok().map(Duration::from_millis);
+    }
+    if let Some(m) = s.strip_suffix('m') {
+        return m.parse::<u64>().ok()?.checked_mul(60).map(Duration::from_secs);
+    }
+    // try_ so a negative, infinite or NaN number of seconds is a bad --duration rather than a panic
+    Duration::try_from_secs_f64(s.strip_suffix('s').unwrap_or(s).parse().ok()?).ok()
 }
 
+fn fail(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    process::exit(2);
+}
+
+fn main() {
+    let mut config = Config::default();
+    let mut primitive = String::from("all");
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| fail(&format!("{arg} needs a value")));
+        match arg.as_str() {
+            "--primitive" => primitive = value(),
+            "--threads" => {
+                config.threads = value().parse().ok().filter(|&n| n > 0).unwrap_or_else(|| fail("bad --threads"))
+            }
+            "--iters" => config.iters = parse_count(&value()).unwrap_or_else(|| fail("bad --iters")),
+            "--duration" => config.duration = Some(parse_duration(&value()).unwrap_or_else(|| fail("bad --duration"))),
+            "--list" => {
+                PRIMITIVES.iter().for_each(|p| println!("{p}"));
+                return;
+            }
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            _ => fail(&format!("unknown argument {arg}")),
+        }
+    }
+
+    let primitives = if primitive == "all" { PRIMITIVES.to_vec() } else { vec![primitive.as_str()] };
+    if let Some(name) = primitives.iter().find(|name| !PRIMITIVES.contains(name)) {
+        fail(&format!("there's no primitive called {name}, see --list"));
+    }
+    println!("{REPORT_HEADER}");
+    for name in primitives {
+        let report = stress::run(name, &config).expect("every primitive in PRIMITIVES has a scenario");
+        println!("{report}");
+    }
+}
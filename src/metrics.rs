@@ -0,0 +1,131 @@
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+use crate::ordering::Relaxed;
+
+// The counters a lock keeps about itself when the `metrics` feature is on. They're only statistics,
+// so everything is Relaxed - a snapshot taken while the lock is busy can be slightly out of step
+pub(crate) struct LockCounters {
+    acquisitions: AtomicU64,
+    wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+    max_hold_nanos: AtomicU64,
+}
+
+impl LockCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            acquisitions: AtomicU64::new(0),
+            wait_nanos: AtomicU64::new(0),
+            max_wait_nanos: AtomicU64::new(0),
+            max_hold_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record_acquire(&self, waited: Duration) {
+        self.acquisitions.fetch_add(1, Relaxed);
+        self.wait_nanos.fetch_add(waited.as_nanos() as u64, Relaxed);
+        self.max_wait_nanos.fetch_max(waited.as_nanos() as u64, Relaxed);
+    }
+
+    pub(crate) fn record_hold(&self, held: Duration) {
+        self.max_hold_nanos.fetch_max(held.as_nanos() as u64, Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LockMetrics {
+        LockMetrics {
+            acquisitions: self.acquisitions.load(Relaxed),
+            total_wait: Duration::from_nanos(self.wait_nanos.load(Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold_nanos.load(Relaxed)),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.acquisitions.store(0, Relaxed);
+        self.wait_nanos.store(0, Relaxed);
+        self.max_wait_nanos.store(0, Relaxed);
+        self.max_hold_nanos.store(0, Relaxed);
+    }
+}
+
+// A snapshot of a lock's counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LockMetrics {
+    // how many times the lock has been acquired
+    pub acquisitions: u64,
+    // the time spent waiting for the lock, added up over every acquisition
+    pub total_wait: Duration,
+    // the longest a single acquisition waited. With an unfair lock the average can look fine while one
+    // unlucky thread waits far longer than everyone else, and this is where that shows up
+    pub max_wait: Duration,
+    // the longest the lock has been held in one go
+    pub max_hold: Duration,
+}
+
+impl LockMetrics {
+    pub fn average_wait(&self) -> Duration {
+        if self.acquisitions == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total_wait.as_nanos() / self.acquisitions as u128) as u64)
+    }
+}
+
+// The counters a channel keeps when the `metrics` feature is on, for watching backpressure: a max_depth that
+// creeps up towards the capacity, or blocked send time that keeps growing, means the receivers can't keep up.
+// Relaxed like LockCounters
+pub(crate) struct ChannelCounters {
+    sends: AtomicU64,
+    receives: AtomicU64,
+    blocked_send_nanos: AtomicU64,
+    max_depth: AtomicU64,
+}
+
+impl ChannelCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            sends: AtomicU64::new(0),
+            receives: AtomicU64::new(0),
+            blocked_send_nanos: AtomicU64::new(0),
+            max_depth: AtomicU64::new(0),
+        }
+    }
+
+    // depth is how many messages were in the channel just after these went in
+    pub(crate) fn record_send(&self, messages: usize, depth: usize) {
+        self.sends.fetch_add(messages as u64, Relaxed);
+        self.max_depth.fetch_max(depth as u64, Relaxed);
+    }
+
+    pub(crate) fn record_receive(&self, messages: usize) {
+        self.receives.fetch_add(messages as u64, Relaxed);
+    }
+
+    pub(crate) fn record_blocked_send(&self, blocked: Duration) {
+        self.blocked_send_nanos.fetch_add(blocked.as_nanos() as u64, Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ChannelStats {
+        ChannelStats {
+            sends: self.sends.load(Relaxed),
+            receives: self.receives.load(Relaxed),
+            blocked_send_time: Duration::from_nanos(self.blocked_send_nanos.load(Relaxed)),
+            max_depth: self.max_depth.load(Relaxed) as usize,
+        }
+    }
+}
+
+// A snapshot of a channel's counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelStats {
+    // messages that went into the channel (not counting any an Overflow policy threw away)
+    pub sends: u64,
+    // messages taken out of it
+    pub receives: u64,
+    // the time senders spent waiting for room, added up over every send. Only a bounded channel ever has to
+    // wait, so it's zero for the others
+    pub blocked_send_time: Duration,
+    // the most messages that have been in the channel at once
+    pub max_depth: usize,
+}
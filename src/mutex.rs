@@ -0,0 +1,273 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::AtomicU32;
+use std::time::{Duration, Instant};
+
+use crate::deadline::Deadline;
+use crate::futex::{wait, wait_timeout, wake_one};
+#[cfg(feature = "metrics")]
+use crate::metrics::{LockCounters, LockMetrics};
+use crate::ordering::{Relaxed, Acquire, Release};
+use crate::relax::cpu_relax;
+use crate::sched::pause;
+#[cfg(feature = "timed_guard")]
+use crate::timedguard::TimedGuard;
+use crate::trace::{check_blocking, trace_event};
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+// Locked, and there may be threads asleep waiting for it
+const CONTENDED: u32 = 2;
+
+// A mutex that puts waiting threads to sleep on the futex module rather than spinning, for locks that can be
+// held for a while. Unlock only makes a syscall when the state says someone might be asleep, so an
+// uncontended lock and unlock never leave userspace
+pub struct Mutex<T> {
+    state: AtomicU32,
+    value: UnsafeCell<T>,
+    #[cfg(feature = "metrics")]
+    metrics: LockCounters,
+    // when the lock was last taken, for the hold time. Only the thread holding the lock touches it
+    #[cfg(feature = "metrics")]
+    acquired: UnsafeCell<Option<Instant>>,
+}
+
+unsafe impl<T> Sync for Mutex<T> where T: Send {}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            value: UnsafeCell::new(value),
+            #[cfg(feature = "metrics")]
+            metrics: LockCounters::new(),
+            #[cfg(feature = "metrics")]
+            acquired: UnsafeCell::new(None),
+        }
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        let waited = if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_err() {
+            check_blocking!("Mutex::lock");
+            self.wait_for_lock(None).expect("there's no deadline to pass")
+        } else {
+            Duration::ZERO
+        };
+        self.locked(waited)
+    }
+
+    // Same as lock, but the guard reports it if it's held for longer than timedguard's threshold
+    #[cfg(feature = "timed_guard")]
+    #[track_caller]
+    pub fn lock_timed(&self) -> TimedGuard<MutexGuard<'_, T>> {
+        TimedGuard::new(self.lock())
+    }
+
+    // Same as lock, but gives up with Err(Timeout) once timeout has passed
+    pub fn lock_timeout(&self, timeout: impl Into<Deadline>) -> Result<MutexGuard<'_, T>, Timeout> {
+        // the deadline's only worked out when there's a wait, so the uncontended lock doesn't read the clock
+        let waited = if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_err() {
+            self.wait_for_lock(timeout.into().instant()).ok_or(Timeout)?
+        } else {
+            Duration::ZERO
+        };
+        Ok(self.locked(waited))
+    }
+
+    // Locks, runs f on the value and unlocks again in one go, or returns Err(Timeout) without running f if
+    // the lock couldn't be had in time. There's no guard to keep alive by mistake - in a timeout path it's all
+    // too easy to hang on to one past where it should have been dropped, or to use the value on the error path
+    pub fn lock_timeout_map<R>(
+        &self,
+        timeout: impl Into<Deadline>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, Timeout> {
+        self.lock_timeout(timeout).map(|mut guard| f(&mut guard))
+    }
+
+    // lock_contended, and how long it waited for the metrics - only timed with the `metrics` feature, so it's
+    // zero without. None if the deadline passed
+    fn wait_for_lock(&self, deadline: Option<Instant>) -> Option<Duration> {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        if !lock_contended(&self.state, deadline) {
+            return None;
+        }
+        #[cfg(feature = "metrics")]
+        return Some(start.elapsed());
+        #[cfg(not(feature = "metrics"))]
+        Some(Duration::ZERO)
+    }
+
+    // Everything after the state's been taken, whichever way it was
+    fn locked(&self, waited: Duration) -> MutexGuard<'_, T> {
+        #[cfg(feature = "metrics")]
+        {
+            self.metrics.record_acquire(waited);
+            // Safety: we hold the lock
+            unsafe { *self.acquired.get() = Some(Instant::now()) };
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = waited;
+        trace_event!("mutex acquired");
+        pause!("Mutex::locked");
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed)
+            .is_ok()
+            .then(|| self.locked(Duration::ZERO))
+    }
+
+    // Only for a guard that's going away (or giving up the lock for a while) to call, or something in the crate
+    // that holds the lock without a MutexGuard, like the named locks in registry
+    pub(crate) fn unlock(&self) {
+        // Safety: we still hold the lock
+        #[cfg(feature = "metrics")]
+        if let Some(acquired) = unsafe { (*self.acquired.get()).take() } {
+            self.metrics.record_hold(acquired.elapsed());
+        }
+        pause!("Mutex::unlock");
+        if self.state.swap(UNLOCKED, Release) == CONTENDED {
+            wake_one(&self.state);
+        }
+        trace_event!("mutex released");
+    }
+
+    // No locking needed, the &mut means nobody else can have the mutex
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    // Same as SpinLock's: how often the mutex has been taken, and how long threads waited for it and held it.
+    // A stretch unlocked in a Condvar wait or MutexGuard::unlocked counts as letting go and taking it again
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> LockMetrics {
+        self.metrics.snapshot()
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&self) {
+        self.metrics.reset();
+    }
+}
+
+// Returns false if the deadline passed before the lock could be had
+#[cold]
+fn lock_contended(state: &AtomicU32, deadline: Option<Instant>) -> bool {
+    // Spin for a little while first, as the lock is often let go quickly - but only while nobody is asleep on
+    // it, otherwise we'd be jumping the queue
+    let mut spins = 0;
+    while state.load(Relaxed) == LOCKED && spins < 100 {
+        spins += 1;
+        cpu_relax();
+    }
+    if state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok() {
+        return true;
+    }
+    // From here on the lock is marked contended, so whoever unlocks it wakes someone up. There's no telling
+    // whether we were the last waiter, so it stays marked contended after we get it - that costs at most one
+    // spare wake (and the same goes for giving up on a timeout)
+    while state.swap(CONTENDED, Acquire) != UNLOCKED {
+        trace_event!("mutex waiter sleeping");
+        match deadline {
+            None => wait(state, CONTENDED),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                wait_timeout(state, CONTENDED, deadline - now);
+            }
+        }
+    }
+    true
+}
+
+// It lived here before there was a deadline module
+pub use crate::deadline::Timeout;
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// Like SpinLock's, only the state: std's Mutex try_locks to show the value, but then a held lock shows nothing
+// useful anyway
+impl<T> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.load(Relaxed);
+        f.debug_struct("Mutex")
+            .field("locked", &(state != UNLOCKED))
+            .field("contended", &(state == CONTENDED))
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct MutexGuard<'a, T> {
+    // Condvar::wait needs to unlock the mutex and lock it again
+    pub(crate) mutex: &'a Mutex<T>,
+}
+
+// A shared guard hands out &T, so sharing it between threads needs T: Sync - without this the guard would be
+// Sync whenever the Mutex is, which only asks for T: Send. Unlike std's, it can be sent to (and unlocked on)
+// another thread, as the futex doesn't care which thread unlocks it
+unsafe impl<T> Send for MutexGuard<'_, T> where T: Send {}
+unsafe impl<T> Sync for MutexGuard<'_, T> where T: Sync {}
+
+impl<T> MutexGuard<'_, T> {
+    // Unlocks the mutex while f runs and locks it again afterwards, for calling out to something that mustn't
+    // happen with the lock held. Like with Condvar::wait, the value can change in the meantime, so anything
+    // worked out from it before has to be checked again after
+    pub fn unlocked<R>(this: &mut Self, f: impl FnOnce() -> R) -> R {
+        // Locks again on the way out, even if f panics, as the guard will unlock when it's dropped. The guard
+        // only holds the &Mutex, so the new guard can be forgotten and the old one carries on as it was
+        struct Relock<'a, T>(&'a Mutex<T>);
+
+        impl<T> Drop for Relock<'_, T> {
+            fn drop(&mut self) {
+                mem::forget(self.0.lock());
+            }
+        }
+
+        this.mutex.unlock();
+        let _relock = Relock(this.mutex);
+        f()
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: the guard means the mutex is locked, so nothing else can get at the value
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
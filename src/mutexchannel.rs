@@ -1,34 +1,454 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::thread;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+use crate::condvar::Condvar;
+#[cfg(feature = "metrics")]
+use crate::metrics::{ChannelCounters, ChannelStats};
+use crate::mutex::{Mutex, MutexGuard};
+use crate::ordering::Relaxed;
+use crate::trace::check_blocking;
+use crate::waitstrategy::WaitStrategy;
+
+// Which receiver gets the next message when several are blocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fairness {
+    // Whoever gets the lock first after a send - cheapest, but a receiver can be passed over again and again
+    #[default]
+    Unfair,
+    // The receivers blocked in receive and receive_up_to get messages in the order they started waiting.
+    // Every send wakes all of them so the one at the front can go, which costs more with lots of receivers
+    Fifo,
+}
+
 pub struct MutexChannel<T> {
     queue: Mutex<VecDeque<T>>,
     item_ready: Condvar,
+    // How many threads are waiting in receive_if. Only changed while the queue is locked
+    selective_waiters: AtomicUsize,
+    fairness: Fairness,
+    // With Fifo, every blocking receive takes a ticket and waits until now_serving gets to it. Both are only
+    // changed while the queue is locked, like selective_waiters
+    next_ticket: AtomicU64,
+    now_serving: AtomicU64,
+    // Set by with_byte_budget. queued_bytes is how much the queued messages add up to, only changed while the
+    // queue is locked, and room is where senders wait for it to go down
+    budget: Option<ByteBudget<T>>,
+    queued_bytes: AtomicUsize,
+    room: Condvar,
+    #[cfg(feature = "metrics")]
+    stats: ChannelCounters,
+}
+
+struct ByteBudget<T> {
+    bytes: usize,
+    size_of: Box<dyn Fn(&T) -> usize + Send + Sync>,
 }
 
 impl<T> MutexChannel<T> {
     pub fn new() -> Self {
+        Self::with_fairness(Fairness::Unfair)
+    }
+
+    pub fn with_fairness(fairness: Fairness) -> Self {
         Self {
             queue: Mutex::new(VecDeque::new()),
-            item_ready: Condvar::new()
+            item_ready: Condvar::new(),
+            selective_waiters: AtomicUsize::new(0),
+            fairness,
+            next_ticket: AtomicU64::new(0),
+            now_serving: AtomicU64::new(0),
+            budget: None,
+            queued_bytes: AtomicUsize::new(0),
+            room: Condvar::new(),
+            #[cfg(feature = "metrics")]
+            stats: ChannelCounters::new(),
         }
     }
 
+    // Bounds the channel by memory rather than by message count, for messages that vary a lot in size (Vec or
+    // String payloads). size_of says how many bytes a message takes up - |v: &Vec<u8>| v.len(), say - and has to
+    // give the same answer for a message every time it's asked. Senders block while the queued messages add up
+    // to more than bytes. A message bigger than the whole budget is let in once the queue is empty, rather than
+    // blocking forever
+    pub fn with_byte_budget(bytes: usize, size_of: impl Fn(&T) -> usize + Send + Sync + 'static) -> Self {
+        let mut channel = Self::new();
+        channel.budget = Some(ByteBudget { bytes, size_of: Box::new(size_of) });
+        channel
+    }
+
+    pub fn fairness(&self) -> Fairness {
+        self.fairness
+    }
+
+    // How much the queued messages add up to by the byte budget's size_of, or None if there's no budget.
+    // A snapshot, like len
+    pub fn queued_bytes(&self) -> Option<usize> {
+        self.budget.as_ref().map(|_| self.queued_bytes.load(Relaxed))
+    }
+
     // when a message is sent, it's sent to the back of the queue and alerts a receiving thread that a message can be popped
     // this wakes the thread up and allows it to receive a message
     pub fn send(&self, message: T) {
-        self.queue.lock().unwrap().push_back(message);
-        self.item_ready.notify_one();
+        let mut b = self.queue.lock();
+        if let Some(budget) = &self.budget {
+            b = self.wait_for_room(b, budget, &message);
+        }
+        b.push_back(message);
+        self.count_sent(1, b.len());
+        self.notify(b, 1);
     }
 
+    // Same as send, but hands the message back rather than waiting when it doesn't fit in the byte budget.
+    // Without a budget there's always room
+    pub fn try_send(&self, message: T) -> Result<(), T> {
+        let mut b = self.queue.lock();
+        if let Some(budget) = &self.budget {
+            let size = (budget.size_of)(&message);
+            if !b.is_empty() && self.queued_bytes.load(Relaxed).saturating_add(size) > budget.bytes {
+                return Err(message);
+            }
+            self.queued_bytes.fetch_add(size, Relaxed);
+        }
+        b.push_back(message);
+        self.count_sent(1, b.len());
+        self.notify(b, 1);
+        Ok(())
+    }
+
+    // Wakes enough receivers for the messages that were just sent. A receiver in receive_if might not want the
+    // message, and if it was the only one woken, a receiver that did want it would sleep through it - so while
+    // there are any, everyone is woken. Takes the queue still locked, and unlocks it before waking anyone
+    fn notify(&self, b: MutexGuard<'_, VecDeque<T>>, sent: usize) {
+        // The count has to be read before unlocking: a receive_if could start waiting in between, and a
+        // notify_one would go to it. Relaxed is enough as it's only changed with the queue locked.
+        // With Fifo the receiver at the front of the line has to be woken, and there's no telling which one that is
+        let one = sent == 1 && self.selective_waiters.load(Relaxed) == 0 && self.fairness == Fairness::Unfair;
+        drop(b);
+        if sent == 0 {
+            return;
+        }
+        if one {
+            self.item_ready.notify_one();
+        } else {
+            self.item_ready.notify_all();
+        }
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
     pub fn receive(&self) -> T {
-        let mut b = self.queue.lock().unwrap();
+        let mut b = self.queue.lock();
+        check_blocking!(when b.is_empty(), "MutexChannel::receive");
+        if self.fairness == Fairness::Fifo {
+            let mut b = self.wait_for_turn(b);
+            let message = self.served(&mut b, |b| b.pop_front().unwrap());
+            self.took(slice::from_ref(&message));
+            return message;
+        }
+
+        // wait until there's a message at the front of the queue - the mutex is unlocked while waiting
+        // this means that the mutex can be used between several threads
+        b = self.item_ready.wait_while(b, |b| b.is_empty());
+        let message = b.pop_front().unwrap();
+        self.took(slice::from_ref(&message));
+        message
+    }
 
+    // The next message if there is one, without waiting. With Fifo it doesn't go ahead of receivers already
+    // waiting in line, and comes back empty while there are any
+    pub fn try_receive(&self) -> Option<T> {
+        let mut b = self.queue.lock();
+        if self.fairness == Fairness::Fifo && self.now_serving.load(Relaxed) != self.next_ticket.load(Relaxed) {
+            return None;
+        }
+        let message = b.pop_front()?;
+        self.took(slice::from_ref(&message));
+        Some(message)
+    }
+
+    // Same as receive, but waits with the strategy instead of sleeping on the condvar. With Fifo it takes a
+    // ticket like receive does and is served in its turn
+    pub fn receive_with(&self, strategy: &impl WaitStrategy) -> T {
+        let mut b = self.queue.lock();
+        let ticket = (self.fairness == Fairness::Fifo).then(|| self.next_ticket.fetch_add(1, Relaxed));
+        let mut attempt = 0;
+        while b.is_empty() || ticket.is_some_and(|ticket| self.now_serving.load(Relaxed) != ticket) {
+            drop(b);
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+            b = self.queue.lock();
+        }
+        let message = match ticket {
+            Some(_) => self.served(&mut b, |b| b.pop_front().unwrap()),
+            None => b.pop_front().unwrap(),
+        };
+        self.took(slice::from_ref(&message));
+        message
+    }
+
+    // Same as receive, but the message comes wrapped in a Delivery that has to be acked once it's been dealt
+    // with. If the thread panics first, the Delivery puts the message back at the front of the queue for
+    // another receiver, so a worker dying halfway through doesn't lose it - at-least-once rather than
+    // at-most-once. Which also means a message can be seen twice, so handling it twice should be harmless
+    pub fn receive_acked(&self) -> Delivery<'_, T> {
+        Delivery { channel: self, message: Some(self.receive()) }
+    }
+
+    // Puts a message that was taken back at the front, ahead of anything sent since. It's already been let into
+    // the byte budget once, so it goes straight back in without waiting for room
+    fn redeliver(&self, message: T) {
+        let mut b = self.queue.lock();
+        if let Some(budget) = &self.budget {
+            self.queued_bytes.fetch_add((budget.size_of)(&message), Relaxed);
+        }
+        b.push_front(message);
+        self.notify(b, 1);
+    }
+
+    // Pushes every message under one lock, rather than locking (and waking a receiver) once per message
+    pub fn send_all(&self, messages: impl IntoIterator<Item = T>) {
+        if self.budget.is_some() {
+            // any of them might have to wait for room, and the ones in front have to be there for receivers to
+            // take in the meantime, so with a budget they go in one at a time
+            messages.into_iter().for_each(|message| self.send(message));
+            return;
+        }
+        let mut b = self.queue.lock();
+        let before = b.len();
+        b.extend(messages);
+        let sent = b.len() - before;
+        self.count_sent(sent, b.len());
+        // with more than one message, every receiver is woken rather than leaving messages waiting
+        self.notify(b, sent);
+    }
+
+    // Blocks until there's a message that matches the predicate, and takes that message out of the queue.
+    // The messages in front of it are left where they are, in order
+    pub fn receive_if(&self, mut predicate: impl FnMut(&T) -> bool) -> T {
+        let mut b = self.queue.lock();
         loop {
-        // if there's a message that can be returned from the front of the VecDeque queue, return it
-            if let Some(message) = b.pop_front() {
+            if let Some(i) = b.iter().position(&mut predicate) {
+                let message = b.remove(i).unwrap();
+                self.took(slice::from_ref(&message));
                 return message;
             }
-        // wait until this thread receives a notification to loop again - the mutex is unlocked while waiting
-        // this means that the mutex can be used between several threads
-            b = self.item_ready.wait(b).unwrap();
+            self.selective_waiters.fetch_add(1, Relaxed);
+            b = self.item_ready.wait(b);
+            self.selective_waiters.fetch_sub(1, Relaxed);
+        }
+    }
+
+    // Blocks until there's at least one message, then takes up to n of them in one go
+    pub fn receive_up_to(&self, n: usize) -> Vec<T> {
+        let mut b = self.queue.lock();
+        if self.fairness == Fairness::Fifo {
+            let mut b = self.wait_for_turn(b);
+            let n = n.min(b.len());
+            let taken = self.served(&mut b, |b| b.drain(..n).collect::<Vec<_>>());
+            self.took(&taken);
+            return taken;
+        }
+        b = self.item_ready.wait_while(b, |b| b.is_empty());
+        let n = n.min(b.len());
+        let taken: Vec<_> = b.drain(..n).collect();
+        self.took(&taken);
+        taken
+    }
+
+    // Waits until there's room in the byte budget for the message, and counts its bytes in
+    fn wait_for_room<'a>(
+        &self,
+        mut b: MutexGuard<'a, VecDeque<T>>,
+        budget: &ByteBudget<T>,
+        message: &T,
+    ) -> MutexGuard<'a, VecDeque<T>> {
+        let size = (budget.size_of)(message);
+        let full = |b: &mut VecDeque<T>| {
+            !b.is_empty() && self.queued_bytes.load(Relaxed).saturating_add(size) > budget.bytes
+        };
+        if full(&mut b) {
+            #[cfg(feature = "metrics")]
+            let start = Instant::now();
+            b = self.room.wait_while(b, full);
+            #[cfg(feature = "metrics")]
+            self.stats.record_blocked_send(start.elapsed());
+        }
+        self.queued_bytes.fetch_add(size, Relaxed);
+        b
+    }
+
+    // Everything that goes with messages coming out of the queue, called with it still locked: counting them,
+    // and with a byte budget, taking their bytes off and waking the senders waiting for room
+    fn took(&self, taken: &[T]) {
+        self.count_received(taken.len());
+        if let Some(budget) = &self.budget {
+            let bytes: usize = taken.iter().map(|message| (budget.size_of)(message)).sum();
+            let queued = self.queued_bytes.load(Relaxed);
+            self.queued_bytes.store(queued.saturating_sub(bytes), Relaxed);
+            self.room.notify_all();
+        }
+    }
+
+    // Counts messages going in and out for stats, called with the queue still locked so the depth is exact.
+    // They do nothing without the `metrics` feature
+    fn count_sent(&self, sent: usize, depth: usize) {
+        #[cfg(feature = "metrics")]
+        self.stats.record_send(sent, depth);
+        #[cfg(not(feature = "metrics"))]
+        let _ = (sent, depth);
+    }
+
+    fn count_received(&self, received: usize) {
+        #[cfg(feature = "metrics")]
+        self.stats.record_receive(received);
+        #[cfg(not(feature = "metrics"))]
+        let _ = received;
+    }
+
+    // Takes a ticket and waits until it's this receiver's turn and there's a message, for Fifo
+    fn wait_for_turn<'a>(&self, b: MutexGuard<'a, VecDeque<T>>) -> MutexGuard<'a, VecDeque<T>> {
+        let ticket = self.next_ticket.fetch_add(1, Relaxed);
+        self.item_ready.wait_while(b, |b| self.now_serving.load(Relaxed) != ticket || b.is_empty())
+    }
+
+    // Takes this receiver's messages and moves the line on. If there's anything left, the next receiver in
+    // line may have gone back to sleep while it wasn't its turn yet, so everyone is woken again
+    fn served<R>(&self, b: &mut VecDeque<T>, take: impl FnOnce(&mut VecDeque<T>) -> R) -> R {
+        let taken = take(b);
+        self.now_serving.fetch_add(1, Relaxed);
+        if !b.is_empty() {
+            self.item_ready.notify_all();
         }
+        taken
+    }
+
+    // Looks at the next message without taking it off the queue. The channel stays locked for as long as the
+    // Peek is alive, so senders and receivers on other threads are held up until it's dropped
+    pub fn peek(&self) -> Option<Peek<'_, T>> {
+        let queue = self.queue.lock();
+        if queue.is_empty() {
+            return None;
+        }
+        Some(Peek { queue })
+    }
+
+    // The closure form of peek, which can't accidentally keep the channel locked
+    pub fn peek_with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.queue.lock().front().map(f)
+    }
+
+    // How many messages are queued. Other threads can send and receive as soon as the lock is let go,
+    // so by the time the caller looks at it, it's a snapshot rather than the exact count
+    pub fn len(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // There's no limit on how many messages can be queued (a byte budget limits their size, not their number),
+    // so there's no capacity
+    pub fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    // Takes every message that's queued right now, without waiting - the Vec is empty if there were none
+    pub fn drain(&self) -> Vec<T> {
+        let mut b = self.queue.lock();
+        let taken: Vec<_> = b.drain(..).collect();
+        self.took(&taken);
+        taken
+    }
+
+    // Sends, receives and the deepest the queue has been. Without a byte budget a send never waits and
+    // blocked_send_time stays at zero - max_depth is the one to watch
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> ChannelStats {
+        self.stats.snapshot()
+    }
+}
+
+// The next message in a MutexChannel, borrowed while the channel is locked
+pub struct Peek<'a, T> {
+    // never empty, peek checks before making one
+    queue: MutexGuard<'a, VecDeque<T>>,
+}
+
+impl<T> Deref for Peek<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.queue[0]
     }
-}
\ No newline at end of file
+}
+
+// A message from receive_acked that hasn't been acked yet
+pub struct Delivery<'a, T> {
+    channel: &'a MutexChannel<T>,
+    // only None once ack has taken it
+    message: Option<T>,
+}
+
+impl<T> Delivery<'_, T> {
+    // Marks the message as dealt with and hands it over
+    pub fn ack(mut self) -> T {
+        self.message.take().unwrap()
+    }
+}
+
+impl<T> Deref for Delivery<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.message.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for Delivery<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.message.as_mut().unwrap()
+    }
+}
+
+// Only a panic sends the message back. Dropping it otherwise is taken as being done with it, the same as a
+// message from receive going out of scope - an early return can't put it back on the queue forever
+impl<T> Drop for Delivery<'_, T> {
+    fn drop(&mut self) {
+        if let Some(message) = self.message.take() {
+            if thread::panicking() {
+                self.channel.redeliver(message);
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for Delivery<'_, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Delivery").field(&**self).finish()
+    }
+}
+
+impl<T> Default for MutexChannel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// len locks the queue, so this only shows it if nobody else has it locked, rather than waiting
+impl<T> fmt::Debug for MutexChannel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("MutexChannel");
+        match self.queue.try_lock() {
+            Some(queue) => d.field("len", &queue.len()),
+            None => d.field("len", &format_args!("<locked>")),
+        };
+        d.field("fairness", &self.fairness).finish_non_exhaustive()
+    }
+}
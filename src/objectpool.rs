@@ -0,0 +1,134 @@
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::AtomicUsize;
+
+use crate::ordering::Relaxed;
+use crate::segqueue::SegQueue;
+use crate::waitqueue::WaitQueue;
+
+// A pool of reusable objects (buffers, connections etc). Threads check an object out, and it goes back
+// into the pool when the Pooled handle is dropped. New objects are only made with the factory when the pool
+// is empty, and never more than cap of them if there is one.
+// Idle objects are handed out first in, first out - the one that's been back in the pool longest goes out
+// next, so the load is spread over all of them (connections stay alive, buffers get reused evenly) rather
+// than a few warm ones taking it all
+pub struct ObjectPool<T> {
+    // the objects that aren't checked out, in a lock-free queue so checkouts and returns never wait on a lock
+    idle: SegQueue<T>,
+    factory: Box<dyn Fn() -> T + Send + Sync>,
+    cap: Option<usize>,
+    // how many objects the factory has made that still belong to the pool
+    created: AtomicUsize,
+    // threads parked in checkout waiting for an object to come back
+    waiters: WaitQueue,
+}
+
+impl<T> ObjectPool<T> {
+    pub fn new(factory: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        Self {
+            idle: SegQueue::new(),
+            factory: Box::new(factory),
+            cap: None,
+            created: AtomicUsize::new(0),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn with_cap(cap: usize, factory: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        assert!(cap > 0, "an ObjectPool with a cap of 0 could never hand anything out");
+        Self {
+            cap: Some(cap),
+            ..Self::new(factory)
+        }
+    }
+
+    // Returns an idle object, or makes a new one if there's room. Returns None if the pool is at its cap
+    // and everything is checked out
+    pub fn try_checkout(&self) -> Option<Pooled<'_, T>> {
+        let object = match self.idle.pop() {
+            Some(object) => object,
+            None => {
+                let cap = self.cap.unwrap_or(usize::MAX);
+                // claim a slot before making the object, so two threads can't both take the last one
+                self.created.fetch_update(Relaxed, Relaxed, |n| (n < cap).then_some(n + 1)).ok()?;
+                (self.factory)()
+            }
+        };
+        Some(Pooled { pool: self, object: ManuallyDrop::new(object) })
+    }
+
+    // Like try_checkout, but parks until an object is returned if the pool is at its cap
+    pub fn checkout(&self) -> Pooled<'_, T> {
+        loop {
+            if let Some(object) = self.try_checkout() {
+                return object;
+            }
+            let mut object = None;
+            // try again after joining the queue, otherwise an object returned in between would never wake us
+            self.waiters.wait(|| {
+                object = self.try_checkout();
+                object.is_none()
+            });
+            if let Some(object) = object {
+                return object;
+            }
+        }
+    }
+
+    pub fn idle(&self) -> usize {
+        self.idle.len()
+    }
+
+    pub fn created(&self) -> usize {
+        self.created.load(Relaxed)
+    }
+
+    fn release(&self, object: T) {
+        self.idle.push(object);
+        self.wake_waiters();
+    }
+
+    // Wakes every waiting thread rather than just one, as a woken thread might lose the object to
+    // a thread that wasn't waiting, and the others would then sleep through the next release
+    fn wake_waiters(&self) {
+        self.waiters.wake_all();
+    }
+}
+
+// An object checked out of an ObjectPool - it goes back to the pool when this is dropped
+pub struct Pooled<'a, T> {
+    pool: &'a ObjectPool<T>,
+    object: ManuallyDrop<T>,
+}
+
+impl<T> Pooled<'_, T> {
+    // Takes the object out of the pool for good, making room for the factory to make another
+    pub fn detach(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        this.pool.created.fetch_sub(1, Relaxed);
+        this.pool.wake_waiters();
+        // Safety: `this` is never dropped, so the object is only taken out once
+        unsafe { ManuallyDrop::take(&mut this.object) }
+    }
+}
+
+impl<T> Deref for Pooled<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<T> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.object
+    }
+}
+
+impl<T> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        // Safety: the object isn't used again after this
+        let object = unsafe { ManuallyDrop::take(&mut self.object) };
+        self.pool.release(object);
+    }
+}
@@ -1,6 +1,8 @@
 use std::mem::MaybeUninit;
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicBool, Ordering::{Relaxed, Release, Acquire}};
+use std::sync::atomic::AtomicBool;
 use std::thread;
 use std::sync::Arc;
 
+use crate::ordering::{Relaxed, Release, Acquire};
+
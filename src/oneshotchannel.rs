@@ -1,17 +1,28 @@
+use std::fmt;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicBool, Ordering::{Relaxed, Release, Acquire}};
+use std::sync::atomic::{AtomicBool, AtomicU32};
 use std::thread;
 use std::thread::Thread;
 
+use crate::futex::{park, wait, wake_one};
+#[cfg(feature = "metrics")]
+use crate::metrics::{ChannelCounters, ChannelStats};
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::sched::pause;
+use crate::trace::{check_blocking, trace_event};
+use crate::waitstrategy::WaitStrategy;
+
 
 // message - holds some data we may want to use
 // ready - lets us know whether or not it is ready
 pub struct OneshotChannel<T> {
     message: UnsafeCell<MaybeUninit<T>>,
     ready: AtomicBool,
-    in_use: AtomicBool
+    in_use: AtomicBool,
+    #[cfg(feature = "metrics")]
+    stats: ChannelCounters,
 }
 
 // this impl tells the compiler our new channel is (reasonably) safe to share as long as T is Send
@@ -23,6 +34,8 @@ impl<T> OneshotChannel<T> {
             message: UnsafeCell::new(MaybeUninit::uninit()),
             ready: AtomicBool::new(false),
             in_use: AtomicBool::new(false),
+            #[cfg(feature = "metrics")]
+            stats: ChannelCounters::new(),
         }
     }
 
@@ -35,7 +48,12 @@ impl<T> OneshotChannel<T> {
             panic!("Can't send more than one message!");
         }
         unsafe {(*self.message.get()).write(message)};
+        pause!("OneshotChannel::send written");
+        // counted before the message is let go, so a receiver that has it sees the send counted too
+        #[cfg(feature = "metrics")]
+        self.stats.record_send(1, 1);
         self.ready.store(true, Release);
+        trace_event!("oneshot channel sent");
     }
 
     // if Receive doesn't check the status of self.ready.load, this would be in Acquire memory ordering
@@ -44,16 +62,51 @@ impl<T> OneshotChannel<T> {
         self.ready.load(Relaxed)
     }
 
+    // 1 if there's a message waiting, 0 otherwise. Like is_ready this is only a snapshot - the message can be
+    // sent or received by another thread straight after
+    pub fn len(&self) -> usize {
+        self.ready.load(Relaxed) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    // With one message at most, this is mostly whether it's been sent and received yet
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> ChannelStats {
+        self.stats.snapshot()
+    }
 
     pub fn receive(&self) -> T {
         // if is_ready wasn't called, panic and produce a message - this makes it safe to use
         if !self.ready.swap(false, Acquire) {
             panic!("No message available!");
         }
+        #[cfg(feature = "metrics")]
+        self.stats.record_receive(1);
+        trace_event!("oneshot channel received");
         unsafe { (*self.message.get()).assume_init_read() }
     }
 }
 
+impl<T> Default for OneshotChannel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Whether the message is waiting, not the message - reading it would race with the receive
+impl<T> fmt::Debug for OneshotChannel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OneshotChannel").field("ready", &self.is_ready()).finish_non_exhaustive()
+    }
+}
+
 impl<T> Drop for OneshotChannel<T> {
     fn drop(&mut self) {
         if *self.ready.get_mut() {
@@ -62,21 +115,6 @@ impl<T> Drop for OneshotChannel<T> {
     }
 }
 
-pub fn simulate_oneshot_channel() {
-    let channel = OneshotChannel::new();
-    let t = thread::current();
-    thread::scope(|s| {
-        s.spawn(|| {
-            channel.send("hello world!");
-            t.unpark();
-        });
-        while !channel.is_ready() {
-            thread::park();
-        }
-        assert_eq!(channel.receive(), "hello world!");
-    })
-}
-
 pub struct Sender<'a, T> {
     channel: &'a Channel<T>,
     receiving_thread: Thread,
@@ -89,22 +127,36 @@ pub struct Receiver<'a, T> {
     _no_send: PhantomData<*const ()>
 }
 
-struct Channel<T> { // no longer `pub`
+// The split version: send and receive go through a Sender and a Receiver borrowed from the channel, so sending
+// twice (or receiving on the wrong thread) doesn't compile rather than panicking
+pub struct Channel<T> {
     message: UnsafeCell<MaybeUninit<T>>,
     ready: AtomicBool,
+    // whether the Receiver is still around, for the Sender to find out nobody's going to receive
+    receiver: AtomicU32,
+    #[cfg(feature = "metrics")]
+    stats: ChannelCounters,
 }
 
+const RECEIVER_ALIVE: u32 = 0;
+// alive, and the sender is asleep in closed() waiting for it to go
+const SENDER_WAITING: u32 = 1;
+const RECEIVER_DROPPED: u32 = 2;
+
 unsafe impl<T> Sync for Channel<T> where T: Send {}
 
 impl<T> Channel<T> {
     pub const fn new() -> Self {
         Self {
             message: UnsafeCell::new(MaybeUninit::uninit()),
-            ready: AtomicBool::new(false)
+            ready: AtomicBool::new(false),
+            receiver: AtomicU32::new(RECEIVER_ALIVE),
+            #[cfg(feature = "metrics")]
+            stats: ChannelCounters::new(),
         }
     }
 
-    pub fn split<'a>(&mut self) -> (Sender<T>, Receiver<T>) {
+    pub fn split(&mut self) -> (Sender<'_, T>, Receiver<'_, T>) {
         // By overwriting *self with a new empty channel (where Self is a Channel<T>), we make sure it's in the 
         // expected state before we return the sender and receiver
         *self = Self::new();
@@ -119,25 +171,130 @@ impl<T> Channel<T> {
             }
         )
     }
+
+    // split starts these from zero again, along with everything else
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> ChannelStats {
+        self.stats.snapshot()
+    }
+}
+
+impl<T> Default for Channel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Channel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("ready", &self.ready.load(Relaxed))
+            .field("receiver_dropped", &(self.receiver.load(Relaxed) == RECEIVER_DROPPED))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Sender<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").field("closed", &self.is_closed()).finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").field("len", &self.len()).finish_non_exhaustive()
+    }
 }
 
 impl<T> Sender<'_, T> {
     pub fn send(self, message: T) {
         unsafe { (*self.channel.message.get()).write(message)};
+        #[cfg(feature = "metrics")]
+        self.channel.stats.record_send(1, 1);
         self.channel.ready.store(true, Release);
+        trace_event!("oneshot channel sent, unparking receiver");
         self.receiving_thread.unpark();
     }
+
+    // Whether the Receiver has been dropped, so a message sent now would never be received. Worth checking
+    // now and then while working out an expensive message, to give up on it early
+    pub fn is_closed(&self) -> bool {
+        self.channel.receiver.load(Relaxed) == RECEIVER_DROPPED
+    }
+
+    // Blocks until the Receiver is dropped. Meant to run alongside the work (on another thread, or in a select
+    // loop of some kind), as there's no waking it up again if the message gets sent instead
+    pub fn closed(&self) {
+        let receiver = &self.channel.receiver;
+        // let the receiver know it has to wake us when it goes
+        if receiver.compare_exchange(RECEIVER_ALIVE, SENDER_WAITING, Relaxed, Relaxed) == Err(RECEIVER_DROPPED) {
+            return;
+        }
+        while receiver.load(Relaxed) == SENDER_WAITING {
+            trace_event!("oneshot sender waiting for the receiver to close");
+            wait(receiver, SENDER_WAITING);
+        }
+    }
 }
 
 impl<T> Receiver<'_, T> {
-    pub fn receive(&self) -> T { 
+    // 1 if the message has arrived, 0 otherwise. The sender is on another thread, so this can go from 0 to 1
+    // at any moment - it's a snapshot
+    pub fn len(&self) -> usize {
+        self.channel.ready.load(Relaxed) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> ChannelStats {
+        self.channel.stats.snapshot()
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn receive(&self) -> T {
+        check_blocking!(when !self.channel.ready.load(Relaxed), "oneshotchannel::Receiver::receive");
+        while !self.channel.ready.swap(false, Acquire) {
+            trace_event!("oneshot receiver parking");
+            park(None);
+            trace_event!("oneshot receiver unparked");
+        }
+        #[cfg(feature = "metrics")]
+        self.channel.stats.record_receive(1);
+        trace_event!("oneshot channel received");
+        unsafe { (*self.channel.message.get()).assume_init_read() }
+    }
+
+    // Same as receive, but waits for the message with the given strategy rather than parking straight away.
+    // Spinning for a bit first saves the cost of parking and unparking when the message is about to arrive
+    pub fn receive_with(&self, strategy: &impl WaitStrategy) -> T {
+        let mut attempt = 0;
         while !self.channel.ready.swap(false, Acquire) {
-            thread::park();
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
         }
+        #[cfg(feature = "metrics")]
+        self.channel.stats.record_receive(1);
+        trace_event!("oneshot channel received");
         unsafe { (*self.channel.message.get()).assume_init_read() }
     }
 }
 
+impl<T> Drop for Receiver<'_, T> {
+    fn drop(&mut self) {
+        // only a sender asleep in closed() needs the syscall to wake it
+        if self.channel.receiver.swap(RECEIVER_DROPPED, Relaxed) == SENDER_WAITING {
+            wake_one(&self.channel.receiver);
+        }
+    }
+}
+
 impl<T> Drop for Channel<T> {
     fn drop(&mut self) {
         if *self.ready.get_mut() {
@@ -147,14 +304,3 @@ impl<T> Drop for Channel<T> {
         }
     }
 }
-
-pub fn simulate_oneshot_channel_with_sender_and_receiver() {
-    let mut channel = Channel::new();
-    thread::scope(|s| {
-        let (sender, receiver) = channel.split();
-        s.spawn(move || {
-            sender.send("hello world!");
-        });
-        assert_eq!(receiver.receive(), "hello world!");
-    })
-}
\ No newline at end of file
@@ -0,0 +1,27 @@
+// The memory orderings every primitive in the crate uses, in place of std's Ordering variants. Normally they're
+// exactly those, but with the strict_ordering feature every one of them is SeqCst - for ruling orderings in or
+// out when something only goes wrong on weakly ordered hardware (ARM, say). If a bug goes away with it on, an
+// ordering somewhere is too weak; if it's still there, it's something else. SeqCst everywhere costs a fence or
+// two per operation on ARM, so it's for debugging, not for shipping.
+//
+// Orderings callers pass in themselves (to AtomicF64::load, say) are theirs and are left alone
+// without std only some of them are used
+#[cfg(not(feature = "strict_ordering"))]
+#[cfg_attr(not(feature = "std"), allow(unused_imports))]
+pub(crate) use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst};
+
+#[cfg(feature = "strict_ordering")]
+pub(crate) use strict::*;
+
+#[cfg(feature = "strict_ordering")]
+#[allow(non_upper_case_globals)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+mod strict {
+    use core::sync::atomic::Ordering;
+
+    pub(crate) const Relaxed: Ordering = Ordering::SeqCst;
+    pub(crate) const Acquire: Ordering = Ordering::SeqCst;
+    pub(crate) const Release: Ordering = Ordering::SeqCst;
+    pub(crate) const AcqRel: Ordering = Ordering::SeqCst;
+    pub(crate) const SeqCst: Ordering = Ordering::SeqCst;
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use std::task::Wake;
+
+use crate::deadline::Deadline;
+use crate::futex::{wait, wait_timeout, wake_one};
+use crate::ordering::{Relaxed, Release, Acquire};
+
+// The states are picked so a park can move down one with a single fetch_sub: NOTIFIED to EMPTY (a token was
+// waiting, so return straight away) or EMPTY to PARKED (nothing yet, so go to sleep)
+const PARKED: u32 = 0;
+const EMPTY: u32 = 1;
+const NOTIFIED: u32 = 2;
+
+// thread::park and unpark as a value of its own, on the futex module: one thread parks on it, and any thread
+// can unpark it. Like thread::park there's a token, so an unpark that comes before the park isn't lost - the
+// park just returns straight away. Unlike thread::park it doesn't need a Thread handle, so it can be put
+// anywhere (in a Waker, say - it implements Wake, which is what executor::block_on uses it for).
+// Only one thread can be parked on it at once
+pub struct Parker {
+    state: AtomicU32,
+}
+
+impl Parker {
+    pub const fn new() -> Self {
+        Self { state: AtomicU32::new(EMPTY) }
+    }
+
+    // Blocks until unpark is called, or returns straight away if it already was since the last park.
+    // Acquire, so whatever the unparking thread did before unpark is visible after
+    pub fn park(&self) {
+        if self.state.fetch_sub(1, Acquire) == NOTIFIED {
+            return;
+        }
+        loop {
+            wait(&self.state, PARKED);
+            if self.state.compare_exchange(NOTIFIED, EMPTY, Acquire, Relaxed).is_ok() {
+                return;
+            }
+            // a spurious wake up, still PARKED
+        }
+    }
+
+    // Same as park, but gives up after timeout. Like thread::park_timeout it can also return early for no
+    // reason, so the caller checks whatever it was waiting for
+    pub fn park_timeout(&self, timeout: impl Into<Deadline>) {
+        let remaining = timeout.into().remaining();
+        if self.state.fetch_sub(1, Acquire) == NOTIFIED {
+            return;
+        }
+        match remaining {
+            Some(timeout) => wait_timeout(&self.state, PARKED, timeout),
+            None => wait(&self.state, PARKED),
+        }
+        // woken or not, this takes the token if an unpark came in
+        self.state.swap(EMPTY, Acquire);
+    }
+
+    // Wakes the parked thread, or lets its next park return straight away. The syscall is only made when a
+    // thread is actually asleep
+    pub fn unpark(&self) {
+        if self.state.swap(NOTIFIED, Release) == PARKED {
+            wake_one(&self.state);
+        }
+    }
+}
+
+impl Default for Parker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Wake needs std's Arc, as that's what Waker::from takes
+impl Wake for Parker {
+    fn wake(self: Arc<Self>) {
+        self.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.unpark();
+    }
+}
@@ -0,0 +1,155 @@
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::AtomicU32;
+use std::sync::Once;
+
+use crate::ordering::{Relaxed, Acquire, Release};
+
+// A mutex with priority inheritance, using the kernel's PI futexes. If a high priority thread blocks on the
+// mutex while a low priority thread holds it, the kernel boosts the holder to the waiter's priority until it
+// unlocks. Without that, a medium priority thread can keep the holder from ever running, and the high
+// priority thread waits on it indefinitely (priority inversion) - which is why realtime code normally can't
+// use a userspace lock at all.
+// The kernel decides the format of the futex word: 0 when unlocked, otherwise the owner's thread id, plus
+// FUTEX_WAITERS once a thread has blocked in the kernel. Uncontended locking and unlocking never leave userspace,
+// and when they do, the kernel hands the lock over with its own atomic operations on the word (and the syscall
+// is a full barrier), so the value is published just like with the compare exchanges
+pub struct PiMutex<T> {
+    futex: AtomicU32,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for PiMutex<T> {}
+
+impl<T: Default> Default for PiMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for PiMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// The owner is whatever thread id is in the futex word, which is as good as a name in top or a debugger
+impl<T> fmt::Debug for PiMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let owner = self.futex.load(Relaxed) & libc::FUTEX_TID_MASK;
+        f.debug_struct("PiMutex").field("owner", &(owner != 0).then_some(owner)).finish_non_exhaustive()
+    }
+}
+
+thread_local! {
+    // gettid is a syscall, and the lock needs it every time
+    static TID: Cell<u32> = const { Cell::new(0) };
+}
+
+fn current_tid() -> u32 {
+    // A forked child starts out with a copy of the forking thread's cache, holding the parent's tid - its locks
+    // would name a thread in the other process as the owner, and the kernel turns down its unlocks. The handler
+    // goes in before anything's cached, so every cache that can be copied into a child gets cleared there
+    static FORK_HANDLER: Once = Once::new();
+    TID.with(|tid| {
+        if tid.get() == 0 {
+            FORK_HANDLER.call_once(|| unsafe {
+                libc::pthread_atfork(None, None, Some(forget_tid));
+            });
+            tid.set(unsafe { libc::gettid() } as u32);
+        }
+        tid.get()
+    })
+}
+
+// Runs in the child after a fork, on the only thread it has
+extern "C" fn forget_tid() {
+    let _ = TID.try_with(|tid| tid.set(0));
+}
+
+fn futex_pi(futex: &AtomicU32, op: libc::c_int) -> std::io::Result<()> {
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            futex as *const AtomicU32,
+            op | libc::FUTEX_PRIVATE_FLAG,
+            0,
+            std::ptr::null::<libc::timespec>(),
+        )
+    };
+    if result == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) }
+}
+
+impl<T> PiMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self { futex: AtomicU32::new(0), value: UnsafeCell::new(value) }
+    }
+
+    pub fn lock(&self) -> PiGuard<'_, T> {
+        let tid = current_tid();
+        if self.futex.compare_exchange(0, tid, Acquire, Relaxed).is_err() {
+            // contended: the kernel queues us by priority, boosts the owner, and hands us the lock
+            loop {
+                match futex_pi(&self.futex, libc::FUTEX_LOCK_PI) {
+                    Ok(()) => break,
+                    // the owner is exiting, or a signal came in - try again
+                    Err(e) if matches!(e.raw_os_error(), Some(libc::EAGAIN | libc::EINTR)) => continue,
+                    Err(e) => panic!("FUTEX_LOCK_PI failed: {e}"),
+                }
+            }
+        }
+        PiGuard { mutex: self, _not_send: PhantomData }
+    }
+
+    pub fn try_lock(&self) -> Option<PiGuard<'_, T>> {
+        self.futex
+            .compare_exchange(0, current_tid(), Acquire, Relaxed)
+            .ok()
+            .map(|_| PiGuard { mutex: self, _not_send: PhantomData })
+    }
+
+    // No futex and no syscall, the &mut means nobody else has the mutex
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+pub struct PiGuard<'a, T> {
+    mutex: &'a PiMutex<T>,
+    // the kernel only lets the owning thread unlock a PI futex, so the guard can't be sent to another thread
+    _not_send: PhantomData<*const ()>,
+}
+
+// Sharing it is fine though, as that only hands out &T
+unsafe impl<T: Sync> Sync for PiGuard<'_, T> {}
+
+impl<T> Deref for PiGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: the guard means this thread owns the mutex
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for PiGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T> Drop for PiGuard<'_, T> {
+    fn drop(&mut self) {
+        // with nobody waiting the word is just our tid. If it's anything else the FUTEX_WAITERS bit is set,
+        // and the kernel has to pick the next owner (and undo our priority boost)
+        let tid = current_tid();
+        if self.mutex.futex.compare_exchange(tid, 0, Release, Relaxed).is_err() {
+            futex_pi(&self.mutex.futex, libc::FUTEX_UNLOCK_PI).expect("FUTEX_UNLOCK_PI failed");
+        }
+    }
+}
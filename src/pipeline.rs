@@ -0,0 +1,247 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::thread::{self, JoinHandle};
+
+use crate::arc::Arc;
+use crate::boundedchannel::{sync_channel, Receiver};
+use crate::mutex::Mutex;
+use crate::ordering::{Relaxed, AcqRel};
+use crate::sequencer::Sequencer;
+
+// The error a pipeline finishes with: the first one any stage returned
+pub type Error = Box<dyn StdError + Send + Sync>;
+
+// Stages of parallel processing wired together with bounded channels, so there's no channel plumbing to
+// write by hand:
+//
+//     let lengths = Pipeline::source(64, paths)
+//         .try_map(8, |path| fs::read_to_string(path))
+//         .map(2, |text| text.lines().count())
+//         .collect()?;
+//
+// Every stage runs on its own threads. The channels between them are bounded, so a slow stage holds up the ones
+// before it instead of a queue building up in front of it (backpressure). When the source runs out, each stage
+// finishes what it has and closes the channel after it, so the end of the input flows down the pipeline.
+// An error (or a cancel) flows back up: every stage stops taking new items, and the first error is what run
+// returns. Stages with more than one thread don't keep items in order, unless they're added with map_ordered
+pub struct Pipeline<T> {
+    output: Receiver<T>,
+    capacity: usize,
+    control: Arc<Control>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+struct Control {
+    cancelled: AtomicBool,
+    error: Mutex<Option<Error>>,
+}
+
+impl Control {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Relaxed)
+    }
+
+    // Only the first error is kept, the ones after it are usually fallout from the cancel
+    fn fail(&self, error: Error) {
+        let mut slot = self.error.lock();
+        if slot.is_none() {
+            *slot = Some(error);
+        }
+        self.cancelled.store(true, Relaxed);
+    }
+}
+
+impl<T: Send + 'static> Pipeline<T> {
+    // Starts a pipeline with a thread feeding items into it. capacity is the size of every channel in the
+    // pipeline, which is how far one stage can get ahead of the next
+    pub fn source<I>(capacity: usize, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: Send + 'static,
+    {
+        let control = Arc::new(Control { cancelled: AtomicBool::new(false), error: Mutex::new(None) });
+        let (sender, output) = sync_channel(capacity);
+        let items = items.into_iter();
+        let thread = {
+            let control = control.clone();
+            thread::spawn(move || {
+                for item in items {
+                    // a send fails once everything downstream has gone
+                    if control.is_cancelled() || sender.send(item).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+        Self { output, capacity, control, threads: vec![thread] }
+    }
+
+    // Adds a stage that runs f over every item on the given number of threads
+    pub fn map<U: Send + 'static>(self, threads: usize, f: impl Fn(T) -> U + Send + Sync + 'static) -> Pipeline<U> {
+        self.try_map(threads, move |item| Ok::<_, Error>(f(item)))
+    }
+
+    // Same as map, but f can fail, which cancels the whole pipeline
+    pub fn try_map<U, E>(self, threads: usize, f: impl Fn(T) -> Result<U, E> + Send + Sync + 'static) -> Pipeline<U>
+    where
+        U: Send + 'static,
+        E: Into<Error>,
+    {
+        assert!(threads > 0, "a pipeline stage needs at least one thread");
+        let Self { output: input, capacity, control, threads: mut handles } = self;
+        let (sender, output) = sync_channel(capacity);
+        let f = Arc::new(f);
+        for _ in 0..threads {
+            let (input, sender, control, f) = (input.clone(), sender.clone(), control.clone(), f.clone());
+            handles.push(thread::spawn(move || {
+                // ends when the stage before is done, or when something's gone wrong anywhere
+                while let Ok(item) = input.receive() {
+                    if control.is_cancelled() {
+                        break;
+                    }
+                    match f(item) {
+                        Ok(item) => {
+                            if sender.send(item).is_err() {
+                                break;
+                            }
+                        }
+                        Err(error) => {
+                            control.fail(error.into());
+                            break;
+                        }
+                    }
+                }
+            }));
+        }
+        // the workers hold the only senders and receivers now, so the channels close when they finish
+        Pipeline { output, capacity, control, threads: handles }
+    }
+
+    // Same as map, but the items come out of the stage in the order they went in. Each one is numbered as it's
+    // taken off the input, and a Sequencer after the workers puts the results back in that order before they
+    // go on. Its window is the pipeline's capacity, so one slow item holds the workers up once they're that
+    // far ahead of it
+    pub fn map_ordered<U>(self, threads: usize, f: impl Fn(T) -> U + Send + Sync + 'static) -> Pipeline<U>
+    where
+        U: Send + 'static,
+    {
+        assert!(threads > 0, "a pipeline stage needs at least one thread");
+        let Self { output: input, capacity, control, threads: mut handles } = self;
+        let (sender, output) = sync_channel(capacity);
+        let sequencer = Arc::new(Sequencer::new(capacity));
+        // taking an item and its number happen together under the lock, or two workers could number theirs
+        // the other way round from how they came off the channel
+        let input = Arc::new(Mutex::new((input, 0u64)));
+        let running = Arc::new(AtomicUsize::new(threads));
+        let f = Arc::new(f);
+        for _ in 0..threads {
+            let (input, sequencer, control, running, f) =
+                (input.clone(), sequencer.clone(), control.clone(), running.clone(), f.clone());
+            handles.push(thread::spawn(move || {
+                loop {
+                    let next = {
+                        let mut input = input.lock();
+                        let item = input.0.receive();
+                        item.map(|item| {
+                            input.1 += 1;
+                            (input.1 - 1, item)
+                        })
+                    };
+                    let Ok((seq, item)) = next else { break };
+                    // leaving early leaves a gap that would never be filled, so the sequencer is closed to
+                    // stop the other workers waiting on it
+                    if control.is_cancelled() || sequencer.push(seq, f(item)).is_err() {
+                        sequencer.close();
+                        return;
+                    }
+                }
+                // the last worker out closes it, once every item is in. AcqRel so the other workers' pushes are
+                // all in before the close
+                if running.fetch_sub(1, AcqRel) == 1 {
+                    sequencer.close();
+                }
+            }));
+        }
+        handles.push(thread::spawn(move || {
+            while let Some(item) = sequencer.pop() {
+                if sender.send(item).is_err() {
+                    // nothing downstream any more, so the workers can stop too
+                    sequencer.close();
+                    break;
+                }
+            }
+        }));
+        Pipeline { output, capacity, control, threads: handles }
+    }
+
+    // Stops the pipeline early, wherever it's got to. run returns Err(Cancelled)
+    pub fn canceller(&self) -> Canceller {
+        Canceller { control: self.control.clone() }
+    }
+
+    // Runs every item out of the last stage through sink, on the calling thread, and waits for all the stages
+    // to finish. A panic in any stage is passed on from here
+    pub fn run(self, mut sink: impl FnMut(T)) -> Result<(), Error> {
+        self.try_run(|item| {
+            sink(item);
+            Ok::<_, Error>(())
+        })
+    }
+
+    // Same as run, but the sink can fail too, which cancels the rest of the pipeline
+    pub fn try_run<E: Into<Error>>(self, mut sink: impl FnMut(T) -> Result<(), E>) -> Result<(), Error> {
+        let Self { output, control, threads, .. } = self;
+        while let Ok(item) = output.receive() {
+            if control.is_cancelled() {
+                break;
+            }
+            if let Err(error) = sink(item) {
+                control.fail(error.into());
+                break;
+            }
+        }
+        // the last stage can't send any more, which stops it if it's still going
+        drop(output);
+        for thread in threads {
+            if let Err(panic) = thread.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+        let error = control.error.lock().take();
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    pub fn collect(self) -> Result<Vec<T>, Error> {
+        let mut items = Vec::new();
+        self.run(|item| items.push(item))?;
+        Ok(items)
+    }
+}
+
+// Cancels a pipeline from anywhere, even while run is blocked on another thread
+#[derive(Clone)]
+pub struct Canceller {
+    control: Arc<Control>,
+}
+
+impl Canceller {
+    pub fn cancel(&self) {
+        self.control.fail(Box::new(Cancelled));
+    }
+}
+
+// What run returns after a cancel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("pipeline cancelled")
+    }
+}
+
+impl StdError for Cancelled {}
@@ -0,0 +1,239 @@
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::time::Instant;
+
+use crate::arc::Arc;
+use crate::boundedchannel::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use crate::deadline::Deadline;
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::receive::Receive;
+use crate::segqueue::SegQueue;
+use crate::trace::check_blocking;
+use crate::waitqueue::WaitQueue;
+
+// How many priority messages in a row priority_channel delivers while normal ones are waiting
+pub const DEFAULT_BURST: u32 = 16;
+
+// An unbounded multi-producer single-consumer channel with two lanes, for workers that get both bulk data and
+// control messages (stop, reconfigure, flush) - a control message at the back of a long queue of data only gets
+// seen once all the data's done, which is usually too late. send_priority puts a message in the priority lane,
+// and the receiver takes from that lane first, so it's next to be received however much data is queued:
+//
+//     let (sender, receiver) = priority_channel();
+//     sender.send(Chunk(data))?;
+//     sender.send_priority(Stop)?;
+//     assert!(matches!(receiver.receive()?, Stop));
+//
+// Always taking the priority lane first would let a steady stream of priority messages starve the normal lane
+// for good, though. So after burst priority messages in a row while normal ones were waiting, the receiver takes
+// one normal message before going back to the priority lane. Within a lane it's first in, first out, but
+// nothing's promised about the order of messages in different lanes beyond that
+pub fn priority_channel<T>() -> (Sender<T>, Receiver<T>) {
+    priority_channel_with_burst(DEFAULT_BURST)
+}
+
+// Same as priority_channel, but with burst priority messages delivered for every normal one when both lanes have
+// messages waiting. A burst of 1 takes turns between the lanes
+pub fn priority_channel_with_burst<T>(burst: u32) -> (Sender<T>, Receiver<T>) {
+    assert!(burst > 0, "a priority channel has to deliver at least one priority message at a time");
+    let chan = Arc::new(Chan {
+        priority: SegQueue::new(),
+        normal: SegQueue::new(),
+        burst,
+        senders: AtomicUsize::new(1),
+        receiver: AtomicBool::new(true),
+        waiting: WaitQueue::new(),
+    });
+    (Sender { chan: chan.clone() }, Receiver { chan, streak: Cell::new(0) })
+}
+
+struct Chan<T> {
+    priority: SegQueue<T>,
+    normal: SegQueue<T>,
+    burst: u32,
+    senders: AtomicUsize,
+    // whether the Receiver's still there
+    receiver: AtomicBool,
+    // the receiver, when it's waiting for a message
+    waiting: WaitQueue,
+}
+
+// Can be cloned, for any number of senders
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+// There's only ever the one, so it isn't Clone (or Sync, as it keeps its place in the burst in a Cell)
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+    // priority messages delivered in a row while the normal lane had something in it
+    streak: Cell<u32>,
+}
+
+impl<T> Sender<T> {
+    // Never blocks, as the channel has no capacity to run out of. The message comes back once the Receiver's gone
+    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+        self.push(&self.chan.normal, message)
+    }
+
+    // Same as send, but in the priority lane, ahead of every normal message
+    pub fn send_priority(&self, message: T) -> Result<(), SendError<T>> {
+        self.push(&self.chan.priority, message)
+    }
+
+    fn push(&self, lane: &SegQueue<T>, message: T) -> Result<(), SendError<T>> {
+        if !self.chan.receiver.load(Acquire) {
+            return Err(SendError(message));
+        }
+        lane.push(message);
+        self.chan.waiting.wake_one();
+        Ok(())
+    }
+
+    // Messages waiting in both lanes, a snapshot
+    pub fn len(&self) -> usize {
+        self.chan.priority.len() + self.chan.normal.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.priority.is_empty() && self.chan.normal.is_empty()
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn try_receive(&self) -> Result<T, TryRecvError> {
+        if let Some(message) = self.pop() {
+            return Ok(message);
+        }
+        if self.chan.senders.load(Acquire) == 0 {
+            // a sender could have sent one last message between the pop and the load, so look once more
+            return self.pop().ok_or(TryRecvError::Disconnected);
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    // Blocks while both lanes are empty. Once every sender is gone and there's nothing left, returns RecvError
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn receive(&self) -> Result<T, RecvError> {
+        check_blocking!(
+            when self.is_empty() && self.chan.senders.load(Relaxed) != 0,
+            "prioritychannel::Receiver::receive"
+        );
+        self.receive_until(None).map_err(|_| RecvError)
+    }
+
+    pub fn receive_timeout(&self, timeout: impl Into<Deadline>) -> Result<T, RecvTimeoutError> {
+        self.receive_until(timeout.into().instant())
+    }
+
+    // The messages as they come, ending once every sender is gone
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.receive().ok())
+    }
+
+    // Messages waiting in the priority lane, a snapshot
+    pub fn priority_len(&self) -> usize {
+        self.chan.priority.len()
+    }
+
+    // Messages waiting in both lanes, a snapshot
+    pub fn len(&self) -> usize {
+        self.chan.priority.len() + self.chan.normal.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.priority.is_empty() && self.chan.normal.is_empty()
+    }
+
+    // The priority lane first, unless it's had its burst and there's a normal message waiting
+    fn pop(&self) -> Option<T> {
+        let streak = self.streak.get();
+        if streak < self.chan.burst {
+            if let Some(message) = self.chan.priority.pop() {
+                // only counts towards the burst while there's a normal message being held up
+                self.streak.set(if self.chan.normal.is_empty() { 0 } else { streak + 1 });
+                return Some(message);
+            }
+        }
+        self.streak.set(0);
+        // falls back to the priority lane when the normal one turns out to be empty after all
+        self.chan.normal.pop().or_else(|| self.chan.priority.pop())
+    }
+
+    fn receive_until(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let attempt = || match self.try_receive() {
+            Ok(message) => Some(Ok(message)),
+            Err(TryRecvError::Disconnected) => Some(Err(RecvTimeoutError::Disconnected)),
+            Err(TryRecvError::Empty) => None,
+        };
+        loop {
+            if let Some(received) = attempt() {
+                return received;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            // checked again once it's in the queue, so a send in between isn't missed
+            let mut received = None;
+            self.chan.waiting.wait_until(deadline, || {
+                received = attempt();
+                received.is_none()
+            });
+            if let Some(received) = received {
+                return received;
+            }
+        }
+    }
+}
+
+impl<T> Receive for Receiver<T> {
+    type Item = T;
+
+    fn try_receive(&self) -> Result<T, TryRecvError> {
+        Receiver::try_receive(self)
+    }
+
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>) {
+        queues.push(&self.chan.waiting);
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, Relaxed);
+        Self { chan: self.chan.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // the last sender wakes the receiver so it can find out the channel is disconnected
+        if self.chan.senders.fetch_sub(1, Release) == 1 {
+            self.chan.waiting.wake_all();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // whatever's left in the lanes goes when the last Sender does
+        self.chan.receiver.store(false, Release);
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("priority", &self.priority_len())
+            .field("len", &self.len())
+            .field("burst", &self.chan.burst)
+            .finish_non_exhaustive()
+    }
+}
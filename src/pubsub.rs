@@ -0,0 +1,229 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::sync::atomic::AtomicU64;
+
+use crate::arc::{Arc, Weak};
+use crate::boundedchannel::{sync_channel_with, Overflow, Receiver, SendError, Sender};
+use crate::ordering::Relaxed;
+use crate::rwspinlock::RwSpinLock;
+use crate::trace::trace_event;
+
+// Topic based publish/subscribe: every subscriber gets its own bounded channel for a topic, and publish puts a
+// clone of the message into each one subscribed to it. Dropping the Subscription unsubscribes it.
+//
+//     let bus = Bus::new();
+//     let prices = bus.subscribe("prices", 64);
+//     bus.publish("prices", Tick { .. });
+//     let tick = prices.receive()?;
+//
+// How a subscriber that can't keep up is handled is picked per subscription (see SlowSubscriber). The default
+// makes publish wait for it, same as a full bounded channel - which means one slow subscriber holds up the
+// publisher and so every other subscriber, so for anything where that matters pick one of the others.
+//
+// The subscriber lists are read on every publish and only changed on subscribe and unsubscribe, so they're
+// behind a RwSpinLock - and only for long enough to clone the Senders out, the sends happen after
+pub struct Bus<Topic, T> {
+    inner: Arc<Inner<Topic, T>>,
+}
+
+struct Inner<Topic, T> {
+    topics: RwSpinLock<HashMap<Topic, Vec<Subscriber<T>>>>,
+    next_id: AtomicU64,
+}
+
+struct Subscriber<T> {
+    id: u64,
+    // made with the subscriber's policy as its Overflow
+    sender: Sender<T>,
+}
+
+// What publish does when a subscriber's channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowSubscriber {
+    // Waits for the subscriber to make room
+    #[default]
+    Block,
+    // The subscriber misses the new message
+    DropNewest,
+    // The subscriber misses the oldest message it hasn't received yet, to make room for the new one
+    DropOldest,
+    // Unsubscribes it: it gets what's already in its channel, then finds it disconnected
+    Disconnect,
+}
+
+impl SlowSubscriber {
+    fn overflow(self) -> Overflow {
+        match self {
+            SlowSubscriber::Block => Overflow::Block,
+            SlowSubscriber::DropNewest => Overflow::DropNewest,
+            SlowSubscriber::DropOldest => Overflow::DropOldest,
+            // a full channel hands the message back, which is what tells publish to drop the subscriber
+            SlowSubscriber::Disconnect => Overflow::Fail,
+        }
+    }
+}
+
+// One subscriber's end: it derefs to the Receiver for receiving (and dropped() for what it's missed under
+// DropNewest or DropOldest), and unsubscribes when it's dropped. Once every Bus handle has gone, the receiver
+// finds its channel disconnected
+pub struct Subscription<Topic: Hash + Eq, T> {
+    receiver: Receiver<T>,
+    topic: Topic,
+    id: u64,
+    bus: Weak<Inner<Topic, T>>,
+}
+
+impl<Topic: Hash + Eq, T> Bus<Topic, T> {
+    pub fn new() -> Self {
+        Self { inner: Arc::new(Inner { topics: RwSpinLock::new(HashMap::new()), next_id: AtomicU64::new(0) }) }
+    }
+
+    // A channel of capacity messages for the topic's messages from now on, with publish waiting for room
+    pub fn subscribe(&self, topic: Topic, capacity: usize) -> Subscription<Topic, T>
+    where
+        Topic: Clone,
+    {
+        self.subscribe_with(topic, capacity, SlowSubscriber::Block)
+    }
+
+    // Same as subscribe, but with a say in what happens when the subscriber falls behind
+    pub fn subscribe_with(&self, topic: Topic, capacity: usize, policy: SlowSubscriber) -> Subscription<Topic, T>
+    where
+        Topic: Clone,
+    {
+        let (sender, receiver) = sync_channel_with(capacity, policy.overflow());
+        let id = self.inner.next_id.fetch_add(1, Relaxed);
+        let subscriber = Subscriber { id, sender };
+        self.inner.topics.write().entry(topic.clone()).or_default().push(subscriber);
+        Subscription { receiver, topic, id, bus: Arc::downgrade(&self.inner) }
+    }
+
+    // Sends a clone of message to every subscriber to topic (the last one gets the message itself), and
+    // returns how many it went to - 0 with nobody subscribed, in which case it's dropped. A subscriber it was
+    // dropped for under DropNewest counts too. Subscribers that have gone, or were too slow under Disconnect,
+    // are unsubscribed
+    pub fn publish<Q>(&self, topic: &Q, message: T) -> usize
+    where
+        Topic: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        T: Clone,
+    {
+        let subscribers: Vec<(u64, Sender<T>)> = match self.inner.topics.read().get(topic) {
+            Some(subscribers) => subscribers.iter().map(|s| (s.id, s.sender.clone())).collect(),
+            None => return 0,
+        };
+        let mut message = Some(message);
+        let mut sent = 0;
+        let mut gone = Vec::new();
+        for (i, (id, sender)) in subscribers.iter().enumerate() {
+            let copy = if i + 1 == subscribers.len() { message.take() } else { message.clone() };
+            let copy = copy.expect("only taken for the last subscriber");
+            match sender.send(copy) {
+                Ok(()) => sent += 1,
+                Err(SendError(_)) => gone.push(*id),
+            }
+        }
+        if !gone.is_empty() {
+            trace_event!("bus dropping subscribers");
+            self.inner.remove(topic, |subscriber| gone.contains(&subscriber.id));
+        }
+        sent
+    }
+
+    // How many subscribers the topic has right now
+    pub fn subscribers<Q>(&self, topic: &Q) -> usize
+    where
+        Topic: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.topics.read().get(topic).map_or(0, Vec::len)
+    }
+
+    // The topics with at least one subscriber
+    pub fn topics(&self) -> Vec<Topic>
+    where
+        Topic: Clone,
+    {
+        self.inner.topics.read().keys().cloned().collect()
+    }
+}
+
+impl<Topic: Hash + Eq, T> Inner<Topic, T> {
+    fn remove<Q>(&self, topic: &Q, mut unsubscribe: impl FnMut(&Subscriber<T>) -> bool)
+    where
+        Topic: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut removed = Vec::new();
+        let mut topics = self.topics.write();
+        if let Some(subscribers) = topics.get_mut(topic) {
+            let mut i = 0;
+            while i < subscribers.len() {
+                if unsubscribe(&subscribers[i]) {
+                    removed.push(subscribers.swap_remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+            if subscribers.is_empty() {
+                topics.remove(topic);
+            }
+        }
+        drop(topics);
+        // the last sender wakes the receivers, so that's done outside the lock
+        drop(removed);
+    }
+}
+
+// Clones are more handles to the same bus
+impl<Topic, T> Clone for Bus<Topic, T> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<Topic: Hash + Eq, T> Default for Bus<Topic, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Topic: fmt::Debug, T> fmt::Debug for Bus<Topic, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let topics = self.inner.topics.read();
+        f.debug_map().entries(topics.iter().map(|(topic, subscribers)| (topic, subscribers.len()))).finish()
+    }
+}
+
+impl<Topic: Hash + Eq, T> Subscription<Topic, T> {
+    pub fn topic(&self) -> &Topic {
+        &self.topic
+    }
+}
+
+impl<Topic: Hash + Eq, T> Deref for Subscription<Topic, T> {
+    type Target = Receiver<T>;
+    fn deref(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+}
+
+impl<Topic: Hash + Eq, T> Drop for Subscription<Topic, T> {
+    fn drop(&mut self) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.remove(&self.topic, |subscriber| subscriber.id == self.id);
+        }
+    }
+}
+
+impl<Topic: Hash + Eq + fmt::Debug, T> fmt::Debug for Subscription<Topic, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("topic", &self.topic)
+            .field("receiver", &self.receiver)
+            .finish_non_exhaustive()
+    }
+}
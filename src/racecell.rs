@@ -0,0 +1,149 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicU32;
+use std::time::Instant;
+
+use crate::deadline::Deadline;
+use crate::futex::{wait, wait_timeout, wake_all};
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::sched::pause;
+use crate::trace::trace_event;
+
+const EMPTY: u32 = 0;
+// a try_set won the race and is writing the value in
+const WRITING: u32 = 1;
+const SET: u32 = 2;
+// or'd in by a thread about to sleep in wait, so the winner knows to make the wake syscall
+const WAITING: u32 = 4;
+
+// A cell any number of threads can race to fill: the first try_set wins and the rest get their value back.
+// Everyone else can wait for the winner's value. For redundant work - asking several replicas the same thing,
+// or trying a few strategies at once - where whichever finishes first is the answer:
+//
+//     let answer = RaceCell::new();
+//     thread::scope(|s| {
+//         for replica in &replicas {
+//             s.spawn(|| { let _ = answer.try_set(replica.query()); });
+//         }
+//         use_it(answer.wait());
+//     });
+//
+// Once set the value never changes, so it's handed out as a plain &T
+pub struct RaceCell<T> {
+    state: AtomicU32,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Any thread can set it (so T: Send) and every thread can read it at once (so T: Sync)
+unsafe impl<T> Sync for RaceCell<T> where T: Send + Sync {}
+
+impl<T> RaceCell<T> {
+    pub const fn new() -> Self {
+        Self { state: AtomicU32::new(EMPTY), value: UnsafeCell::new(MaybeUninit::uninit()) }
+    }
+
+    // Sets the value if nobody has yet. Err hands it back to a thread that lost the race
+    pub fn try_set(&self, value: T) -> Result<(), T> {
+        let mut s = self.state.load(Relaxed);
+        loop {
+            if s & (WRITING | SET) != 0 {
+                return Err(value);
+            }
+            // keeps WAITING if it's there, a waiter could have come along already
+            match self.state.compare_exchange_weak(s, s | WRITING, Relaxed, Relaxed) {
+                Ok(_) => break,
+                Err(current) => s = current,
+            }
+        }
+        pause!("RaceCell::try_set won");
+        // Safety: only the one thread that moved it out of EMPTY gets here, and nobody reads the value before SET
+        unsafe { (*self.value.get()).write(value) };
+        // Release, so whoever sees SET sees the value too
+        if self.state.swap(SET, Release) & WAITING != 0 {
+            wake_all(&self.state);
+        }
+        trace_event!("race cell set");
+        Ok(())
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        // Safety: SET means the value's been written, and it's never written again. A waiter that came along too
+        // late can still have or'd in WAITING since
+        (self.state.load(Acquire) & SET != 0).then(|| unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.get().is_some()
+    }
+
+    // Blocks until some thread has set the value
+    pub fn wait(&self) -> &T {
+        self.wait_until(None).expect("no deadline to miss")
+    }
+
+    // Same as wait, but gives up once the deadline has passed
+    pub fn wait_timeout(&self, timeout: impl Into<Deadline>) -> Option<&T> {
+        self.wait_until(timeout.into().instant())
+    }
+
+    fn wait_until(&self, deadline: Option<Instant>) -> Option<&T> {
+        loop {
+            if let Some(value) = self.get() {
+                return Some(value);
+            }
+            // marked before sleeping on the marked value, so a set in between changes it and the wait returns
+            // straight away
+            let s = self.state.fetch_or(WAITING, Relaxed) | WAITING;
+            if s & SET != 0 {
+                continue;
+            }
+            trace_event!("race cell waiter sleeping");
+            match deadline {
+                None => wait(&self.state, s),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    wait_timeout(&self.state, s, deadline - now);
+                }
+            }
+        }
+    }
+
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    // Empties the cell again, which the &mut makes safe - nobody can be racing for it or waiting on it
+    pub fn take(&mut self) -> Option<T> {
+        if *self.state.get_mut() & SET == 0 {
+            return None;
+        }
+        *self.state.get_mut() = EMPTY;
+        // Safety: it was SET, and moving to EMPTY means it won't be read or dropped again
+        Some(unsafe { self.value.get_mut().assume_init_read() })
+    }
+}
+
+impl<T> Default for RaceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for RaceCell<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RaceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("RaceCell").field(value).finish(),
+            None => f.write_str("RaceCell(<unset>)"),
+        }
+    }
+}
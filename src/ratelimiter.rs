@@ -0,0 +1,83 @@
+use std::sync::atomic::AtomicU64;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::atomicupdate::AtomicUpdate;
+use crate::ordering::Relaxed;
+
+// A token bucket: it holds up to burst tokens, refills at a steady rate, and every acquire takes tokens out.
+// There's no background thread topping it up - the bucket is worked out from the time whenever someone asks.
+// All the state is one atomic, the "theoretical arrival time": when the bucket would be back to full if
+// nothing else was taken. Taking n tokens pushes it n intervals further out, and it's allowed as long as that
+// doesn't go more than burst intervals past now (the generic cell rate algorithm, GCRA)
+pub struct RateLimiter {
+    start: Instant,
+    // nanoseconds between tokens
+    interval: u64,
+    burst: u64,
+    // nanoseconds since start
+    full_at: AtomicU64,
+}
+
+impl RateLimiter {
+    // rate tokens every per, with room for burst of them to build up. It starts full
+    pub fn new(rate: u64, per: Duration, burst: u64) -> Self {
+        assert!(rate > 0, "a rate limiter needs a rate of at least one token");
+        Self {
+            start: Instant::now(),
+            interval: (per.as_nanos() / rate as u128).clamp(1, u64::MAX as u128) as u64,
+            burst,
+            full_at: AtomicU64::new(0),
+        }
+    }
+
+    pub fn per_second(rate: u64, burst: u64) -> Self {
+        Self::new(rate, Duration::from_secs(1), burst)
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+
+    // Takes n tokens if the bucket has them, without waiting
+    pub fn try_acquire(&self, n: u64) -> bool {
+        self.acquire_or_wait_time(n).is_ok()
+    }
+
+    // Takes n tokens, sleeping until the bucket has refilled enough if it has to. More than burst could never
+    // fit in the bucket, so that panics rather than waiting forever
+    pub fn acquire(&self, n: u64) {
+        assert!(n <= self.burst, "asked for {n} tokens from a rate limiter that only holds {}", self.burst);
+        while let Err(wait) = self.acquire_or_wait_time(n) {
+            // other threads can take the tokens in the meantime, so it's checked again after the sleep
+            thread::sleep(wait);
+        }
+    }
+
+    // Takes n tokens, or says how long until there would be enough
+    fn acquire_or_wait_time(&self, n: u64) -> Result<(), Duration> {
+        let cost = n.saturating_mul(self.interval);
+        let limit = self.burst.saturating_mul(self.interval);
+        let mut wait = Duration::ZERO;
+        // Relaxed as the tokens don't guard any other memory, they only limit how often things happen
+        self.full_at.try_cas_update(Relaxed, Relaxed, |full_at| {
+            let now = self.now();
+            // a bucket that's been full since before now doesn't get any fuller
+            let new_full_at = full_at.max(now).saturating_add(cost);
+            if new_full_at - now > limit {
+                wait = Duration::from_nanos(new_full_at - now - limit);
+                return None;
+            }
+            Some(new_full_at)
+        })
+        .map(drop)
+        .map_err(|_| wait)
+    }
+
+    // How many tokens are in the bucket right now. A snapshot, as ever
+    pub fn available(&self) -> u64 {
+        let now = self.now();
+        let owed = self.full_at.load(Relaxed).saturating_sub(now);
+        self.burst - owed.div_ceil(self.interval).min(self.burst)
+    }
+}
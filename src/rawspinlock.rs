@@ -0,0 +1,161 @@
+use std::fmt;
+use std::sync::atomic::AtomicU32;
+
+use crate::ordering::{Relaxed, Acquire, Release};
+use crate::relax::cpu_relax;
+use crate::sched::pause;
+use crate::waitstrategy::{Adaptive, WaitStrategy};
+
+// A spinlock with no data attached, just the lock itself. It's #[repr(C)] and only holds a u32, so it has the
+// same layout in every process (and every build) that maps it - it can sit in shared memory and processes can
+// lock it between them, not just threads. The SpinLock in spinlock.rs can't do that: its value and extra fields
+// are laid out however the compiler likes.
+// It's up to the user to decide what the lock protects
+#[repr(C)]
+pub struct RawSpinLock {
+    // UNLOCKED, or the PID of the process holding the lock. Knowing the owner is what lets lock_robust
+    // take the lock back from a process that died holding it
+    owner: AtomicU32,
+}
+
+const UNLOCKED: u32 = 0;
+
+impl RawSpinLock {
+    pub const fn new() -> Self {
+        Self { owner: AtomicU32::new(UNLOCKED) }
+    }
+
+    // Writes a new unlocked lock at ptr and borrows it, for setting up a lock in a region of shared memory.
+    // Only one process should do this, before any of them use the lock.
+    /// # Safety
+    /// ptr has to be valid for writes, aligned for a u32 and stay mapped for as long as 'a
+    pub unsafe fn init_at<'a>(ptr: *mut RawSpinLock) -> &'a Self {
+        ptr.write(Self::new());
+        &*ptr
+    }
+
+    // Borrows a lock that some other process (or earlier code) already set up with init_at.
+    /// # Safety
+    /// ptr has to point to an initialised RawSpinLock that stays mapped for as long as 'a
+    pub unsafe fn from_ptr<'a>(ptr: *const RawSpinLock) -> &'a Self {
+        &*ptr
+    }
+
+    pub fn lock(&self) -> RawGuard<'_> {
+        self.lock_with(&Adaptive)
+    }
+
+    pub fn lock_with(&self, strategy: &impl WaitStrategy) -> RawGuard<'_> {
+        let mut attempt = 0;
+        while !self.try_lock_raw() {
+            // wait until it looks unlocked before trying again, so waiters only read the cache line
+            while self.owner.load(Relaxed) != UNLOCKED {
+                strategy.wait(attempt);
+                attempt = attempt.saturating_add(1);
+            }
+        }
+        pause!("RawSpinLock::locked");
+        RawGuard { lock: self }
+    }
+
+    pub fn try_lock(&self) -> Option<RawGuard<'_>> {
+        self.try_lock_raw().then(|| RawGuard { lock: self })
+    }
+
+    fn try_lock_raw(&self) -> bool {
+        self.owner.compare_exchange(UNLOCKED, std::process::id(), Acquire, Relaxed).is_ok()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.owner.load(Relaxed) != UNLOCKED
+    }
+
+    // The PID of the process holding the lock, if it's locked
+    pub fn owner(&self) -> Option<u32> {
+        Some(self.owner.load(Relaxed)).filter(|&pid| pid != UNLOCKED)
+    }
+
+    // Like lock, but if the process holding the lock has died, the lock is taken over rather than waited on
+    // forever - the same idea as a robust futex. Getting Recovered back means the lock was held by a dead
+    // process, so whatever it protects may have been left half updated and should be checked or reset
+    // before it's trusted.
+    // Dead owners are found by PID, so a PID that's been reused by a new process looks alive. And a zombie
+    // (a child that died but hasn't been waited on) is still alive as far as this is concerned
+    #[cfg(unix)]
+    pub fn lock_robust(&self) -> Result<RawGuard<'_>, Recovered<'_>> {
+        let me = std::process::id();
+        let mut attempt: u32 = 0;
+        loop {
+            match self.owner.compare_exchange(UNLOCKED, me, Acquire, Relaxed) {
+                Ok(_) => return Ok(RawGuard { lock: self }),
+                // checking whether the owner is alive is a syscall, so it's only done every so often
+                Err(owner) if attempt % 64 == 63 && !process_alive(owner) => {
+                    if self.owner.compare_exchange(owner, me, Acquire, Relaxed).is_ok() {
+                        return Err(Recovered { guard: RawGuard { lock: self }, dead_owner: owner });
+                    }
+                }
+                Err(_) => cpu_relax(),
+            }
+            attempt = attempt.wrapping_add(1);
+        }
+    }
+
+    // Unlocks without a guard, for when the lock was taken somewhere a guard couldn't be kept
+    // (or in another process).
+    /// # Safety
+    /// The lock has to be locked, and whoever locked it has to be done with what it protects
+    pub unsafe fn force_unlock(&self) {
+        self.owner.store(UNLOCKED, Release);
+    }
+}
+
+impl Default for RawSpinLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for RawSpinLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawSpinLock").field("owner", &self.owner()).finish()
+    }
+}
+
+// Unlocks the RawSpinLock when dropped
+pub struct RawGuard<'a> {
+    lock: &'a RawSpinLock,
+}
+
+impl Drop for RawGuard<'_> {
+    fn drop(&mut self) {
+        pause!("RawSpinLock::unlock");
+        self.lock.owner.store(UNLOCKED, Release);
+    }
+}
+
+// What lock_robust gives back when it took the lock over from a process that died holding it.
+// The lock is held either way, into_guard gets the guard out once the protected data has been checked
+pub struct Recovered<'a> {
+    guard: RawGuard<'a>,
+    pub dead_owner: u32,
+}
+
+impl<'a> Recovered<'a> {
+    pub fn into_guard(self) -> RawGuard<'a> {
+        self.guard
+    }
+}
+
+impl fmt::Debug for Recovered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recovered").field("dead_owner", &self.dead_owner).finish_non_exhaustive()
+    }
+}
+
+// Signal 0 doesn't send anything, it just checks the process exists. EPERM means it exists but belongs
+// to someone else
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
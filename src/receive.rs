@@ -0,0 +1,213 @@
+use std::sync::atomic::AtomicUsize;
+use std::time::Instant;
+
+use crate::boundedchannel::{self, RecvError, RecvTimeoutError, TryRecvError};
+use crate::deadline::Deadline;
+use crate::ordering::Relaxed;
+use crate::trace::check_blocking;
+use crate::waitqueue::WaitQueue;
+
+// Adapters on the receiving end of a channel, the way Iterator's adapters work on an iterator: map, filter and
+// merge wrap a receiver in a new one, and nothing happens until something receives from that. Receiving from a
+// Map receives from the channel underneath and maps the message; receiving from a Merge takes from whichever of
+// its channels has something, blocking until one of them does. So a small processing graph is just a receiver,
+// with no threads in between to shuffle messages from one channel to the next:
+//
+//     let alerts = metrics.filter(|m| m.value > LIMIT).map(Alert::from).merge(manual_alerts);
+//     while let Ok(alert) = alerts.receive() { page(alert) }
+//
+// Anything that implements try_receive and says which wait queues it blocks in can be a Receive, and the blocking
+// calls come with it. Messages a filter turns down are gone - they've been taken out of the channel, so no other
+// receiver gets them either
+pub trait Receive {
+    type Item;
+
+    fn try_receive(&self) -> Result<Self::Item, TryRecvError>;
+
+    // The queues a receive blocks in until try_receive might have something. Whatever makes it succeed (or
+    // disconnect) has to wake at least one thread in one of them
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>);
+
+    // Blocks until there's a message. Once every sender is gone and there's nothing left, returns RecvError
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    fn receive(&self) -> Result<Self::Item, RecvError> {
+        match self.try_receive() {
+            Ok(message) => return Ok(message),
+            Err(TryRecvError::Disconnected) => return Err(RecvError),
+            Err(TryRecvError::Empty) => {}
+        }
+        check_blocking!("Receive::receive");
+        receive_until(self, None).map_err(|_| RecvError)
+    }
+
+    fn receive_timeout(&self, timeout: impl Into<Deadline>) -> Result<Self::Item, RecvTimeoutError>
+    where
+        Self: Sized,
+    {
+        receive_until(self, timeout.into().instant())
+    }
+
+    // The messages as they come, ending once every sender is gone
+    fn iter(&self) -> impl Iterator<Item = Self::Item> + '_
+    where
+        Self: Sized,
+    {
+        std::iter::from_fn(|| self.receive().ok())
+    }
+
+    fn map<U, F>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: Fn(Self::Item) -> U,
+    {
+        Map { inner: self, f }
+    }
+
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        Self: Sized,
+        P: Fn(&Self::Item) -> bool,
+    {
+        Filter { inner: self, predicate }
+    }
+
+    // Both at once. Neither one's preferred: each receive starts looking at the other one from last time, so a
+    // busy channel can't starve a quiet one. It's disconnected once both are
+    fn merge<R>(self, other: R) -> Merge<Self, R>
+    where
+        Self: Sized,
+        R: Receive<Item = Self::Item>,
+    {
+        Merge { first: self, second: other, next: AtomicUsize::new(0) }
+    }
+}
+
+impl<T> Receive for boundedchannel::Receiver<T> {
+    type Item = T;
+
+    fn try_receive(&self) -> Result<T, TryRecvError> {
+        boundedchannel::Receiver::try_receive(self)
+    }
+
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>) {
+        queues.push(self.receive_queue());
+    }
+}
+
+// What Receive::map returns
+#[derive(Debug, Clone)]
+pub struct Map<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R: Receive, U, F: Fn(R::Item) -> U> Receive for Map<R, F> {
+    type Item = U;
+
+    fn try_receive(&self) -> Result<U, TryRecvError> {
+        self.inner.try_receive().map(&self.f)
+    }
+
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>) {
+        self.inner.wait_queues(queues)
+    }
+}
+
+// What Receive::filter returns
+#[derive(Debug, Clone)]
+pub struct Filter<R, P> {
+    inner: R,
+    predicate: P,
+}
+
+impl<R: Receive, P: Fn(&R::Item) -> bool> Receive for Filter<R, P> {
+    type Item = R::Item;
+
+    // Goes through as many messages as it takes to find one it keeps, or until there are none left
+    fn try_receive(&self) -> Result<R::Item, TryRecvError> {
+        loop {
+            let message = self.inner.try_receive()?;
+            if (self.predicate)(&message) {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>) {
+        self.inner.wait_queues(queues)
+    }
+}
+
+// What Receive::merge returns
+#[derive(Debug)]
+pub struct Merge<A, B> {
+    first: A,
+    second: B,
+    // which one to try first next time, going back and forth
+    next: AtomicUsize,
+}
+
+impl<A: Receive, B: Receive<Item = A::Item>> Receive for Merge<A, B> {
+    type Item = A::Item;
+
+    fn try_receive(&self) -> Result<A::Item, TryRecvError> {
+        let first = || self.first.try_receive();
+        let second = || self.second.try_receive();
+        let (this, other): (&dyn Fn() -> _, &dyn Fn() -> _) = match self.next.fetch_add(1, Relaxed) % 2 {
+            0 => (&first, &second),
+            _ => (&second, &first),
+        };
+        match this() {
+            Ok(message) => Ok(message),
+            // still Empty if the other one's disconnected, as this one isn't
+            Err(TryRecvError::Empty) => other().map_err(|_| TryRecvError::Empty),
+            Err(TryRecvError::Disconnected) => other(),
+        }
+    }
+
+    fn wait_queues<'a>(&'a self, queues: &mut Vec<&'a WaitQueue>) {
+        self.first.wait_queues(queues);
+        self.second.wait_queues(queues);
+    }
+}
+
+// Everything that blocks comes down to this: try, then wait in all the receiver's queues at once and try again,
+// until there's a message, nothing more is coming, or the deadline's passed
+fn receive_until<R: Receive + ?Sized>(receiver: &R, deadline: Option<Instant>) -> Result<R::Item, RecvTimeoutError> {
+    let mut queues = Vec::new();
+    receiver.wait_queues(&mut queues);
+    let attempt = || match receiver.try_receive() {
+        Ok(message) => Some(Ok(message)),
+        Err(TryRecvError::Disconnected) => Some(Err(RecvTimeoutError::Disconnected)),
+        Err(TryRecvError::Empty) => None,
+    };
+    loop {
+        if let Some(received) = attempt() {
+            return received;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(RecvTimeoutError::Timeout);
+        }
+        let mut received = None;
+        let woken = WaitQueue::wait_any_until(&queues, deadline, || {
+            received = attempt();
+            received.is_none()
+        });
+        let received = received.or_else(attempt);
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if received.is_some() || timed_out {
+            // the message may well have come from a different channel than the wake did (or there's none at all),
+            // so the wakes go on to the next waiters in case theirs is still there. Only on the way out, as two
+            // waiters that both went back to waiting would otherwise pass the same wake back and forth for ever
+            for i in woken {
+                queues[i].wake_one();
+            }
+        }
+        if let Some(received) = received {
+            return received;
+        }
+        if timed_out {
+            return Err(RecvTimeoutError::Timeout);
+        }
+    }
+}
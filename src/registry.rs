@@ -0,0 +1,88 @@
+use std::mem;
+use std::sync::OnceLock;
+
+use crate::arc::Arc;
+use crate::mutex::Mutex;
+use crate::weakregistry::WeakRegistry;
+
+// Locks looked up by name, for coordinating on things that don't have a natural place to put a lock (a file
+// path, a tenant id, an external resource) without declaring a static mutex for every one of them:
+//
+//     let _guard = registry::lock("config-file");
+//
+// A name's lock is made the first time it's asked for, and lives as long as someone holds it or is waiting for
+// it - the registry only keeps a Weak, so it's pruned after that like any other WeakRegistry entry. Every
+// caller asking for the same name at the same time gets the same lock.
+//
+// Holding two named locks at once can deadlock like any two locks, if another thread takes them the other way
+// round
+pub struct LockRegistry {
+    locks: WeakRegistry<Box<str>, Mutex<()>>,
+}
+
+impl LockRegistry {
+    pub fn new() -> Self {
+        Self { locks: WeakRegistry::new() }
+    }
+
+    fn get(&self, name: &str) -> Arc<Mutex<()>> {
+        // a lookup by &str first, so a lock that's already around doesn't cost an allocation for the key
+        self.locks.get(name).unwrap_or_else(|| self.locks.get_or_create(name.into(), || Mutex::new(())))
+    }
+
+    // Blocks until the named lock is free, and takes it
+    pub fn lock(&self, name: &str) -> NamedGuard {
+        let lock = self.get(name);
+        // the guard can't be kept, as it borrows from the Arc that's going into the NamedGuard alongside it.
+        // Forgotten instead, and the NamedGuard unlocks the mutex itself
+        mem::forget(lock.lock());
+        NamedGuard { lock }
+    }
+
+    pub fn try_lock(&self, name: &str) -> Option<NamedGuard> {
+        let lock = self.get(name);
+        mem::forget(lock.try_lock()?);
+        Some(NamedGuard { lock })
+    }
+
+    // How many names have a lock right now, including ones nobody's using that haven't been pruned yet
+    pub fn len(&self) -> usize {
+        self.locks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locks.is_empty()
+    }
+}
+
+impl Default for LockRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Holds a named lock until it's dropped
+pub struct NamedGuard {
+    lock: Arc<Mutex<()>>,
+}
+
+impl Drop for NamedGuard {
+    fn drop(&mut self) {
+        // the guard that went with this lock was forgotten in lock/try_lock, so this is the only unlock
+        self.lock.unlock();
+    }
+}
+
+// The registry that lock and try_lock use, one for the whole process
+pub fn global() -> &'static LockRegistry {
+    static GLOBAL: OnceLock<LockRegistry> = OnceLock::new();
+    GLOBAL.get_or_init(LockRegistry::new)
+}
+
+pub fn lock(name: &str) -> NamedGuard {
+    global().lock(name)
+}
+
+pub fn try_lock(name: &str) -> Option<NamedGuard> {
+    global().try_lock(name)
+}
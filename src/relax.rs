@@ -0,0 +1,124 @@
+use core::hint;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU32};
+
+use crate::ordering::{Acquire, Relaxed, Release};
+
+// The instruction every spin loop in the crate issues while it waits, in one place so it can be changed for the
+// whole crate at once. std::hint::spin_loop picks one per architecture, and it's what's used by default: pause on
+// x86 (which also stops the CPU from mis-speculating its way out of the loop), isb on aarch64, and nothing at all
+// where there's no such instruction. That's not always the right pick:
+//
+//     - On aarch64 yield is the instruction that's meant for it, but plenty of cores treat it as a nop, which makes
+//       for a loop that hammers the cache line. isb stalls for a while on every core, so it's the default, but on
+//       cores where yield does something (or with SMT) yield can be the better one
+//     - On targets with no hint, a spin is just a load in a tight loop, and a few nops in between takes some of the
+//       pressure off the cache line
+//     - Embedded code may want something else entirely - wfe on a core that's woken by sev, or a watchdog kick
+//
+//     use rust_atomic_locks::relax::{set_spin_hint, SpinHint};
+//     set_spin_hint(SpinHint::Yield);
+//
+// It's global, and meant to be set once at startup: a change shows up in loops that are already spinning, but
+// there's no telling which spin is the first to use it
+
+// What cpu_relax issues
+#[derive(Debug, Clone, Copy)]
+pub enum SpinHint {
+    // std::hint::spin_loop
+    Default,
+    // aarch64's yield. Anywhere else it's the same as Default
+    Yield,
+    // aarch64's isb sy, which is what Default is there already. Anywhere else it's the same as Default
+    Isb,
+    // spin_loop this many times in a row (at least once), which on a target with no hint instruction is that many
+    // trips round an empty loop. Counts over MAX_REPEAT are capped at it
+    Repeat(u32),
+    // Calls the function instead, for whatever the target needs
+    Custom(fn()),
+}
+
+// Repeat's count shares an atomic with which hint it is, so it has to fit in 24 bits
+pub const MAX_REPEAT: u32 = (1 << 24) - 1;
+
+const DEFAULT: u32 = 0;
+const YIELD: u32 = 1;
+const ISB: u32 = 2;
+const REPEAT: u32 = 3;
+const CUSTOM: u32 = 4;
+
+// The kind of hint in the low 8 bits and Repeat's count above them, so the two always change together. A Custom
+// function's in a slot of its own, written before the hint is, so a spin that sees CUSTOM sees a function too
+static HINT: AtomicU32 = AtomicU32::new(DEFAULT);
+static CUSTOM_HINT: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+pub fn set_spin_hint(hint: SpinHint) {
+    let state = match hint {
+        SpinHint::Default => DEFAULT,
+        SpinHint::Yield => YIELD,
+        SpinHint::Isb => ISB,
+        SpinHint::Repeat(n) => REPEAT | n.clamp(1, MAX_REPEAT) << 8,
+        SpinHint::Custom(f) => {
+            CUSTOM_HINT.store(f as *mut (), Relaxed);
+            CUSTOM
+        }
+    };
+    // Release, for the Custom function
+    HINT.store(state, Release);
+}
+
+pub fn spin_hint() -> SpinHint {
+    let state = HINT.load(Acquire);
+    match state & 0xff {
+        YIELD => SpinHint::Yield,
+        ISB => SpinHint::Isb,
+        REPEAT => SpinHint::Repeat(state >> 8),
+        // Safety: CUSTOM_HINT only ever holds an fn(), and it's stored before CUSTOM is
+        CUSTOM => SpinHint::Custom(unsafe { core::mem::transmute::<*mut (), fn()>(CUSTOM_HINT.load(Relaxed)) }),
+        _ => SpinHint::Default,
+    }
+}
+
+// One trip round a spin loop. An Acquire load and a branch on top of the hint itself, which on x86 is a plain
+// load, and next to pause's tens of cycles isn't worth worrying about
+#[inline]
+pub fn cpu_relax() {
+    match spin_hint() {
+        SpinHint::Default => hint::spin_loop(),
+        SpinHint::Yield => arch::yield_hint(),
+        SpinHint::Isb => arch::isb(),
+        SpinHint::Repeat(n) => {
+            for i in 0..n {
+                // so the loop isn't optimized away where spin_loop is nothing
+                hint::black_box(i);
+                hint::spin_loop();
+            }
+        }
+        SpinHint::Custom(f) => f(),
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", not(miri)))]
+mod arch {
+    use core::arch::asm;
+
+    pub(super) fn yield_hint() {
+        unsafe { asm!("yield", options(nomem, nostack, preserves_flags)) };
+    }
+
+    pub(super) fn isb() {
+        unsafe { asm!("isb sy", options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+// Miri can't run asm, and spin_loop is all it needs
+#[cfg(not(all(target_arch = "aarch64", not(miri))))]
+mod arch {
+    pub(super) fn yield_hint() {
+        core::hint::spin_loop();
+    }
+
+    pub(super) fn isb() {
+        core::hint::spin_loop();
+    }
+}
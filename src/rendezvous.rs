@@ -0,0 +1,207 @@
+use std::mem;
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+use crate::arc::Arc;
+use crate::deadline::Deadline;
+use crate::futex;
+use crate::mutex::Mutex;
+use crate::trace::trace_event;
+
+// A single request/response exchange between two threads: the caller sends a request and blocks until the
+// callee answers it. It's two oneshot channels, one each way, with what the hand-rolled version usually gets
+// wrong handled: the caller can time out, and either side finds out if the other went away instead of
+// waiting forever.
+//
+//     let (caller, callee) = rendezvous::call();
+//     thread::spawn(move || {
+//         if let Ok((request, responder)) = callee.request() {
+//             let _ = responder.respond(request * 2);
+//         }
+//     });
+//     assert_eq!(caller.call(21), Ok(42));
+//
+// Everything is owned (and Send), so the callee can be handed off to a worker thread or through a channel
+pub fn call<Req, Resp>() -> (Caller<Req, Resp>, Callee<Req, Resp>) {
+    let (request_sender, request_receiver) = oneshot();
+    let (response_sender, response_receiver) = oneshot();
+    (
+        Caller { request: request_sender, response: response_receiver },
+        Callee { request: request_receiver, response: response_sender },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallError {
+    // the callee (or its responder) was dropped without responding
+    Disconnected,
+    // no response in time. The callee finds out when it tries to respond
+    Timeout,
+}
+
+// The callee was dropped before it received the request, or the caller before it sent one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disconnected;
+
+pub struct Caller<Req, Resp> {
+    request: OneshotSender<Req>,
+    response: OneshotReceiver<Resp>,
+}
+
+impl<Req, Resp> Caller<Req, Resp> {
+    // Sends the request and blocks until the response comes back
+    pub fn call(self, request: Req) -> Result<Resp, CallError> {
+        self.call_until(request, None)
+    }
+
+    // Same as call, but gives up once the timeout runs out (counting from the call, so it covers the time the
+    // callee takes to pick the request up as well as to answer it)
+    pub fn call_timeout(self, request: Req, timeout: impl Into<Deadline>) -> Result<Resp, CallError> {
+        self.call_until(request, timeout.into().instant())
+    }
+
+    fn call_until(self, request: Req, deadline: Option<Instant>) -> Result<Resp, CallError> {
+        self.request.send(request).map_err(|_| CallError::Disconnected)?;
+        // the response receiver is dropped on the way out, so after a timeout the callee's respond fails
+        self.response.receive_until(deadline)
+    }
+}
+
+pub struct Callee<Req, Resp> {
+    request: OneshotReceiver<Req>,
+    response: OneshotSender<Resp>,
+}
+
+impl<Req, Resp> Callee<Req, Resp> {
+    // Blocks until the request arrives, and returns it with the Responder to answer it with
+    pub fn request(self) -> Result<(Req, Responder<Resp>), Disconnected> {
+        let request = self.request.receive_until(None).map_err(|_| Disconnected)?;
+        Ok((request, Responder { response: self.response }))
+    }
+}
+
+// Answers a request. Dropping it without responding tells the caller it's been disconnected
+pub struct Responder<Resp> {
+    response: OneshotSender<Resp>,
+}
+
+impl<Resp> Responder<Resp> {
+    // Hands back the response if the caller isn't waiting for it any more (it timed out or was dropped)
+    pub fn respond(self, response: Resp) -> Result<(), Resp> {
+        self.response.send(response)
+    }
+
+    // Whether the caller has given up, so there's no point working out a response any more
+    pub fn is_closed(&self) -> bool {
+        matches!(*self.response.slot.lock(), Slot::ReceiverGone)
+    }
+}
+
+// The oneshot channels underneath. Unlike the ones in the oneshotchannel module these are owned rather than
+// borrowed and know when the other end has been dropped, which is what the timeouts and disconnects need.
+// A call only does a couple of lock/unlocks on each, so the state just goes in a Mutex
+enum Slot<T> {
+    Empty { waiter: Option<Thread> },
+    Full(T),
+    SenderGone,
+    ReceiverGone,
+}
+
+fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>) {
+    let slot = Arc::new(Mutex::new(Slot::Empty { waiter: None }));
+    (OneshotSender { slot: slot.clone(), sent: false }, OneshotReceiver { slot })
+}
+
+struct OneshotSender<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+    sent: bool,
+}
+
+impl<T> OneshotSender<T> {
+    fn send(mut self, message: T) -> Result<(), T> {
+        let waiter = {
+            let mut slot = self.slot.lock();
+            match mem::replace(&mut *slot, Slot::Full(message)) {
+                Slot::Empty { waiter } => waiter,
+                Slot::ReceiverGone => {
+                    let Slot::Full(message) = mem::replace(&mut *slot, Slot::ReceiverGone) else { unreachable!() };
+                    return Err(message);
+                }
+                Slot::Full(_) | Slot::SenderGone => unreachable!("a oneshot sender only sends once"),
+            }
+        };
+        self.sent = true;
+        trace_event!("rendezvous sent");
+        // unparked after unlocking, so the receiver doesn't wake up just to wait for the lock
+        if let Some(waiter) = waiter {
+            waiter.unpark();
+        }
+        Ok(())
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        if self.sent {
+            return;
+        }
+        let waiter = {
+            let mut slot = self.slot.lock();
+            match mem::replace(&mut *slot, Slot::SenderGone) {
+                Slot::Empty { waiter } => waiter,
+                // nobody's listening anyway
+                other => {
+                    *slot = other;
+                    None
+                }
+            }
+        };
+        if let Some(waiter) = waiter {
+            waiter.unpark();
+        }
+    }
+}
+
+struct OneshotReceiver<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> OneshotReceiver<T> {
+    fn receive_until(self, deadline: Option<Instant>) -> Result<T, CallError> {
+        loop {
+            {
+                let mut slot = self.slot.lock();
+                match &mut *slot {
+                    Slot::Full(_) => {
+                        let Slot::Full(message) = mem::replace(&mut *slot, Slot::ReceiverGone) else { unreachable!() };
+                        trace_event!("rendezvous received");
+                        return Ok(message);
+                    }
+                    Slot::SenderGone => return Err(CallError::Disconnected),
+                    Slot::Empty { waiter } => {
+                        // only the first time round, after that it's already there
+                        waiter.get_or_insert_with(thread::current);
+                    }
+                    Slot::ReceiverGone => unreachable!("the receiver is only gone once it's dropped"),
+                }
+            }
+            match deadline {
+                None => futex::park(None),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(CallError::Timeout);
+                    }
+                    futex::park(Some(deadline - now));
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for OneshotReceiver<T> {
+    fn drop(&mut self) {
+        // a message that was sent but never received is dropped here, after unlocking
+        let _unreceived = mem::replace(&mut *self.slot.lock(), Slot::ReceiverGone);
+    }
+}
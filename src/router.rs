@@ -0,0 +1,122 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::boundedchannel::{sync_channel, Receiver, SendError, Sender, TrySendError};
+use crate::rwspinlock::RwSpinLock;
+use crate::trace::trace_event;
+
+// Fans messages out over a bounded channel per key: add_route(key, capacity) makes a channel and hands back its
+// receiver, and send(key, message) goes down that key's channel. A message for a key with no route (or one
+// whose receivers have all gone) ends up in the dead letter channel instead, so nothing is dropped silently.
+//
+//     let (router, dead_letters) = Router::new(16);
+//     let orders = router.add_route("orders", 64);
+//     let refunds = router.add_route("refunds", 64);
+//     router.send("orders", job)?;
+//
+// The routes are read far more than they're changed, so they're behind a RwSpinLock: senders only share it for
+// long enough to clone the Sender out, and the send itself (which can block on a full channel) happens
+// outside it, so a slow route never holds up adding or removing another
+pub struct Router<K, T> {
+    routes: RwSpinLock<HashMap<K, Sender<T>>>,
+    dead_letters: Sender<T>,
+}
+
+impl<K: Hash + Eq, T> Router<K, T> {
+    // The receiver is the dead letter channel's, which gets dead_letter_capacity messages before sends to it
+    // block like any other full channel
+    pub fn new(dead_letter_capacity: usize) -> (Self, Receiver<T>) {
+        let (dead_letters, receiver) = sync_channel(dead_letter_capacity);
+        (Self { routes: RwSpinLock::new(HashMap::new()), dead_letters }, receiver)
+    }
+
+    // A new channel for key's messages. A route that was already there is replaced - its receivers get
+    // whatever was sent to it before, then find it disconnected
+    pub fn add_route(&self, key: K, capacity: usize) -> Receiver<T> {
+        let (sender, receiver) = sync_channel(capacity);
+        let old = self.routes.write().insert(key, sender);
+        // dropped outside the lock, as the last sender wakes every receiver
+        drop(old);
+        receiver
+    }
+
+    // Takes the route out, so key's messages go to the dead letters from now on. Returns whether there was one
+    pub fn remove_route<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let old = self.routes.write().remove(key);
+        old.is_some()
+    }
+
+    pub fn has_route<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.routes.read().contains_key(key)
+    }
+
+    pub fn routes(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.routes.read().keys().cloned().collect()
+    }
+
+    fn sender<Q>(&self, key: &Q) -> Option<Sender<T>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.routes.read().get(key).cloned()
+    }
+
+    // Sends down key's route, blocking while it's full, or to the dead letters if there's no route or nobody
+    // receiving on it. Err only once the dead letters' receivers have gone too
+    pub fn send<Q>(&self, key: &Q, message: T) -> Result<(), SendError<T>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let message = match self.sender(key) {
+            Some(sender) => match sender.send(message) {
+                Ok(()) => return Ok(()),
+                Err(SendError(message)) => message,
+            },
+            None => message,
+        };
+        trace_event!("router sending a dead letter");
+        self.dead_letters.send(message)
+    }
+
+    // Same as send, but never blocks - Full if key's route (or the dead letter channel, for a message that
+    // goes there) has no room. A full route isn't a reason to dead letter a message, it's handed back instead
+    pub fn try_send<Q>(&self, key: &Q, message: T) -> Result<(), TrySendError<T>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let message = match self.sender(key) {
+            Some(sender) => match sender.try_send(message) {
+                Err(TrySendError::Disconnected(message)) => message,
+                result => return result,
+            },
+            None => message,
+        };
+        trace_event!("router sending a dead letter");
+        self.dead_letters.try_send(message)
+    }
+}
+
+impl<K: fmt::Debug, T> fmt::Debug for Router<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("routes", &self.routes.read().keys().collect::<Vec<_>>())
+            .field("dead_letters", &self.dead_letters)
+            .finish()
+    }
+}
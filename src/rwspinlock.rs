@@ -0,0 +1,437 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+#[cfg(debug_assertions)]
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+#[cfg(debug_assertions)]
+use std::thread::{self, ThreadId};
+
+use crate::atomicupdate::AtomicUpdate;
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::relax::cpu_relax;
+use crate::sched::pause;
+use crate::trace::check_blocking;
+#[cfg(feature = "async")]
+use crate::wakerlist::{WakerList, WakerNode};
+
+// The state is one atomic: the lowest three bits are flags and the rest counts the readers (in steps of READER)
+const WRITER: usize = 1;
+const UPGRADABLE: usize = 2;
+// Set by a writer (or an upgrading reader) that's waiting, so new readers hold off and it doesn't starve
+const WRITER_WAITING: usize = 4;
+const READER: usize = 8;
+
+// A reader-writer spinlock: any number of readers, or one writer. On top of that, one upgradable reader can hold
+// the lock alongside the plain readers, and later turn into a writer without unlocking in between.
+//
+// With the async feature, tasks can wait for it with read_async and write_async rather than spinning. They sleep
+// until a guard is dropped, which is why the guards wake the list when they go
+pub struct RwSpinLock<T> {
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+    #[cfg(feature = "async")]
+    wakers: WakerList,
+    // Debug builds keep a list of the threads holding a read or upgradable guard, to catch a thread reading
+    // twice. That can deadlock: new readers hold off while a writer is waiting, so if a writer starts waiting
+    // between the two reads, the second read waits for the writer and the writer waits for the first read.
+    // It only happens when the timing is just wrong, so it's much better as a panic every time in tests
+    #[cfg(debug_assertions)]
+    readers: Mutex<Vec<ThreadId>>,
+}
+
+// Readers on different threads share &T, so T has to be Sync as well as Send
+unsafe impl<T> Sync for RwSpinLock<T> where T: Send + Sync {}
+
+impl<T: Default> Default for RwSpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for RwSpinLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// Who's holding it from a single load of the state, without taking the lock
+impl<T> fmt::Debug for RwSpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = self.state.load(Relaxed);
+        f.debug_struct("RwSpinLock")
+            .field("readers", &(s / READER))
+            .field("upgradable", &(s & UPGRADABLE != 0))
+            .field("writer", &(s & WRITER != 0))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> RwSpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+            #[cfg(feature = "async")]
+            wakers: WakerList::new(),
+            #[cfg(debug_assertions)]
+            readers: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Lets the tasks waiting in read_async and write_async try again, after the lock's been let go
+    fn unlocked(&self) {
+        #[cfg(feature = "async")]
+        self.wakers.wake_all();
+    }
+
+    // No readers or writer to wait for, the &mut means nobody else has the lock
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    // Only the blocking reads check, a try_read on a lock the thread's already reading is fine - at worst it
+    // returns None
+    fn check_not_reading(&self) {
+        #[cfg(debug_assertions)]
+        if self.readers.lock().unwrap_or_else(PoisonError::into_inner).contains(&thread::current().id()) {
+            panic!(
+                "recursive read of an RwSpinLock: this thread already holds a read guard for it, and waiting for \
+                 another one deadlocks if a writer starts waiting in between"
+            );
+        }
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        self.check_not_reading();
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            check_blocking!("RwSpinLock::read");
+            cpu_relax();
+        }
+    }
+
+    pub fn try_read(&self) -> Option<ReadGuard<'_, T>> {
+        // readers are let in alongside an upgradable reader, but not while a writer holds or wants the lock. It
+        // only gives up for that, not because another reader got in first
+        self.state.try_cas_update(Acquire, Relaxed, |s| {
+            if s & (WRITER | WRITER_WAITING) != 0 {
+                return None;
+            }
+            assert!(s < usize::MAX - READER, "too many readers");
+            pause!("RwSpinLock::try_read loaded");
+            Some(s + READER)
+        }).ok()?;
+        Some(ReadGuard { lock: self, reader: Reader::add(self) })
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            check_blocking!("RwSpinLock::write");
+            // let new readers know a writer is waiting, otherwise a steady stream of them could keep it out forever
+            self.state.fetch_or(WRITER_WAITING, Relaxed);
+            cpu_relax();
+        }
+    }
+
+    pub fn try_write(&self) -> Option<WriteGuard<'_, T>> {
+        let s = self.state.load(Relaxed);
+        // only free if there's nothing but (maybe) the waiting flag, which the new writer clears
+        if s & !WRITER_WAITING != 0 {
+            return None;
+        }
+        pause!("RwSpinLock::try_write loaded");
+        self.state.compare_exchange(s, WRITER, Acquire, Relaxed).ok()?;
+        Some(WriteGuard { lock: self })
+    }
+
+    // Only one upgradable reader at a time, as two of them trying to upgrade would wait for each other forever
+    pub fn upgradable_read(&self) -> UpgradableGuard<'_, T> {
+        self.check_not_reading();
+        loop {
+            if let Some(guard) = self.try_upgradable_read() {
+                return guard;
+            }
+            cpu_relax();
+        }
+    }
+
+    pub fn try_upgradable_read(&self) -> Option<UpgradableGuard<'_, T>> {
+        self.state.try_cas_update(Acquire, Relaxed, |s| {
+            if s & (WRITER | UPGRADABLE | WRITER_WAITING) != 0 {
+                return None;
+            }
+            pause!("RwSpinLock::try_upgradable_read loaded");
+            Some(s | UPGRADABLE)
+        }).ok()?;
+        Some(UpgradableGuard { lock: self, reader: Reader::add(self) })
+    }
+
+    // read for async code: waits for the writer to go without blocking the thread
+    #[cfg(feature = "async")]
+    pub fn read_async(&self) -> ReadFuture<'_, T> {
+        ReadFuture { lock: self, node: WakerNode::new() }
+    }
+
+    // write for async code. While it waits new readers hold off, same as for a blocking write
+    #[cfg(feature = "async")]
+    pub fn write_async(&self) -> WriteFuture<'_, T> {
+        WriteFuture { lock: self, waiting: false, node: WakerNode::new() }
+    }
+}
+
+#[cfg(feature = "async")]
+pub struct ReadFuture<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    node: WakerNode,
+}
+
+#[cfg(feature = "async")]
+impl<'a, T> Future for ReadFuture<'a, T> {
+    type Output = ReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ReadGuard<'a, T>> {
+        let lock = self.lock;
+        // Safety: the node stays where it is, pinned along with the future, and is only ever used with this
+        // lock's list
+        let node = unsafe { self.into_ref().map_unchecked(|future| &future.node) };
+        if let Some(guard) = lock.try_read() {
+            unsafe { lock.wakers.remove(node) };
+            return Poll::Ready(guard);
+        }
+        // on the list before trying again, so a guard dropped in between wakes the task
+        unsafe { lock.wakers.register(node, cx.waker()) };
+        match lock.try_read() {
+            Some(guard) => {
+                unsafe { lock.wakers.remove(node) };
+                Poll::Ready(guard)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for ReadFuture<'_, T> {
+    fn drop(&mut self) {
+        // Safety: the node hasn't moved since it was pinned
+        unsafe { self.lock.wakers.remove(Pin::new_unchecked(&self.node)) };
+    }
+}
+
+#[cfg(feature = "async")]
+pub struct WriteFuture<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    // whether it's set WRITER_WAITING
+    waiting: bool,
+    node: WakerNode,
+}
+
+#[cfg(feature = "async")]
+impl<'a, T> Future for WriteFuture<'a, T> {
+    type Output = WriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<WriteGuard<'a, T>> {
+        // Safety: the node is never moved out
+        let this = unsafe { self.get_unchecked_mut() };
+        let lock = this.lock;
+        // Safety: the node stays where it is, pinned along with the future, and is only ever used with this
+        // lock's list
+        let node = unsafe { Pin::new_unchecked(&this.node) };
+        if let Some(guard) = lock.try_write() {
+            this.waiting = false;
+            unsafe { lock.wakers.remove(node) };
+            return Poll::Ready(guard);
+        }
+        lock.state.fetch_or(WRITER_WAITING, Relaxed);
+        this.waiting = true;
+        unsafe { lock.wakers.register(node, cx.waker()) };
+        match lock.try_write() {
+            Some(guard) => {
+                this.waiting = false;
+                unsafe { lock.wakers.remove(node) };
+                Poll::Ready(guard)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for WriteFuture<'_, T> {
+    fn drop(&mut self) {
+        // Safety: the node hasn't moved since it was pinned
+        unsafe { self.lock.wakers.remove(Pin::new_unchecked(&self.node)) };
+        // A blocking writer that's given up on waiting doesn't exist, but a dropped future does, and new readers
+        // would hold off for it forever. So it takes the flag down - any other writer still waiting puts it back
+        // up: a blocking one the next time round its loop, and an async one when it's woken by this
+        if self.waiting {
+            self.lock.state.fetch_and(!WRITER_WAITING, Relaxed);
+            self.lock.unlocked();
+        }
+    }
+}
+
+// The thread a read or upgradable guard was taken on, for the readers list in debug builds (and nothing at all
+// in release builds). It's taken off the list when the guard goes, on whichever thread that happens
+#[derive(Clone, Copy)]
+struct Reader {
+    #[cfg(debug_assertions)]
+    thread: ThreadId,
+}
+
+impl Reader {
+    fn add<T>(_lock: &RwSpinLock<T>) -> Self {
+        #[cfg(debug_assertions)]
+        {
+            let thread = thread::current().id();
+            _lock.readers.lock().unwrap_or_else(PoisonError::into_inner).push(thread);
+            Self { thread }
+        }
+        #[cfg(not(debug_assertions))]
+        Self {}
+    }
+
+    fn remove<T>(self, _lock: &RwSpinLock<T>) {
+        #[cfg(debug_assertions)]
+        {
+            let mut readers = _lock.readers.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(i) = readers.iter().position(|&t| t == self.thread) {
+                readers.swap_remove(i);
+            }
+        }
+    }
+}
+
+pub struct ReadGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    reader: Reader,
+}
+
+// None of the guards mind which thread drops them. What they need from T goes by what they hand out: a read
+// guard only ever gives &T, so sending or sharing it needs T: Sync, while a write guard's &mut T needs T: Send
+// to go to another thread. An upgradable guard can become a write guard wherever it ends up, so it needs both
+unsafe impl<T: Sync> Send for ReadGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for ReadGuard<'_, T> {}
+
+impl<T> Deref for ReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: while there's a read guard there's no writer, only other readers
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for ReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.reader.remove(self.lock);
+        // only the last reader out can let anyone waiting in
+        if self.lock.state.fetch_sub(READER, Release) / READER == 1 {
+            self.lock.unlocked();
+        }
+    }
+}
+
+pub struct WriteGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+}
+
+unsafe impl<T: Send> Send for WriteGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for WriteGuard<'_, T> {}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    // Safety: the write guard has the lock all to itself
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // only clear our own bit, another writer may have set WRITER_WAITING in the meantime
+        self.lock.state.fetch_and(!WRITER, Release);
+        self.lock.unlocked();
+    }
+}
+
+pub struct UpgradableGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    reader: Reader,
+}
+
+unsafe impl<T: Send + Sync> Send for UpgradableGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for UpgradableGuard<'_, T> {}
+
+impl<'a, T> UpgradableGuard<'a, T> {
+    // Waits for the plain readers to leave and turns this into a write guard. The UPGRADABLE bit is held the
+    // whole time, so no other writer can get in between - what was read through this guard is still up to date
+    pub fn upgrade(self) -> WriteGuard<'a, T> {
+        let lock = self.lock;
+        self.reader.remove(lock);
+        // the bit is handed over to the write guard, so this guard mustn't release it
+        mem::forget(self);
+        loop {
+            let s = lock.state.load(Relaxed);
+            pause!("UpgradableGuard::upgrade loaded");
+            if s & !WRITER_WAITING == UPGRADABLE {
+                if lock.state.compare_exchange_weak(s, WRITER, Acquire, Relaxed).is_ok() {
+                    return WriteGuard { lock };
+                }
+            } else if s & WRITER_WAITING == 0 {
+                // stop new readers coming in while we wait for the current ones to finish
+                lock.state.fetch_or(WRITER_WAITING, Relaxed);
+            }
+            cpu_relax();
+        }
+    }
+
+    // Gives up the upgradable slot but keeps reading, letting another thread take the slot
+    pub fn downgrade(self) -> ReadGuard<'a, T> {
+        let (lock, reader) = (self.lock, self.reader);
+        mem::forget(self);
+        // add a reader and drop the flag in one step, so there's no gap where a writer could get in
+        lock.state.fetch_add(READER - UPGRADABLE, Release);
+        ReadGuard { lock, reader }
+    }
+}
+
+impl<T> Deref for UpgradableGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for UpgradableGuard<'_, T> {
+    fn drop(&mut self) {
+        self.reader.remove(self.lock);
+        if self.lock.state.fetch_sub(UPGRADABLE, Release) / READER == 0 {
+            self.lock.unlocked();
+        }
+    }
+}
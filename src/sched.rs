@@ -0,0 +1,148 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+// Pause points, for making races happen on purpose.
+// The primitives call pause!("Weak::upgrade loaded") and the like at the atomic steps that matter (just before a
+// compare exchange that another thread could get in ahead of, say). If the thread has a Pause installed, it's
+// told about the point and can stop the thread there, or yield, or whatever it likes. Without one a pause point
+// does nothing, and in release builds they aren't compiled in at all
+
+pub trait Pause: Send + Sync {
+    fn pause(&self, point: &'static str);
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn Pause>>> = const { RefCell::new(None) };
+}
+
+// Installs a Pause for the current thread (or takes it away with None), handing back the one it replaces
+pub fn set_pause(pause: Option<Arc<dyn Pause>>) -> Option<Arc<dyn Pause>> {
+    CURRENT.with(|current| current.replace(pause))
+}
+
+#[doc(hidden)]
+pub fn pause_point(point: &'static str) {
+    // cloned out first so the RefCell isn't borrowed while the thread is paused
+    // try_with, as a lock can be taken in another thread local's destructor after this one's gone - there's no
+    // scheduler to pause for by then anyway
+    let pause = CURRENT.try_with(|current| current.borrow().clone()).ok().flatten();
+    if let Some(pause) = pause {
+        pause.pause(point);
+    }
+}
+
+macro_rules! pause {
+    ($point:expr) => {
+        #[cfg(debug_assertions)]
+        $crate::sched::pause_point($point);
+    };
+}
+
+pub(crate) use pause;
+
+// How far a thread runs in a step of a Scheduler: until it reaches the named pause point, or until it's done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Until {
+    Point(&'static str),
+    Done,
+}
+
+// Runs threads one at a time in a set order, switching between them at pause points, so a particular
+// interleaving happens every time rather than once in a million runs.
+// The steps say which thread runs and how far: (0, Until::Point("Weak::upgrade loaded")) runs thread 0 until
+// it reaches that point and stops it there, then the next step picks who runs next. Every other thread waits,
+// either before it starts or at the pause point it stopped at. Once the steps run out, everything left runs
+// freely.
+// Only one thread runs at a time, so a step that needs a thread that's stopped to move first (spinning on a
+// lock it holds, say) never ends - the steps have to be written with that in mind
+pub struct Scheduler {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    turn: Condvar,
+}
+
+struct State {
+    steps: VecDeque<(usize, Until)>,
+    finished: Vec<usize>,
+}
+
+impl State {
+    // Drops steps for threads that are already done, as they can't run any more
+    fn skip_finished(&mut self) {
+        while self.steps.front().is_some_and(|(id, _)| self.finished.contains(id)) {
+            self.steps.pop_front();
+        }
+    }
+
+    fn my_turn(&self, id: usize) -> bool {
+        self.steps.front().is_none_or(|&(running, _)| running == id)
+    }
+}
+
+impl Shared {
+    fn wait_for_turn(&self, id: usize) {
+        let state = self.state.lock().unwrap();
+        drop(self.turn.wait_while(state, |state| !state.my_turn(id)).unwrap());
+    }
+}
+
+// The Pause for one of a Scheduler's threads
+struct ScheduledThread {
+    shared: Arc<Shared>,
+    id: usize,
+}
+
+impl Pause for ScheduledThread {
+    fn pause(&self, point: &'static str) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.steps.front() == Some(&(self.id, Until::Point(point))) {
+            // this step is done, let whoever's next run and wait for our next turn
+            state.steps.pop_front();
+            state.skip_finished();
+            self.shared.turn.notify_all();
+            drop(self.shared.turn.wait_while(state, |state| !state.my_turn(self.id)).unwrap());
+        }
+    }
+}
+
+// Marks the thread finished even if it panics, so the other threads don't wait for it forever
+struct Finish<'a> {
+    shared: &'a Shared,
+    id: usize,
+}
+
+impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+        set_pause(None);
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.finished.push(self.id);
+        state.skip_finished();
+        self.shared.turn.notify_all();
+    }
+}
+
+impl Scheduler {
+    pub fn new(steps: impl IntoIterator<Item = (usize, Until)>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State { steps: steps.into_iter().collect(), finished: Vec::new() }),
+                turn: Condvar::new(),
+            }),
+        }
+    }
+
+    // Wraps f to run as thread id of the schedule, to pass to thread::spawn (or a scope's spawn)
+    pub fn thread<R>(&self, id: usize, f: impl FnOnce() -> R + Send) -> impl FnOnce() -> R + Send {
+        let shared = self.shared.clone();
+        move || {
+            set_pause(Some(Arc::new(ScheduledThread { shared: shared.clone(), id })));
+            let _finish = Finish { shared: &shared, id };
+            shared.wait_for_turn(id);
+            f()
+        }
+    }
+}
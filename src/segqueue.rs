@@ -0,0 +1,322 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicPtr, AtomicUsize};
+
+use crate::cachepadded::CachePadded;
+use crate::epoch::{self, Guard};
+use crate::ordering::{Relaxed, Release, Acquire, SeqCst};
+use crate::relax::cpu_relax;
+use crate::waitstrategy::{SpinThenYield, WaitStrategy};
+
+// An unbounded multi-producer multi-consumer queue with no lock, laid out like crossbeam's SegQueue: values
+// go in blocks of slots, and the blocks are linked together as the queue grows. Like BoundedQueue, a push or pop
+// claims a position with a compare exchange on the tail or head, and then only touches its own slot - except
+// there's no capacity to run out of, as there's always another block.
+//
+// A block can only be freed once the head has moved past it, but a thread that claimed one of its slots can
+// still be writing or reading it by then. So every operation pins the thread with the epoch module, and the pop
+// that moves the head on to the next block retires the old one there instead of freeing it
+
+// Each block has BLOCK_CAP slots, and positions go up by LAP per block. The extra position at the end of each
+// lap (offset BLOCK_CAP) doesn't have a slot: it means the next block is being installed, and everyone waits
+const LAP: usize = 32;
+const BLOCK_CAP: usize = LAP - 1;
+// The positions are shifted up by one to make room for a flag in the lowest bit of the head
+const SHIFT: usize = 1;
+// Set in the head when the tail is known to be in a later block, so a pop can skip checking whether the
+// queue's empty
+const HAS_NEXT: usize = 1;
+
+// Set on a slot once its value has been written
+const WRITTEN: usize = 1;
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    state: AtomicUsize,
+}
+
+impl<T> Slot<T> {
+    // The pusher claims the slot before writing it, so a popper can get there first and has to wait
+    fn wait_written(&self) {
+        let mut attempt = 0;
+        while self.state.load(Acquire) & WRITTEN == 0 {
+            SpinThenYield::default().wait(attempt);
+            attempt += 1;
+        }
+    }
+}
+
+struct Block<T> {
+    next: AtomicPtr<Block<T>>,
+    slots: [Slot<T>; BLOCK_CAP],
+}
+
+impl<T> Block<T> {
+    fn new() -> Box<Self> {
+        Box::new(Self {
+            next: AtomicPtr::new(ptr::null_mut()),
+            slots: std::array::from_fn(|_| Slot { value: UnsafeCell::new(MaybeUninit::uninit()), state: AtomicUsize::new(0) }),
+        })
+    }
+
+    // The pusher that took the last slot links the next block in just after, so it might not be there yet
+    fn wait_next(&self) -> *mut Block<T> {
+        let mut attempt = 0;
+        loop {
+            let next = self.next.load(Acquire);
+            if !next.is_null() {
+                return next;
+            }
+            SpinThenYield::default().wait(attempt);
+            attempt += 1;
+        }
+    }
+}
+
+// One end of the queue: its position, and the block that position is in
+struct Position<T> {
+    index: AtomicUsize,
+    block: AtomicPtr<Block<T>>,
+}
+
+pub struct SegQueue<T> {
+    head: CachePadded<Position<T>>,
+    tail: CachePadded<Position<T>>,
+}
+
+unsafe impl<T: Send> Sync for SegQueue<T> {}
+unsafe impl<T: Send> Send for SegQueue<T> {}
+
+impl<T> SegQueue<T> {
+    // Doesn't allocate until the first push
+    pub const fn new() -> Self {
+        Self {
+            head: CachePadded::new(Position { index: AtomicUsize::new(0), block: AtomicPtr::new(ptr::null_mut()) }),
+            tail: CachePadded::new(Position { index: AtomicUsize::new(0), block: AtomicPtr::new(ptr::null_mut()) }),
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let _guard = epoch::pin();
+        let mut attempt = 0;
+        let mut tail = self.tail.index.load(Acquire);
+        let mut block = self.tail.block.load(Acquire);
+        // made ahead of time by the pusher that's about to take a block's last slot, so it can link it in
+        // straight after without anyone waiting on an allocation
+        let mut next_block = None;
+
+        loop {
+            let offset = (tail >> SHIFT) % LAP;
+
+            // another pusher is installing the next block
+            if offset == BLOCK_CAP {
+                SpinThenYield::default().wait(attempt);
+                attempt += 1;
+                tail = self.tail.index.load(Acquire);
+                block = self.tail.block.load(Acquire);
+                continue;
+            }
+
+            if offset + 1 == BLOCK_CAP && next_block.is_none() {
+                next_block = Some(Block::new());
+            }
+
+            // the very first push puts the first block in
+            if block.is_null() {
+                let new = Box::into_raw(Block::new());
+                if self.tail.block.compare_exchange(block, new, Release, Relaxed).is_ok() {
+                    self.head.block.store(new, Release);
+                    block = new;
+                } else {
+                    // someone else got there first, and this one will do for the next block instead
+                    next_block = Some(unsafe { Box::from_raw(new) });
+                    tail = self.tail.index.load(Acquire);
+                    block = self.tail.block.load(Acquire);
+                    continue;
+                }
+            }
+
+            let new_tail = tail + (1 << SHIFT);
+            match self.tail.index.compare_exchange_weak(tail, new_tail, SeqCst, Acquire) {
+                Ok(_) => unsafe {
+                    // took the last slot, so this pusher links in the next block and moves the tail on to it
+                    if offset + 1 == BLOCK_CAP {
+                        let next_block = Box::into_raw(next_block.take().unwrap());
+                        let next_index = new_tail.wrapping_add(1 << SHIFT);
+                        self.tail.block.store(next_block, Release);
+                        self.tail.index.store(next_index, Release);
+                        (*block).next.store(next_block, Release);
+                    }
+                    let slot = &(*block).slots[offset];
+                    slot.value.get().write(MaybeUninit::new(value));
+                    slot.state.fetch_or(WRITTEN, Release);
+                    return;
+                },
+                Err(actual) => {
+                    tail = actual;
+                    block = self.tail.block.load(Acquire);
+                    cpu_relax();
+                }
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+        let mut attempt = 0;
+        let mut head = self.head.index.load(Acquire);
+        let mut block = self.head.block.load(Acquire);
+
+        loop {
+            let offset = (head >> SHIFT) % LAP;
+
+            // a pusher is installing the next block
+            if offset == BLOCK_CAP {
+                SpinThenYield::default().wait(attempt);
+                attempt += 1;
+                head = self.head.index.load(Acquire);
+                block = self.head.block.load(Acquire);
+                continue;
+            }
+
+            let mut new_head = head + (1 << SHIFT);
+
+            if new_head & HAS_NEXT == 0 {
+                fence(SeqCst);
+                let tail = self.tail.index.load(Relaxed);
+                if head >> SHIFT == tail >> SHIFT {
+                    return None;
+                }
+                // if the tail's in a later block, later pops don't need to check for empty again until the head
+                // gets to a new block
+                if (head >> SHIFT) / LAP != (tail >> SHIFT) / LAP {
+                    new_head |= HAS_NEXT;
+                }
+            }
+
+            // the first block is still being put in
+            if block.is_null() {
+                SpinThenYield::default().wait(attempt);
+                attempt += 1;
+                head = self.head.index.load(Acquire);
+                block = self.head.block.load(Acquire);
+                continue;
+            }
+
+            match self.head.index.compare_exchange_weak(head, new_head, SeqCst, Acquire) {
+                Ok(_) => unsafe {
+                    // took the last slot, so this pop moves the head on to the next block
+                    if offset + 1 == BLOCK_CAP {
+                        let next = (*block).wait_next();
+                        let mut next_index = (new_head & !HAS_NEXT).wrapping_add(1 << SHIFT);
+                        if !(*next).next.load(Relaxed).is_null() {
+                            next_index |= HAS_NEXT;
+                        }
+                        self.head.block.store(next, Release);
+                        self.head.index.store(next_index, Release);
+                    }
+                    let slot = &(*block).slots[offset];
+                    slot.wait_written();
+                    let value = slot.value.get().read().assume_init();
+                    if offset + 1 == BLOCK_CAP {
+                        retire(&guard, block);
+                    }
+                    return Some(value);
+                },
+                Err(actual) => {
+                    head = actual;
+                    block = self.head.block.load(Acquire);
+                    cpu_relax();
+                }
+            }
+        }
+    }
+
+    // How many values are in the queue. Only a snapshot with other threads pushing and popping
+    pub fn len(&self) -> usize {
+        loop {
+            let mut tail = self.tail.index.load(SeqCst);
+            let mut head = self.head.index.load(SeqCst);
+
+            // only trust the pair if the tail didn't move while the head was read
+            if self.tail.index.load(SeqCst) == tail {
+                tail &= !((1 << SHIFT) - 1);
+                head &= !((1 << SHIFT) - 1);
+
+                // the slotless position at the end of a lap counts as the start of the next block
+                if (tail >> SHIFT) & (LAP - 1) == LAP - 1 {
+                    tail = tail.wrapping_add(1 << SHIFT);
+                }
+                if (head >> SHIFT) & (LAP - 1) == LAP - 1 {
+                    head = head.wrapping_add(1 << SHIFT);
+                }
+
+                // count from the start of the head's block, so the positions can't have wrapped
+                let lap = (head >> SHIFT) / LAP;
+                tail = tail.wrapping_sub((lap * LAP) << SHIFT) >> SHIFT;
+                head = head.wrapping_sub((lap * LAP) << SHIFT) >> SHIFT;
+
+                // take off the slotless position at the end of every lap in between
+                return tail - head - tail / LAP;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.index.load(SeqCst);
+        let tail = self.tail.index.load(SeqCst);
+        head >> SHIFT == tail >> SHIFT
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        None
+    }
+}
+
+// Hands a block the head has moved past to the epoch module. Every value in it has been (or is being) popped,
+// and nobody will find it through the queue any more
+fn retire<T>(guard: &Guard, block: *mut Block<T>) {
+    // Safety: only the pop that moved the head past the block retires it, and the slots are MaybeUninit so
+    // dropping the block later doesn't touch any T
+    unsafe { guard.defer_destroy(block) };
+}
+
+impl<T> Default for SegQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for SegQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SegQueue").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for SegQueue<T> {
+    // With &mut self nobody else is using the queue, so whatever's left is dropped and freed straight away
+    fn drop(&mut self) {
+        let mut head = *self.head.index.get_mut() & !((1 << SHIFT) - 1);
+        let tail = *self.tail.index.get_mut() & !((1 << SHIFT) - 1);
+        let mut block = *self.head.block.get_mut();
+
+        unsafe {
+            while head != tail {
+                let offset = (head >> SHIFT) % LAP;
+                if offset < BLOCK_CAP {
+                    (*(*block).slots[offset].value.get()).assume_init_drop();
+                } else {
+                    let next = *(*block).next.get_mut();
+                    drop(Box::from_raw(block));
+                    block = next;
+                }
+                head = head.wrapping_add(1 << SHIFT);
+            }
+            if !block.is_null() {
+                drop(Box::from_raw(block));
+            }
+        }
+    }
+}
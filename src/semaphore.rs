@@ -0,0 +1,290 @@
+use std::collections::BTreeSet;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::mem;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use crate::deadline::Deadline;
+use crate::mutex::{Mutex, MutexGuard};
+use crate::trace::check_blocking;
+use crate::waitqueue::WaitQueue;
+use crate::waitstrategy::WaitStrategy;
+#[cfg(feature = "async")]
+use crate::wakerlist::{WakerList, WakerNode};
+
+// A counting semaphore where a thread can take several permits at once, with acquire_many(n).
+// Waiters are served strictly in the order they arrived: nobody gets permits while someone ahead of them is
+// still waiting, even if there are enough free for them. Otherwise a big request could wait forever while a
+// stream of small ones kept taking the permits as they came back. With the async feature tasks can wait their
+// turn too, with acquire_async - threads and tasks queue up in the same line. A blocked thread is only woken
+// once it's at the front, so returning permits doesn't wake the whole queue just for all but one to sleep again
+pub struct Semaphore {
+    state: Mutex<State>,
+    // The threads waiting their turn, each parked under its ticket, so a change only wakes the thread whose
+    // turn it is rather than all of them
+    waiters: WaitQueue,
+    // the tasks waiting, which are all woken on every change - a task's ticket isn't something the list knows
+    #[cfg(feature = "async")]
+    wakers: WakerList,
+}
+
+struct State {
+    permits: usize,
+    // Every acquire takes a ticket and waits for now_serving to get to it
+    next_ticket: u64,
+    now_serving: u64,
+    // Tickets whose acquire timed out before their turn came, skipped over when it does
+    abandoned: BTreeSet<u64>,
+}
+
+impl State {
+    fn ready(&self, ticket: u64, n: usize) -> bool {
+        self.now_serving == ticket && self.permits >= n
+    }
+
+    // Moves on to the next ticket that still has someone waiting on it
+    fn advance(&mut self) {
+        self.now_serving += 1;
+        while self.abandoned.remove(&self.now_serving) {
+            self.now_serving += 1;
+        }
+    }
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(State { permits, next_ticket: 0, now_serving: 0, abandoned: BTreeSet::new() }),
+            waiters: WaitQueue::new(),
+            #[cfg(feature = "async")]
+            wakers: WakerList::new(),
+        }
+    }
+
+    // Unlocks the state and lets whoever might be able to go now check: the thread holding the ticket being
+    // served, if it's parked, and every task
+    fn notify(&self, state: MutexGuard<'_, State>) {
+        let serving = state.now_serving;
+        drop(state);
+        self.waiters.wake_key(serving);
+        #[cfg(feature = "async")]
+        self.wakers.wake_all();
+    }
+
+    // Parks until ticket is being served and there are n permits free, and hands the state back locked along
+    // with whether they are - false means the deadline passed first
+    fn wait_turn<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, State>,
+        ticket: u64,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> (MutexGuard<'a, State>, bool) {
+        while !state.ready(ticket, n) {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return (state, false);
+            }
+            drop(state);
+            // checked again once this thread's in the queue, so a notify in between isn't missed
+            self.waiters.wait_keyed_until(ticket, deadline, || !self.state.lock().ready(ticket, n));
+            state = self.state.lock();
+        }
+        (state, true)
+    }
+
+    // Takes n permits for the ticket being served and moves the line on
+    fn take(&self, mut state: MutexGuard<'_, State>, n: usize) -> Permit<'_> {
+        state.permits -= n;
+        state.advance();
+        // the next in line may be able to go too
+        self.notify(state);
+        Permit { semaphore: self, n }
+    }
+
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn acquire(&self) -> Permit<'_> {
+        self.acquire_many(1)
+    }
+
+    // Blocks until it's this thread's turn and there are n permits free, and takes them all at once.
+    // Asking for more than the semaphore will ever have blocks forever (and everyone behind it too)
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn acquire_many(&self, n: usize) -> Permit<'_> {
+        let mut state = self.state.lock();
+        // before it takes a ticket, which would hold up everyone behind it if it panicked with it
+        check_blocking!(when state.now_serving != state.next_ticket || state.permits < n, "Semaphore::acquire");
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let (state, _) = self.wait_turn(state, ticket, n, None);
+        self.take(state, n)
+    }
+
+    pub fn acquire_with(&self, strategy: &impl WaitStrategy) -> Permit<'_> {
+        self.acquire_many_with(1, strategy)
+    }
+
+    // Same as acquire_many, but waits its turn with the strategy instead of parking, for waits
+    // short enough that spinning beats a sleep and a wake. It takes a ticket like acquire_many and keeps its
+    // place in line, so a big request still can't be starved by small ones
+    pub fn acquire_many_with(&self, n: usize, strategy: &impl WaitStrategy) -> Permit<'_> {
+        let mut state = self.state.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let mut attempt = 0;
+        while !state.ready(ticket, n) {
+            drop(state);
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+            state = self.state.lock();
+        }
+        self.take(state, n)
+    }
+
+    pub fn acquire_timeout(&self, timeout: impl Into<Deadline>) -> Option<Permit<'_>> {
+        self.acquire_many_timeout(1, timeout)
+    }
+
+    // Same as acquire_many, but gives up once timeout has passed, waiting in line included - a limiter in front
+    // of a connection pool would rather fail a request than hang it
+    pub fn acquire_many_timeout(&self, n: usize, timeout: impl Into<Deadline>) -> Option<Permit<'_>> {
+        let deadline = timeout.into().instant();
+        let mut state = self.state.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let (mut state, ready) = self.wait_turn(state, ticket, n, deadline);
+        if !ready {
+            // everyone behind would wait on this ticket forever, so it's given up: passed on now if it's the one
+            // being served, or skipped once it comes up if not
+            if state.now_serving == ticket {
+                state.advance();
+                self.notify(state);
+            } else {
+                state.abandoned.insert(ticket);
+            }
+            return None;
+        }
+        Some(self.take(state, n))
+    }
+
+    pub fn try_acquire(&self) -> Option<Permit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    // Takes n permits if they're free and nobody is waiting - jumping in ahead of a waiter would be barging
+    pub fn try_acquire_many(&self, n: usize) -> Option<Permit<'_>> {
+        let mut state = self.state.lock();
+        if state.now_serving != state.next_ticket || state.permits < n {
+            return None;
+        }
+        state.permits -= n;
+        Some(Permit { semaphore: self, n })
+    }
+
+    // A snapshot, other threads can take and return permits straight after
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().permits
+    }
+
+    // Puts n more permits in, for good - it's how permits come back when a Permit is dropped too. Together with
+    // Permit::forget this resizes the semaphore while it's in use: add_permits to grow it, and acquire_many(n)
+    // then forget to shrink it, which waits for n to be handed back first
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.state.lock();
+        state.permits += n;
+        self.notify(state);
+    }
+
+    #[cfg(feature = "async")]
+    pub fn acquire_async(&self) -> AcquireFuture<'_> {
+        self.acquire_many_async(1)
+    }
+
+    // acquire_many for async code: the task waits in the same line as the threads, without blocking its thread.
+    // It takes its ticket when it's first polled, and dropping it before it's done gives its place up
+    #[cfg(feature = "async")]
+    pub fn acquire_many_async(&self, n: usize) -> AcquireFuture<'_> {
+        AcquireFuture { semaphore: self, n, ticket: None, node: WakerNode::new() }
+    }
+}
+
+#[cfg(feature = "async")]
+pub struct AcquireFuture<'a> {
+    semaphore: &'a Semaphore,
+    n: usize,
+    // None before the first poll, and again once it's done
+    ticket: Option<u64>,
+    node: WakerNode,
+}
+
+#[cfg(feature = "async")]
+impl<'a> Future for AcquireFuture<'a> {
+    type Output = Permit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit<'a>> {
+        // Safety: the node is never moved out
+        let this = unsafe { self.get_unchecked_mut() };
+        let semaphore = this.semaphore;
+        // Safety: the node stays where it is, pinned along with the future
+        let node = unsafe { Pin::new_unchecked(&this.node) };
+        let mut state = semaphore.state.lock();
+        let ticket = *this.ticket.get_or_insert_with(|| {
+            state.next_ticket += 1;
+            state.next_ticket - 1
+        });
+        if !state.ready(ticket, this.n) {
+            // registered with the state still locked, so whatever changes it next wakes this task
+            // Safety: the node's only ever used with this semaphore's list
+            unsafe { semaphore.wakers.register(node, cx.waker()) };
+            return Poll::Pending;
+        }
+        this.ticket = None;
+        // Safety: as above
+        unsafe { semaphore.wakers.remove(node) };
+        Poll::Ready(semaphore.take(state, this.n))
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AcquireFuture<'_> {
+    fn drop(&mut self) {
+        // Safety: the node hasn't moved since it was pinned, and is only ever used with this semaphore's list
+        unsafe { self.semaphore.wakers.remove(Pin::new_unchecked(&self.node)) };
+        // given up partway through waiting, which is the same as an acquire_many_timeout timing out
+        if let Some(ticket) = self.ticket {
+            let mut state = self.semaphore.state.lock();
+            if state.now_serving == ticket {
+                state.advance();
+                self.semaphore.notify(state);
+            } else {
+                state.abandoned.insert(ticket);
+            }
+        }
+    }
+}
+
+// Permits taken from a Semaphore, given back when it's dropped
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+    n: usize,
+}
+
+impl Permit<'_> {
+    pub fn permits(&self) -> usize {
+        self.n
+    }
+
+    // Uses the permits up rather than giving them back, so the semaphore has that many fewer from now on
+    pub fn forget(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.n);
+    }
+}
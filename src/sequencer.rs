@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::condvar::Condvar;
+use crate::mutex::{Mutex, MutexGuard};
+
+// Puts results back in order after a parallel stage. Every item is pushed with its sequence number (its
+// position in the input, counting from 0) by whichever thread worked it out, in whatever order they finish,
+// and pop hands them out strictly as 0, 1, 2, ...
+//
+//     let sequencer = Sequencer::new(64);
+//     // on the workers
+//     sequencer.push(seq, work(item));
+//     // on the consumer
+//     while let Some(result) = sequencer.pop() { ... }
+//
+// Results that finish early wait in a reorder buffer of window slots. It's bounded, so a worker that gets more
+// than window ahead of the oldest result still missing blocks in push until that one turns up - otherwise one
+// slow item could make the buffer grow without limit behind it
+pub struct Sequencer<T> {
+    state: Mutex<State<T>>,
+    // signalled when the next item arrives, for pop
+    ready: Condvar,
+    // signalled when the window moves on, for push
+    room: Condvar,
+    window: usize,
+}
+
+struct State<T> {
+    // the sequence number pop hands out next, which is the one at the front of buffer
+    next: u64,
+    // buffer[i] is item next + i, if it's arrived
+    buffer: VecDeque<Option<T>>,
+    closed: bool,
+}
+
+impl<T> Sequencer<T> {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "a sequencer needs room for at least one item");
+        Self {
+            state: Mutex::new(State { next: 0, buffer: VecDeque::with_capacity(window), closed: false }),
+            ready: Condvar::new(),
+            room: Condvar::new(),
+            window,
+        }
+    }
+
+    // Hands in item number seq, blocking while it's window or more ahead of the next one out. Every number
+    // has to be pushed exactly once. Err gives the item back if the sequencer has been closed
+    pub fn push(&self, seq: u64, item: T) -> Result<(), T> {
+        let state = self.state.lock();
+        assert!(seq >= state.next, "item {seq} was pushed after it had already been handed out");
+        let mut state = self.room.wait_while(state, |state| !state.closed && seq - state.next >= self.window as u64);
+        if state.closed {
+            return Err(item);
+        }
+        let i = (seq - state.next) as usize;
+        if state.buffer.len() <= i {
+            state.buffer.resize_with(i + 1, || None);
+        }
+        assert!(state.buffer[i].is_none(), "item {seq} was pushed twice");
+        state.buffer[i] = Some(item);
+        let is_next = i == 0;
+        drop(state);
+        // anything else only fills a gap further back, which pop isn't waiting on
+        if is_next {
+            self.ready.notify_one();
+        }
+        Ok(())
+    }
+
+    // Blocks until the next item in sequence has been pushed, and takes it. None once the sequencer is closed
+    // and the next item isn't there - after a close the items that are already in order still come out first
+    pub fn pop(&self) -> Option<T> {
+        let state = self.state.lock();
+        let state = self.ready.wait_while(state, |state| !state.closed && !Self::next_is_ready(state));
+        self.take_next(state)
+    }
+
+    // Takes the next item if it's been pushed, without waiting
+    pub fn try_pop(&self) -> Option<T> {
+        self.take_next(self.state.lock())
+    }
+
+    // Stops the sequencer: pushes fail from now on (and blocked ones give up), and pop returns None once it
+    // gets to a gap. For when the producers are done, or something's gone wrong and a gap will never be filled
+    pub fn close(&self) {
+        self.state.lock().closed = true;
+        self.ready.notify_all();
+        self.room.notify_all();
+    }
+
+    // The sequence number pop is waiting for
+    pub fn next_seq(&self) -> u64 {
+        self.state.lock().next
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    fn next_is_ready(state: &State<T>) -> bool {
+        state.buffer.front().is_some_and(Option::is_some)
+    }
+
+    fn take_next(&self, mut state: MutexGuard<'_, State<T>>) -> Option<T> {
+        if !Self::next_is_ready(&state) {
+            return None;
+        }
+        let item = state.buffer.pop_front().flatten();
+        state.next += 1;
+        drop(state);
+        // the window has moved on by one, which can let any of the blocked pushes through
+        self.room.notify_all();
+        item
+    }
+}
+
+// Where the sequencer has got to, without the items
+impl<T> fmt::Debug for Sequencer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Sequencer");
+        match self.state.try_lock() {
+            Some(state) => d.field("next_seq", &state.next).field("closed", &state.closed),
+            None => d.field("next_seq", &format_args!("<locked>")),
+        };
+        d.field("window", &self.window).finish_non_exhaustive()
+    }
+}
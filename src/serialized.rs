@@ -0,0 +1,146 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::mutex::Mutex;
+
+// The biggest message a SerializedReceiver accepts. The length comes off the wire, so without a limit a corrupt
+// (or hostile) frame could make it try to allocate whatever it says
+pub const MAX_FRAME: usize = 64 << 20;
+
+// Channel ends that carry messages over a byte stream instead of memory, so the same send/receive code works
+// between processes: over a pipe, a TCP connection, a unix socket, or anything else that's Write on one side
+// and Read on the other.
+//
+//     let (a, b) = UnixStream::pair()?;
+//     let sender = SerializedSender::new(a);
+//     let receiver = SerializedReceiver::new(b);
+//     sender.send(Job { id: 1 })?;
+//     let job: Job = receiver.receive()?;
+//
+// Every message is encoded with bincode and framed with its length (a u32, little endian) in front, so the
+// receiver knows where one ends without any help from the stream. The stream is behind a lock on both ends,
+// so threads can share one and their frames never get mixed up
+
+pub struct SerializedSender<T, W> {
+    writer: Mutex<W>,
+    // fn(T) so the sender is Send and Sync whatever T is, it never holds one
+    _message: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize, W: Write> SerializedSender<T, W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer: Mutex::new(writer), _message: PhantomData }
+    }
+
+    // Writes the message to the stream and flushes it. Blocks for as long as the write does, which is the
+    // stream's version of a full channel
+    pub fn send(&self, message: T) -> Result<(), Error> {
+        let payload = bincode::serialize(&message).map_err(Error::Serialize)?;
+        let len = u32::try_from(payload.len()).ok().filter(|&len| len as usize <= MAX_FRAME);
+        let len = len.ok_or(Error::TooBig(payload.len()))?;
+        let mut writer = self.writer.lock();
+        // the length and the payload go in one write, so an unbuffered writer doesn't turn a message into two
+        // packets (or two syscalls)
+        let mut frame = Vec::with_capacity(4 + payload.len());
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&payload);
+        writer.write_all(&frame).map_err(Error::from_io)?;
+        writer.flush().map_err(Error::from_io)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+pub struct SerializedReceiver<T, R> {
+    reader: Mutex<R>,
+    // fn() -> T as the receiver only ever hands Ts out
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned, R: Read> SerializedReceiver<T, R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader: Mutex::new(reader), _message: PhantomData }
+    }
+
+    // Blocks until a whole message has been read. Err(Disconnected) once the other end has closed the stream
+    // and every message it sent has been received
+    pub fn receive(&self) -> Result<T, Error> {
+        let mut reader = self.reader.lock();
+        let mut len = [0; 4];
+        // a stream that ends between frames is the other end going away, but one that ends partway through a
+        // frame lost part of a message, so that one's left as an io error
+        loop {
+            match reader.read(&mut len[..1]) {
+                Ok(0) => return Err(Error::Disconnected),
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::from_io(e)),
+            }
+        }
+        reader.read_exact(&mut len[1..]).map_err(Error::Io)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_FRAME {
+            return Err(Error::TooBig(len));
+        }
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload).map_err(Error::Io)?;
+        drop(reader);
+        bincode::deserialize(&payload).map_err(Error::Serialize)
+    }
+
+    // Receives messages until the other end closes the stream (or something goes wrong)
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.receive().ok())
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // The other end closed the stream - the cross-process version of a channel with nobody on the other side
+    Disconnected,
+    // A message (or a length read off the stream) over MAX_FRAME bytes
+    TooBig(usize),
+    Serialize(bincode::Error),
+    Io(io::Error),
+}
+
+impl Error {
+    // A broken pipe or a reset connection on send (or receive) means the other end is gone, same as an EOF
+    fn from_io(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset => Error::Disconnected,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Disconnected => f.write_str("the other end of the stream has gone"),
+            Error::TooBig(len) => write!(f, "a {len} byte message is over the {MAX_FRAME} byte limit"),
+            Error::Serialize(e) => write!(f, "couldn't encode or decode the message: {e}"),
+            Error::Io(e) => write!(f, "couldn't use the stream: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Serialize(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
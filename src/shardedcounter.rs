@@ -0,0 +1,71 @@
+use std::sync::atomic::AtomicUsize;
+use std::thread;
+
+use crate::cachepadded::CachePadded;
+use crate::ordering::Relaxed;
+
+// Hands out shard indexes to threads round robin, the first time each thread touches a ShardedCounter
+static NEXT_THREAD_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_INDEX: usize = NEXT_THREAD_INDEX.fetch_add(1, Relaxed);
+}
+
+// Which shard this thread uses, before taking it modulo the number of shards. Shared with the other sharded
+// types, so a thread lands on the same shard index in all of them
+pub(crate) fn thread_index() -> usize {
+    THREAD_INDEX.with(|i| *i)
+}
+
+// A counter for things that get incremented far more often than they're read (metrics, statistics).
+// Every thread adds to its own cache padded shard, so writers don't fight over one cache line, and reading
+// the counter sums all of the shards
+pub struct ShardedCounter {
+    shards: Box<[CachePadded<AtomicUsize>]>,
+}
+
+impl ShardedCounter {
+    // One shard per core, which is enough for most threads to get a shard to themselves
+    pub fn new() -> Self {
+        Self::with_shards(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "ShardedCounter needs at least one shard");
+        Self {
+            shards: (0..shards).map(|_| CachePadded::new(AtomicUsize::new(0))).collect(),
+        }
+    }
+
+    fn shard(&self) -> &AtomicUsize {
+        &self.shards[thread_index() % self.shards.len()]
+    }
+
+    // Relaxed is enough everywhere - the counter only counts, it isn't used to synchronise anything else
+    pub fn add(&self, n: usize) {
+        self.shard().fetch_add(n, Relaxed);
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    // The shards are read one after another, so with concurrent adds the sum is only a rough snapshot -
+    // it's never less than the adds that finished before sum was called, though
+    pub fn sum(&self) -> usize {
+        self.shards.iter().map(|shard| shard.load(Relaxed)).sum()
+    }
+
+    // Adds that race with a reset may or may not be counted
+    pub fn reset(&self) {
+        for shard in self.shards.iter() {
+            shard.store(0, Relaxed);
+        }
+    }
+}
+
+impl Default for ShardedCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
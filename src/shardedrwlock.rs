@@ -0,0 +1,132 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::thread;
+
+use crate::cachepadded::CachePadded;
+use crate::rwspinlock::{self, RwSpinLock};
+use crate::shardedcounter::thread_index;
+
+// A reader-writer lock for data that's read all the time and written hardly ever (global registries, config).
+// Even a plain RwSpinLock has every reader writing to the same atomic, so the cache line bounces between cores
+// on every read. Here there's one lock per shard, each on its own cache line, and a reader only locks the shard
+// for its thread - readers on different shards never touch the same memory. A writer has to lock every shard,
+// so writes get slower the more shards there are (the "big reader" lock, or brlock)
+pub struct ShardedRwLock<T> {
+    // the shards don't hold anything themselves, they only say who's reading
+    shards: Box<[CachePadded<RwSpinLock<()>>]>,
+    value: UnsafeCell<T>,
+}
+
+// Same as RwSpinLock: readers on different threads share &T
+unsafe impl<T> Sync for ShardedRwLock<T> where T: Send + Sync {}
+
+impl<T> ShardedRwLock<T> {
+    // One shard per core, like ShardedCounter
+    pub fn new(value: T) -> Self {
+        Self::with_shards(value, thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn with_shards(value: T, shards: usize) -> Self {
+        assert!(shards > 0, "ShardedRwLock needs at least one shard");
+        Self {
+            shards: (0..shards).map(|_| CachePadded::new(RwSpinLock::new(()))).collect(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self) -> &RwSpinLock<()> {
+        &self.shards[thread_index() % self.shards.len()]
+    }
+
+    // Like RwSpinLock::read, reading again on a thread that's already reading is a panic in debug builds
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        ReadGuard { lock: self, _shard: self.shard().read() }
+    }
+
+    pub fn try_read(&self) -> Option<ReadGuard<'_, T>> {
+        Some(ReadGuard { lock: self, _shard: self.shard().try_read()? })
+    }
+
+    // Locks every shard. They're always locked in index order, so two writers can't deadlock by each holding
+    // a shard the other is waiting for - whoever gets shard 0 first gets the lot
+    pub fn write_all(&self) -> WriteGuard<'_, T> {
+        WriteGuard { lock: self, _shards: self.shards.iter().map(|shard| shard.write()).collect() }
+    }
+
+    // Gives up (and unlocks whatever shards it got) as soon as one of them is taken
+    pub fn try_write_all(&self) -> Option<WriteGuard<'_, T>> {
+        let shards = self.shards.iter().map(|shard| shard.try_write()).collect::<Option<_>>()?;
+        Some(WriteGuard { lock: self, _shards: shards })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for ShardedRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for ShardedRwLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for ShardedRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedRwLock").field("shards", &self.shards.len()).finish_non_exhaustive()
+    }
+}
+
+pub struct ReadGuard<'a, T> {
+    lock: &'a ShardedRwLock<T>,
+    _shard: rwspinlock::ReadGuard<'a, ()>,
+}
+
+// A read guard can be dropped on another thread than the one whose shard it holds - the shard just sees the
+// reader leave from elsewhere. As for RwSpinLock, reading needs T: Sync and writing T: Send
+unsafe impl<T: Sync> Send for ReadGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for ReadGuard<'_, T> {}
+
+impl<T> Deref for ReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: a writer needs every shard, including the one this guard is reading
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+pub struct WriteGuard<'a, T> {
+    lock: &'a ShardedRwLock<T>,
+    _shards: Vec<rwspinlock::WriteGuard<'a, ()>>,
+}
+
+unsafe impl<T: Send> Send for WriteGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for WriteGuard<'_, T> {}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    // Safety: with every shard write locked there are no readers on any of them, and no other writer
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
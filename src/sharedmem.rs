@@ -0,0 +1,228 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::sync::atomic::AtomicU32;
+
+use crate::ordering::{Acquire, Release};
+use crate::rawspinlock::RawSpinLock;
+use crate::waitstrategy::{WaitStrategy, SpinThenYield};
+
+// Written into the header last, so a process attaching to the region can tell the channel was set up
+const MAGIC: u32 = 0x5348_4d43;
+
+// The start of the region. Everything in it is #[repr(C)] and fixed size, so every process that maps the
+// region agrees on where things are
+#[repr(C)]
+struct Header {
+    magic: AtomicU32,
+    lock: RawSpinLock,
+    capacity: u64,
+    // size_of::<T>() when the channel was set up, so attaching with the wrong message type gets caught
+    message_size: u64,
+    // the rest is only touched with the lock held
+    head: UnsafeCell<u64>,
+    len: UnsafeCell<u64>,
+}
+
+// A bounded channel that lives entirely inside a region of memory the caller provides, so it can be put in
+// shared memory and used between processes. One process sets it up with init and the others attach to it.
+// Messages are copied in and out byte for byte, which is why T has to be Copy - and it should be plain data
+// too, as a pointer or reference in a message means nothing in another process's address space.
+// There's no way to park a thread in another process, so blocking sends and receives spin and then yield
+pub struct SharedMemChannel<'a, T> {
+    header: &'a Header,
+    slots: *mut MaybeUninit<T>,
+    _region: PhantomData<&'a [T]>,
+}
+
+unsafe impl<T: Copy + Send> Send for SharedMemChannel<'_, T> {}
+unsafe impl<T: Copy + Send> Sync for SharedMemChannel<'_, T> {}
+
+impl<'a, T: Copy> SharedMemChannel<'a, T> {
+    // Where the slots start, after the header and lined up for T
+    fn slots_offset() -> usize {
+        mem::size_of::<Header>().next_multiple_of(mem::align_of::<T>())
+    }
+
+    // How many bytes a region needs to hold a channel with room for capacity messages
+    pub fn size_for(capacity: usize) -> usize {
+        Self::slots_offset() + capacity * mem::size_of::<T>()
+    }
+
+    fn check_region(region: *mut u8, len: usize) {
+        assert!(
+            (region as usize).is_multiple_of(mem::align_of::<Header>().max(mem::align_of::<T>())),
+            "the region isn't aligned for the channel"
+        );
+        assert!(len >= Self::slots_offset(), "the region is too small for the channel");
+    }
+
+    // Sets up an empty channel at the start of the region.
+    /// # Safety
+    /// region has to be valid for reads and writes for len bytes for as long as 'a, and nothing else can be
+    /// using that memory (including a channel attached to it from another process) while this runs
+    pub unsafe fn init(region: *mut u8, len: usize, capacity: usize) -> Self {
+        Self::check_region(region, len);
+        assert!(len >= Self::size_for(capacity), "the region is too small for {capacity} messages");
+        let header = region.cast::<Header>();
+        header.write(Header {
+            magic: AtomicU32::new(0),
+            lock: RawSpinLock::new(),
+            capacity: capacity as u64,
+            message_size: mem::size_of::<T>() as u64,
+            head: UnsafeCell::new(0),
+            len: UnsafeCell::new(0),
+        });
+        // Release so a process that sees the magic number sees the rest of the header too
+        (*header).magic.store(MAGIC, Release);
+        Self::from_region(region)
+    }
+
+    // Attaches to a channel another process (or this one) already set up with init.
+    /// # Safety
+    /// region has to be valid for reads and writes for len bytes for as long as 'a, and has to be the
+    /// same memory init was called on - the same shared memory mapped into this process, say
+    pub unsafe fn attach(region: *mut u8, len: usize) -> Self {
+        Self::check_region(region, len);
+        let header = &*region.cast::<Header>();
+        assert_eq!(header.magic.load(Acquire), MAGIC, "no channel has been set up in the region");
+        assert_eq!(header.message_size, mem::size_of::<T>() as u64, "the channel holds a different message type");
+        assert!(len >= Self::size_for(header.capacity as usize), "the region is smaller than the channel");
+        Self::from_region(region)
+    }
+
+    unsafe fn from_region(region: *mut u8) -> Self {
+        Self {
+            header: &*region.cast::<Header>(),
+            slots: region.add(Self::slots_offset()).cast(),
+            _region: PhantomData,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.header.capacity as usize
+    }
+
+    pub fn try_send(&self, message: T) -> Result<(), T> {
+        let _guard = self.header.lock.lock();
+        // Safety: the lock is held, so nothing else is touching head, len or the slots
+        unsafe {
+            let len = *self.header.len.get();
+            if len == self.header.capacity {
+                return Err(message);
+            }
+            let slot = (*self.header.head.get() + len) % self.header.capacity;
+            self.slots.add(slot as usize).write(MaybeUninit::new(message));
+            *self.header.len.get() = len + 1;
+        }
+        Ok(())
+    }
+
+    pub fn try_receive(&self) -> Option<T> {
+        let _guard = self.header.lock.lock();
+        unsafe {
+            let len = *self.header.len.get();
+            if len == 0 {
+                return None;
+            }
+            let head = *self.header.head.get();
+            let message = self.slots.add(head as usize).read().assume_init();
+            *self.header.head.get() = (head + 1) % self.header.capacity;
+            *self.header.len.get() = len - 1;
+            Some(message)
+        }
+    }
+
+    // Waits while the channel is full
+    pub fn send(&self, mut message: T) {
+        let strategy = SpinThenYield::default();
+        let mut attempt = 0;
+        while let Err(m) = self.try_send(message) {
+            message = m;
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    // Waits while the channel is empty
+    pub fn receive(&self) -> T {
+        let strategy = SpinThenYield::default();
+        let mut attempt = 0;
+        loop {
+            if let Some(message) = self.try_receive() {
+                return message;
+            }
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let _guard = self.header.lock.lock();
+        unsafe { *self.header.len.get() as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// A region of memory that's shared with other processes, for putting a RawSpinLock or SharedMemChannel in.
+// anonymous regions are shared with child processes made with fork, and file regions with any process that
+// opens the same file
+#[cfg(all(unix, feature = "shared_memory"))]
+pub struct SharedRegion {
+    ptr: *mut u8,
+    len: usize,
+}
+
+#[cfg(all(unix, feature = "shared_memory"))]
+unsafe impl Send for SharedRegion {}
+#[cfg(all(unix, feature = "shared_memory"))]
+unsafe impl Sync for SharedRegion {}
+
+#[cfg(all(unix, feature = "shared_memory"))]
+impl SharedRegion {
+    pub fn anonymous(len: usize) -> std::io::Result<Self> {
+        unsafe { Self::map(len, libc::MAP_SHARED | libc::MAP_ANONYMOUS, -1) }
+    }
+
+    // Maps the file at path, creating it (and growing it to len bytes) if it needs to
+    pub fn open(path: impl AsRef<std::path::Path>, len: usize) -> std::io::Result<Self> {
+        use std::os::fd::AsRawFd;
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if file.metadata()?.len() < len as u64 {
+            file.set_len(len as u64)?;
+        }
+        // the mapping stays valid after the file is closed
+        unsafe { Self::map(len, libc::MAP_SHARED, file.as_raw_fd()) }
+    }
+
+    unsafe fn map(len: usize, flags: libc::c_int, fd: libc::c_int) -> std::io::Result<Self> {
+        let ptr = libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, flags, fd, 0);
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        // mmap hands back page aligned memory, which is plenty for anything the crate puts in it
+        Ok(Self { ptr: ptr.cast(), len })
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(all(unix, feature = "shared_memory"))]
+impl Drop for SharedRegion {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
@@ -0,0 +1,369 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, AtomicU64};
+
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::waitstrategy::{SpinThenYield, WaitStrategy};
+
+// Written into the header last, so a process attaching to the region can tell the ring was set up
+const MAGIC: u32 = 0x5348_4d52;
+
+// Each slot is the message's length as a u64 and then the message, rounded up so the next slot's length is
+// aligned
+const LEN_SIZE: usize = mem::size_of::<u64>();
+
+// The start of the region. It's #[repr(C)] and every field is an atomic (or padding), so every process that
+// maps the region agrees on where things are and nothing in it is a pointer
+#[repr(C)]
+struct Header {
+    magic: AtomicU32,
+    // set while a RingProducer (or RingConsumer) exists in some process, so there's only ever one of each
+    producer: AtomicU32,
+    consumer: AtomicU32,
+    _reserved: AtomicU32,
+    message_size: AtomicU64,
+    capacity: AtomicU64,
+    _pad0: [u8; 32],
+    // How many messages have ever been received and sent. Only the consumer writes head and only the producer
+    // writes tail, and they're a cache line apart so the two sides don't slow each other down
+    head: AtomicU64,
+    _pad1: [u8; 56],
+    tail: AtomicU64,
+    _pad2: [u8; 56],
+}
+
+// A single producer single consumer ring buffer of fixed size messages that lives in a region of memory the
+// caller provides, for passing bytes between two processes through shared memory (an mmapped file, or an
+// anonymous mapping shared with a forked child). Every slot holds up to message_size bytes.
+//
+// Unlike SharedMemChannel there's no lock: with one process on each end, the producer only moves the tail
+// and the consumer only moves the head, so a send or receive is a copy and a couple of atomics. Who's the
+// producer and who's the consumer is recorded in the region, so a second process trying to be either gets an
+// error rather than breaking the ring. As with SharedMemChannel, waiting spins and then yields, as there's no
+// parking a thread in another process
+pub struct ShmRing<'a> {
+    header: *const Header,
+    slots: *mut u8,
+    message_size: usize,
+    // the bytes from one slot to the next
+    stride: usize,
+    capacity: usize,
+    _region: PhantomData<&'a mut [u8]>,
+}
+
+unsafe impl Send for ShmRing<'_> {}
+
+impl<'a> ShmRing<'a> {
+    fn stride(message_size: usize) -> Option<usize> {
+        message_size.checked_add(LEN_SIZE).map(|size| size.next_multiple_of(LEN_SIZE))
+    }
+
+    // How many bytes a region needs to hold a ring of capacity messages of up to message_size bytes
+    pub fn size_for(message_size: usize, capacity: usize) -> usize {
+        Self::checked_size_for(message_size, capacity).expect("the ring's size overflows usize")
+    }
+
+    fn checked_size_for(message_size: usize, capacity: usize) -> Option<usize> {
+        Self::stride(message_size)?.checked_mul(capacity)?.checked_add(mem::size_of::<Header>())
+    }
+
+    fn check_region(region: &[u8]) -> Result<(), RingError> {
+        if !(region.as_ptr() as usize).is_multiple_of(mem::align_of::<Header>()) {
+            return Err(RingError::Misaligned);
+        }
+        if region.len() < mem::size_of::<Header>() {
+            return Err(RingError::TooSmall);
+        }
+        Ok(())
+    }
+
+    // Sets up an empty ring at the start of the region, forgetting anything that was there before (including
+    // a producer or consumer that was using it, so it's for when nothing else is)
+    pub fn init(region: &'a mut [u8], message_size: usize, capacity: usize) -> Result<Self, RingError> {
+        Self::check_region(region)?;
+        if message_size == 0 || capacity == 0 {
+            return Err(RingError::Empty);
+        }
+        match Self::checked_size_for(message_size, capacity) {
+            Some(size) if size <= region.len() => {}
+            _ => return Err(RingError::TooSmall),
+        }
+        let header = region.as_mut_ptr().cast::<Header>();
+        // Safety: the region is big enough and aligned for the header, and with &mut nothing in this process
+        // is using it
+        unsafe {
+            header.write(Header {
+                magic: AtomicU32::new(0),
+                producer: AtomicU32::new(0),
+                consumer: AtomicU32::new(0),
+                _reserved: AtomicU32::new(0),
+                message_size: AtomicU64::new(message_size as u64),
+                capacity: AtomicU64::new(capacity as u64),
+                _pad0: [0; 32],
+                head: AtomicU64::new(0),
+                _pad1: [0; 56],
+                tail: AtomicU64::new(0),
+                _pad2: [0; 56],
+            });
+            // Release so a process that sees the magic number sees the rest of the header too
+            (*header).magic.store(MAGIC, Release);
+        }
+        Ok(Self::from_region(region, message_size, capacity))
+    }
+
+    // Attaches to a ring another process (or this one) already set up with init. The header comes from
+    // whatever else maps the region, so it's checked rather than trusted
+    pub fn attach(region: &'a mut [u8]) -> Result<Self, RingError> {
+        Self::check_region(region)?;
+        // Safety: checked to be big enough and aligned for the header
+        let header = unsafe { &*region.as_mut_ptr().cast::<Header>() };
+        if header.magic.load(Acquire) != MAGIC {
+            return Err(RingError::NotInitialised);
+        }
+        let message_size = usize::try_from(header.message_size.load(Relaxed)).map_err(|_| RingError::TooSmall)?;
+        let capacity = usize::try_from(header.capacity.load(Relaxed)).map_err(|_| RingError::TooSmall)?;
+        if message_size == 0 || capacity == 0 {
+            return Err(RingError::Empty);
+        }
+        match Self::checked_size_for(message_size, capacity) {
+            Some(size) if size <= region.len() => {}
+            _ => return Err(RingError::TooSmall),
+        }
+        Ok(Self::from_region(region, message_size, capacity))
+    }
+
+    fn from_region(region: &'a mut [u8], message_size: usize, capacity: usize) -> Self {
+        let start = region.as_mut_ptr();
+        Self {
+            header: start.cast(),
+            // Safety: the region was checked to have room for the header and every slot
+            slots: unsafe { start.add(mem::size_of::<Header>()) },
+            message_size,
+            stride: Self::stride(message_size).unwrap(),
+            capacity,
+            _region: PhantomData,
+        }
+    }
+
+    fn header(&self) -> &Header {
+        // Safety: checked in init/attach, and the region lives for 'a
+        unsafe { &*self.header }
+    }
+
+    pub fn message_size(&self) -> usize {
+        self.message_size
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Becomes the ring's producer. Err(Claimed) if there's already one, in this process or another
+    pub fn into_producer(self) -> Result<RingProducer<'a>, RingError> {
+        let header = self.header();
+        header.producer.compare_exchange(0, 1, Acquire, Relaxed).map_err(|_| RingError::Claimed)?;
+        let tail = header.tail.load(Relaxed);
+        Ok(RingProducer { ring: self, tail })
+    }
+
+    // Becomes the ring's consumer. Err(Claimed) if there's already one, in this process or another
+    pub fn into_consumer(self) -> Result<RingConsumer<'a>, RingError> {
+        let header = self.header();
+        header.consumer.compare_exchange(0, 1, Acquire, Relaxed).map_err(|_| RingError::Claimed)?;
+        let head = header.head.load(Relaxed);
+        Ok(RingConsumer { ring: self, head })
+    }
+
+    // Both ends in this process, for handing to two threads
+    pub fn split(self) -> Result<(RingProducer<'a>, RingConsumer<'a>), RingError> {
+        // two handles on the same region, one for each end, which is what a process each would have
+        let other = Self { _region: PhantomData, ..self };
+        let producer = self.into_producer()?;
+        Ok((producer, other.into_consumer()?))
+    }
+
+    // Where the slot for position pos starts
+    fn slot(&self, pos: u64) -> *mut u8 {
+        // Safety: pos % capacity is a slot index, and the region has room for capacity slots
+        unsafe { self.slots.add((pos % self.capacity as u64) as usize * self.stride) }
+    }
+
+    // How many messages are in the ring. With the other end in another process this can't be more than a
+    // snapshot, and a corrupt header can make it anything
+    fn len_between(&self, head: u64, tail: u64) -> usize {
+        let len = tail.wrapping_sub(head);
+        assert!(len <= self.capacity as u64, "the ring's positions are corrupt (head {head}, tail {tail})");
+        len as usize
+    }
+}
+
+// The sending end of a ShmRing
+pub struct RingProducer<'a> {
+    ring: ShmRing<'a>,
+    // only this end writes the tail, so it's kept here as well as in the header
+    tail: u64,
+}
+
+impl RingProducer<'_> {
+    pub fn message_size(&self) -> usize {
+        self.ring.message_size
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity
+    }
+
+    // Copies the message into the ring, or hands it back if the ring's full.
+    // Panics if the message is longer than message_size
+    pub fn try_send<'m>(&mut self, message: &'m [u8]) -> Result<(), &'m [u8]> {
+        assert!(
+            message.len() <= self.ring.message_size,
+            "a {} byte message is too big for the ring's {} byte slots",
+            message.len(),
+            self.ring.message_size
+        );
+        // Acquire so the consumer is done reading the slot before it's written again
+        let head = self.ring.header().head.load(Acquire);
+        if self.ring.len_between(head, self.tail) == self.ring.capacity {
+            return Err(message);
+        }
+        let slot = self.ring.slot(self.tail);
+        // Safety: the slot is past the head, so the consumer isn't reading it, and only this end writes
+        unsafe {
+            slot.cast::<u64>().write(message.len() as u64);
+            ptr::copy_nonoverlapping(message.as_ptr(), slot.add(LEN_SIZE), message.len());
+        }
+        self.tail = self.tail.wrapping_add(1);
+        // Release so the consumer sees the message before the new tail
+        self.ring.header().tail.store(self.tail, Release);
+        Ok(())
+    }
+
+    // Waits while the ring is full
+    pub fn send(&mut self, message: &[u8]) {
+        let strategy = SpinThenYield::default();
+        let mut attempt = 0;
+        while self.try_send(message).is_err() {
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.ring.header().head.load(Acquire);
+        self.ring.len_between(head, self.tail)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for RingProducer<'_> {
+    // Lets another producer take over, in this process or another
+    fn drop(&mut self) {
+        self.ring.header().producer.store(0, Release);
+    }
+}
+
+// The receiving end of a ShmRing
+pub struct RingConsumer<'a> {
+    ring: ShmRing<'a>,
+    // only this end writes the head, so it's kept here as well as in the header
+    head: u64,
+}
+
+impl RingConsumer<'_> {
+    pub fn message_size(&self) -> usize {
+        self.ring.message_size
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity
+    }
+
+    // Runs f on the oldest message while it's still in the ring, without copying it out, or None if the ring's
+    // empty. The slot isn't handed back to the producer until f returns
+    pub fn try_receive_with<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        // Acquire so the message is there before it's read
+        let tail = self.ring.header().tail.load(Acquire);
+        if self.ring.len_between(self.head, tail) == 0 {
+            return None;
+        }
+        let slot = self.ring.slot(self.head);
+        // Safety: the slot is before the tail, so the producer's done writing it and won't again until the head
+        // moves past it. The length came from the other process, so it's checked before it's trusted
+        let result = unsafe {
+            let len = slot.cast::<u64>().read();
+            assert!(len <= self.ring.message_size as u64, "the ring has a corrupt message length ({len})");
+            f(std::slice::from_raw_parts(slot.add(LEN_SIZE), len as usize))
+        };
+        self.head = self.head.wrapping_add(1);
+        // Release so the producer can't overwrite the slot before it's been read
+        self.ring.header().head.store(self.head, Release);
+        Some(result)
+    }
+
+    pub fn try_receive(&mut self) -> Option<Vec<u8>> {
+        self.try_receive_with(<[u8]>::to_vec)
+    }
+
+    // Waits while the ring is empty
+    pub fn receive(&mut self) -> Vec<u8> {
+        let strategy = SpinThenYield::default();
+        let mut attempt = 0;
+        loop {
+            if let Some(message) = self.try_receive() {
+                return message;
+            }
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.ring.header().tail.load(Acquire);
+        self.ring.len_between(self.head, tail)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for RingConsumer<'_> {
+    // Lets another consumer take over, in this process or another
+    fn drop(&mut self) {
+        self.ring.header().consumer.store(0, Release);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingError {
+    // The region isn't aligned to 8 bytes (mmapped memory always is)
+    Misaligned,
+    // The region can't hold the header, or the ring it's meant to hold
+    TooSmall,
+    // A message size or capacity of 0
+    Empty,
+    // attach found no ring in the region
+    NotInitialised,
+    // The ring already has a producer (or consumer)
+    Claimed,
+}
+
+impl fmt::Display for RingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RingError::Misaligned => "the region isn't aligned for the ring",
+            RingError::TooSmall => "the region is too small for the ring",
+            RingError::Empty => "the ring needs a message size and capacity of at least 1",
+            RingError::NotInitialised => "no ring has been set up in the region",
+            RingError::Claimed => "the ring already has a producer (or consumer)",
+        })
+    }
+}
+
+impl std::error::Error for RingError {}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::arc::Arc;
+use crate::boundedchannel::{Receiver, TryRecvError};
+use crate::condvar::Condvar;
+use crate::deadline::Deadline;
+use crate::event::Event;
+use crate::mutex::Mutex;
+use crate::trace::trace_event;
+
+// Stopping a service cleanly: the controller hands a ShutdownSignal to every worker, shutdown() tells them all
+// to stop, and wait_for_completion() blocks until each one has finished up and acked (or dropped) its signal.
+//
+//     let shutdown = ShutdownController::new();
+//     for _ in 0..4 {
+//         let signal = shutdown.signal();
+//         let jobs = jobs.clone();
+//         thread::spawn(move || {
+//             while let Some(job) = signal.receive(&jobs) {
+//                 job.run();
+//             }
+//         });
+//     }
+//     ...
+//     shutdown.shutdown();
+//     shutdown.wait_for_completion();
+//
+// A worker can check is_shutdown between bits of work, park in wait, or - most often - receive from a
+// channel with the signal, which stops waiting for the next message as soon as shutdown is called
+pub struct ShutdownController {
+    inner: Arc<Inner>,
+}
+
+// One worker's end. Dropping it (or ack) tells the controller the worker is done
+pub struct ShutdownSignal {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    triggered: Event,
+    // signals that haven't been acked yet, and where wait_for_completion waits for them to run out
+    workers: Mutex<usize>,
+    finished: Condvar,
+    // the channels signals are blocked receiving on right now, woken on shutdown so they see it
+    receivers: Mutex<Receivers>,
+}
+
+struct Receivers {
+    next: u64,
+    wakers: HashMap<u64, Box<dyn Fn() + Send + Sync>>,
+}
+
+impl ShutdownController {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                triggered: Event::new(),
+                workers: Mutex::new(0),
+                finished: Condvar::new(),
+                receivers: Mutex::new(Receivers { next: 0, wakers: HashMap::new() }),
+            }),
+        }
+    }
+
+    // A signal for one more worker, which wait_for_completion will wait for. Handing one out after shutdown is
+    // fine - it's shut down from the start
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal::register(&self.inner)
+    }
+
+    // Tells every worker to stop. Calling it again does nothing
+    pub fn shutdown(&self) {
+        self.inner.triggered.set();
+        // set first, so a receiver registering after this sees it when it checks
+        for wake in self.inner.receivers.lock().wakers.values() {
+            wake();
+        }
+        trace_event!("shutdown triggered");
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.triggered.is_set()
+    }
+
+    // How many signals haven't been acked yet
+    pub fn workers(&self) -> usize {
+        *self.inner.workers.lock()
+    }
+
+    // Blocks until every signal has been acked or dropped. It doesn't call shutdown itself, so it can be
+    // called first to wait for workers that stop by themselves
+    pub fn wait_for_completion(&self) {
+        let workers = self.inner.workers.lock();
+        drop(self.inner.finished.wait_while(workers, |workers| *workers > 0));
+    }
+
+    // Same as wait_for_completion, but gives up once the deadline has passed - returns whether everyone
+    // finished. After a timeout it's up to the caller what to do about the stragglers
+    pub fn wait_for_completion_timeout(&self, timeout: impl Into<Deadline>) -> bool {
+        let workers = self.inner.workers.lock();
+        let (_, result) = self.inner.finished.wait_timeout_while(workers, timeout, |workers| *workers > 0);
+        !result.timed_out()
+    }
+}
+
+impl Default for ShutdownController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ShutdownController {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownController")
+            .field("shutdown", &self.is_shutdown())
+            .field("workers", &self.workers())
+            .finish()
+    }
+}
+
+impl ShutdownSignal {
+    fn register(inner: &Arc<Inner>) -> Self {
+        *inner.workers.lock() += 1;
+        Self { inner: inner.clone() }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.triggered.is_set()
+    }
+
+    // Parks until shutdown is called
+    pub fn wait(&self) {
+        self.inner.triggered.wait();
+    }
+
+    // Same as wait, but gives up once the deadline has passed. Returns whether it's shut down - false means
+    // carry on, which makes it a sleep that ends early on shutdown
+    pub fn wait_timeout(&self, timeout: impl Into<Deadline>) -> bool {
+        self.inner.triggered.wait_timeout(timeout)
+    }
+
+    // The next message from the channel, or None once shutdown is called or every sender has gone - whichever
+    // comes first. A receiver blocked here wakes up straight away on shutdown. Messages still in the channel
+    // at that point are left for someone else to drain
+    pub fn receive<T: Send + 'static>(&self, receiver: &Receiver<T>) -> Option<T> {
+        if self.is_shutdown() {
+            return None;
+        }
+        // the controller's lock is only taken when there's going to be a wait, not for every message
+        match receiver.try_receive() {
+            Ok(message) => return Some(message),
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {}
+        }
+        // the channel only goes on the list for as long as this is blocked on it
+        let id = {
+            let mut receivers = self.inner.receivers.lock();
+            let id = receivers.next;
+            receivers.next += 1;
+            receivers.wakers.insert(id, Box::new(receiver.receive_waker()));
+            id
+        };
+        let received = receiver.receive_unless(|| self.is_shutdown());
+        self.inner.receivers.lock().wakers.remove(&id);
+        received?.ok()
+    }
+
+    // Tells the controller this worker is done, same as dropping the signal
+    pub fn ack(self) {}
+}
+
+// A clone is another worker to wait for, with a signal of its own to ack
+impl Clone for ShutdownSignal {
+    fn clone(&self) -> Self {
+        Self::register(&self.inner)
+    }
+}
+
+impl Drop for ShutdownSignal {
+    fn drop(&mut self) {
+        let mut workers = self.inner.workers.lock();
+        *workers -= 1;
+        if *workers == 0 {
+            drop(workers);
+            self.inner.finished.notify_all();
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownSignal").field("shutdown", &self.is_shutdown()).finish()
+    }
+}
@@ -1,11 +1,38 @@
-use std::{thread, ops::DerefMut};
-use core::sync::atomic::{AtomicBool, Ordering::{Acquire, Release}};
+use std::fmt;
+use std::ops::DerefMut;
+use core::sync::atomic::AtomicBool;
 use core::cell::UnsafeCell;
 use std::ops::Deref;
+use std::mem;
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::allocator::Allocator;
+use crate::arc::Arc;
+use crate::deadline::{Deadline, Timeout};
+#[cfg(feature = "metrics")]
+use crate::metrics::{LockCounters, LockMetrics};
+use crate::ordering::{Relaxed, Acquire, Release};
+use crate::sched::pause;
+#[cfg(feature = "timed_guard")]
+use crate::timedguard::TimedGuard;
+use crate::trace::{check_blocking, trace_event};
+use crate::waitstrategy::{Adaptive, WaitStrategy};
+#[cfg(feature = "watchdog")]
+use crate::watchdog::{HolderSlot, Spinning};
 
 pub struct SpinLock<T> {
     locked: AtomicBool,
-    value: UnsafeCell<T>
+    value: UnsafeCell<T>,
+    #[cfg(feature = "metrics")]
+    metrics: LockCounters,
+    // only used to label trace events, so it's not kept around without the feature
+    #[cfg(feature = "tracing")]
+    name: Option<&'static str>,
+    // where the current holder took the lock, for the watchdog to report
+    #[cfg(feature = "watchdog")]
+    holder: HolderSlot,
 }
 
 impl<T> SpinLock<T> {
@@ -13,25 +40,257 @@ impl<T> SpinLock<T> {
         Self {
             locked: AtomicBool::new(false),
             value: UnsafeCell::new(value),
+            #[cfg(feature = "metrics")]
+            metrics: LockCounters::new(),
+            #[cfg(feature = "tracing")]
+            name: None,
+            #[cfg(feature = "watchdog")]
+            holder: HolderSlot::new(),
+        }
+    }
+
+    // Same as new, but the name is attached to the lock's trace events when the `tracing` feature is on
+    pub const fn new_named(name: &'static str, value: T) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = name;
+        Self {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+            #[cfg(feature = "metrics")]
+            metrics: LockCounters::new(),
+            #[cfg(feature = "tracing")]
+            name: Some(name),
+            #[cfg(feature = "watchdog")]
+            holder: HolderSlot::new(),
         }
     }
 
     // Value in spinlock is accessed here. The data is locked until it's unlocked
-    pub fn lock<'a>(&'a self) -> Guard<T> {
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn lock<'a>(&'a self) -> Guard<'a, T> {
+        check_blocking!(when self.locked.load(Relaxed), "SpinLock::lock");
+        self.lock_with(&Adaptive)
+    }
+
+    // Same as lock, but the guard reports it if it's held for longer than timedguard's threshold
+    #[cfg(feature = "timed_guard")]
+    #[track_caller]
+    pub fn lock_timed(&self) -> TimedGuard<Guard<'_, T>> {
+        TimedGuard::new(self.lock())
+    }
+
+    // Same as lock, but with a say in what the thread does while the lock is taken - for locks that can be
+    // held for a while, spinning then yielding or parking wastes a lot less CPU than spinning the whole time
+    pub fn lock_with<'a>(&'a self, strategy: &impl WaitStrategy) -> Guard<'a, T> {
+        self.lock_until(strategy, None).expect("without a deadline it only returns once it has the lock")
+    }
+
+    // Same as lock, but gives up with Err(Timeout) once timeout has passed
+    pub fn lock_timeout(&self, timeout: impl Into<Deadline>) -> Result<Guard<'_, T>, Timeout> {
+        self.lock_until(&Adaptive, timeout.into().instant()).ok_or(Timeout)
+    }
+
+    // Locks, runs f on the value and unlocks again in one go, or returns Err(Timeout) without running f if the
+    // lock couldn't be had in time - the timed version of with, same as Mutex::lock_timeout_map
+    pub fn lock_timeout_map<R>(
+        &self,
+        timeout: impl Into<Deadline>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, Timeout> {
+        self.lock_timeout(timeout).map(|mut guard| f(&mut guard))
+    }
+
+    fn lock_until(&self, strategy: &impl WaitStrategy, deadline: Option<Instant>) -> Option<Guard<'_, T>> {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let mut attempt = 0;
+        #[cfg(feature = "watchdog")]
+        let mut spinning = Spinning::start();
         while self.locked.swap(true, Acquire) {
-            std::hint::spin_loop();
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            #[cfg(feature = "watchdog")]
+            spinning.check(&self.holder);
+            strategy.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.record_acquire(start.elapsed());
+        Some(self.acquired())
+    }
+
+    // Runs f on the value with the lock held, and unlocks as soon as it returns (or panics). For short critical
+    // sections - there's no guard to keep around by accident while doing something slow afterwards
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    // Same as with, but if the lock is already taken f isn't run at all and it's None straight away
+    pub fn try_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        if self.locked.swap(true, Acquire) {
+            return None;
         }
-        Guard {lock: self}
+        // got it first time, so there was no wait
+        #[cfg(feature = "metrics")]
+        self.metrics.record_acquire(Duration::ZERO);
+        Some(f(&mut self.acquired()))
+    }
+
+    // Everything after the flag's been taken, whichever way it was
+    fn acquired(&self) -> Guard<'_, T> {
+        #[cfg(feature = "watchdog")]
+        self.holder.record();
+        trace_event!(lock = self.name, "spinlock acquired");
+        pause!("SpinLock::locked");
+        Guard {
+            lock: self,
+            #[cfg(feature = "metrics")]
+            acquired: Instant::now(),
+        }
+    }
+
+    // With &mut self nobody else can be holding the lock, so there's nothing to spin on
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    // Unlocks without a guard, for when the lock was taken somewhere a guard couldn't be kept - across an FFI
+    // boundary, say, where lock and unlock are separate calls. The hold time isn't recorded for the metrics
+    /// # Safety
+    /// The lock has to be locked, with its guard forgotten, and whoever locked it has to be done with the value
+    pub unsafe fn force_unlock(&self) {
+        pause!("SpinLock::force_unlock");
+        #[cfg(feature = "watchdog")]
+        self.holder.clear();
+        self.locked.store(false, Release);
+        trace_event!(lock = self.name, "spinlock released");
+    }
+
+    // A snapshot of how often this lock has been taken and how long threads waited for it and held it
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> LockMetrics {
+        self.metrics.snapshot()
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&self) {
+        self.metrics.reset();
+    }
+}
+
+// For a SpinLock shared with an Arc: with, but taking the Arc itself rather than a borrow of it. The Arc's kept
+// until the lock's been let go, so a detached thread only needs to be handed the Arc - there's no borrow of the
+// lock to outlive the spawn, and no need to leak it to get a 'static one:
+//
+//     let counter = counter.clone();
+//     thread::spawn(move || counter.with_owned(|n| *n += 1));
+//
+// If it was the last Arc, the value's dropped on the way out, after unlocking
+impl<T, A: Allocator> Arc<SpinLock<T>, A> {
+    pub fn with_owned<R>(self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.with(f)
     }
 }
 
 // This has to be called because otherwise, we cannot 
 unsafe impl<T> Sync for SpinLock<T> where T: Send {}
 
+impl<T: Default> Default for SpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for SpinLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// Serializing takes the lock for as long as it takes to serialize the value, so it waits for (and holds up)
+// other threads like any other lock
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for SpinLock<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.lock().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for SpinLock<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(SpinLock::new)
+    }
+}
+
+// Only whether it's locked right now - showing the value would mean taking the lock, and Debug shouldn't block
+impl<T> fmt::Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinLock").field("locked", &self.locked.load(Relaxed)).finish_non_exhaustive()
+    }
+}
+
 pub struct Guard<'a, T> {
-    lock: &'a SpinLock<T>
+    lock: &'a SpinLock<T>,
+    // when the lock was taken, to work out how long it was held for
+    #[cfg(feature = "metrics")]
+    acquired: Instant,
 }
 
+// Unlocking is just a store, so the guard can go to another thread and be dropped there - it hands over &mut T,
+// so that needs T: Send. A shared guard hands out &T, so sharing it between threads needs T: Sync - without
+// this the guard would be Sync whenever the SpinLock is, which only asks for T: Send
+unsafe impl<T: Send> Send for Guard<'_, T> {}
+unsafe impl<T: Sync> Sync for Guard<'_, T> {}
+
+impl<'a, T> Guard<'a, T> {
+    // Keeps the lock locked forever and hands back the value for as long as the lock lives.
+    // Useful for something that's set up once under the lock and then never unlocked again.
+    // It's Guard::leak(guard) rather than guard.leak() so it can't be mixed up with a leak method on T
+    pub fn leak(this: Self) -> &'a mut T {
+        let lock = this.lock;
+        // skipping the guard's drop is what keeps the lock locked
+        mem::forget(this);
+        // Safety: the lock is never unlocked again, so nothing else can ever get at the value
+        unsafe { &mut *lock.value.get() }
+    }
+
+    // Unlocks the lock while f runs and locks it again afterwards, for calling out to something (a callback,
+    // a slow syscall) that mustn't happen with the lock held. Other threads can change the value in the
+    // meantime, so anything worked out from it before has to be checked again after
+    pub fn unlocked<R>(this: &mut Self, f: impl FnOnce() -> R) -> R {
+        // Locks again on the way out, even if f panics - the guard is still around and will unlock when it's
+        // dropped, so it has to be holding the lock by then
+        struct Relock<'g, 'a, T>(&'g mut Guard<'a, T>);
+
+        impl<T> Drop for Relock<'_, '_, T> {
+            fn drop(&mut self) {
+                let guard = self.0.lock.lock();
+                // the old guard has already unlocked, so it's forgotten rather than dropped
+                mem::forget(mem::replace(self.0, guard));
+            }
+        }
+
+        this.release();
+        let _relock = Relock(this);
+        f()
+    }
+
+    fn release(&self) {
+        #[cfg(feature = "metrics")]
+        self.lock.metrics.record_hold(self.acquired.elapsed());
+        pause!("SpinLock::unlock");
+        #[cfg(feature = "watchdog")]
+        self.lock.holder.clear();
+        self.lock.locked.store(false, Release);
+        trace_event!(lock = self.lock.name, "spinlock released");
+    }
+}
 
 impl<T> Deref for Guard<'_, T> {
     type Target = T;
@@ -53,23 +312,42 @@ impl<T> DerefMut for Guard<'_, T> {
 // Drop automatically gets rid of the value once it's out of scope - this doesn't need to be called explicitly
 impl<T> Drop for Guard<'_, T> {
     fn drop(&mut self) {
-        self.lock.locked.store(false, Release);
+        self.release();
     }
 }
 
-pub fn simulate_spinlock() {
-    // create a new Spinlock with a vec inside of the spinlock
-    let x = SpinLock::new(Vec::new());
-    thread::scope(|s| {
-        // create a new thread that will lock the spinlock to that thread
-        // after done, the spinlock is free so it can be locked again in another thread
-        s.spawn(|| x.lock().push(1));
-        s.spawn(|| {
-            let mut g = x.lock();
-            g.push(2);
-            g.push(2);
-        });
-    });
-    let g = x.lock();
-    assert!(g.as_slice() == [1, 2, 2] || g.as_slice() == [2, 2, 1]);
-}
\ No newline at end of file
+// Where the lock's flag lives, to put locks in a fixed order. It's the flag rather than the lock itself because
+// a lock inside another lock's value can start at the same address as it
+fn order_key<T>(lock: &SpinLock<T>) -> usize {
+    &lock.locked as *const AtomicBool as usize
+}
+
+// Locks two locks at once. They're always taken in the same order (by address) whichever way round they're
+// passed, so one thread doing lock_both(&a, &b) and another lock_both(&b, &a) can't deadlock the way taking
+// them one at a time in those orders can. The guards come back in the order the locks were passed
+pub fn lock_both<'a, A, B>(a: &'a SpinLock<A>, b: &'a SpinLock<B>) -> (Guard<'a, A>, Guard<'a, B>) {
+    // locking the same lock twice would spin forever
+    assert_ne!(order_key(a), order_key(b), "lock_both was given the same lock twice");
+    if order_key(a) < order_key(b) {
+        let a = a.lock();
+        (a, b.lock())
+    } else {
+        let b = b.lock();
+        (a.lock(), b)
+    }
+}
+
+// Same as lock_both, for any number of locks of the same type
+pub fn lock_all<'a, T, const N: usize>(locks: [&'a SpinLock<T>; N]) -> [Guard<'a, T>; N] {
+    let mut order: [usize; N] = std::array::from_fn(|i| i);
+    order.sort_unstable_by_key(|&i| order_key(locks[i]));
+    assert!(
+        order.windows(2).all(|w| order_key(locks[w[0]]) != order_key(locks[w[1]])),
+        "lock_all was given the same lock twice"
+    );
+    let mut guards: [Option<Guard<'a, T>>; N] = std::array::from_fn(|_| None);
+    for i in order {
+        guards[i] = Some(locks[i].lock());
+    }
+    guards.map(|guard| guard.unwrap())
+}
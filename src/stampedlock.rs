@@ -0,0 +1,277 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem;
+#[cfg(not(miri))]
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+#[cfg(not(miri))]
+use std::ptr;
+use std::sync::atomic::{fence, AtomicU64};
+
+use crate::ordering::{Relaxed, Release, Acquire};
+use crate::sched::pause;
+use crate::waitstrategy::{Adaptive, WaitStrategy};
+
+// The state is one atomic: the low bits are the same as RwSpinLock's (flags, then the reader count) and the top
+// 32 bits are a version, which every write bumps on its way out. A stamp is the version plus the WRITER bit,
+// so it stops matching as soon as a writer gets in, and stays that way once it's done
+const WRITER: u64 = 1;
+// Set by a writer that's waiting, so new readers hold off and it doesn't starve
+const WRITER_WAITING: u64 = 2;
+const READER: u64 = 4;
+const READERS: u64 = 0xffff_fffc;
+const VERSION: u64 = 1 << 32;
+const STAMP: u64 = !(READERS | WRITER_WAITING);
+
+// A reader-writer lock with a third way to read that doesn't write to the lock at all. optimistic_read takes
+// a stamp, the value is read without locking anything, and validate says whether a write got in the way - if
+// not, what was read is good, and if so it's read again under read(). Readers that only ever load the state
+// don't fight over its cache line like the reader count does, so when writes are rare the reads scale with
+// the cores:
+//
+//     if let Some(stamp) = lock.optimistic_read() {
+//         if let Some(point) = lock.peek(stamp) {
+//             return point;
+//         }
+//     }
+//     *lock.read()
+//
+// (which is what get does). The value can change halfway through an optimistic read, so it's only offered for
+// T: Copy, copied out and thrown away if the stamp doesn't hold up - a torn copy never gets handed out.
+// The version wraps after 2^32 writes, so a stamp held across that many could validate wrongly; stamps are for
+// reads that take nanoseconds, not for keeping around
+pub struct StampedLock<T> {
+    state: AtomicU64,
+    value: UnsafeCell<T>,
+}
+
+// Readers on different threads share &T, so T has to be Sync as well as Send
+unsafe impl<T> Sync for StampedLock<T> where T: Send + Sync {}
+
+// What optimistic_read hands out, to check later with validate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp(u64);
+
+impl<T: Default> Default for StampedLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for StampedLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for StampedLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = self.state.load(Relaxed);
+        f.debug_struct("StampedLock")
+            .field("version", &(s / VERSION))
+            .field("readers", &((s & READERS) / READER))
+            .field("writer", &(s & WRITER != 0))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> StampedLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self { state: AtomicU64::new(0), value: UnsafeCell::new(value) }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    // A stamp to read against, or None if a writer has the lock right now (a read would only fail validate)
+    pub fn optimistic_read(&self) -> Option<Stamp> {
+        // Acquire, so the read that follows sees everything up to the write that set this version
+        let s = self.state.load(Acquire);
+        (s & WRITER == 0).then_some(Stamp(s & STAMP))
+    }
+
+    // Whether nothing has written since the stamp was taken, so whatever was read after taking it is consistent.
+    // Readers holding read guards don't count, they don't change anything
+    pub fn validate(&self, stamp: Stamp) -> bool {
+        // the fence keeps the reads of the value from moving after the load below: a write they saw has to show
+        // up in it
+        fence(Acquire);
+        self.state.load(Relaxed) & STAMP == stamp.0
+    }
+
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        let mut attempt = 0;
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            Adaptive.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    pub fn try_read(&self) -> Option<ReadGuard<'_, T>> {
+        let s = self.state.load(Relaxed);
+        if s & (WRITER | WRITER_WAITING) != 0 {
+            return None;
+        }
+        assert!(s & READERS != READERS, "too many readers");
+        pause!("StampedLock::try_read loaded");
+        self.state.compare_exchange_weak(s, s + READER, Acquire, Relaxed).ok()?;
+        Some(ReadGuard { lock: self })
+    }
+
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        let mut attempt = 0;
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            // let new readers know a writer is waiting, otherwise a steady stream of them could keep it out forever
+            self.state.fetch_or(WRITER_WAITING, Relaxed);
+            Adaptive.wait(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    pub fn try_write(&self) -> Option<WriteGuard<'_, T>> {
+        let s = self.state.load(Relaxed);
+        if s & (WRITER | READERS) != 0 {
+            return None;
+        }
+        pause!("StampedLock::try_write loaded");
+        self.lock_for_write(s)
+    }
+
+    // Takes the write lock, but only if nothing has written since the stamp - the usual way to act on an
+    // optimistic read, as whatever was decided from it still holds. None if something has, or if there are
+    // readers in the way right now
+    pub fn try_write_at(&self, stamp: Stamp) -> Option<WriteGuard<'_, T>> {
+        let s = self.state.load(Relaxed);
+        if s & STAMP != stamp.0 || s & READERS != 0 {
+            return None;
+        }
+        self.lock_for_write(s)
+    }
+
+    // The value if it can be read without a write getting in the way since the stamp, None if not
+    pub fn peek(&self, stamp: Stamp) -> Option<T>
+    where
+        T: Copy,
+    {
+        // Reading while a writer writes is a data race as far as the memory model goes, even though the copy is
+        // only kept when validate shows there wasn't one - the same trade every seqlock makes. Miri would
+        // (rightly) stop on it, so under Miri it's a plain read under the lock instead, with the same result
+        #[cfg(miri)]
+        loop {
+            if !self.validate(stamp) {
+                return None;
+            }
+            if let Some(guard) = self.try_read() {
+                return self.validate(stamp).then(|| *guard);
+            }
+        }
+        #[cfg(not(miri))]
+        {
+            // Safety: a volatile read into MaybeUninit, so a torn value is never looked at - it's only
+            // assume_init'd once validate shows nothing was writing
+            let value = unsafe { ptr::read_volatile(self.value.get() as *const MaybeUninit<T>) };
+            self.validate(stamp).then(|| unsafe { value.assume_init() })
+        }
+    }
+
+    // The value, optimistically if a write doesn't get in the way and under a read lock if it does
+    pub fn get(&self) -> T
+    where
+        T: Copy,
+    {
+        if let Some(value) = self.optimistic_read().and_then(|stamp| self.peek(stamp)) {
+            return value;
+        }
+        *self.read()
+    }
+
+    fn lock_for_write(&self, s: u64) -> Option<WriteGuard<'_, T>> {
+        // clears WRITER_WAITING too, it's ours now
+        self.state.compare_exchange(s, (s & !WRITER_WAITING) | WRITER, Acquire, Relaxed).ok()?;
+        // an optimistic reader has to see WRITER before it can see anything written under it, and the Acquire
+        // above only keeps the writes from moving up past the load half of the swap
+        fence(Release);
+        Some(WriteGuard { lock: self })
+    }
+}
+
+pub struct ReadGuard<'a, T> {
+    lock: &'a StampedLock<T>,
+}
+
+// Same as RwSpinLock's guards: &T (all a read guard gives out) needs T: Sync on another thread, and the write
+// guard's &mut T needs T: Send
+unsafe impl<T: Sync> Send for ReadGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for ReadGuard<'_, T> {}
+
+impl<T> ReadGuard<'_, T> {
+    // The stamp for what this guard sees: nothing can write while it's held, so it stays valid at least that long
+    pub fn stamp(&self) -> Stamp {
+        Stamp(self.lock.state.load(Relaxed) & STAMP)
+    }
+}
+
+impl<T> Deref for ReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: while there's a read guard there's no writer, only other readers
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for ReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Release);
+    }
+}
+
+pub struct WriteGuard<'a, T> {
+    lock: &'a StampedLock<T>,
+}
+
+unsafe impl<T: Send> Send for WriteGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for WriteGuard<'_, T> {}
+
+impl<'a, T> WriteGuard<'a, T> {
+    // Finishes the write and goes straight to reading, with no gap for another writer. The version moves on as
+    // for any write, so stamps from before still fail
+    pub fn downgrade(self) -> ReadGuard<'a, T> {
+        let lock = self.lock;
+        mem::forget(self);
+        lock.state.fetch_add(VERSION + READER - WRITER, Release);
+        ReadGuard { lock }
+    }
+}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    // Safety: the write guard has the lock all to itself
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // the new version and the unlock in one go. Wrapping past the top is fine, it's only compared for
+        // equality - and it leaves a WRITER_WAITING another writer set alone
+        self.lock.state.fetch_add(VERSION - WRITER, Release);
+    }
+}
@@ -0,0 +1,752 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering::Relaxed};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_atomic_locks::actor::spawn_actor;
+use rust_atomic_locks::arc::Arc;
+use rust_atomic_locks::arcstr::ArcStr;
+use rust_atomic_locks::atomicbitset::AtomicBitSet;
+use rust_atomic_locks::atomicfloat::AtomicF64;
+use rust_atomic_locks::atomicoption::AtomicOption;
+use rust_atomic_locks::atomicwaker::AtomicWaker;
+use rust_atomic_locks::boundedchannel::sync_channel;
+use rust_atomic_locks::boundedqueue::BoundedQueue;
+use rust_atomic_locks::concurrenthashmap::ConcurrentHashMap;
+use rust_atomic_locks::concurrentlru::ConcurrentLru;
+use rust_atomic_locks::duplex::duplex;
+use rust_atomic_locks::event::Event;
+use rust_atomic_locks::frozencell::FrozenCell;
+use rust_atomic_locks::futex;
+use rust_atomic_locks::hybridlock::HybridLock;
+use rust_atomic_locks::irqspinlock::{InterruptController, IrqSpinLock};
+use rust_atomic_locks::latch::Latch;
+use rust_atomic_locks::mutex::Mutex;
+use rust_atomic_locks::mutexchannel::MutexChannel;
+use rust_atomic_locks::objectpool::ObjectPool;
+use rust_atomic_locks::oneshotchannel::OneshotChannel;
+use rust_atomic_locks::prioritychannel::priority_channel;
+use rust_atomic_locks::pubsub::{Bus, SlowSubscriber};
+use rust_atomic_locks::racecell::RaceCell;
+use rust_atomic_locks::ratelimiter::RateLimiter;
+use rust_atomic_locks::rawspinlock::RawSpinLock;
+use rust_atomic_locks::registry::LockRegistry;
+use rust_atomic_locks::router::Router;
+use rust_atomic_locks::rwspinlock::RwSpinLock;
+use rust_atomic_locks::segqueue::SegQueue;
+use rust_atomic_locks::semaphore::Semaphore;
+use rust_atomic_locks::shardedcounter::ShardedCounter;
+use rust_atomic_locks::shardedrwlock::ShardedRwLock;
+use rust_atomic_locks::sharedmem::SharedMemChannel;
+use rust_atomic_locks::shmring::ShmRing;
+use rust_atomic_locks::shutdown::ShutdownController;
+use rust_atomic_locks::spinlock::SpinLock;
+use rust_atomic_locks::stampedlock::StampedLock;
+use rust_atomic_locks::striped::Striped;
+use rust_atomic_locks::taskgroup::TaskGroup;
+use rust_atomic_locks::threadlocal::ThreadLocal;
+use rust_atomic_locks::threadpool::ThreadPool;
+use rust_atomic_locks::triplebuffer::triple_buffer;
+use rust_atomic_locks::versioned::Versioned;
+use rust_atomic_locks::waitmap::WaitMap;
+use rust_atomic_locks::weakregistry::WeakRegistry;
+
+// Contention scenarios for every primitive in the crate, for checking how they behave (and how fast they are)
+// on a particular machine. Every thread hammers the same primitive with one kind of operation, and the
+// report has the throughput and the latency of a single operation
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub threads: usize,
+    // operations per thread
+    pub iters: u64,
+    // stops early once this much time has gone by, even if the threads haven't done all their iters
+    pub duration: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            iters: 100_000,
+            duration: None,
+        }
+    }
+}
+
+pub struct Report {
+    pub primitive: &'static str,
+    pub threads: usize,
+    pub ops: u64,
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+impl Report {
+    pub fn throughput(&self) -> f64 {
+        self.ops as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<16} {:>7} {:>11} {:>13.0} {:>9?} {:>9?} {:>9?} {:>9?}",
+            self.primitive, self.threads, self.ops, self.throughput(), self.p50, self.p99, self.p999, self.max
+        )
+    }
+}
+
+// The header that goes with Report's Display
+pub const REPORT_HEADER: &str =
+    "primitive        threads         ops         ops/s       p50       p99     p99.9       max";
+
+pub const PRIMITIVES: &[&str] = &[
+    "spinlock",
+    "mutex",
+    "hybridlock",
+    "rwspinlock",
+    "shardedrwlock",
+    "stampedlock",
+    "rawspinlock",
+    "irqspinlock",
+    #[cfg(all(target_os = "linux", feature = "pi_mutex"))]
+    "pimutex",
+    "striped",
+    "concurrenthashmap",
+    "concurrentlru",
+    "shardedcounter",
+    "atomicbitset",
+    "atomicfloat",
+    "atomicoption",
+    "atomicwaker",
+    "threadlocal",
+    "frozencell",
+    "arc",
+    "arcstr",
+    "objectpool",
+    "weakregistry",
+    "registry",
+    "event",
+    "latch",
+    "racecell",
+    "shutdown",
+    "waitmap",
+    "semaphore",
+    "ratelimiter",
+    "futex",
+    "oneshotchannel",
+    "mutexchannel",
+    "boundedchannel",
+    "prioritychannel",
+    "duplex",
+    "router",
+    "pubsub",
+    "actor",
+    "taskgroup",
+    "boundedqueue",
+    "segqueue",
+    "sharedmemchannel",
+    "shmring",
+    "triplebuffer",
+    "versioned",
+];
+
+// Runs the scenario for the named primitive, or None if there's no primitive with that name
+pub fn run(primitive: &str, config: &Config) -> Option<Report> {
+    let threads = config.threads;
+    let report = match primitive {
+        "spinlock" => {
+            let lock = SpinLock::new(0u64);
+            measure("spinlock", config, |_| |_| *lock.lock() += 1)
+        }
+        "mutex" => {
+            let lock = Mutex::new(0u64);
+            measure("mutex", config, |_| |_| *lock.lock() += 1)
+        }
+        // short critical sections, so it should settle on spinning wherever spinning helps
+        "hybridlock" => {
+            let lock = HybridLock::new(0u64);
+            measure("hybridlock", config, |_| |_| *lock.lock() += 1)
+        }
+        // mostly reads, with a write every tenth operation
+        "rwspinlock" => {
+            let lock = RwSpinLock::new(0u64);
+            measure("rwspinlock", config, |_| {
+                |i| {
+                    if i % 10 == 0 {
+                        *lock.write() += 1;
+                    } else {
+                        std::hint::black_box(*lock.read());
+                    }
+                }
+            })
+        }
+        // the same 1 in 10 writes, which each have to lock every shard
+        "shardedrwlock" => {
+            let lock = ShardedRwLock::new(0u64);
+            measure("shardedrwlock", config, |_| {
+                |i| {
+                    if i % 10 == 0 {
+                        *lock.write_all() += 1;
+                    } else {
+                        std::hint::black_box(*lock.read());
+                    }
+                }
+            })
+        }
+        // the rwspinlock mix again, with the reads done optimistically
+        "stampedlock" => {
+            let lock = StampedLock::new(0u64);
+            measure("stampedlock", config, |_| {
+                |i| {
+                    if i % 10 == 0 {
+                        *lock.write() += 1;
+                    } else {
+                        std::hint::black_box(lock.get());
+                    }
+                }
+            })
+        }
+        "rawspinlock" => {
+            let lock = RawSpinLock::new();
+            let counter = AtomicU64::new(0);
+            measure("rawspinlock", config, |_| {
+                |_| {
+                    let _guard = lock.lock();
+                    counter.store(counter.load(Relaxed) + 1, Relaxed);
+                }
+            })
+        }
+        "irqspinlock" => {
+            let lock = IrqSpinLock::<u64, NoInterrupts>::new(0);
+            measure("irqspinlock", config, |_| |_| *lock.lock() += 1)
+        }
+        #[cfg(all(target_os = "linux", feature = "pi_mutex"))]
+        "pimutex" => {
+            let lock = rust_atomic_locks::pimutex::PiMutex::new(0u64);
+            measure("pimutex", config, |_| |_| *lock.lock() += 1)
+        }
+        "striped" => {
+            let striped = Striped::new(16, || 0u64);
+            measure("striped", config, |_| |i| *striped.lock(&(i % 64)) += 1)
+        }
+        // each thread inserts, looks up and removes its own keys, which spread over every stripe
+        "concurrenthashmap" => {
+            let map = ConcurrentHashMap::new();
+            measure("concurrenthashmap", config, |t| {
+                let map = &map;
+                move |i| {
+                    let key = (t as u64) << 32 | (i % 1024);
+                    match i % 3 {
+                        0 => {
+                            map.insert(key, i);
+                        }
+                        1 => {
+                            std::hint::black_box(map.get(&key, |v| *v));
+                        }
+                        _ => {
+                            map.remove(&key);
+                        }
+                    }
+                }
+            })
+        }
+        // gets over more keys than fit, filling in the misses like a real cache would, so about half of them hit
+        "concurrentlru" => {
+            let cache = ConcurrentLru::new(1024);
+            measure("concurrentlru", config, |_| {
+                |i| {
+                    let key = i.wrapping_mul(0x9e37_79b9) % 2048;
+                    if cache.get(&key, |v| *v).is_none() {
+                        cache.put(key, i);
+                    }
+                }
+            })
+        }
+        "shardedcounter" => {
+            let counter = ShardedCounter::new();
+            measure("shardedcounter", config, |_| |_| counter.increment())
+        }
+        // claims a free bit and gives it back, with twice as many bits as threads
+        "atomicbitset" => {
+            let bits = AtomicBitSet::new(threads * 2);
+            measure("atomicbitset", config, |_| {
+                |_| {
+                    let bit = bits.find_and_set_first_zero().expect("there's always a free bit");
+                    bits.clear(bit);
+                }
+            })
+        }
+        // every thread adding to the one float, which is as contended as its compare_exchange loop gets
+        "atomicfloat" => {
+            let total = AtomicF64::new(0.0);
+            measure("atomicfloat", config, |_| |_| {
+                total.fetch_add(1.0, Relaxed);
+            })
+        }
+        // every thread swapping its own box in and taking whatever was there, so the boxes keep changing hands
+        "atomicoption" => {
+            let slot = AtomicOption::none();
+            measure("atomicoption", config, |_| |i| drop(slot.swap(Some(Box::new(i)))))
+        }
+        // half registering and half waking, the way a future and whatever completes it race
+        "atomicwaker" => {
+            let waker = AtomicWaker::new();
+            measure("atomicwaker", config, |t| {
+                let waker = &waker;
+                move |_| {
+                    if t % 2 == 0 {
+                        waker.register(std::task::Waker::noop());
+                    } else {
+                        waker.wake();
+                    }
+                }
+            })
+        }
+        "threadlocal" => {
+            let local = ThreadLocal::new();
+            measure("threadlocal", config, |_| {
+                |_| {
+                    local.get_or(|| AtomicU64::new(0)).fetch_add(1, Relaxed);
+                }
+            })
+        }
+        // frozen before the threads start, so this is what a read costs from then on
+        "frozencell" => {
+            let cell = FrozenCell::new(0u64);
+            cell.freeze();
+            measure("frozencell", config, |_| |_| {
+                std::hint::black_box(cell.get());
+            })
+        }
+        "arc" => {
+            let arc = Arc::new(0u64);
+            measure("arc", config, |_| |_| drop(std::hint::black_box(arc.clone())))
+        }
+        "arcstr" => {
+            let s = ArcStr::from("a string shared by every thread");
+            measure("arcstr", config, |_| |_| drop(std::hint::black_box(s.clone())))
+        }
+        // half as many objects as threads, so threads have to wait for each other's to come back
+        "objectpool" => {
+            let pool = ObjectPool::with_cap(threads.div_ceil(2), || 0u64);
+            measure("objectpool", config, |_| |_| *pool.checkout() += 1)
+        }
+        // a handful of keys whose values mostly die straight away, so it's a mix of upgrades and creates
+        "weakregistry" => {
+            let registry = WeakRegistry::new();
+            measure("weakregistry", config, |_| |i| drop(registry.get_or_create(i % 8, || i)))
+        }
+        // named locks looked up on every operation, on a few names so some are contended
+        "registry" => {
+            let locks = LockRegistry::new();
+            let names: Vec<String> = (0..4).map(|i| format!("lock-{i}")).collect();
+            measure("registry", config, |_| |i| drop(locks.lock(&names[i as usize % names.len()])))
+        }
+        "event" => {
+            let event = Event::new();
+            measure("event", config, |_| {
+                |i| {
+                    if i % 2 == 0 {
+                        event.set();
+                    } else {
+                        event.reset();
+                    }
+                    std::hint::black_box(event.is_set());
+                }
+            })
+        }
+        "latch" => {
+            let latch = Latch::new(usize::MAX);
+            measure("latch", config, |_| |_| latch.count_down())
+        }
+        // every thread going through the same cells in order, racing to set each one and then reading the
+        // winner's value. There are only so many cells, so once they've all been set it's the cost of losing
+        "racecell" => {
+            let cells: Vec<RaceCell<u64>> = (0..config.iters.min(65_536)).map(|_| RaceCell::new()).collect();
+            measure("racecell", config, |_| {
+                |i| {
+                    let cell = &cells[(i % cells.len() as u64) as usize];
+                    let _ = cell.try_set(i);
+                    std::hint::black_box(cell.wait());
+                }
+            })
+        }
+        // a worker joining and acking on every operation, which is the controller's count of who's still running
+        "shutdown" => {
+            let shutdown = ShutdownController::new();
+            let result = measure("shutdown", config, |_| {
+                |_| {
+                    let signal = shutdown.signal();
+                    std::hint::black_box(signal.is_shutdown());
+                    signal.ack();
+                }
+            });
+            shutdown.shutdown();
+            shutdown.wait_for_completion();
+            result
+        }
+        // a ring: every thread posts to the next one's key and wakes it, then waits on its own key for what the
+        // one before posted. The wait gives up after a millisecond, so a neighbour that's stopped early can't
+        // hold a thread up for good
+        "waitmap" => {
+            let map = WaitMap::new();
+            let posted: Vec<AtomicU64> = (0..threads).map(|_| AtomicU64::new(0)).collect();
+            measure("waitmap", config, |t| {
+                let (map, posted) = (&map, &posted);
+                move |_| {
+                    let next = (t + 1) % threads;
+                    posted[next].fetch_add(1, Relaxed);
+                    map.wake(&next);
+                    map.wait_timeout_while(t, Duration::from_millis(1), || posted[t].load(Relaxed) == 0);
+                    // only this thread takes from its own count, so it can't go below 0
+                    if posted[t].load(Relaxed) > 0 {
+                        posted[t].fetch_sub(1, Relaxed);
+                    }
+                }
+            })
+        }
+        // half as many permits as threads, so there's always someone queueing
+        "semaphore" => {
+            let semaphore = Semaphore::new((threads / 2).max(1));
+            measure("semaphore", config, |_| |_| drop(semaphore.acquire()))
+        }
+        // refilling faster than anyone can take tokens, so this is the cost of the compare exchange on the
+        // shared timestamp rather than of waiting for tokens
+        "ratelimiter" => {
+            let limiter = RateLimiter::per_second(1_000_000_000, 1_000_000);
+            measure("ratelimiter", config, |_| |_| {
+                limiter.try_acquire(1);
+            })
+        }
+        // a wake with nobody waiting, which is the cost of the syscall
+        "futex" => {
+            let word = AtomicU32::new(0);
+            measure("futex", config, |_| |_| futex::wake_one(&word))
+        }
+        // a new channel per message, sent and received on the same thread
+        "oneshotchannel" => measure("oneshotchannel", config, |_| {
+            |i| {
+                let channel = OneshotChannel::new();
+                channel.send(i);
+                std::hint::black_box(channel.receive());
+            }
+        }),
+        // For the channels every thread sends a message and then receives one. A thread has always sent more
+        // than it has received when it goes to receive, so there's always a message for it eventually, and
+        // there are never more messages in flight than threads - so nothing blocks for good, whenever the
+        // threads stop
+        "mutexchannel" => {
+            let channel = MutexChannel::new();
+            measure("mutexchannel", config, |_| {
+                |i| {
+                    channel.send(i);
+                    std::hint::black_box(channel.receive());
+                }
+            })
+        }
+        "boundedchannel" => {
+            let (sender, receiver) = sync_channel(threads);
+            measure("boundedchannel", config, |_| {
+                let (sender, receiver) = (sender.clone(), receiver.clone());
+                move |i| {
+                    sender.send(i).unwrap();
+                    std::hint::black_box(receiver.receive().unwrap());
+                }
+            })
+        }
+        // every thread sending, one in ten down the priority lane, and thread 0 also taking whatever's waiting
+        // (up to a message per thread, so it keeps up) as the channel's one receiver
+        "prioritychannel" => {
+            let (sender, receiver) = priority_channel();
+            let receiver = SpinLock::new(Some(receiver));
+            measure("prioritychannel", config, |t| {
+                let sender = sender.clone();
+                let receiver = if t == 0 { receiver.lock().take() } else { None };
+                move |i| {
+                    // the sends fail once thread 0 is done and the receiver's gone, which is fine
+                    let _ = if i % 10 == 0 { sender.send_priority(i) } else { sender.send(i) };
+                    if let Some(receiver) = &receiver {
+                        for _ in 0..threads {
+                            if receiver.try_receive().is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+        }
+        // pairs of threads, one calling and the other answering. An odd thread out plays both ends, sending each
+        // way in turn. Either end stopping early disconnects the other, so nothing waits for good
+        "duplex" => {
+            let ends: Vec<_> = (0..threads.div_ceil(2))
+                .map(|_| {
+                    let (caller, answerer) = duplex::<u64, u64>();
+                    SpinLock::new((Some(caller), Some(answerer)))
+                })
+                .collect();
+            measure("duplex", config, |t| {
+                let mut ends = ends[t / 2].lock();
+                let caller = ends.0.take();
+                let answerer = if caller.is_none() || t + 1 == threads { ends.1.take() } else { None };
+                move |i| match (&caller, &answerer) {
+                    (Some(caller), Some(answerer)) => {
+                        let _ = caller.send(i);
+                        let _ = answerer.receive().map(|i| answerer.send(i));
+                        std::hint::black_box(caller.receive().ok());
+                    }
+                    (Some(caller), None) => {
+                        std::hint::black_box(caller.call(i).ok());
+                    }
+                    (None, Some(answerer)) => {
+                        let _ = answerer.receive().map(|i| answerer.send(i));
+                    }
+                    _ => unreachable!(),
+                }
+            })
+        }
+        // four routes with a channel each, and every thread sending down one and then receiving from it, the
+        // same as the channels above
+        "router" => {
+            let (router, _dead_letters) = Router::new(1);
+            let receivers: Vec<_> = (0..4usize).map(|key| router.add_route(key, threads)).collect();
+            measure("router", config, |t| {
+                let (router, receiver) = (&router, receivers[t % 4].clone());
+                move |i| {
+                    router.send(&(t % 4), i).unwrap();
+                    std::hint::black_box(receiver.receive().unwrap());
+                }
+            })
+        }
+        // every thread subscribed to one of four topics, publishing to it and then taking a message off its
+        // subscription. Each publish is a message for every subscriber, more than they take, so the channels
+        // drop their oldest rather than hold the publishers up
+        "pubsub" => {
+            let bus = Bus::new();
+            measure("pubsub", config, |t| {
+                let (bus, topic) = (&bus, t % 4);
+                let subscription = bus.subscribe_with(topic, 64, SlowSubscriber::DropOldest);
+                move |i| {
+                    bus.publish(&topic, i);
+                    std::hint::black_box(subscription.try_receive().ok());
+                }
+            })
+        }
+        // every thread sends to the one actor, so this is how fast a single thread can get through a mailbox
+        // that's being filled from all sides
+        "actor" => {
+            let actor = spawn_actor(0u64, |total, n| *total += n);
+            let result = measure("actor", config, |_| {
+                let actor = actor.clone();
+                move |i| actor.send(i).unwrap()
+            });
+            actor.join().unwrap();
+            result
+        }
+        // a group of one task per operation, spawned on a pool with a worker per thread and joined, which is
+        // the round trip through the pool's queue
+        "taskgroup" => {
+            let pool = ThreadPool::new(threads).expect("couldn't start the pool's threads");
+            measure("taskgroup", config, |_| {
+                |i| {
+                    let group = TaskGroup::<()>::new(&pool);
+                    group.spawn(move |_| {
+                        std::hint::black_box(i);
+                        Ok(())
+                    });
+                    group.join().unwrap();
+                }
+            })
+        }
+        "boundedqueue" => {
+            let queue = BoundedQueue::new(threads);
+            measure("boundedqueue", config, |_| {
+                |i| {
+                    // there's room for one message per thread, but push can see a slot as full for a moment
+                    // while the pop that's emptying it finishes
+                    let mut value = i;
+                    while let Err(v) = queue.push(value) {
+                        value = v;
+                        std::hint::spin_loop();
+                    }
+                    // another thread can pop our message before we get to it, but then theirs is there
+                    while queue.pop().is_none() {
+                        std::hint::spin_loop();
+                    }
+                }
+            })
+        }
+        // a push and a pop each, so the queue stays short but keeps moving through blocks, and retiring them
+        "segqueue" => {
+            let queue = SegQueue::new();
+            measure("segqueue", config, |_| {
+                |i| {
+                    queue.push(i);
+                    std::hint::black_box(queue.pop());
+                }
+            })
+        }
+        "sharedmemchannel" => {
+            let mut region = vec![0u64; SharedMemChannel::<u64>::size_for(threads).div_ceil(8)];
+            let len = region.len() * 8;
+            let channel = unsafe { SharedMemChannel::<u64>::init(region.as_mut_ptr().cast(), len, threads) };
+            measure("sharedmemchannel", config, |_| {
+                |i| {
+                    channel.send(i);
+                    std::hint::black_box(channel.receive());
+                }
+            })
+        }
+        // pairs of threads, a producer and a consumer on each ring, neither waiting for the other
+        "shmring" => {
+            // u64s so the rings are aligned, standing in for shared memory
+            let mut regions: Vec<_> =
+                (0..threads.div_ceil(2)).map(|_| vec![0u64; ShmRing::size_for(8, 64) / 8]).collect();
+            let ends: Vec<_> = regions
+                .iter_mut()
+                .map(|region| {
+                    let bytes = unsafe { std::slice::from_raw_parts_mut(region.as_mut_ptr().cast(), region.len() * 8) };
+                    let (producer, consumer) = ShmRing::init(bytes, 8, 64).unwrap().split().unwrap();
+                    SpinLock::new((Some(producer), Some(consumer)))
+                })
+                .collect();
+            measure("shmring", config, |t| {
+                let mut ends = ends[t / 2].lock();
+                let mut producer = ends.0.take();
+                let mut consumer = if producer.is_none() { ends.1.take() } else { None };
+                move |i| match (&mut producer, &mut consumer) {
+                    (Some(producer), _) => {
+                        let _ = producer.try_send(&i.to_le_bytes());
+                    }
+                    (_, Some(consumer)) => {
+                        std::hint::black_box(consumer.try_receive_with(|m| m.len()));
+                    }
+                    _ => unreachable!(),
+                }
+            })
+        }
+        // pairs of threads, a writer publishing and a reader reading each buffer
+        "triplebuffer" => {
+            let ends: Vec<_> = (0..threads.div_ceil(2))
+                .map(|_| {
+                    let (input, output) = triple_buffer(0);
+                    SpinLock::new((Some(input), Some(output)))
+                })
+                .collect();
+            measure("triplebuffer", config, |t| {
+                let mut ends = ends[t / 2].lock();
+                let mut input = ends.0.take();
+                let mut output = if input.is_none() { ends.1.take() } else { None };
+                move |i| match (&mut input, &mut output) {
+                    (Some(input), _) => input.write(i),
+                    (_, Some(output)) => {
+                        std::hint::black_box(*output.read());
+                    }
+                    _ => unreachable!(),
+                }
+            })
+        }
+        // thread 0 publishing a new value every time round and the rest picking up whatever's latest
+        "versioned" => {
+            let state = Versioned::new(0);
+            measure("versioned", config, |t| {
+                let state = &state;
+                move |i| {
+                    if t == 0 {
+                        state.publish(i);
+                    } else {
+                        std::hint::black_box(state.latest());
+                    }
+                }
+            })
+        }
+        _ => return None,
+    };
+    Some(report)
+}
+
+// Stands in for an interrupt controller, as nothing in the harness is an interrupt handler
+struct NoInterrupts;
+
+unsafe impl InterruptController for NoInterrupts {
+    type State = ();
+    fn disable() {}
+    unsafe fn restore(_state: ()) {}
+}
+
+// About how many latencies are kept for the percentiles. Timing every operation would cost about as much
+// as the faster operations themselves, so only every so many are timed
+const SAMPLES: u64 = 200_000;
+
+// Runs the threads and times them. make_op is called on each thread (with its index) to set up that
+// thread's operation, which is then called with the operation number until the thread is done
+fn measure<F, Op>(primitive: &'static str, config: &Config, make_op: F) -> Report
+where
+    F: Fn(usize) -> Op + Sync,
+    Op: FnMut(u64),
+{
+    let stride = (config.iters * config.threads as u64 / SAMPLES).max(1);
+    let start_gate = Latch::new(1);
+    let ops = AtomicU64::new(0);
+    let mut latencies = Vec::new();
+    let start = thread::scope(|s| {
+        let handles: Vec<_> = (0..config.threads)
+            .map(|t| {
+                let (make_op, start_gate, ops) = (&make_op, &start_gate, &ops);
+                let duration = config.duration;
+                let iters = config.iters;
+                s.spawn(move || {
+                    let mut op = make_op(t);
+                    let mut latencies = Vec::with_capacity((iters / stride) as usize + 1);
+                    start_gate.wait();
+                    let started = Instant::now();
+                    let mut i = 0;
+                    while i < iters {
+                        // checking the clock every operation would skew the numbers, so it's every 256
+                        if i % 256 == 0 && duration.is_some_and(|d| started.elapsed() >= d) {
+                            break;
+                        }
+                        if i % stride == 0 {
+                            let before = Instant::now();
+                            op(i);
+                            latencies.push(before.elapsed());
+                        } else {
+                            op(i);
+                        }
+                        i += 1;
+                    }
+                    ops.fetch_add(i, Relaxed);
+                    latencies
+                })
+            })
+            .collect();
+        let start = Instant::now();
+        start_gate.count_down();
+        for handle in handles {
+            latencies.extend(handle.join().unwrap());
+        }
+        start
+    });
+    let elapsed = start.elapsed();
+    latencies.sort_unstable();
+    let percentile = |p: f64| {
+        latencies.get(((latencies.len() as f64 * p) as usize).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+    Report {
+        primitive,
+        threads: config.threads,
+        ops: ops.into_inner(),
+        elapsed,
+        p50: percentile(0.5),
+        p99: percentile(0.99),
+        p999: percentile(0.999),
+        max: latencies.last().copied().unwrap_or_default(),
+    }
+}
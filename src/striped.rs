@@ -0,0 +1,125 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::DerefMut;
+
+use crate::mutex::{Mutex, MutexGuard};
+use crate::rwspinlock::{self, RwSpinLock};
+use crate::spinlock::{Guard, SpinLock};
+
+// Striped spreads some shared state over a number of locks (stripes), picking the stripe by hashing a key.
+// Threads working on keys in different stripes never contend with each other, unlike with one big lock.
+// The stripes are SpinLocks unless L says otherwise - a Mutex suits stripes that are held for a while, as its
+// waiters sleep instead of spinning:
+//
+//     let sessions: Striped<HashMap<u64, Session>, Mutex<_>> = Striped::with_lock_type(16, HashMap::new);
+pub struct Striped<T, L = SpinLock<T>> {
+    stripes: Box<[L]>,
+    hasher: RandomState,
+    _value: PhantomData<fn() -> T>,
+}
+
+// What Striped needs from the lock around each stripe: to make one, and to lock it for changing the value.
+// An RwSpinLock locks for writing
+pub trait StripeLock<T> {
+    type Guard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    fn new(value: T) -> Self;
+
+    fn lock(&self) -> Self::Guard<'_>;
+}
+
+impl<T> Striped<T> {
+    // init is called once per stripe to create its initial value
+    pub fn new(stripes: usize, init: impl FnMut() -> T) -> Self {
+        Self::with_lock_type(stripes, init)
+    }
+}
+
+impl<T, L: StripeLock<T>> Striped<T, L> {
+    // Same as new, with stripes of whichever lock L is
+    pub fn with_lock_type(stripes: usize, mut init: impl FnMut() -> T) -> Self {
+        assert!(stripes > 0, "Striped needs at least one stripe");
+        Self {
+            stripes: (0..stripes).map(|_| L::new(init())).collect(),
+            hasher: RandomState::new(),
+            _value: PhantomData,
+        }
+    }
+
+    pub fn stripes(&self) -> usize {
+        self.stripes.len()
+    }
+
+    // The same key always maps to the same stripe
+    pub fn stripe_index<K: Hash + ?Sized>(&self, key: &K) -> usize {
+        (self.hasher.hash_one(key) % self.stripes.len() as u64) as usize
+    }
+
+    pub fn get_lock<K: Hash + ?Sized>(&self, key: &K) -> &L {
+        &self.stripes[self.stripe_index(key)]
+    }
+
+    pub fn lock<K: Hash + ?Sized>(&self, key: &K) -> L::Guard<'_> {
+        self.get_lock(key).lock()
+    }
+
+    // Locks a stripe by its position rather than by a key, for walking over the stripes one by one
+    pub fn lock_index(&self, index: usize) -> L::Guard<'_> {
+        self.stripes[index].lock()
+    }
+
+    // Locks every stripe, which gives a consistent snapshot across all of them.
+    // The stripes are always locked in index order, so two threads calling lock_all can't deadlock
+    // by each holding a stripe the other one is waiting for
+    pub fn lock_all(&self) -> Vec<L::Guard<'_>> {
+        self.stripes.iter().map(|stripe| stripe.lock()).collect()
+    }
+}
+
+impl<T> StripeLock<T> for SpinLock<T> {
+    type Guard<'a>
+        = Guard<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        SpinLock::new(value)
+    }
+
+    fn lock(&self) -> Guard<'_, T> {
+        SpinLock::lock(self)
+    }
+}
+
+impl<T> StripeLock<T> for Mutex<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        Mutex::new(value)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, T> {
+        Mutex::lock(self)
+    }
+}
+
+impl<T> StripeLock<T> for RwSpinLock<T> {
+    type Guard<'a>
+        = rwspinlock::WriteGuard<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        RwSpinLock::new(value)
+    }
+
+    fn lock(&self) -> rwspinlock::WriteGuard<'_, T> {
+        self.write()
+    }
+}
@@ -0,0 +1,232 @@
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::arc::Arc;
+use crate::condvar::Condvar;
+use crate::deadline::Deadline;
+use crate::event::Event;
+use crate::mutex::Mutex;
+use crate::threadpool::ThreadPool;
+use crate::trace::{check_blocking, trace_event};
+
+// A set of tasks on a ThreadPool that succeed or fail together. If any of them fails - returns an Err or
+// panics - the rest are cancelled, and join returns that first failure:
+//
+//     let group = TaskGroup::new(&pool);
+//     for shard in shards {
+//         group.spawn(move |token| {
+//             for chunk in shard.chunks() {
+//                 if token.is_cancelled() {
+//                     return Ok(());
+//                 }
+//                 upload(chunk)?;
+//             }
+//             Ok(())
+//         });
+//     }
+//     group.join()?;
+//
+// Cancelling can't stop a task that's already running, so each one gets a CancellationToken to check between
+// bits of work (or to sleep on with wait_timeout, which ends early on a cancel). Tasks that haven't started by
+// the time the group's cancelled don't run at all.
+//
+// No task outlives the group: dropping it without join cancels whatever's still running and waits for it to
+// stop. Since it waits, joining (or dropping) a group from one of the pool's own jobs can deadlock once every
+// worker is doing the same, with the tasks queued behind them
+pub struct TaskGroup<'pool, E> {
+    pool: &'pool ThreadPool,
+    shared: Arc<Shared<E>>,
+}
+
+struct Shared<E> {
+    token: CancellationToken,
+    // tasks spawned that haven't finished (or been skipped) yet, and where join waits for them to run out
+    running: Mutex<usize>,
+    finished: Condvar,
+    // only the first failure is kept, the ones after it are usually fallout from the cancel
+    error: Mutex<Option<TaskError<E>>>,
+}
+
+// What a task is handed to find out its group's been cancelled
+#[derive(Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<Event>,
+}
+
+// Why a group failed: the first task to fail, and how
+#[derive(Debug)]
+pub enum TaskError<E> {
+    Failed(E),
+    // the panic's payload, for resume_unwind to carry on with it
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl<'pool, E: Send + 'static> TaskGroup<'pool, E> {
+    pub fn new(pool: &'pool ThreadPool) -> Self {
+        Self {
+            pool,
+            shared: Arc::new(Shared {
+                token: CancellationToken { cancelled: Arc::new(Event::new()) },
+                running: Mutex::new(0),
+                finished: Condvar::new(),
+                error: Mutex::new(None),
+            }),
+        }
+    }
+
+    // Queues task on the pool, blocking while the pool's queue is full. Once the group's cancelled, it's
+    // counted as finished without running
+    pub fn spawn(&self, task: impl FnOnce(&CancellationToken) -> Result<(), E> + Send + 'static) {
+        *self.shared.running.lock() += 1;
+        let shared = self.shared.clone();
+        self.pool.execute(move || {
+            if !shared.token.is_cancelled() {
+                match panic::catch_unwind(AssertUnwindSafe(|| task(&shared.token))) {
+                    Ok(Ok(())) => {}
+                    Ok(Err(error)) => shared.fail(TaskError::Failed(error)),
+                    Err(payload) => shared.fail(TaskError::Panicked(payload)),
+                }
+            }
+            let mut running = shared.running.lock();
+            *running -= 1;
+            if *running == 0 {
+                shared.finished.notify_all();
+            }
+        });
+    }
+
+    // Cancels every task in the group, the same as one of them failing but without an error for join
+    pub fn cancel(&self) {
+        self.shared.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.token.is_cancelled()
+    }
+
+    // A token for watching the group from outside it
+    pub fn token(&self) -> CancellationToken {
+        self.shared.token.clone()
+    }
+
+    // Tasks that haven't finished yet, a snapshot
+    pub fn running(&self) -> usize {
+        *self.shared.running.lock()
+    }
+
+    // Waits for every task to finish, and returns the first failure if there was one. A group that was only
+    // cancelled (with cancel) returns Ok
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn join(self) -> Result<(), TaskError<E>> {
+        check_blocking!(when self.running() > 0, "TaskGroup::join");
+        self.wait();
+        match self.shared.error.lock().take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> TaskGroup<'_, E> {
+    fn wait(&self) {
+        let running = self.shared.running.lock();
+        drop(self.shared.finished.wait_while(running, |running| *running > 0));
+    }
+}
+
+impl<E> Shared<E> {
+    fn fail(&self, error: TaskError<E>) {
+        let mut slot = self.error.lock();
+        if slot.is_none() {
+            trace_event!("task group failed, cancelling the rest");
+            *slot = Some(error);
+        }
+        drop(slot);
+        self.token.cancel();
+    }
+}
+
+// Dropped without join (a ? or a panic on the way), it cancels the tasks and waits for them, and any error
+// they had is lost
+impl<E> Drop for TaskGroup<'_, E> {
+    fn drop(&mut self) {
+        if *self.shared.running.lock() > 0 {
+            self.shared.token.cancel();
+            self.wait();
+        }
+    }
+}
+
+impl<E> fmt::Debug for TaskGroup<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskGroup")
+            .field("running", &*self.shared.running.lock())
+            .field("cancelled", &self.shared.token.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CancellationToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.is_set()
+    }
+
+    // Parks until the group's cancelled
+    pub fn wait(&self) {
+        self.cancelled.wait();
+    }
+
+    // Same as wait, but gives up once the deadline has passed. Returns whether it's cancelled - false means
+    // carry on, which makes it a sleep that ends early on a cancel
+    pub fn wait_timeout(&self, timeout: impl Into<Deadline>) -> bool {
+        self.cancelled.wait_timeout(timeout)
+    }
+
+    fn cancel(&self) {
+        self.cancelled.set();
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+impl<E> TaskError<E> {
+    // Carries on with the panic if that's what it was, so only a task's own errors are left to handle
+    pub fn into_failure(self) -> E {
+        match self {
+            TaskError::Failed(error) => error,
+            TaskError::Panicked(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for TaskError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(error) => write!(f, "task failed: {error}"),
+            TaskError::Panicked(payload) => {
+                // the two types panic! gives its message as
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("Box<dyn Any>");
+                write!(f, "task panicked: {message}")
+            }
+        }
+    }
+}
+
+impl<E: StdError + 'static> StdError for TaskError<E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            TaskError::Failed(error) => Some(error),
+            TaskError::Panicked(_) => None,
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+
+use crate::ordering::{Relaxed, Release, Acquire, AcqRel};
+
+// Every thread gets its own index into the ThreadLocals the first time it touches one. Indexes aren't reused
+// when threads exit: a new thread picking up an old index would find the old thread's value waiting for it
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_ID: usize = NEXT_THREAD_ID.fetch_add(1, Relaxed);
+}
+
+// Bucket b has room for 2^b threads, so the buckets only grow as more threads show up and a value never moves
+// once it's been put in (unlike a Vec, which would move everything when it grows)
+const BUCKETS: usize = usize::BITS as usize;
+
+struct Entry<T> {
+    present: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// One value of T per thread that uses it, created lazily with get_or. Unlike the thread_local! macro it isn't
+// static - it's a normal value, and all of the threads' values can be iterated over and are dropped with it
+pub struct ThreadLocal<T: Send> {
+    buckets: [AtomicPtr<Entry<T>>; BUCKETS],
+}
+
+// Each thread only ever gets at its own value through &self, so T only needs to be Send to share the container.
+// Iterating hands out &T to other threads' values, which is why iter needs T: Sync on top
+unsafe impl<T: Send> Sync for ThreadLocal<T> {}
+
+// Works out which bucket and which slot in it a thread's value lives in
+fn location(id: usize) -> (usize, usize) {
+    let bucket = (usize::BITS - 1 - (id + 1).leading_zeros()) as usize;
+    (bucket, id + 1 - (1 << bucket))
+}
+
+fn bucket_size(bucket: usize) -> usize {
+    1 << bucket
+}
+
+impl<T: Send> ThreadLocal<T> {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicPtr::new(ptr::null_mut()) }; BUCKETS],
+        }
+    }
+
+    fn entry(&self, id: usize) -> Option<&Entry<T>> {
+        let (bucket, index) = location(id);
+        let ptr = self.buckets[bucket].load(Acquire);
+        if ptr.is_null() {
+            return None;
+        }
+        // Safety: a bucket is never freed before the ThreadLocal, and index is always within its size
+        Some(unsafe { &*ptr.add(index) })
+    }
+
+    // This thread's value, if it has one
+    pub fn get(&self) -> Option<&T> {
+        let entry = self.entry(THREAD_ID.with(|id| *id))?;
+        // Acquire matches the Release in insert, which matters when a thread other than the owner is iterating
+        if !entry.present.load(Acquire) {
+            return None;
+        }
+        Some(unsafe { (*entry.value.get()).assume_init_ref() })
+    }
+
+    // This thread's value, making it with init if it doesn't have one yet
+    pub fn get_or(&self, init: impl FnOnce() -> T) -> &T {
+        match self.get() {
+            Some(value) => value,
+            None => self.insert(init()),
+        }
+    }
+
+    fn insert(&self, value: T) -> &T {
+        let id = THREAD_ID.with(|id| *id);
+        let (bucket, _) = location(id);
+        if self.buckets[bucket].load(Acquire).is_null() {
+            let new: Box<[Entry<T>]> = (0..bucket_size(bucket))
+                .map(|_| Entry { present: AtomicBool::new(false), value: UnsafeCell::new(MaybeUninit::uninit()) })
+                .collect();
+            let new = Box::into_raw(new) as *mut Entry<T>;
+            // another thread in the same bucket may have beaten us to it, in which case ours is thrown away
+            if self.buckets[bucket].compare_exchange(ptr::null_mut(), new, AcqRel, Acquire).is_err() {
+                drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(new, bucket_size(bucket))) });
+            }
+        }
+        let entry = self.entry(id).unwrap();
+        // Safety: only this thread ever writes to its own entry, and it's not present yet so nothing reads it
+        let value = unsafe { (*entry.value.get()).write(value) };
+        entry.present.store(true, Release);
+        value
+    }
+
+    // Every thread's value. Threads adding values while this runs may or may not be included
+    pub fn iter(&self) -> impl Iterator<Item = &T>
+    where
+        T: Sync,
+    {
+        self.entries().map(|entry| unsafe { (*entry.value.get()).assume_init_ref() })
+    }
+
+    // With &mut self nobody else can be using the values, so they can be changed (e.g. reset) in place
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries().map(|entry| unsafe { (*entry.value.get()).assume_init_mut() })
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry<T>> {
+        self.buckets.iter().enumerate().flat_map(|(bucket, ptr)| {
+            let ptr = ptr.load(Acquire);
+            let entries: &[Entry<T>] = if ptr.is_null() {
+                &[]
+            } else {
+                unsafe { &*ptr::slice_from_raw_parts(ptr, bucket_size(bucket)) }
+            };
+            entries.iter().filter(|entry| entry.present.load(Acquire))
+        })
+    }
+
+    // Drops every thread's value, so the next get_or on each thread makes a new one
+    pub fn clear(&mut self) {
+        for entry in self.entries() {
+            entry.present.store(false, Relaxed);
+            unsafe { (*entry.value.get()).assume_init_drop() };
+        }
+    }
+}
+
+impl<T: Send> Default for ThreadLocal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send> Drop for ThreadLocal<T> {
+    fn drop(&mut self) {
+        self.clear();
+        for (bucket, ptr) in self.buckets.iter_mut().enumerate() {
+            let ptr = *ptr.get_mut();
+            if !ptr.is_null() {
+                drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, bucket_size(bucket))) });
+            }
+        }
+    }
+}
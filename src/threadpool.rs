@@ -0,0 +1,277 @@
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::AtomicUsize;
+use std::thread::{self, JoinHandle};
+
+use crate::arc::Arc;
+use crate::boundedchannel::{sync_channel, Sender};
+use crate::ordering::Relaxed;
+
+// How many jobs can be queued before execute blocks, unless the builder says otherwise
+const DEFAULT_QUEUE: usize = 1024;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// A fixed set of worker threads running the jobs handed to execute, in the order they were handed in (though
+// with more than one worker they can finish in any order). The queue is a bounded channel, so execute blocks
+// while it's full instead of letting it grow without limit. A job that panics is counted and the worker carries
+// on with the next one.
+//
+//     let pool = ThreadPool::builder().threads(4).name("worker").build()?;
+//     pool.execute(|| crunch());
+//     pool.join();
+//
+// Dropping the pool (or join) lets the workers finish everything that's queued, then waits for them
+pub struct ThreadPool {
+    // None once it's been shut down, which closes the channel
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    panicked: Arc<AtomicUsize>,
+}
+
+// What the pool's threads are made with. Everything's optional: by default there's a thread per core, named
+// after the pool's index, with std's default stack size
+pub struct ThreadPoolBuilder {
+    threads: Option<usize>,
+    name: Option<String>,
+    stack_size: Option<usize>,
+    queue: usize,
+    #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+    cores: Vec<usize>,
+    #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+    priority: Option<ThreadPriority>,
+}
+
+// How the workers are scheduled, relative to the rest of the system
+#[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadPriority {
+    // A nice value, -20 (most CPU) to 19 (least). Only root (or CAP_SYS_NICE) can go below where it is already
+    Nice(i32),
+    // SCHED_FIFO at the given priority, 1 to 99: the workers run ahead of every normal thread until they block.
+    // Needs CAP_SYS_NICE or an RLIMIT_RTPRIO that allows it
+    Realtime(i32),
+}
+
+impl ThreadPool {
+    // A pool of the given number of threads, with everything else left at the builder's defaults
+    pub fn new(threads: usize) -> io::Result<Self> {
+        Self::builder().threads(threads).build()
+    }
+
+    pub fn builder() -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+    }
+
+    // Queues f to run on one of the workers, blocking while the queue's full
+    pub fn execute(&self, f: impl FnOnce() + Send + 'static) {
+        let jobs = self.jobs.as_ref().expect("the jobs channel is only taken on shutdown");
+        // the workers only stop once it's closed, so there's always one to take it
+        jobs.send(Box::new(f)).unwrap_or_else(|_| unreachable!("the workers outlive the pool"));
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    // Jobs waiting for a worker, a snapshot
+    pub fn queued(&self) -> usize {
+        self.jobs.as_ref().map_or(0, Sender::len)
+    }
+
+    // How many jobs have panicked so far
+    pub fn panicked(&self) -> usize {
+        self.panicked.load(Relaxed)
+    }
+
+    // Waits for every queued job to run and the workers to finish, the same as dropping it but spelled out
+    pub fn join(self) {}
+
+    fn shut_down(&mut self) {
+        // closing the channel is what tells the workers to stop, once they've emptied it
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            // the jobs' panics are caught, so the workers don't panic themselves
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+impl ThreadPoolBuilder {
+    pub fn new() -> Self {
+        Self {
+            threads: None,
+            name: None,
+            stack_size: None,
+            queue: DEFAULT_QUEUE,
+            #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+            cores: Vec::new(),
+            #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+            priority: None,
+        }
+    }
+
+    // The number of workers, by default available_parallelism
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "a thread pool needs at least one thread");
+        self.threads = Some(threads);
+        self
+    }
+
+    // The workers are named prefix-0, prefix-1 and so on, which is what shows up in panics, debuggers and top
+    pub fn name(mut self, prefix: impl Into<String>) -> Self {
+        self.name = Some(prefix.into());
+        self
+    }
+
+    pub fn stack_size(mut self, bytes: usize) -> Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    // How many jobs can wait for a worker before execute blocks
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue = capacity;
+        self
+    }
+
+    // Pins each worker to one of the cores, going round them in order - worker i gets cores[i % cores.len()].
+    // A worker that stays on one core keeps its cache warm and doesn't get moved around by the scheduler, which
+    // is what makes contention benchmarks come out the same from one run to the next
+    #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+    pub fn pin_to_cores(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.cores = cores.into_iter().collect();
+        self
+    }
+
+    #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+    pub fn priority(mut self, priority: ThreadPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    // Starts the workers. Each one applies its pinning and priority before it takes any jobs, and build waits to
+    // hear they have - if any of them can't (a core that doesn't exist, a priority it isn't allowed), the pool is
+    // shut down again and that's the error
+    pub fn build(self) -> io::Result<ThreadPool> {
+        let threads = match self.threads {
+            Some(threads) => threads,
+            None => thread::available_parallelism()?.get(),
+        };
+        let (jobs, queue) = sync_channel::<Job>(self.queue);
+        let (ready, started) = sync_channel(threads);
+        let panicked = Arc::new(AtomicUsize::new(0));
+        let mut pool = ThreadPool { jobs: Some(jobs), workers: Vec::with_capacity(threads), panicked };
+        for i in 0..threads {
+            let mut builder = thread::Builder::new();
+            if let Some(prefix) = &self.name {
+                builder = builder.name(format!("{prefix}-{i}"));
+            }
+            if let Some(bytes) = self.stack_size {
+                builder = builder.stack_size(bytes);
+            }
+            let setup = self.setup(i);
+            let (queue, ready, panicked) = (queue.clone(), ready.clone(), pool.panicked.clone());
+            let worker = builder.spawn(move || {
+                let result = setup();
+                let ok = result.is_ok();
+                let _ = ready.send(result);
+                drop(ready);
+                if !ok {
+                    return;
+                }
+                while let Ok(job) = queue.receive() {
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        panicked.fetch_add(1, Relaxed);
+                    }
+                }
+            })?;
+            pool.workers.push(worker);
+        }
+        drop((queue, ready));
+        for result in started.iter() {
+            result?;
+        }
+        Ok(pool)
+    }
+
+    // What worker i does to itself before taking any jobs
+    fn setup(&self, _i: usize) -> impl FnOnce() -> io::Result<()> + Send + 'static {
+        #[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+        {
+            let core = (!self.cores.is_empty()).then(|| self.cores[_i % self.cores.len()]);
+            let priority = self.priority;
+            move || {
+                if let Some(core) = core {
+                    linux::pin_to_core(core)?;
+                }
+                if let Some(priority) = priority {
+                    linux::set_priority(priority)?;
+                }
+                Ok(())
+            }
+        }
+        #[cfg(not(all(target_os = "linux", feature = "thread_tuning")))]
+        || Ok(())
+    }
+}
+
+impl Default for ThreadPoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Both of these only ever change the calling thread
+#[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+mod linux {
+    use std::io;
+    use std::mem;
+
+    use super::ThreadPriority;
+
+    pub(super) fn pin_to_core(core: usize) -> io::Result<()> {
+        if core >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("core {core} is out of range")));
+        }
+        // Safety: cpu_set_t is a plain bitmask, all zeroes is an empty set, and core is in range of it
+        let result = unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            libc::CPU_SET(core, &mut set);
+            // 0 is the calling thread
+            libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set)
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+        let result = match priority {
+            // On Linux a nice value is per thread, and PRIO_PROCESS with a thread id sets just that thread's
+            // Safety: plain syscalls on the calling thread
+            ThreadPriority::Nice(nice) => unsafe {
+                libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, nice)
+            },
+            ThreadPriority::Realtime(priority) => {
+                let param = libc::sched_param { sched_priority: priority };
+                // pthread_setschedparam returns the error rather than setting errno
+                match unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) } {
+                    0 => 0,
+                    error => return Err(io::Error::from_raw_os_error(error)),
+                }
+            }
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ordering::Relaxed;
+
+// For catching locks held for too long in development - a file read or a network call made with a lock held,
+// which makes every other thread wait for the I/O too. A TimedGuard wraps any guard (SpinLock::lock_timed and
+// Mutex::lock_timed hand one out) and notes when it was taken and where; if it's still held after the
+// threshold by the time it's dropped, the callback gets told where it was taken and how long for.
+//
+// It only reads the clock twice per lock, but it's still for debugging: the report comes after the fact, and
+// the watchdog is the one for a guard that's never let go at all
+
+// 0 means off
+static THRESHOLD_NANOS: AtomicU64 = AtomicU64::new(0);
+type Callback = Arc<dyn Fn(&HeldTooLong) + Send + Sync>;
+static CALLBACK: RwLock<Option<Callback>> = RwLock::new(None);
+
+// What the callback is told about a guard that was held too long
+#[derive(Debug, Clone)]
+pub struct HeldTooLong {
+    pub held: Duration,
+    pub threshold: Duration,
+    // where the guard was taken
+    pub location: &'static Location<'static>,
+    pub thread: String,
+}
+
+impl fmt::Display for HeldTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a lock taken at {} was held for {:?} by thread {} (the threshold is {:?})",
+            self.location, self.held, self.thread, self.threshold
+        )
+    }
+}
+
+// Sets the threshold for every TimedGuard without one of its own, and what happens when one's held for longer -
+// timedguard::log to just print it. Until this is called, guards with their own threshold are logged
+pub fn set_threshold(threshold: Duration, callback: impl Fn(&HeldTooLong) + Send + Sync + 'static) {
+    *CALLBACK.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(callback));
+    THRESHOLD_NANOS.store((threshold.as_nanos() as u64).max(1), Relaxed);
+}
+
+// Stops checking the guards that use the global threshold
+pub fn disable() {
+    THRESHOLD_NANOS.store(0, Relaxed);
+}
+
+// Prints the report to stderr
+pub fn log(report: &HeldTooLong) {
+    eprintln!("{report}");
+}
+
+fn global_threshold() -> Option<Duration> {
+    match THRESHOLD_NANOS.load(Relaxed) {
+        0 => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+// Send and Sync whenever G is, it adds nothing tied to a thread
+pub struct TimedGuard<G> {
+    guard: G,
+    acquired: Instant,
+    location: &'static Location<'static>,
+    // None for the global one, looked up at drop time
+    threshold: Option<Duration>,
+}
+
+impl<G> TimedGuard<G> {
+    // Starts timing a guard that's just been taken. The location is the caller's, so a function that takes a
+    // lock and wraps it should be #[track_caller] too, like lock_timed is
+    #[track_caller]
+    pub fn new(guard: G) -> Self {
+        Self { guard, acquired: Instant::now(), location: Location::caller(), threshold: None }
+    }
+
+    // Same as new, but with a threshold of its own rather than the global one - for the one lock that has to be
+    // held for longer (or should be much quicker) than the rest
+    #[track_caller]
+    pub fn with_threshold(guard: G, threshold: Duration) -> Self {
+        let mut timed = Self::new(guard);
+        timed.threshold = Some(threshold);
+        timed
+    }
+
+    // How long it's been held so far
+    pub fn held(&self) -> Duration {
+        self.acquired.elapsed()
+    }
+
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+impl<G: Deref> Deref for TimedGuard<G> {
+    type Target = G::Target;
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for TimedGuard<G> {
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
+
+// The check's done before the inner guard goes (it's dropped after this), so the time it takes to report
+// counts as held too - but only for a guard that's over the threshold already
+impl<G> Drop for TimedGuard<G> {
+    fn drop(&mut self) {
+        let Some(threshold) = self.threshold.or_else(global_threshold) else {
+            return;
+        };
+        let held = self.acquired.elapsed();
+        if held <= threshold {
+            return;
+        }
+        let current = thread::current();
+        let report = HeldTooLong {
+            held,
+            threshold,
+            location: self.location,
+            thread: current.name().map_or_else(|| format!("{:?}", current.id()), str::to_owned),
+        };
+        // cloned out, so a callback that calls set_threshold doesn't deadlock on the lock
+        let callback = CALLBACK.read().unwrap_or_else(PoisonError::into_inner).clone();
+        match callback {
+            Some(callback) => callback(&report),
+            None => log(&report),
+        }
+    }
+}
+
+impl<G: fmt::Debug> fmt::Debug for TimedGuard<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimedGuard")
+            .field("guard", &self.guard)
+            .field("held", &self.held())
+            .field("location", &self.location)
+            .finish()
+    }
+}
@@ -0,0 +1,29 @@
+// Emits a tracing event at trace level when the `tracing` feature is on, and compiles to nothing when it's off,
+// so the primitives don't need a #[cfg] around every event
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+pub(crate) use trace_event;
+
+// Panics if a blocking call's about to wait on a thread that's running async tasks, when the `blocking_check`
+// feature is on (see blockingcheck), and compiles to nothing when it's off - the condition in the `when` form
+// included, so working out whether it would wait costs nothing without the feature. The function it's in needs
+// #[cfg_attr(feature = "blocking_check", track_caller)] for the check to see where it was called from
+macro_rules! check_blocking {
+    (when $would_wait:expr, $what:expr) => {
+        #[cfg(feature = "blocking_check")]
+        if $would_wait {
+            crate::blockingcheck::check($what);
+        }
+    };
+    ($what:expr) => {
+        #[cfg(feature = "blocking_check")]
+        crate::blockingcheck::check($what);
+    };
+}
+
+pub(crate) use check_blocking;
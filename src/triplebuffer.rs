@@ -0,0 +1,85 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::AtomicU8;
+
+use crate::arc::Arc;
+use crate::ordering::{Relaxed, AcqRel};
+use crate::sched::pause;
+
+// Set in `back` when the buffer in the middle has been written since the reader last took it
+const DIRTY: u8 = 4;
+
+// Three copies of the value: one the writer owns, one the reader owns, and one in the middle that they swap
+// their own buffer with. Neither side ever waits for the other - the writer can publish as often as it likes,
+// and the reader always gets the latest value that was completely written
+struct Shared<T> {
+    buffers: [UnsafeCell<T>; 3],
+    // the index of the middle buffer, plus DIRTY if it holds something the reader hasn't seen
+    back: AtomicU8,
+}
+
+// Each buffer is only ever used by whichever side owns its index, so sharing is fine as long as T can move
+// between threads
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct Input<T> {
+    shared: Arc<Shared<T>>,
+    index: u8,
+}
+
+pub struct Output<T> {
+    shared: Arc<Shared<T>>,
+    index: u8,
+}
+
+// Makes a triple buffer with all three buffers starting as copies of initial
+pub fn triple_buffer<T: Clone + Send>(initial: T) -> (Input<T>, Output<T>) {
+    let shared = Arc::new(Shared {
+        buffers: [UnsafeCell::new(initial.clone()), UnsafeCell::new(initial.clone()), UnsafeCell::new(initial)],
+        back: AtomicU8::new(1),
+    });
+    (
+        Input { shared: shared.clone(), index: 0 },
+        Output { shared, index: 2 },
+    )
+}
+
+impl<T: Send> Input<T> {
+    // The writer's own buffer, to update in place before publishing (which saves building a whole new T)
+    pub fn input_buffer(&mut self) -> &mut T {
+        // Safety: the reader never touches the buffer the writer owns
+        unsafe { &mut *self.shared.buffers[self.index as usize].get() }
+    }
+
+    // Hands the writer's buffer over to the middle, and takes the old middle buffer to write into next
+    pub fn publish(&mut self) {
+        // Release so the reader sees everything written to the buffer, Acquire so we see the reader is
+        // done with the buffer we get back, if it's the one the reader just swapped out
+        pause!("Input::publish");
+        let old = self.shared.back.swap(self.index | DIRTY, AcqRel);
+        self.index = old & !DIRTY;
+    }
+
+    pub fn write(&mut self, value: T) {
+        *self.input_buffer() = value;
+        self.publish();
+    }
+}
+
+impl<T: Send> Output<T> {
+    // Whether there's a value the reader hasn't seen yet
+    pub fn updated(&self) -> bool {
+        self.shared.back.load(Relaxed) & DIRTY != 0
+    }
+
+    // The latest published value. If nothing new has been published, it's the same value as last time
+    pub fn read(&mut self) -> &T {
+        if self.updated() {
+            // give the middle our buffer (not dirty, so we won't take it back) and take the new one
+            pause!("Output::read updated");
+            let old = self.shared.back.swap(self.index, AcqRel);
+            self.index = old & !DIRTY;
+        }
+        // Safety: the writer never touches the buffer the reader owns
+        unsafe { &*self.shared.buffers[self.index as usize].get() }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::mem;
+use std::sync::atomic::AtomicU64;
+use std::time::Instant;
+
+use crate::arc::Arc;
+use crate::deadline::Deadline;
+use crate::ordering::{Release, Acquire};
+use crate::spinlock::SpinLock;
+use crate::trace::trace_event;
+use crate::waitqueue::WaitQueue;
+
+// The latest value of something, numbered: a writer publishes a new value whenever it has one, and readers
+// either look at whatever's current or park until a version they need turns up. Meant for a simulation's state
+// from one frame to the next, or config that gets reloaded - the readers want the newest value and don't care
+// about the ones in between, so nothing queues up behind a slow reader like it would in a channel:
+//
+//     let mut seen = 0;
+//     loop {
+//         let frame = state.wait_newer_than(seen);
+//         seen = frame.version;
+//         render(&frame.value);
+//     }
+//
+// Values are handed out as an Arc, so a reader can hang on to one for as long as it likes without holding
+// anything up. Versions start at 0 for the value it's made with and go up by one per publish
+pub struct Versioned<T> {
+    current: SpinLock<Snapshot<T>>,
+    // the same as current's version, for checking without the lock
+    version: AtomicU64,
+    waiters: WaitQueue,
+}
+
+// One published value and its version
+pub struct Snapshot<T> {
+    pub version: u64,
+    pub value: Arc<T>,
+}
+
+impl<T> Clone for Snapshot<T> {
+    fn clone(&self) -> Self {
+        Self { version: self.version, value: self.value.clone() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Snapshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot").field("version", &self.version).field("value", &self.value).finish()
+    }
+}
+
+impl<T> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: SpinLock::new(Snapshot { version: 0, value: Arc::new(value) }),
+            version: AtomicU64::new(0),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    // Makes value the current one and wakes every reader waiting for it. Returns its version.
+    // Publishing from more than one thread works, the versions just go to whoever gets the lock first
+    pub fn publish(&self, value: T) -> u64 {
+        let value = Arc::new(value);
+        let (version, old) = {
+            let mut current = self.current.lock();
+            let version = current.version + 1;
+            let old = mem::replace(&mut *current, Snapshot { version, value });
+            // Release, so a reader that sees the new version and then takes the lock finds the value there
+            self.version.store(version, Release);
+            (version, old)
+        };
+        // the old value could be big, it's let go of outside the lock
+        drop(old);
+        trace_event!("versioned value published");
+        self.waiters.wake_all();
+        version
+    }
+
+    // The latest version, without the value. A snapshot - there can be a newer one by the time it's looked at
+    pub fn version(&self) -> u64 {
+        self.version.load(Acquire)
+    }
+
+    pub fn latest(&self) -> Snapshot<T> {
+        self.current.lock().clone()
+    }
+
+    // Parks until version v (or a later one) has been published, and returns the latest. If the writer has
+    // moved on more than once since, the ones in between are skipped
+    pub fn wait_for_version(&self, v: u64) -> Snapshot<T> {
+        self.wait_until(v, None).expect("no deadline to miss")
+    }
+
+    pub fn wait_newer_than(&self, v: u64) -> Snapshot<T> {
+        self.wait_for_version(v + 1)
+    }
+
+    // wait_for_version, giving up after timeout. None if version v still hadn't turned up by then
+    pub fn wait_for_version_timeout(&self, v: u64, timeout: impl Into<Deadline>) -> Option<Snapshot<T>> {
+        self.wait_until(v, timeout.into().instant())
+    }
+
+    fn wait_until(&self, v: u64, deadline: Option<Instant>) -> Option<Snapshot<T>> {
+        while self.version() < v {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            trace_event!("versioned reader parking");
+            // checking again once we're in the queue, so a publish in between wakes us rather than being missed
+            self.waiters.wait_until(deadline, || self.version() < v);
+        }
+        Some(self.latest())
+    }
+}
+
+impl<T: Default> Default for Versioned<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Versioned<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// The version only, the value is behind the lock
+impl<T> fmt::Debug for Versioned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Versioned").field("version", &self.version()).finish_non_exhaustive()
+    }
+}
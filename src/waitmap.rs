@@ -0,0 +1,195 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::time::Instant;
+
+use crate::arc::Arc;
+use crate::deadline::Deadline;
+use crate::mutex::Mutex;
+use crate::trace::check_blocking;
+use crate::waitqueue::WaitQueue;
+
+// Threads waiting for different things by name - a response to request 17, a lock on "users.db" - where a single
+// Condvar would mean waking every waiter for every event, and all but one of them going straight back to sleep.
+// Each key has its own wait queue, so a wake only gets to the threads waiting on that key:
+//
+//     let responses = WaitMap::new();
+//     // the caller
+//     responses.wait_while(id, || !done.contains(id));
+//     // the thread that reads responses off the socket
+//     done.insert(id);
+//     responses.wake(&id);
+//
+// Like a Condvar there's no memory of a wake: one that comes before the waiter is waiting is gone. So whatever's
+// waited for has to be state the waiter can check, and wait_while does the check once it's in the queue, so a
+// wake between the check and going to sleep can't be missed - the waker changes the state first, then wakes.
+// The plain wait is only for when the waiter doesn't mind missing the ones before it.
+//
+// A key's queue is only there while something waits on it, so the map doesn't grow with every key that's ever
+// been waited on
+pub struct WaitMap<K> {
+    keys: Mutex<HashMap<K, Key>>,
+}
+
+struct Key {
+    // an Arc so waiters and wakers can use it without the map locked
+    queue: Arc<WaitQueue>,
+    // the threads in wait or wait_while for the key, which can be more than are in the queue right now
+    waiters: usize,
+}
+
+impl<K: Eq + Hash> WaitMap<K> {
+    pub fn new() -> Self {
+        Self { keys: Mutex::new(HashMap::new()) }
+    }
+
+    // Wakes every thread waiting on the key, and returns how many were in its queue
+    pub fn wake<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.queue(key) {
+            Some(queue) => queue.wake_all(),
+            None => 0,
+        }
+    }
+
+    // Wakes the thread that's been waiting on the key longest. Returns false if there wasn't one
+    pub fn wake_one<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.queue(key).is_some_and(|queue| queue.wake_one())
+    }
+
+    // Wakes every thread waiting on a key that matches, for when an event covers several of them (everything for
+    // one connection, say). Returns how many there were
+    pub fn wake_all_matching(&self, mut matches: impl FnMut(&K) -> bool) -> usize {
+        // the queues are woken after letting go of the map, so waiters on their way in or out don't wait on it
+        let queues: Vec<Arc<WaitQueue>> =
+            self.keys.lock().iter().filter(|(key, _)| matches(key)).map(|(_, key)| key.queue.clone()).collect();
+        queues.iter().map(|queue| queue.wake_all()).sum()
+    }
+
+    // Whether anything's waiting on the key, a snapshot
+    pub fn is_waiting<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.keys.lock().contains_key(key)
+    }
+
+    // How many keys have something waiting on them, a snapshot
+    pub fn len(&self) -> usize {
+        self.keys.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.lock().is_empty()
+    }
+
+    fn queue<Q>(&self, key: &Q) -> Option<Arc<WaitQueue>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.keys.lock().get(key).map(|key| key.queue.clone())
+    }
+}
+
+impl<K: Eq + Hash + Clone> WaitMap<K> {
+    // Blocks until the next wake for the key
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn wait(&self, key: K) {
+        check_blocking!("WaitMap::wait");
+        let waiting = self.enter(key);
+        waiting.queue.wait(|| true);
+    }
+
+    // Blocks while condition returns true, checking it again after every wake for the key
+    #[cfg_attr(feature = "blocking_check", track_caller)]
+    pub fn wait_while(&self, key: K, mut condition: impl FnMut() -> bool) {
+        if !condition() {
+            return;
+        }
+        check_blocking!("WaitMap::wait_while");
+        self.wait_while_until(key, None, condition);
+    }
+
+    // Same as wait_while, but gives up once the timeout has passed. Returns whether condition turned false, which
+    // it can do right at the deadline as well as before it
+    pub fn wait_timeout_while(
+        &self,
+        key: K,
+        timeout: impl Into<Deadline>,
+        mut condition: impl FnMut() -> bool,
+    ) -> bool {
+        if !condition() {
+            return true;
+        }
+        self.wait_while_until(key, timeout.into().instant(), condition)
+    }
+
+    fn wait_while_until(&self, key: K, deadline: Option<Instant>, mut condition: impl FnMut() -> bool) -> bool {
+        let waiting = self.enter(key);
+        loop {
+            // checked in the queue, so a wake after it isn't lost
+            let mut done = false;
+            waiting.queue.wait_until(deadline, || {
+                done = !condition();
+                !done
+            });
+            if done || !condition() {
+                return true;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
+        }
+    }
+
+    // Counts the thread in as a waiter on the key, making its queue if it's the first. The key's cloned into the map,
+    // and the waiter keeps its own to take itself back out with
+    fn enter(&self, key: K) -> Waiting<'_, K> {
+        let mut keys = self.keys.lock();
+        let entry = keys.entry(key.clone()).or_insert_with(|| Key { queue: Arc::new(WaitQueue::new()), waiters: 0 });
+        entry.waiters += 1;
+        let queue = entry.queue.clone();
+        Waiting { map: self, key, queue }
+    }
+}
+
+// A thread's place among a key's waiters, given up on the way out however it gets there (a panic in the
+// condition included)
+struct Waiting<'a, K: Eq + Hash> {
+    map: &'a WaitMap<K>,
+    key: K,
+    queue: Arc<WaitQueue>,
+}
+
+impl<K: Eq + Hash> Drop for Waiting<'_, K> {
+    fn drop(&mut self) {
+        let mut keys = self.map.keys.lock();
+        let entry = keys.get_mut(&self.key).expect("a key stays in the map while it has waiters");
+        entry.waiters -= 1;
+        if entry.waiters == 0 {
+            keys.remove(&self.key);
+        }
+    }
+}
+
+impl<K: Eq + Hash> Default for WaitMap<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash> fmt::Debug for WaitMap<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitMap").field("keys", &self.len()).finish()
+    }
+}
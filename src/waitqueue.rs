@@ -0,0 +1,268 @@
+use std::cell::Cell;
+use std::marker::PhantomPinned;
+use std::ptr;
+use std::sync::atomic::AtomicBool;
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+use crate::futex;
+use crate::ordering::{Release, Acquire};
+use crate::spinlock::SpinLock;
+
+// The list of threads parked waiting for something, for the primitives that keep track of their own waiters
+// (the bounded channel, Event, ObjectPool and Semaphore) rather than sleeping on a futex. The Mutex and Condvar
+// have no list of their own to move onto it - their waiters sleep on the futex word, and the kernel keeps track
+// of them.
+//
+// It's an intrusive list: each waiter's node lives on its own stack for as long as it waits, and the list links
+// the nodes together, so waiting never allocates (a Vec<Thread> has to grow, and shrink back with retain). The
+// links are only touched with a small spinlock held, which is never held for long - only for a few pointer
+// swaps, and the unparks in wake_all
+pub struct WaitQueue {
+    links: SpinLock<Links>,
+}
+
+struct Links {
+    head: *const Node,
+    tail: *const Node,
+}
+
+// The pointers are to nodes that only go away once they're unlinked, which takes the lock
+unsafe impl Send for Links {}
+
+struct Node {
+    thread: Thread,
+    // what wake_key picks the node out by. 0 for the waits that don't give one
+    key: u64,
+    prev: Cell<*const Node>,
+    next: Cell<*const Node>,
+    // set by the wake that unlinks the node, as the last thing it does with it. Once the waiter sees it, the
+    // node's its own again and can go
+    notified: AtomicBool,
+    // other threads have pointers to it while it's linked, so it mustn't move
+    _pinned: PhantomPinned,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self { links: SpinLock::new(Links { head: ptr::null(), tail: ptr::null() }) }
+    }
+
+    // Parks the thread until a wake_one or wake_all gets to it, or until the deadline. should_park is called
+    // once the thread is in the queue and before it parks, with the check for whatever it's waiting for - a
+    // wake that comes in between the check and the park then isn't lost, the park just returns straight away.
+    // If it returns false, the thread leaves the queue again without parking.
+    // Returns whether the thread was woken (rather than timing out or not parking at all). Like thread::park
+    // it can return early for no reason, so it's called in a loop that checks again
+    pub fn wait_until(&self, deadline: Option<Instant>, should_park: impl FnOnce() -> bool) -> bool {
+        self.wait_keyed_until(0, deadline, should_park)
+    }
+
+    // Same as wait_until, but with a key that wake_key can pick this waiter out by - for a primitive that knows
+    // exactly which waiter can go next (the Semaphore, whose waiters hold numbered tickets) and would rather wake
+    // just that one than all of them to find out
+    pub fn wait_keyed_until(&self, key: u64, deadline: Option<Instant>, should_park: impl FnOnce() -> bool) -> bool {
+        let node = Node::new(thread::current(), key);
+        self.links.lock().push_back(&node);
+        // takes the node out again however this returns, a panic in should_park included, as the list can't
+        // be left pointing at a node that's gone
+        let _leave = Leave { queue: self, node: &node };
+        if !should_park() {
+            return false;
+        }
+        // Acquire, so whatever the waking thread did before waking is visible from here
+        while !node.notified.load(Acquire) {
+            match deadline {
+                None => futex::park(None),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    futex::park(Some(deadline - now));
+                }
+            }
+        }
+        true
+    }
+
+    pub fn wait(&self, should_park: impl FnOnce() -> bool) -> bool {
+        self.wait_until(None, should_park)
+    }
+
+    // Same as wait_until, but in several queues at once, for waiting on whichever of several things comes first
+    // (the receive::Merge adapter waits on all its channels like this). A wake from any of them gets the thread
+    // out, and it returns the indexes of the queues that woke it - usually one, but more can get in before it's
+    // out of them all. A wake_one that comes to this thread isn't going to another one, so a waiter that uses
+    // what it was woken for from a different queue passes the others' wakes on again.
+    // This one allocates, as it needs a node per queue
+    pub fn wait_any_until(
+        queues: &[&WaitQueue],
+        deadline: Option<Instant>,
+        should_park: impl FnOnce() -> bool,
+    ) -> Vec<usize> {
+        let thread = thread::current();
+        // never pushed to once they're linked, so they don't move
+        let nodes: Vec<Node> = queues.iter().map(|_| Node::new(thread.clone(), 0)).collect();
+        for (queue, node) in queues.iter().zip(&nodes) {
+            queue.links.lock().push_back(node);
+        }
+        let leave: Vec<Leave<'_>> = queues.iter().zip(&nodes).map(|(&queue, node)| Leave { queue, node }).collect();
+        if should_park() {
+            while !nodes.iter().any(|node| node.notified.load(Acquire)) {
+                match deadline {
+                    None => futex::park(None),
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        futex::park(Some(deadline - now));
+                    }
+                }
+            }
+        }
+        // once a node's out of its queue, notified can't change any more
+        drop(leave);
+        nodes.iter().enumerate().filter(|(_, node)| node.notified.load(Acquire)).map(|(i, _)| i).collect()
+    }
+
+    // Wakes the thread that's been waiting longest. Returns false if there wasn't one
+    pub fn wake_one(&self) -> bool {
+        let thread = {
+            let mut links = self.links.lock();
+            let node = links.head;
+            if node.is_null() {
+                return false;
+            }
+            // Safety: a linked node is still on its waiter's stack, as the waiter can't leave wait_until
+            // without unlinking it (which takes the lock) or seeing notified (which is only set below)
+            unsafe {
+                links.remove(node);
+                Node::notify(node)
+            }
+        };
+        thread.unpark();
+        true
+    }
+
+    // Wakes the thread that's been waiting longest with key (there's normally only the one). Returns false if
+    // there wasn't one
+    pub fn wake_key(&self, key: u64) -> bool {
+        let thread = {
+            let mut links = self.links.lock();
+            let mut node = links.head;
+            // Safety: as in wake_one, every linked node is alive
+            while !node.is_null() && unsafe { (*node).key } != key {
+                node = unsafe { (*node).next.get() };
+            }
+            if node.is_null() {
+                return false;
+            }
+            // Safety: as in wake_one
+            unsafe {
+                links.remove(node);
+                Node::notify(node)
+            }
+        };
+        thread.unpark();
+        true
+    }
+
+    // Wakes every waiting thread, and returns how many there were. They're unparked with the lock still held,
+    // as letting go first would mean copying the threads out somewhere - which is the allocation this is here
+    // to avoid
+    pub fn wake_all(&self) -> usize {
+        let mut links = self.links.lock();
+        let mut woken = 0;
+        while !links.head.is_null() {
+            let node = links.head;
+            // Safety: as in wake_one
+            let thread = unsafe {
+                links.remove(node);
+                Node::notify(node)
+            };
+            thread.unpark();
+            woken += 1;
+        }
+        woken
+    }
+
+    // A snapshot, threads come and go
+    pub fn is_empty(&self) -> bool {
+        self.links.lock().head.is_null()
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node {
+    fn new(thread: Thread, key: u64) -> Self {
+        Self {
+            thread,
+            key,
+            prev: Cell::new(ptr::null()),
+            next: Cell::new(ptr::null()),
+            notified: AtomicBool::new(false),
+            _pinned: PhantomPinned,
+        }
+    }
+
+    // Marks a node that's just been unlinked as woken, and hands back its thread to unpark. The node can be gone
+    // as soon as notified is set, so the thread is cloned out first
+    unsafe fn notify(node: *const Node) -> Thread {
+        let thread = (*node).thread.clone();
+        (*node).notified.store(true, Release);
+        thread
+    }
+}
+
+impl Links {
+    fn push_back(&mut self, node: &Node) {
+        node.prev.set(self.tail);
+        node.next.set(ptr::null());
+        if self.tail.is_null() {
+            self.head = node;
+        } else {
+            // Safety: the tail is linked, so it's alive
+            unsafe { (*self.tail).next.set(node) };
+        }
+        self.tail = node;
+    }
+
+    // Safety: node has to be in this list
+    unsafe fn remove(&mut self, node: *const Node) {
+        let (prev, next) = ((*node).prev.get(), (*node).next.get());
+        if prev.is_null() {
+            self.head = next;
+        } else {
+            (*prev).next.set(next);
+        }
+        if next.is_null() {
+            self.tail = prev;
+        } else {
+            (*next).prev.set(prev);
+        }
+    }
+}
+
+// Unlinks a waiter's node on the way out of wait_until, unless a wake already has
+struct Leave<'a> {
+    queue: &'a WaitQueue,
+    node: &'a Node,
+}
+
+impl Drop for Leave<'_> {
+    fn drop(&mut self) {
+        let mut links = self.queue.links.lock();
+        // notified is only set with the lock held, so it can't change under us here
+        if !self.node.notified.load(Acquire) {
+            // Safety: not notified means nothing has unlinked it
+            unsafe { links.remove(self.node) };
+        }
+    }
+}
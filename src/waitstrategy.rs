@@ -0,0 +1,143 @@
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+use crate::futex;
+use crate::relax::cpu_relax;
+
+// What a thread does while it waits for something (a lock to be unlocked, a message to arrive) that it has
+// just checked for and not found. wait is called once per failed check, with attempt counting up from 0,
+// so a strategy can start off spinning and back off to something cheaper the longer the wait goes on.
+// Parking strategies always park with a timeout: not every primitive knows to unpark a waiting thread
+// (a spinlock doesn't keep a list of them), so the thread has to wake up and check again by itself
+pub trait WaitStrategy {
+    fn wait(&self, attempt: u32);
+}
+
+// Lowest latency, but burns a whole core for as long as the wait lasts
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BusySpin;
+
+impl WaitStrategy for BusySpin {
+    fn wait(&self, _attempt: u32) {
+        cpu_relax();
+    }
+}
+
+// What SpinLock::lock and RawSpinLock::lock use: spins like BusySpin while there are cores to spare, and yields
+// straight away when there aren't (see cpu_budget), as then the thread holding the lock may well be waiting for
+// the spinner's core
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Adaptive;
+
+impl WaitStrategy for Adaptive {
+    fn wait(&self, _attempt: u32) {
+        if cpu_budget().spinning_helps() {
+            cpu_relax();
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
+// Spins for a while, then gives the rest of the time slice to other threads each time around
+#[derive(Debug, Clone, Copy)]
+pub struct SpinThenYield {
+    pub spins: u32,
+}
+
+// The default doesn't spin at all when spinning won't help, same for SpinThenPark
+impl Default for SpinThenYield {
+    fn default() -> Self {
+        Self { spins: default_spins() }
+    }
+}
+
+impl WaitStrategy for SpinThenYield {
+    fn wait(&self, attempt: u32) {
+        if attempt < self.spins {
+            cpu_relax();
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
+// Spins for a while, then parks. Cheapest on CPU for long waits, but waking up from a park is slow
+#[derive(Debug, Clone, Copy)]
+pub struct SpinThenPark {
+    pub spins: u32,
+    pub park_timeout: Duration,
+}
+
+impl Default for SpinThenPark {
+    fn default() -> Self {
+        Self { spins: default_spins(), park_timeout: Duration::from_micros(100) }
+    }
+}
+
+impl WaitStrategy for SpinThenPark {
+    fn wait(&self, attempt: u32) {
+        if attempt < self.spins {
+            cpu_relax();
+        } else {
+            futex::park(Some(self.park_timeout));
+        }
+    }
+}
+
+// Parks straight away, for when the wait is known to be long (or the machine only has one core)
+#[derive(Debug, Clone, Copy)]
+pub struct ParkImmediately {
+    pub park_timeout: Duration,
+}
+
+impl Default for ParkImmediately {
+    fn default() -> Self {
+        Self { park_timeout: Duration::from_micros(100) }
+    }
+}
+
+impl WaitStrategy for ParkImmediately {
+    fn wait(&self, _attempt: u32) {
+        futex::park(Some(self.park_timeout));
+    }
+}
+
+// Lets a closure be used as a strategy
+impl<F: Fn(u32)> WaitStrategy for F {
+    fn wait(&self, attempt: u32) {
+        self(attempt)
+    }
+}
+
+fn default_spins() -> u32 {
+    if cpu_budget().spinning_helps() { 100 } else { 0 }
+}
+
+// Whether this process has the CPU for a waiting thread to spin. Spinning only pays off when the thread it's
+// waiting for is running on another core at the same time - with one core to run on, the spinner is just burning
+// the time slice the other thread needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuBudget {
+    // available_parallelism is 1. That's the smaller of the cores in the affinity mask (sched_getaffinity) and
+    // the cgroup CPU quota, so a process pinned to one core or a 1 vCPU container counts even on a big host
+    SingleCore,
+    Spare,
+}
+
+impl CpuBudget {
+    pub fn spinning_helps(self) -> bool {
+        self == CpuBudget::Spare
+    }
+}
+
+// The CPU budget of this process. It's only worked out the first time it's asked for, so on every wait after
+// that it's a single load
+pub fn cpu_budget() -> CpuBudget {
+    static BUDGET: OnceLock<CpuBudget> = OnceLock::new();
+    *BUDGET.get_or_init(|| match thread::available_parallelism().map_or(1, |n| n.get()) {
+        1 => CpuBudget::SingleCore,
+        _ => CpuBudget::Spare,
+    })
+}
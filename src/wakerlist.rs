@@ -0,0 +1,162 @@
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomPinned;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicUsize};
+use std::task::Waker;
+
+use crate::ordering::{Relaxed, SeqCst};
+use crate::spinlock::SpinLock;
+
+// WaitQueue for futures: the list of tasks waiting on a primitive's async methods (Semaphore::acquire_async,
+// RwSpinLock::read_async and write_async), where WaitQueue's waiters are parked threads. Same idea too - each
+// future carries its own node, pinned along with it, and the list links the nodes together, so waiting never
+// allocates. Unlike a thread a future can give up at any point by being dropped, so the owner has to call remove
+// from its Drop.
+//
+// A woken node is taken off the list, and the future puts it back on (with whatever its Waker is by then) the
+// next time it's polled and still can't go
+pub(crate) struct WakerList {
+    links: SpinLock<Links>,
+    // How many nodes are linked, so waking an empty list doesn't take the lock - the guard drops wake every time
+    waiting: AtomicUsize,
+}
+
+struct Links {
+    head: *const WakerNode,
+    tail: *const WakerNode,
+}
+
+// The pointers are to nodes that only go away once they're unlinked, which takes the lock
+unsafe impl Send for Links {}
+
+pub(crate) struct WakerNode {
+    // Everything in here is only touched with the list's lock held
+    waker: UnsafeCell<Option<Waker>>,
+    prev: Cell<*const WakerNode>,
+    next: Cell<*const WakerNode>,
+    linked: Cell<bool>,
+    // the list has pointers to it while it's linked, so it mustn't move
+    _pinned: PhantomPinned,
+}
+
+// Only the list touches it, with its lock held. The future holding it can go to another thread
+unsafe impl Send for WakerNode {}
+unsafe impl Sync for WakerNode {}
+
+impl WakerNode {
+    pub(crate) const fn new() -> Self {
+        Self {
+            waker: UnsafeCell::new(None),
+            prev: Cell::new(ptr::null()),
+            next: Cell::new(ptr::null()),
+            linked: Cell::new(false),
+            _pinned: PhantomPinned,
+        }
+    }
+}
+
+impl WakerList {
+    pub(crate) const fn new() -> Self {
+        Self { links: SpinLock::new(Links { head: ptr::null(), tail: ptr::null() }), waiting: AtomicUsize::new(0) }
+    }
+
+    // Puts the node on the list to be woken with waker, or just swaps its Waker if it's already on. Called from
+    // poll before checking whether it can go, so a wake in between isn't lost - it just polls again.
+    // Safety: the node can only ever be used with this list
+    pub(crate) unsafe fn register(&self, node: Pin<&WakerNode>, waker: &Waker) {
+        let node = node.get_ref();
+        let mut links = self.links.lock();
+        let slot = &mut *node.waker.get();
+        // no clone when the task's Waker hasn't changed since last time, which it usually hasn't. One that has is
+        // dropped once the lock's let go, same as in remove
+        let old = match slot {
+            Some(old) if old.will_wake(waker) => None,
+            _ => slot.replace(waker.clone()),
+        };
+        if !node.linked.get() {
+            links.push_back(node);
+            self.waiting.fetch_add(1, Relaxed);
+        }
+        drop(links);
+        drop(old);
+        // pairs with the fence in wake_all: either the waker sees this node on the list, or the check the caller
+        // does next sees what the waker changed
+        fence(SeqCst);
+    }
+
+    // Takes the node off the list, if it's on it. Called once the future's done, and from its Drop
+    // Safety: the node can only ever be used with this list
+    pub(crate) unsafe fn remove(&self, node: Pin<&WakerNode>) {
+        let node = node.get_ref();
+        let mut links = self.links.lock();
+        if node.linked.get() {
+            links.remove(node);
+            self.waiting.fetch_sub(1, Relaxed);
+        }
+        // dropped once the lock's let go, for the same reason wake_all wakes without it
+        let waker = (*node.waker.get()).take();
+        drop(links);
+        drop(waker);
+    }
+
+    // Wakes every task that's on the list right now. Each one's unlinked and woken one at a time with the lock
+    // let go in between, as waking (or dropping) a Waker can run just about anything - dropping the last one to
+    // a task can drop its future, whose Drop takes the lock. Tasks that get back on the list meanwhile are left
+    // for the next wake
+    pub(crate) fn wake_all(&self) {
+        fence(SeqCst);
+        let mut n = self.waiting.load(Relaxed);
+        while n > 0 {
+            let waker = {
+                let mut links = self.links.lock();
+                let node = links.head;
+                if node.is_null() {
+                    return;
+                }
+                self.waiting.fetch_sub(1, Relaxed);
+                // Safety: a linked node is alive, its future can't be dropped without taking it off (which
+                // takes the lock)
+                unsafe {
+                    links.remove(node);
+                    (*(*node).waker.get()).take()
+                }
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            n -= 1;
+        }
+    }
+}
+
+impl Links {
+    fn push_back(&mut self, node: &WakerNode) {
+        node.prev.set(self.tail);
+        node.next.set(ptr::null());
+        node.linked.set(true);
+        if self.tail.is_null() {
+            self.head = node;
+        } else {
+            // Safety: the tail is linked, so it's alive
+            unsafe { (*self.tail).next.set(node) };
+        }
+        self.tail = node;
+    }
+
+    // Safety: node has to be in this list
+    unsafe fn remove(&mut self, node: *const WakerNode) {
+        let (prev, next) = ((*node).prev.get(), (*node).next.get());
+        if prev.is_null() {
+            self.head = next;
+        } else {
+            (*prev).next.set(next);
+        }
+        if next.is_null() {
+            self.tail = prev;
+        } else {
+            (*next).prev.set(prev);
+        }
+        (*node).linked.set(false);
+    }
+}
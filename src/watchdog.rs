@@ -0,0 +1,112 @@
+use std::backtrace::Backtrace;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ordering::Relaxed;
+
+// A starvation watchdog for SpinLock, for tracking down a thread that never gets the lock or a guard that was
+// forgotten (or leaked) somewhere. While it's on, every lock records the backtrace of where it was taken,
+// and a thread that spins for longer than the threshold reports who's holding the lock and where they took it.
+// Capturing a backtrace on every lock is very slow, so it's for debugging only - it's off until enable is called
+
+// 0 means off
+static THRESHOLD_NANOS: AtomicU64 = AtomicU64::new(0);
+static PANIC: AtomicBool = AtomicBool::new(false);
+
+// What a thread does when it's been spinning too long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnStarve {
+    // print the report to stderr once and keep waiting
+    Log,
+    // panic with the report, failing the test (or thread) that's stuck
+    Panic,
+}
+
+// Turns the watchdog on for every SpinLock. Locks taken before this don't have a backtrace to report
+pub fn enable(threshold: Duration, on_starve: OnStarve) {
+    PANIC.store(on_starve == OnStarve::Panic, Relaxed);
+    THRESHOLD_NANOS.store((threshold.as_nanos() as u64).max(1), Relaxed);
+}
+
+pub fn disable() {
+    THRESHOLD_NANOS.store(0, Relaxed);
+}
+
+fn threshold() -> Option<Duration> {
+    match THRESHOLD_NANOS.load(Relaxed) {
+        0 => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+struct Holder {
+    thread: String,
+    backtrace: Backtrace,
+}
+
+// Where a lock's current holder took it. It has its own (std) mutex as the waiters read it while the holder
+// could be clearing it
+pub(crate) struct HolderSlot(Mutex<Option<Holder>>);
+
+impl HolderSlot {
+    pub(crate) const fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    // Called by the thread that just took the lock
+    pub(crate) fn record(&self) {
+        if threshold().is_none() {
+            return;
+        }
+        let current = thread::current();
+        let thread = current.name().map_or_else(|| format!("{:?}", current.id()), str::to_owned);
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(Holder { thread, backtrace: Backtrace::force_capture() });
+    }
+
+    // Called by the holder just before it unlocks
+    pub(crate) fn clear(&self) {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).take();
+    }
+
+    fn report(&self, waited: Duration) -> String {
+        match &*self.0.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(holder) => format!(
+                "spun for {waited:?} waiting for a SpinLock held by thread {}, which locked it at:\n{}",
+                holder.thread, holder.backtrace
+            ),
+            None => format!("spun for {waited:?} waiting for a SpinLock, taken before the watchdog was enabled"),
+        }
+    }
+}
+
+// Kept by a thread while it spins for a lock
+pub(crate) struct Spinning {
+    // None if the watchdog was off when the thread started waiting
+    start: Option<Instant>,
+    reported: bool,
+}
+
+impl Spinning {
+    pub(crate) fn start() -> Self {
+        Self { start: threshold().map(|_| Instant::now()), reported: false }
+    }
+
+    // Called on every spin. Only reports once per wait, so the log isn't flooded
+    pub(crate) fn check(&mut self, holder: &HolderSlot) {
+        let (Some(start), Some(threshold)) = (self.start, threshold()) else {
+            return;
+        };
+        let waited = start.elapsed();
+        if self.reported || waited < threshold {
+            return;
+        }
+        self.reported = true;
+        let report = holder.report(waited);
+        if PANIC.load(Relaxed) {
+            panic!("{report}");
+        }
+        eprintln!("{report}");
+    }
+}
@@ -0,0 +1,85 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::arc::{Arc, Weak};
+use crate::mutex::Mutex;
+
+// Below this many entries it's not worth walking the map to throw out dead ones
+const MIN_PRUNE_AT: usize = 16;
+
+// A cache of shared values that doesn't keep them alive: it only holds Weaks, so a value is dropped as soon as
+// the last Arc anyone got from the registry goes, and the next get_or_create for its key makes a new one.
+// Handy for interning, or for sharing one open handle per path between everyone who currently wants it
+pub struct WeakRegistry<K, T> {
+    state: Mutex<State<K, T>>,
+}
+
+struct State<K, T> {
+    entries: HashMap<K, Weak<T>>,
+    // The dead Weaks are thrown out whenever the map grows to this size. It's set to twice what was left after
+    // the last prune, so pruning costs O(1) per insert on average however many of the entries are dead
+    prune_at: usize,
+}
+
+impl<K: Eq + Hash, T> WeakRegistry<K, T> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State { entries: HashMap::new(), prune_at: MIN_PRUNE_AT }),
+        }
+    }
+
+    // Returns the live value for key if there is one, otherwise makes one with create and registers it.
+    // create runs with the registry locked, so two threads asking for the same new key can't both make a value -
+    // but it also mustn't use the registry itself, or it deadlocks
+    pub fn get_or_create(&self, key: K, create: impl FnOnce() -> T) -> Arc<T> {
+        let mut state = self.state.lock();
+        if let Some(value) = state.entries.get(&key).and_then(Weak::upgrade) {
+            return value;
+        }
+        let value = Arc::new(create());
+        // this replaces (and drops) a dead Weak if there was one for the key
+        state.entries.insert(key, Arc::downgrade(&value));
+        if state.entries.len() >= state.prune_at {
+            state.prune();
+        }
+        value
+    }
+
+    // The live value for key, without making one if there isn't
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<T>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.state.lock().entries.get(key).and_then(Weak::upgrade)
+    }
+
+    // Throws out the entries whose values have been dropped. get_or_create does this by itself now and then, so
+    // this is only needed to free the memory sooner
+    pub fn prune(&self) {
+        self.state.lock().prune();
+    }
+
+    // How many entries there are, including dead ones that haven't been pruned yet
+    pub fn len(&self) -> usize {
+        self.state.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, T> State<K, T> {
+    fn prune(&mut self) {
+        self.entries.retain(|_, weak| weak.strong_count() > 0);
+        self.prune_at = (self.entries.len() * 2).max(MIN_PRUNE_AT);
+    }
+}
+
+impl<K: Eq + Hash, T> Default for WeakRegistry<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
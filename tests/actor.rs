@@ -0,0 +1,80 @@
+use std::thread;
+
+use rust_atomic_locks::actor::{spawn_actor, spawn_actor_with_capacity};
+use rust_atomic_locks::boundedchannel::TrySendError;
+use rust_atomic_locks::rendezvous::{self, Caller};
+
+const ITERS: u64 = if cfg!(miri) { 20 } else { 10_000 };
+
+// What the actor below understands: adding to its total, or replying with it
+enum Message {
+    Add(u64),
+    Total(rendezvous::Callee<(), u64>),
+}
+
+#[test]
+fn messages_from_many_threads() {
+    let actor = spawn_actor(0u64, |total, message| match message {
+        Message::Add(n) => *total += n,
+        Message::Total(callee) => {
+            if let Ok(((), responder)) = callee.request() {
+                let _ = responder.respond(*total);
+            }
+        }
+    });
+    thread::scope(|s| {
+        for _ in 0..4 {
+            let actor = actor.clone();
+            s.spawn(move || {
+                for _ in 0..ITERS {
+                    actor.send(Message::Add(1)).ok().unwrap();
+                }
+            });
+        }
+    });
+    // the mailbox is handled in order, so the total includes everything sent above
+    let (caller, callee): (Caller<(), u64>, _) = rendezvous::call();
+    actor.send(Message::Total(callee)).ok().unwrap();
+    assert_eq!(caller.call(()), Ok(4 * ITERS));
+    actor.join().unwrap();
+}
+
+#[test]
+fn stop_handles_what_was_sent_first() {
+    let (seen, handled) = std::sync::mpsc::channel();
+    let actor = spawn_actor(seen, |seen, n: i32| seen.send(n).unwrap());
+    for n in 0..5 {
+        actor.send(n).unwrap();
+    }
+    let other = actor.clone();
+    actor.stop();
+    assert!(other.is_stopped());
+    // after the stop it doesn't take anything new
+    assert_eq!(other.send(5).map_err(|e| e.0), Err(5));
+    assert!(matches!(other.try_send(6), Err(TrySendError::Disconnected(6))));
+    other.join().unwrap();
+    assert_eq!(handled.iter().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+    actor.join().unwrap();
+}
+
+#[test]
+fn dropping_every_addr_stops_it() {
+    let (done, finished) = std::sync::mpsc::channel::<()>();
+    // the state goes with the actor's thread, and the channel with it
+    let actor = spawn_actor(done, |_, ()| {});
+    actor.send(()).unwrap();
+    drop(actor);
+    assert!(finished.recv().is_err());
+}
+
+#[test]
+fn panicking_handler() {
+    let actor = spawn_actor_with_capacity(1, (), |_, fail: bool| assert!(!fail, "handler failed"));
+    actor.send(false).unwrap();
+    actor.send(true).unwrap();
+    // once the actor has died with the panic, sends fail (after at most a message queued in the meantime)
+    while actor.send(false).is_ok() {
+        thread::yield_now();
+    }
+    assert!(actor.join().is_err());
+}
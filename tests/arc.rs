@@ -0,0 +1,270 @@
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use rust_atomic_locks::allocator::{AllocError, Allocator, Global};
+use rust_atomic_locks::arc::{Arc, Weak};
+
+#[test]
+fn test() {
+    static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct DetectDrop;
+
+    impl Drop for DetectDrop {
+        fn drop(&mut self) {
+            NUM_DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    let x = Arc::new(("hello", DetectDrop));
+    let y = Arc::downgrade(&x);
+    let z = Arc::downgrade(&x);
+
+    let t = std::thread::spawn(move || {
+        let y = y.upgrade().unwrap();
+        assert_eq!(y.0, "hello");
+    });
+
+    assert_eq!(x.0, "hello");
+
+    // Wait for thread to finish
+    t.join().unwrap();
+
+    assert_eq!(NUM_DROPS.load(Relaxed), 0);
+    assert!(z.upgrade().is_some());
+
+    drop(x);
+
+    assert_eq!(NUM_DROPS.load(Relaxed), 1);
+    assert!(z.upgrade().is_none());
+}
+
+#[test]
+fn weak_only_state() {
+    let x = Arc::new(String::from("hello"));
+    let y = Arc::downgrade(&x);
+    let z = y.clone();
+    assert_eq!(y.strong_count(), 1);
+    assert_eq!(y.weak_count(), 2);
+
+    // The data is dropped with the last Arc, but the Weaks keep the allocation (and its counts) alive
+    drop(x);
+    assert_eq!(y.strong_count(), 0);
+    assert_eq!(y.weak_count(), 2);
+    assert!(y.upgrade().is_none());
+
+    drop(z);
+    assert_eq!(y.weak_count(), 1);
+    assert!(y.upgrade().is_none());
+}
+
+#[test]
+fn upgrade_races_final_drop() {
+    static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct DetectDrop;
+
+    impl Drop for DetectDrop {
+        fn drop(&mut self) {
+            NUM_DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    // Miri is far too slow for thousands of threads, a handful still explores the race
+    let (rounds, threads) = if cfg!(miri) { (2, 4) } else { (50, 40) };
+
+    for round in 0..rounds {
+        let x = Arc::new(DetectDrop);
+        let weaks: Vec<Weak<DetectDrop>> = (0..threads).map(|_| Arc::downgrade(&x)).collect();
+        std::thread::scope(|s| {
+            for weak in weaks {
+                s.spawn(move || {
+                    // An upgrade either wins (and keeps the data alive until it's dropped)
+                    // or sees the data is already gone - it must never see dropped data
+                    if let Some(arc) = weak.upgrade() {
+                        assert!(weak.strong_count() >= 1);
+                        drop(arc);
+                    }
+                });
+            }
+            drop(x);
+        });
+        // Whoever dropped the last Arc dropped the data, exactly once
+        assert_eq!(NUM_DROPS.load(Relaxed), round + 1);
+    }
+}
+
+// The same race as above, but with the scheduler picking who goes first instead of hoping for it
+#[test]
+#[cfg(debug_assertions)]
+fn upgrade_vs_final_drop_scheduled() {
+    use rust_atomic_locks::sched::{Scheduler, Until};
+
+    // the upgrade has loaded a count of 1 when the last Arc goes, so its compare exchange fails and it
+    // finds the count is now 0
+    let x = Arc::new(1);
+    let weak = Arc::downgrade(&x);
+    let scheduler = Scheduler::new([(0, Until::Point("Weak::upgrade loaded")), (1, Until::Done), (0, Until::Done)]);
+    std::thread::scope(|s| {
+        let upgraded = s.spawn(scheduler.thread(0, || weak.upgrade().is_some()));
+        s.spawn(scheduler.thread(1, move || drop(x)));
+        assert!(!upgraded.join().unwrap());
+    });
+    assert_eq!(weak.strong_count(), 0);
+
+    // and the other way round: the upgrade gets in just before the last Arc is dropped, and keeps the data alive
+    let x = Arc::new(2);
+    let weak = Arc::downgrade(&x);
+    let scheduler = Scheduler::new([(1, Until::Point("Arc::drop")), (0, Until::Done), (1, Until::Done)]);
+    std::thread::scope(|s| {
+        let upgraded = s.spawn(scheduler.thread(0, || weak.upgrade()));
+        s.spawn(scheduler.thread(1, move || drop(x)));
+        assert_eq!(upgraded.join().unwrap().as_deref(), Some(&2));
+    });
+    assert_eq!(weak.strong_count(), 0);
+}
+
+#[test]
+fn get_mut_while_contended() {
+    let rounds = if cfg!(miri) { 10 } else { 1000 };
+    let mut x = Arc::new(0);
+    let checked = std::sync::Barrier::new(3);
+    std::thread::scope(|s| {
+        for _ in 0..2 {
+            let other = x.clone();
+            let checked = &checked;
+            s.spawn(move || {
+                // clones and weaks coming and going while the main thread tries to get at the data
+                for _ in 0..rounds {
+                    let weak = Arc::downgrade(&other);
+                    drop(weak.upgrade());
+                }
+                // hold on to the clone until the main thread's done checking
+                checked.wait();
+            });
+        }
+        // the threads each hold a clone the whole time, so there's no getting a &mut
+        for _ in 0..rounds {
+            assert!(Arc::get_mut(&mut x).is_none());
+        }
+        checked.wait();
+    });
+    // and with them gone it's unique again
+    *Arc::get_mut(&mut x).unwrap() += 1;
+    assert_eq!(*x, 1);
+
+    // a Weak on its own is enough to stop it too, as it could be upgraded at any time
+    let weak = Arc::downgrade(&x);
+    assert!(Arc::get_mut(&mut x).is_none());
+    drop(weak);
+    assert!(Arc::get_mut(&mut x).is_some());
+}
+
+#[test]
+fn new_in_uses_the_allocator() {
+    // Keeps count of how many allocations it currently has out
+    struct Counting<'a>(&'a AtomicUsize);
+
+    unsafe impl Allocator for Counting<'_> {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.fetch_add(1, Relaxed);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.fetch_sub(1, Relaxed);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    let live = AtomicUsize::new(0);
+    let x = Arc::new_in(String::from("hello"), Counting(&live));
+    let y = Arc::downgrade(&x);
+    assert_eq!(live.load(Relaxed), 1);
+    drop(x);
+    // the Weak still holds the allocation
+    assert_eq!(live.load(Relaxed), 1);
+    drop(y);
+    assert_eq!(live.load(Relaxed), 0);
+}
+
+#[test]
+fn cyclic_and_pinned() {
+    // A node that knows the Arc it lives in
+    struct Node {
+        me: Weak<Node>,
+        value: i32,
+    }
+
+    let node = Arc::new_cyclic(|me| {
+        // the data doesn't exist yet, so there's nothing to upgrade to
+        assert!(me.upgrade().is_none());
+        Node { me: me.clone(), value: 5 }
+    });
+    let again = node.me.upgrade().unwrap();
+    assert_eq!(again.value, 5);
+    assert_eq!(node.me.strong_count(), 2);
+
+    let pinned = Arc::pin(String::from("pinned"));
+    assert_eq!(&**pinned, "pinned");
+}
+
+#[test]
+fn formatting_and_conversions() {
+    let arc: Arc<String> = Arc::from(String::from("hello"));
+    // Debug and Display are T's own, not wrapped
+    assert_eq!(format!("{arc:?}"), "\"hello\"");
+    assert_eq!(arc.to_string(), "hello");
+    assert_eq!(format!("{:?}", Arc::downgrade(&arc)), "(Weak)");
+    assert_eq!(*Arc::<Vec<u8>>::default(), []);
+}
+
+#[test]
+fn as_keys() {
+    use std::collections::{BTreeSet, HashMap};
+
+    // looked up by &str through Borrow, the same as with std's Arc
+    let mut counts: HashMap<Arc<String>, usize> = HashMap::new();
+    counts.insert(Arc::new(String::from("a")), 1);
+    assert_eq!(counts.get(&String::from("a")), Some(&1));
+    // equal values are equal Arcs, even in separate allocations
+    assert_eq!(Arc::new(1), Arc::new(1));
+
+    let sorted: BTreeSet<_> = [3, 1, 2].into_iter().map(Arc::new).collect();
+    assert_eq!(sorted.iter().map(|n| **n).collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(Arc::new(1) < Arc::new(2));
+    let arc = Arc::new(String::from("b"));
+    let s: &String = arc.as_ref();
+    assert_eq!(s, "b");
+}
+
+#[test]
+fn raw_pointers() {
+    // the sort of callback a C library calls with the user data it was registered with
+    extern "C" fn callback(user_data: *const std::ffi::c_void) -> usize {
+        // Safety: it's the pointer into_raw gave the library, which still counts as an Arc
+        let arc = std::mem::ManuallyDrop::new(unsafe { Arc::<Vec<usize>>::from_raw(user_data.cast()) });
+        arc.len()
+    }
+
+    let arc = Arc::new(vec![1, 2, 3]);
+    let weak = Arc::downgrade(&arc);
+    assert_eq!(Arc::as_ptr(&arc), &*arc as *const _);
+    let raw = Arc::into_raw(arc);
+    assert_eq!(callback(raw.cast()), 3);
+    assert_eq!(weak.strong_count(), 1);
+    unsafe {
+        Arc::<Vec<usize>>::increment_strong_count(raw);
+        assert_eq!(weak.strong_count(), 2);
+        Arc::<Vec<usize>>::decrement_strong_count(raw);
+        let arc = Arc::<Vec<usize>>::from_raw(raw);
+        assert_eq!(*arc, [1, 2, 3]);
+    }
+    assert_eq!(weak.strong_count(), 0);
+
+    // the allocator comes back along with the rest
+    let arc = Arc::new_in(5u8, Global);
+    let arc = unsafe { Arc::<u8, Global>::from_raw(Arc::into_raw(arc)) };
+    assert_eq!(*arc, 5);
+}
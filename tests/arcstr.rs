@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::thread;
+
+use rust_atomic_locks::arcstr::ArcStr;
+
+#[test]
+fn shared_between_threads() {
+    let s = ArcStr::from("hello, world");
+    assert_eq!(size_of::<ArcStr>(), size_of::<usize>());
+    assert_eq!(s.as_str(), "hello, world");
+    assert_eq!(s.len(), 12);
+    assert_eq!(ArcStr::strong_count(&s), 1);
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            let s = s.clone();
+            scope.spawn(move || {
+                for _ in 0..if cfg!(miri) { 10 } else { 1000 } {
+                    let t = s.clone();
+                    assert!(t.starts_with("hello"));
+                }
+            });
+        }
+    });
+    assert_eq!(ArcStr::strong_count(&s), 1);
+
+    let t = s.clone();
+    assert!(ArcStr::ptr_eq(&s, &t));
+    let u = ArcStr::from(String::from("hello, world"));
+    assert!(!ArcStr::ptr_eq(&s, &u));
+    assert_eq!(s, u);
+    assert_eq!(s, "hello, world");
+    let shorter = ArcStr::from("hello");
+    assert!(shorter < u);
+
+    // looked up with a &str
+    let mut map = HashMap::new();
+    map.insert(s, 1);
+    assert_eq!(map.get("hello, world"), Some(&1));
+
+    let empty = ArcStr::default();
+    assert!(empty.is_empty());
+    assert_eq!(format!("{empty:?} {t}"), "\"\" hello, world");
+}
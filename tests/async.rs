@@ -0,0 +1,115 @@
+#![cfg(feature = "async")]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use rust_atomic_locks::executor::{block_on, Executor};
+use rust_atomic_locks::rwspinlock::RwSpinLock;
+use rust_atomic_locks::semaphore::Semaphore;
+
+fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(Waker::noop()))
+}
+
+#[test]
+fn semaphore_tasks_and_threads_share_the_line() {
+    let n = if cfg!(miri) { 10 } else { 500 };
+    let semaphore = Semaphore::new(2);
+    let (inside, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let work = || {
+        let now = inside.fetch_add(1, Relaxed) + 1;
+        most.fetch_max(now, Relaxed);
+        thread::yield_now();
+        inside.fetch_sub(1, Relaxed);
+    };
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..n {
+                    let _permit = block_on(semaphore.acquire_async());
+                    work();
+                }
+            });
+        }
+        s.spawn(|| {
+            for _ in 0..n {
+                let _permit = semaphore.acquire();
+                work();
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..n {
+                let _permits = block_on(semaphore.acquire_many_async(2));
+                work();
+            }
+        });
+    });
+    assert!(most.load(Relaxed) <= 2);
+    assert_eq!(semaphore.available_permits(), 2);
+}
+
+#[test]
+fn dropped_acquire_gives_up_its_place() {
+    static SEMAPHORE: Semaphore = Semaphore::new(0);
+    let mut first = Box::pin(SEMAPHORE.acquire_async());
+    let mut second = Box::pin(SEMAPHORE.acquire_async());
+    assert!(poll_once(first.as_mut()).is_pending());
+    assert!(poll_once(second.as_mut()).is_pending());
+    // the second is behind the first, until the first gives up
+    drop(first);
+    SEMAPHORE.add_permits(1);
+    assert!(poll_once(second.as_mut()).is_ready());
+
+    let mut executor = Executor::new();
+    executor.spawn(async {
+        drop(SEMAPHORE.acquire_async().await);
+    });
+    executor.spawn(async {
+        SEMAPHORE.add_permits(1);
+    });
+    executor.run();
+}
+
+#[test]
+fn rwspinlock_tasks_wait_for_the_guards() {
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    let lock = RwSpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..n {
+                    *block_on(lock.write_async()) += 1;
+                }
+            });
+        }
+        s.spawn(|| {
+            for _ in 0..n {
+                let value = *block_on(lock.read_async());
+                assert!(value <= 3 * n);
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..n {
+                *lock.write() += 1;
+            }
+        });
+    });
+    assert_eq!(lock.into_inner(), 3 * n);
+}
+
+#[test]
+fn dropped_write_lets_readers_back_in() {
+    let lock = RwSpinLock::new(());
+    let read = lock.read();
+    let mut write = Box::pin(lock.write_async());
+    assert!(poll_once(write.as_mut()).is_pending());
+    // a waiting writer keeps new readers out...
+    assert!(lock.try_read().is_none());
+    drop(write);
+    // ...but not once it's gone
+    assert!(lock.try_read().is_some());
+    drop(read);
+}
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::atomicbitset::AtomicBitSet;
+
+#[test]
+fn atomic_bitset() {
+    let slots = AtomicBitSet::new(100);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                // every thread claims 25 slots, and no two threads get the same one
+                for _ in 0..25 {
+                    slots.find_and_set_first_zero().unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(slots.count_ones(), 100);
+    assert_eq!(slots.find_and_set_first_zero(), None);
+
+    assert!(slots.clear(70));
+    assert!(!slots.test(70));
+    assert_eq!(slots.find_and_set_first_zero(), Some(70));
+    assert!(slots.set(70));
+    slots.clear(3);
+    slots.clear(99);
+    assert_eq!(slots.iter().filter(|i| [2, 3, 4, 98, 99].contains(i)).collect::<Vec<_>>(), [2, 4, 98]);
+}
+
+#[test]
+fn claimed_slots_are_unique() {
+    // the threads hand slots back and claim them again, and never hold one someone else also holds
+    let rounds = if cfg!(miri) { 5 } else { 500 };
+    let slots = AtomicBitSet::new(8);
+    let owners: Vec<_> = (0..8).map(|_| AtomicBool::new(false)).collect();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..rounds {
+                    let Some(slot) = slots.find_and_set_first_zero() else { continue };
+                    assert!(!owners[slot].swap(true, Relaxed));
+                    owners[slot].store(false, Relaxed);
+                    assert!(slots.clear(slot));
+                }
+            });
+        }
+    });
+    assert_eq!(slots.count_ones(), 0);
+}
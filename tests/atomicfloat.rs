@@ -0,0 +1,39 @@
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+
+use rust_atomic_locks::atomicfloat::{AtomicF32, AtomicF64};
+
+const ITERS: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+#[test]
+fn concurrent_adds() {
+    // halves add up exactly, so nothing's lost to rounding and any lost update shows
+    let total = AtomicF64::new(0.0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    total.fetch_add(0.5, Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(total.into_inner(), 2.0 * ITERS as f64);
+}
+
+#[test]
+fn like_the_integers() {
+    let x = AtomicF32::from(1.5);
+    assert_eq!(x.fetch_sub(0.5, Relaxed), 1.5);
+    assert_eq!(x.fetch_max(3.0, Relaxed), 1.0);
+    assert_eq!(x.fetch_min(f32::NAN, Relaxed), 3.0);
+    assert_eq!(x.swap(-0.0, Relaxed), 3.0);
+    // bits are compared, so 0.0 doesn't match -0.0
+    assert_eq!(x.compare_exchange(0.0, 1.0, Relaxed, Relaxed), Err(-0.0));
+    assert_eq!(x.compare_exchange(-0.0, 1.0, Relaxed, Relaxed), Ok(-0.0));
+    assert_eq!(x.fetch_update(Relaxed, Relaxed, |v| (v < 1.0).then_some(0.0)), Err(1.0));
+    assert_eq!(format!("{x:?}"), "1.0");
+    let nan = AtomicF64::new(f64::NAN);
+    assert!(nan.compare_exchange(f64::NAN, 0.0, Relaxed, Relaxed).is_ok());
+    assert_eq!(AtomicF64::default().load(Relaxed), 0.0);
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::atomicoption::AtomicOption;
+
+const ROUNDS: usize = if cfg!(miri) { 5 } else { 1000 };
+
+#[test]
+fn take_swap_and_insert() {
+    let slot = AtomicOption::none();
+    assert!(slot.is_none());
+    assert_eq!(slot.take(), None);
+    assert_eq!(slot.try_insert(Box::new(1)), Ok(()));
+    // only goes in while it's empty
+    assert_eq!(slot.try_insert(Box::new(2)), Err(Box::new(2)));
+    assert_eq!(slot.swap(Some(Box::new(3))), Some(Box::new(1)));
+    assert_eq!(slot.take(), Some(Box::new(3)));
+    assert!(slot.is_none());
+
+    let mut slot = AtomicOption::from(Box::new(String::from("a")));
+    slot.get_mut().unwrap().push('b');
+    assert_eq!(slot.into_inner().as_deref().map(String::as_str), Some("ab"));
+}
+
+#[test]
+fn handed_over_exactly_once() {
+    for _ in 0..ROUNDS {
+        let slot = AtomicOption::none();
+        let taken = AtomicUsize::new(0);
+        thread::scope(|s| {
+            s.spawn(|| slot.try_insert(Box::new(vec![1, 2, 3])).unwrap());
+            for _ in 0..3 {
+                s.spawn(|| {
+                    // keep trying until it's been put in and taken, by us or someone else
+                    while taken.load(Relaxed) == 0 {
+                        if let Some(v) = slot.take() {
+                            assert_eq!(*v, [1, 2, 3]);
+                            taken.fetch_add(1, Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(taken.load(Relaxed), 1);
+    }
+}
+
+#[test]
+fn first_insert_wins() {
+    let slot = AtomicOption::none();
+    let losers = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for i in 0..4 {
+            let (slot, losers) = (&slot, &losers);
+            s.spawn(move || {
+                if let Err(mine) = slot.try_insert(Box::new(i)) {
+                    assert_eq!(*mine, i);
+                    losers.fetch_add(1, Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(losers.load(Relaxed), 3);
+    assert!(slot.take().is_some());
+}
+
+#[test]
+fn drops_what_it_holds() {
+    static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct DetectDrop;
+
+    impl Drop for DetectDrop {
+        fn drop(&mut self) {
+            NUM_DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    let slot = AtomicOption::from(Box::new(DetectDrop));
+    drop(slot.swap(Some(Box::new(DetectDrop))));
+    assert_eq!(NUM_DROPS.load(Relaxed), 1);
+    drop(slot);
+    assert_eq!(NUM_DROPS.load(Relaxed), 2);
+    drop(AtomicOption::<DetectDrop>::none().into_inner());
+    assert_eq!(NUM_DROPS.load(Relaxed), 2);
+}
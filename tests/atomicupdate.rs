@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicI8, AtomicU64, AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::atomicfloat::AtomicF64;
+use rust_atomic_locks::atomicupdate::{atomic_update, try_atomic_update, AtomicUpdate, Backoff};
+
+#[test]
+fn updates() {
+    let n = AtomicUsize::new(5);
+    assert_eq!(atomic_update(&n, |n| n * 2), 5);
+    assert_eq!(n.load(Relaxed), 10);
+
+    // Err hands back the value f turned down, and leaves it alone
+    let tokens = AtomicI8::new(1);
+    assert_eq!(try_atomic_update(&tokens, |n| (n > 0).then(|| n - 1)), Ok(1));
+    assert_eq!(tokens.try_cas_update(Relaxed, Relaxed, |n| (n > 0).then(|| n - 1)), Err(0));
+
+    // a NaN matches itself, so the loop ends
+    let x = AtomicF64::new(f64::NAN);
+    assert!(x.cas_update(Relaxed, Relaxed, |_| 1.5).is_nan());
+    assert_eq!(x.load(Relaxed), 1.5);
+
+    let mut backoff = Backoff::new();
+    while !backoff.is_yielding() {
+        backoff.spin();
+    }
+    backoff.reset();
+    assert!(!backoff.is_yielding());
+}
+
+#[test]
+fn contended() {
+    let n = if cfg!(miri) { 50 } else { 10_000 };
+    let count = AtomicU64::new(0);
+    let high_water = AtomicU64::new(0);
+    thread::scope(|s| {
+        for t in 0..4 {
+            let (count, high_water) = (&count, &high_water);
+            s.spawn(move || {
+                for i in 0..n {
+                    count.cas_update(Relaxed, Relaxed, |c| c + 1);
+                    high_water.cas_update(Relaxed, Relaxed, |h| h.max(t * n + i));
+                }
+            });
+        }
+    });
+    assert_eq!(count.into_inner(), 4 * n);
+    assert_eq!(high_water.into_inner(), 4 * n - 1);
+}
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::thread;
+
+use rust_atomic_locks::atomicwaker::AtomicWaker;
+
+struct CountWakes(AtomicUsize);
+
+impl Wake for CountWakes {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Relaxed);
+    }
+}
+
+#[test]
+fn wakes_once_per_register() {
+    let count = Arc::new(CountWakes(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let slot = AtomicWaker::new();
+
+    // nothing registered, so nothing happens
+    slot.wake();
+    slot.register(&waker);
+    slot.wake();
+    slot.wake();
+    assert_eq!(count.0.load(Relaxed), 1);
+    assert!(slot.take().is_none());
+}
+
+#[test]
+fn wake_racing_register() {
+    // registers and wakes overlapping every which way, which Miri checks for races on the slot
+    let rounds = if cfg!(miri) { 20 } else { 10_000 };
+    let count = Arc::new(CountWakes(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let slot = AtomicWaker::new();
+    let woken = AtomicUsize::new(0);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..rounds {
+                slot.wake();
+                woken.fetch_add(1, Relaxed);
+            }
+        });
+        for _ in 0..rounds {
+            slot.register(&waker);
+        }
+    });
+    assert_eq!(woken.load(Relaxed), rounds);
+
+    // and it's left in a state where a register and a wake work as normal
+    let before = count.0.load(Relaxed);
+    slot.register(&waker);
+    slot.wake();
+    assert_eq!(count.0.load(Relaxed), before + 1);
+}
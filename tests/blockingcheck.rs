@@ -0,0 +1,67 @@
+#![cfg(feature = "blocking_check")]
+
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+
+use rust_atomic_locks::blockingcheck::{self, in_async_context};
+use rust_atomic_locks::boundedchannel::sync_channel;
+use rust_atomic_locks::executor::{block_on, Executor};
+use rust_atomic_locks::mutex::Mutex;
+
+#[test]
+#[should_panic(expected = "Mutex::lock would block an async executor's thread")]
+fn contended_lock_in_a_task() {
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _held = LOCK.lock();
+    let mut executor = Executor::new();
+    executor.spawn(async {
+        drop(LOCK.lock());
+    });
+    executor.run();
+}
+
+#[test]
+fn calls_that_dont_wait_are_fine() {
+    let lock = Mutex::new(0);
+    let (sender, receiver) = sync_channel(1);
+    block_on(async {
+        *lock.lock() += 1;
+        sender.send(1).unwrap();
+        assert_eq!(receiver.receive(), Ok(1));
+    });
+    // and outside a task, waiting is what they're for
+    sender.send(2).unwrap();
+    assert!(!in_async_context());
+}
+
+#[test]
+fn receive_and_block_on_in_a_task() {
+    let (_sender, receiver) = sync_channel::<()>(1);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| block_on(async { receiver.receive() })));
+    let message = *result.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.starts_with("Receiver::receive would block"), "{message}");
+
+    let result = panic::catch_unwind(|| block_on(async { block_on(async {}) }));
+    assert!(result.is_err());
+    // the marks came off again on the way out
+    assert!(!in_async_context());
+}
+
+thread_local! {
+    static OTHER_RUNTIME: Cell<bool> = const { Cell::new(false) };
+}
+
+#[test]
+fn other_runtimes() {
+    // it's global, so it only reports this test's thread
+    blockingcheck::set_detector(|| OTHER_RUNTIME.get());
+    assert!(!in_async_context());
+    OTHER_RUNTIME.set(true);
+    assert!(in_async_context());
+    OTHER_RUNTIME.set(false);
+    let entered = blockingcheck::enter();
+    assert!(in_async_context());
+    drop(entered);
+    assert!(!in_async_context());
+    blockingcheck::clear_detector();
+}
@@ -0,0 +1,235 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::boundedchannel::{
+    sync_channel, sync_channel_fair, sync_channel_with, Overflow, RecvError, RecvTimeoutError, SendError,
+    SenderOccupancy, SendTimeoutError, TryRecvError, TrySendError,
+};
+
+#[test]
+fn bounded_channel() {
+    let per_sender = if cfg!(miri) { 20 } else { 100 };
+    let (sender, receiver) = sync_channel(4);
+    thread::scope(|s| {
+        for t in 0..3 {
+            let sender = sender.clone();
+            s.spawn(move || {
+                for i in 0..per_sender {
+                    sender.send(t * per_sender + i).unwrap();
+                }
+            });
+        }
+        // the channel only ends once every sender (this one included) is dropped
+        drop(sender);
+        let mut received: Vec<_> = receiver.iter().collect();
+        received.sort();
+        assert_eq!(received, (0..3 * per_sender).collect::<Vec<_>>());
+    });
+    assert_eq!(receiver.try_receive(), Err(TryRecvError::Disconnected));
+
+    let (sender, receiver) = sync_channel(1);
+    sender.try_send(1).unwrap();
+    assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+    drop(receiver);
+    assert_eq!(sender.send(3), Err(SendError(3)));
+}
+
+#[test]
+fn receiver_dropped_while_sender_blocked() {
+    let (sender, receiver) = sync_channel(1);
+    sender.send(1).unwrap();
+    thread::scope(|s| {
+        // the channel is full, so this send blocks until the receiver goes, and then gets its message back.
+        // (if the receiver goes first it gets it straight back instead, which is just as good)
+        let blocked = s.spawn(|| sender.send(2));
+        thread::sleep(Duration::from_millis(10));
+        drop(receiver);
+        assert_eq!(blocked.join().unwrap(), Err(SendError(2)));
+    });
+}
+
+#[test]
+fn sender_dropped_while_receiver_blocked() {
+    let (sender, receiver) = sync_channel::<i32>(1);
+    thread::scope(|s| {
+        let blocked = s.spawn(|| receiver.receive());
+        drop(sender);
+        assert_eq!(blocked.join().unwrap(), Err(RecvError));
+    });
+}
+
+#[test]
+fn receive_batch() {
+    let (sender, receiver) = sync_channel(8);
+    // nothing arrives, so it waits out the timeout and comes back empty
+    assert!(receiver.receive_batch(4, Duration::from_millis(10)).is_empty());
+
+    // more than enough already there, so it takes a full batch without waiting
+    for i in 0..6 {
+        sender.send(i).unwrap();
+    }
+    assert_eq!(receiver.receive_batch(4, Duration::from_secs(60)), [0, 1, 2, 3]);
+    // fewer than asked for, so the timeout flushes what there is
+    assert_eq!(receiver.receive_batch(4, Duration::from_millis(10)), [4, 5]);
+
+    thread::scope(|s| {
+        // messages that turn up while it's waiting go in the same batch
+        s.spawn(|| {
+            for i in 0..3 {
+                thread::sleep(Duration::from_millis(1));
+                sender.send(i).unwrap();
+            }
+        });
+        assert_eq!(receiver.receive_batch(3, Duration::from_secs(60)), [0, 1, 2]);
+    });
+
+    // and once the senders are gone there's no waiting for the rest of the timeout
+    sender.send(9).unwrap();
+    drop(sender);
+    assert_eq!(receiver.receive_batch(4, Duration::from_secs(60)), [9]);
+    assert!(receiver.receive_batch(4, Duration::MAX).is_empty());
+}
+
+#[test]
+fn overflow_policies() {
+    // each policy with a full channel of [0, 1]
+    let full = |overflow| {
+        let (sender, receiver) = sync_channel_with(2, overflow);
+        sender.send(0).unwrap();
+        sender.send(1).unwrap();
+        (sender, receiver)
+    };
+
+    let (sender, receiver) = full(Overflow::DropNewest);
+    assert_eq!(sender.send(2), Ok(()));
+    assert_eq!(receiver.iter().take(2).collect::<Vec<_>>(), [0, 1]);
+    assert_eq!(receiver.dropped(), 1);
+
+    let (sender, receiver) = full(Overflow::DropOldest);
+    assert_eq!(sender.send(2), Ok(()));
+    assert_eq!(sender.send(3), Ok(()));
+    assert_eq!(receiver.iter().take(2).collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(sender.dropped(), 2);
+
+    let (sender, receiver) = full(Overflow::Fail);
+    assert_eq!(sender.send(2), Err(SendError(2)));
+    assert_eq!(receiver.try_receive(), Ok(0));
+    assert_eq!(sender.send(2), Ok(()));
+    assert_eq!(receiver.dropped(), 0);
+
+    // try_send doesn't go by the policy
+    let (sender, _receiver) = full(Overflow::DropOldest);
+    assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+}
+
+#[test]
+fn drop_oldest_never_blocks() {
+    // a sender that's much faster than the receiver just keeps going, and the receiver sees a rising
+    // sequence with gaps where messages were thrown away
+    let messages = if cfg!(miri) { 50 } else { 10_000 };
+    let (sender, receiver) = sync_channel_with(4, Overflow::DropOldest);
+    thread::scope(|s| {
+        s.spawn(move || {
+            for i in 0..messages {
+                sender.send(i).unwrap();
+            }
+        });
+        let received: Vec<_> = receiver.iter().collect();
+        assert!(received.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(received.len() as u64 + receiver.dropped(), messages);
+    });
+}
+
+#[test]
+fn timeouts() {
+    let (sender, receiver) = sync_channel(1);
+    assert_eq!(receiver.receive_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+    sender.send_timeout(1, Duration::from_millis(5)).unwrap();
+    assert_eq!(sender.send_timeout(2, Duration::from_millis(5)), Err(SendTimeoutError::Timeout(2)));
+    thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(10));
+            receiver.receive().unwrap();
+        });
+        // the receive above makes room well before this runs out
+        sender.send_timeout(3, Duration::from_secs(10)).unwrap();
+    });
+    assert_eq!(receiver.receive_timeout(Duration::from_secs(10)), Ok(3));
+    drop(sender);
+    assert_eq!(receiver.receive_timeout(Duration::from_secs(10)), Err(RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn debug_shows_len() {
+    let (sender, receiver) = sync_channel(4);
+    sender.send(1).unwrap();
+    assert_eq!(format!("{receiver:?}"), "Receiver { len: 1, capacity: 4, .. }");
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn stats() {
+    let (sender, receiver) = sync_channel(2);
+    sender.send(1).unwrap();
+    sender.send(2).unwrap();
+    thread::scope(|s| {
+        // the channel is full, so this one waits for the receive below
+        let blocked = s.spawn(|| sender.send(3));
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(receiver.receive(), Ok(1));
+        blocked.join().unwrap().unwrap();
+    });
+    assert_eq!(receiver.receive(), Ok(2));
+    let stats = sender.stats();
+    assert_eq!((stats.sends, stats.receives, stats.max_depth), (3, 2, 2));
+    assert!(stats.blocked_send_time > Duration::ZERO);
+    // both ends see the same counts
+    assert_eq!(receiver.stats(), stats);
+}
+
+#[test]
+fn quota_per_sender() {
+    let (chatty, receiver) = sync_channel_fair(4);
+    let quiet = chatty.clone();
+    // two senders, so two places each
+    chatty.try_send("a").unwrap();
+    chatty.try_send("b").unwrap();
+    assert_eq!(chatty.try_send("c"), Err(TrySendError::Full("c")));
+    // there's still room for the other one
+    quiet.try_send("x").unwrap();
+    assert_eq!(chatty.occupancy(), Some(SenderOccupancy { queued: 2, quota: 2, peak: 2 }));
+    assert_eq!(quiet.occupancy(), Some(SenderOccupancy { queued: 1, quota: 2, peak: 1 }));
+
+    // receiving one of chatty's gives it its place back
+    assert_eq!(receiver.receive(), Ok("a"));
+    chatty.try_send("c").unwrap();
+    // with quiet gone chatty gets the whole channel
+    drop(quiet);
+    assert_eq!(chatty.occupancy().unwrap().quota, 4);
+    chatty.try_send("d").unwrap();
+}
+
+#[test]
+fn chatty_sender_blocks_on_its_own_share() {
+    let (chatty, receiver) = sync_channel_fair(4);
+    let quiet = chatty.clone();
+    let count = if cfg!(miri) { 10 } else { 1000 };
+    let watch = quiet.clone();
+    thread::scope(|s| {
+        s.spawn(move || {
+            for n in 0..count {
+                chatty.send(n).unwrap();
+            }
+        });
+        // the chatty one can never hold up the quiet one, which only needs its own places. The third Sender
+        // makes the shares one each
+        thread::sleep(Duration::from_millis(10));
+        quiet.send_timeout(-1, Duration::ZERO).unwrap();
+        // chatty's one place and quiet's
+        assert!(receiver.len() <= 2);
+        assert_eq!(watch.occupancy().unwrap().quota, 1);
+        drop((quiet, watch));
+        let received: Vec<i32> = receiver.iter().collect();
+        assert_eq!(received.len(), count as usize + 1);
+    });
+}
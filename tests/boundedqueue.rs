@@ -0,0 +1,50 @@
+use std::thread;
+
+use rust_atomic_locks::boundedqueue::BoundedQueue;
+
+#[test]
+fn bounded_queue() {
+    let per_thread = if cfg!(miri) { 50 } else { 1000 };
+    let queue = BoundedQueue::new(16);
+    thread::scope(|s| {
+        for t in 0..2 {
+            let queue = &queue;
+            s.spawn(move || {
+                for i in 0..per_thread {
+                    let mut value = t * per_thread + i;
+                    // spin until there's room
+                    while let Err(v) = queue.push(value) {
+                        value = v;
+                        std::hint::spin_loop();
+                    }
+                }
+            });
+        }
+        let mut sum = 0;
+        for _ in 0..2 * per_thread {
+            loop {
+                if let Some(v) = queue.pop() {
+                    sum += v;
+                    break;
+                }
+                std::hint::spin_loop();
+            }
+        }
+        assert_eq!(sum, (0..2 * per_thread).sum::<usize>());
+    });
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn full_and_empty() {
+    // capacity 1 is the smallest the sequence numbers have to cope with
+    let queue = BoundedQueue::new(1);
+    assert_eq!(queue.pop(), None);
+    assert_eq!(queue.push(1), Ok(()));
+    assert_eq!(queue.push(2), Err(2));
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.pop(), None);
+    // values left in the queue are dropped with it
+    queue.push(3).unwrap();
+    drop(queue);
+}
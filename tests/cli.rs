@@ -0,0 +1,15 @@
+use std::process::Command;
+
+// Bad input is a usage error (exit code 2), not a panic
+#[test]
+#[cfg_attr(miri, ignore)]
+fn bad_durations() {
+    for duration in ["-1", "inf", "nan", "-5s", "10x"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rust-atomic-locks"))
+            .args(["--duration", duration, "--list"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "--duration {duration}");
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("bad --duration"), "--duration {duration}");
+    }
+}
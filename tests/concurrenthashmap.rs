@@ -0,0 +1,61 @@
+use std::thread;
+
+use rust_atomic_locks::concurrenthashmap::ConcurrentHashMap;
+
+#[test]
+fn concurrent_hash_map() {
+    let per_thread = if cfg!(miri) { 25 } else { 250 };
+    let map = ConcurrentHashMap::new();
+    thread::scope(|s| {
+        for t in 0..4 {
+            let map = &map;
+            s.spawn(move || {
+                for i in 0..per_thread {
+                    map.insert(format!("key-{}", t * per_thread + i), i);
+                }
+            });
+        }
+    });
+    // the second thread's eleventh key
+    let key = format!("key-{}", per_thread + 10);
+    assert_eq!(map.len(), 4 * per_thread as usize);
+    assert_eq!(map.get(&key, |v| *v), Some(10));
+    assert_eq!(map.remove(&key), Some(10));
+    assert!(!map.contains_key(&key));
+
+    let mut sum = 0;
+    map.for_each(|_, v| sum += v);
+    assert_eq!(sum, 4 * (0..per_thread).sum::<i32>() - 10);
+    assert_eq!(map.snapshot().len(), 4 * per_thread as usize - 1);
+}
+
+#[test]
+fn insert_and_remove_the_same_keys() {
+    // two threads insert the same keys and two remove them, so every key ends up either there or not -
+    // never counted twice
+    let keys = if cfg!(miri) { 5 } else { 500 };
+    let map = ConcurrentHashMap::new();
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for k in 0..keys {
+                    map.insert(k, k);
+                }
+            });
+            s.spawn(|| {
+                for k in 0..keys {
+                    map.remove(&k);
+                }
+            });
+        }
+    });
+    assert_eq!(map.len(), (0..keys).filter(|k| map.contains_key(k)).count());
+    map.for_each(|k, v| assert_eq!(k, v));
+
+    // the callback can use the map, with no stripe left locked under it
+    map.for_each(|k, v| {
+        assert_eq!(map.get(k, |v| *v), Some(*v));
+        map.insert(*k, v + 1);
+    });
+    map.for_each(|k, v| assert_eq!(*v, k + 1));
+}
@@ -0,0 +1,47 @@
+use std::thread;
+
+use rust_atomic_locks::concurrentlru::ConcurrentLru;
+
+#[test]
+fn evicts_the_least_recently_used() {
+    // one stripe, so it's an exact LRU
+    let cache = ConcurrentLru::with_stripes(2, 1);
+    cache.put("a", 1);
+    cache.put("b", 2);
+    // a is used, so b is the one to go
+    assert_eq!(cache.get("a", |v| *v), Some(1));
+    assert_eq!(cache.put("c", 3), None);
+    assert!(!cache.contains_key("b"));
+    assert_eq!(cache.put("a", 10), Some(1));
+    assert_eq!(cache.get("b", |v| *v), None);
+    assert_eq!(cache.remove("c"), Some(3));
+    assert_eq!(cache.len(), 1);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 1, 1));
+    assert_eq!(stats.hit_ratio(), 0.5);
+}
+
+#[test]
+fn never_over_capacity() {
+    let per_thread = if cfg!(miri) { 20 } else { 2_000 };
+    let cache = ConcurrentLru::new(100);
+    thread::scope(|s| {
+        for t in 0..4 {
+            let cache = &cache;
+            s.spawn(move || {
+                for i in 0..per_thread {
+                    cache.put(t * per_thread + i, i);
+                    cache.get(&(t * per_thread + i / 2), |v| assert!(*v <= i));
+                }
+            });
+        }
+    });
+    assert!(cache.len() <= cache.capacity());
+    let stats = cache.stats();
+    assert_eq!(stats.hits + stats.misses, 4 * per_thread as u64);
+    // every put was of a new key, so whatever isn't still there was evicted
+    assert_eq!(stats.evictions, 4 * per_thread as u64 - cache.len() as u64);
+    cache.clear();
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::condvar::Condvar;
+use rust_atomic_locks::mutex::Mutex;
+
+#[test]
+fn condvar() {
+    let messages = if cfg!(miri) { 20 } else { 1000 };
+    let queue = Mutex::new(VecDeque::new());
+    let not_empty = Condvar::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..messages {
+                queue.lock().push_back(i);
+                not_empty.notify_one();
+            }
+        });
+        let mut received = Vec::new();
+        let mut q = queue.lock();
+        while received.len() < messages {
+            match q.pop_front() {
+                Some(i) => received.push(i),
+                // the mutex is unlocked while waiting, and locked again by the time it returns
+                None => q = not_empty.wait(q),
+            }
+        }
+        assert_eq!(received, (0..messages).collect::<Vec<_>>());
+    });
+}
+
+#[test]
+fn notify_all_wakes_everyone() {
+    let go = Mutex::new(false);
+    let changed = Condvar::new();
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| {
+                let mut go = go.lock();
+                while !*go {
+                    go = changed.wait(go);
+                }
+            });
+        }
+        thread::sleep(Duration::from_millis(10));
+        *go.lock() = true;
+        changed.notify_all();
+    });
+}
+
+// The notify lands after the waiter has unlocked the mutex but before it's gone to sleep, which a condvar
+// that only looked at whether it was notified while asleep would miss
+#[test]
+#[cfg(debug_assertions)]
+fn notify_between_unlock_and_sleep() {
+    use rust_atomic_locks::sched::{Scheduler, Until};
+
+    let ready = Mutex::new(false);
+    let changed = Condvar::new();
+    let scheduler = Scheduler::new([(0, Until::Point("Condvar::wait unlocked")), (1, Until::Done), (0, Until::Done)]);
+    thread::scope(|s| {
+        s.spawn(scheduler.thread(0, || {
+            let mut ready = ready.lock();
+            while !*ready {
+                ready = changed.wait(ready);
+            }
+        }));
+        s.spawn(scheduler.thread(1, || {
+            *ready.lock() = true;
+            changed.notify_one();
+        }));
+    });
+}
+
+#[test]
+fn wait_while() {
+    let count = Mutex::new(0);
+    let changed = Condvar::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..3 {
+                *count.lock() += 1;
+                changed.notify_all();
+            }
+        });
+        // however the notifies and wake ups land, it only comes back once the count is there
+        let count = changed.wait_while(count.lock(), |count| *count < 3);
+        assert_eq!(*count, 3);
+    });
+
+    // nothing's going to change it, so this times out with the condition still true
+    let (guard, result) = changed.wait_timeout_while(count.lock(), Duration::from_millis(10), |count| *count < 4);
+    assert!(result.timed_out());
+    assert_eq!(*guard, 3);
+    drop(guard);
+
+    // and a condition that's already false doesn't wait at all
+    let (_guard, result) = changed.wait_timeout_while(count.lock(), Duration::from_secs(60), |count| *count < 3);
+    assert!(!result.timed_out());
+}
@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+use rust_atomic_locks::condvar::Condvar;
+use rust_atomic_locks::deadline::Deadline;
+use rust_atomic_locks::event::Event;
+use rust_atomic_locks::mutex::Mutex;
+use rust_atomic_locks::semaphore::Semaphore;
+use rust_atomic_locks::spinlock::SpinLock;
+
+#[test]
+fn conversions() {
+    let now = Instant::now();
+    assert_eq!(Deadline::from(now), Deadline::At(now));
+    assert_eq!(Deadline::from(Duration::from_secs(1)).remaining(), Some(Duration::from_secs(1)));
+    assert_eq!(Deadline::from(None), Deadline::Never);
+    assert_eq!(Deadline::Never.instant(), None);
+    // too far off to be an Instant, so no deadline at all
+    assert_eq!(Deadline::After(Duration::MAX).instant(), None);
+    assert_eq!(Deadline::At(now).remaining(), Some(Duration::ZERO));
+}
+
+#[test]
+fn the_same_deadline_everywhere() {
+    // one Instant shared by several waits, which is already behind us - they all give up straight away
+    let deadline = Instant::now();
+    let mutex = Mutex::new(0);
+    let guard = mutex.lock();
+    assert!(mutex.lock_timeout(deadline).is_err());
+    let (guard, result) = Condvar::new().wait_timeout_while(guard, deadline, |_| true);
+    assert!(result.timed_out());
+    drop(guard);
+    assert!(Semaphore::new(0).acquire_timeout(deadline).is_none());
+    assert!(!Event::new().wait_timeout(deadline));
+
+    // and Never is the same as the plain call, which is fine when nothing's in the way
+    assert!(mutex.lock_timeout(Deadline::Never).is_ok());
+    assert!(SpinLock::new(()).lock_timeout(Deadline::Never).is_ok());
+    assert!(Semaphore::new(1).acquire_timeout(Deadline::Never).is_some());
+}
@@ -0,0 +1,48 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::boundedchannel::RecvError;
+use rust_atomic_locks::duplex::{duplex, duplex_with_capacity};
+use rust_atomic_locks::receive::Receive;
+use rust_atomic_locks::rendezvous::CallError;
+
+#[test]
+fn call_and_answer() {
+    let (main, worker) = duplex::<u32, String>();
+    let calls = if cfg!(miri) { 10 } else { 1000 };
+    thread::scope(|s| {
+        s.spawn(move || {
+            while let Ok(n) = worker.receive() {
+                worker.send(n.to_string()).unwrap();
+            }
+        });
+        for n in 0..calls {
+            assert_eq!(main.call(n), Ok(n.to_string()));
+        }
+        // dropping main is what stops the worker
+        drop(main);
+    });
+}
+
+#[test]
+fn either_end_sees_the_other_go() {
+    let (a, b) = duplex_with_capacity::<i32, i32>(2);
+    a.send(1).unwrap();
+    drop(a);
+    // what was sent before it went still arrives
+    assert_eq!(b.receive(), Ok(1));
+    assert_eq!(b.receive(), Err(RecvError));
+    assert!(b.send(2).is_err());
+    assert_eq!(b.call(3), Err(CallError::Disconnected));
+}
+
+#[test]
+fn call_timeout_and_adapters() {
+    let (a, b) = duplex::<i32, i32>();
+    assert_eq!(a.call_timeout(1, Duration::from_millis(10)), Err(CallError::Timeout));
+    assert_eq!(b.receive(), Ok(1));
+    // an end is a Receive like any receiver
+    b.send(5).unwrap();
+    let doubled = a.map(|n| n * 2);
+    assert_eq!(Receive::try_receive(&doubled), Ok(10));
+}
@@ -0,0 +1,61 @@
+use std::thread;
+
+use rust_atomic_locks::dynamic::{new_channel, new_lock, ChannelKind, LockKind, ParseKindError};
+
+#[test]
+fn every_lock_kind() {
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    for name in ["spin", "mutex", "rwlock", "hybrid"] {
+        let kind: LockKind = name.parse().unwrap();
+        let lock = new_lock(kind, 0);
+        assert_eq!(lock.kind(), kind);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..n {
+                        lock.with(|value| *value += 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(lock.read(|value| *value), 4 * n);
+        assert_eq!(lock.try_with(|value| *value * 2), Some(8 * n));
+    }
+}
+
+#[test]
+fn every_channel_kind() {
+    let unbounded = new_channel::<u32>("mutex".parse().unwrap());
+    assert_eq!(unbounded.capacity(), None);
+    assert_eq!(unbounded.try_receive(), None);
+
+    let bounded = new_channel::<u32>("bounded:2".parse().unwrap());
+    assert_eq!(bounded.capacity(), Some(2));
+    bounded.send(1);
+    assert_eq!(bounded.try_send(2), Ok(()));
+    assert_eq!(bounded.try_send(3), Err(3));
+    assert_eq!(bounded.len(), 2);
+    assert_eq!((bounded.receive(), bounded.try_receive()), (1, Some(2)));
+    assert!(bounded.is_empty());
+
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    for channel in [unbounded, bounded] {
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..n {
+                    channel.send(i);
+                }
+            });
+            assert_eq!((0..n).map(|_| channel.receive()).collect::<Vec<_>>(), (0..n).collect::<Vec<_>>());
+        });
+    }
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!("futex".parse::<LockKind>(), Err(ParseKindError("futex".into())));
+    assert_eq!("bounded:4".parse(), Ok(ChannelKind::Bounded(4)));
+    for bad in ["bounded:0", "bounded:", "bounded", "spsc"] {
+        assert!(bad.parse::<ChannelKind>().is_err(), "{bad}");
+    }
+}
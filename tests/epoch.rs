@@ -0,0 +1,47 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::mpsc;
+use std::thread;
+
+use rust_atomic_locks::epoch;
+
+static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct DetectDrop;
+
+impl Drop for DetectDrop {
+    fn drop(&mut self) {
+        NUM_DROPS.fetch_add(1, Relaxed);
+    }
+}
+
+// Pinning (and unpinning) is what moves the epoch on and frees garbage, a bit at a time
+fn churn() {
+    for _ in 0..1000 {
+        drop(epoch::pin());
+    }
+}
+
+#[test]
+fn not_freed_while_another_thread_is_pinned() {
+    let (pinned, wait_for_pin) = mpsc::channel();
+    let (unpin, wait_for_unpin) = mpsc::channel::<()>();
+    thread::scope(|s| {
+        s.spawn(move || {
+            let guard = epoch::pin();
+            pinned.send(()).unwrap();
+            wait_for_unpin.recv().unwrap();
+            drop(guard);
+        });
+        wait_for_pin.recv().unwrap();
+
+        let garbage = Box::into_raw(Box::new(DetectDrop));
+        unsafe { epoch::pin().defer_destroy(garbage) };
+        // the other thread could still be reading it, as far as the epoch knows
+        churn();
+        assert_eq!(NUM_DROPS.load(Relaxed), 0);
+
+        unpin.send(()).unwrap();
+    });
+    churn();
+    assert_eq!(NUM_DROPS.load(Relaxed), 1);
+}
@@ -0,0 +1,34 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::event::Event;
+
+#[test]
+fn event() {
+    let ready = Event::new();
+    assert!(!ready.wait_timeout(Duration::from_millis(10)));
+    thread::scope(|s| {
+        // all three threads are held at the event until the main thread sets it
+        for _ in 0..3 {
+            s.spawn(|| ready.wait());
+        }
+        thread::sleep(Duration::from_millis(10));
+        ready.set();
+    });
+    // the event stays set until it's reset
+    assert!(ready.wait_timeout(Duration::from_millis(10)));
+    ready.reset();
+    assert!(!ready.is_set());
+}
+
+#[test]
+fn set_before_wait() {
+    // setting it before anyone waits isn't lost, and setting it twice is the same as once
+    let ready = Event::new();
+    ready.set();
+    ready.set();
+    thread::scope(|s| {
+        s.spawn(|| ready.wait());
+    });
+    assert!(ready.is_set());
+}
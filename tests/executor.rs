@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::future::{poll_fn, Future};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering::{Acquire, Release}};
+use std::sync::Arc;
+use std::task::Poll;
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::atomicwaker::AtomicWaker;
+use rust_atomic_locks::executor::{block_on, Executor};
+
+// The simplest leaf future there is: set from another thread, waking whoever's waiting through an AtomicWaker
+#[derive(Default)]
+struct Flag {
+    set: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Flag {
+    fn set(&self) {
+        self.set.store(true, Release);
+        self.waker.wake();
+    }
+
+    fn wait(&self) -> impl Future<Output = ()> + '_ {
+        poll_fn(|cx| {
+            // registered before the check, so a set in between still wakes the task
+            self.waker.register(cx.waker());
+            if self.set.load(Acquire) { Poll::Ready(()) } else { Poll::Pending }
+        })
+    }
+}
+
+// Pending once, after asking to be polled again, so other tasks get a turn
+fn yield_now() -> impl Future<Output = ()> {
+    let mut yielded = false;
+    poll_fn(move |cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+}
+
+#[test]
+fn block_on_wakes_from_another_thread() {
+    let flag = Arc::new(Flag::default());
+    let setter = {
+        let flag = flag.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            flag.set();
+        })
+    };
+    assert_eq!(block_on(async { flag.wait().await; 5 }), 5);
+    setter.join().unwrap();
+}
+
+#[test]
+fn tasks_take_turns() {
+    // Rc and RefCell, as the tasks all stay on this thread
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let flag = Arc::new(Flag::default());
+    let mut executor = Executor::new();
+    for task in 0..3 {
+        let log = log.clone();
+        executor.spawn(async move {
+            for step in 0..2 {
+                log.borrow_mut().push((task, step));
+                yield_now().await;
+            }
+        });
+    }
+    {
+        let (log, flag) = (log.clone(), flag.clone());
+        executor.spawn(async move {
+            flag.wait().await;
+            log.borrow_mut().push((3, 0));
+        });
+    }
+    let setter = thread::spawn(move || flag.set());
+    executor.run();
+    setter.join().unwrap();
+
+    let log = log.borrow();
+    // the yielding tasks go round in turn, and the waiting one finishes whenever the flag's set
+    let yielding: Vec<_> = log.iter().copied().filter(|&(task, _)| task < 3).collect();
+    assert_eq!(yielding, [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+    assert!(log.contains(&(3, 0)));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "ffi")]
+
+use std::ffi::c_void;
+use std::ptr;
+use std::thread;
+
+use rust_atomic_locks::ffi::*;
+
+// The functions called the way C would call them, pointers and all
+#[test]
+fn spinlock() {
+    let lock = atomiclocks_spinlock_new();
+    unsafe {
+        atomiclocks_spinlock_lock(lock);
+        assert!(!atomiclocks_spinlock_try_lock(lock));
+        atomiclocks_spinlock_unlock(lock);
+        assert!(atomiclocks_spinlock_try_lock(lock));
+        atomiclocks_spinlock_unlock(lock);
+        atomiclocks_spinlock_free(lock);
+        atomiclocks_spinlock_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn channel() {
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    // the pointer goes to the other thread as an address, the way C code doesn't think twice about
+    let channel = atomiclocks_channel_new(4) as usize;
+    let received = thread::spawn(move || {
+        let channel = channel as *const AtomicLocksChannel;
+        (0..n).map(|_| unsafe { *Box::from_raw(atomiclocks_channel_recv(channel).cast::<usize>()) }).sum::<usize>()
+    });
+    let channel = channel as *mut AtomicLocksChannel;
+    for i in 0..n {
+        unsafe { atomiclocks_channel_send(channel, Box::into_raw(Box::new(i)).cast::<c_void>()) };
+    }
+    assert_eq!(received.join().unwrap(), n * (n - 1) / 2);
+    unsafe {
+        let mut message = ptr::null_mut();
+        assert!(!atomiclocks_channel_try_recv(channel, &mut message));
+        let mut x = 1;
+        assert!(atomiclocks_channel_try_send(channel, ptr::addr_of_mut!(x).cast()));
+        assert!(atomiclocks_channel_try_recv(channel, &mut message));
+        assert_eq!(*message.cast::<i32>(), 1);
+        atomiclocks_channel_free(channel);
+    }
+    assert!(atomiclocks_channel_new(0).is_null());
+}
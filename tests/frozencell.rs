@@ -0,0 +1,52 @@
+use std::thread;
+
+use rust_atomic_locks::frozencell::{Frozen, FrozenCell};
+
+#[test]
+fn written_then_frozen() {
+    let cell = FrozenCell::new(Vec::new());
+    assert_eq!(cell.try_get(), None);
+    cell.write().unwrap().push(1);
+    thread::scope(|s| {
+        for i in 2..4 {
+            let cell = &cell;
+            s.spawn(move || cell.write().unwrap().push(i));
+        }
+    });
+    let frozen = cell.freeze();
+    assert_eq!(frozen.len(), 3);
+    assert!(cell.is_frozen());
+    assert!(matches!(cell.write(), Err(Frozen)));
+    // freezing again is fine
+    assert!(std::ptr::eq(cell.freeze(), frozen));
+    assert_eq!(format!("{cell:?}"), format!("FrozenCell({frozen:?})"));
+}
+
+#[test]
+fn readers_see_everything_written_before_the_freeze() {
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    let cell = FrozenCell::new(0);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..n {
+                *cell.write().unwrap() += 1;
+            }
+            cell.freeze();
+        });
+        s.spawn(|| loop {
+            if let Some(&value) = cell.try_get() {
+                assert_eq!(value, n);
+                // and having seen it frozen, the unchecked read is fine too
+                assert_eq!(unsafe { *cell.get_unchecked() }, n);
+                break;
+            }
+            thread::yield_now();
+        });
+    });
+}
+
+#[test]
+#[should_panic(expected = "FrozenCell read before it was frozen")]
+fn get_before_freeze() {
+    FrozenCell::new(1).get();
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicU32, Ordering::{Acquire, Relaxed, Release}};
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::boundedchannel::sync_channel;
+use rust_atomic_locks::futex::{set_spin_hook, wait, wait_timeout, wake_all, wake_one};
+
+#[test]
+fn futex() {
+    let ready = AtomicU32::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                // a loop, as wait can return without anything having changed
+                while ready.load(Acquire) == 0 {
+                    wait(&ready, 0);
+                }
+            });
+        }
+        thread::sleep(Duration::from_millis(10));
+        ready.store(1, Release);
+        wake_all(&ready);
+    });
+
+    // waiting on a value the atomic doesn't hold returns straight away
+    wait(&ready, 0);
+    assert_eq!(ready.load(Relaxed), 1);
+}
+
+#[test]
+fn wake_with_nobody_waiting() {
+    // waking an atomic nobody waits on does nothing, and isn't remembered for the next waiter either
+    let a = AtomicU32::new(0);
+    wake_one(&a);
+    wake_all(&a);
+    thread::scope(|s| {
+        s.spawn(|| {
+            while a.load(Acquire) == 0 {
+                wait(&a, 0);
+            }
+        });
+        thread::sleep(Duration::from_millis(10));
+        a.store(1, Release);
+        wake_one(&a);
+    });
+}
+
+#[test]
+fn wait_timeout_gives_up() {
+    // nobody's going to wake it, so only the timeout gets it out (it may come back early, but never hangs)
+    let a = AtomicU32::new(0);
+    wait_timeout(&a, 0, Duration::from_millis(5));
+    assert_eq!(a.load(Relaxed), 0);
+}
+
+#[test]
+fn spin_hook_instead_of_sleeping() {
+    // a thread with a spin hook never sleeps, it calls the hook instead - both on a futex and in a channel
+    thread_local! {
+        static SPINS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+    let ready = AtomicU32::new(0);
+    let (sender, receiver) = sync_channel(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            set_spin_hook(Some(|| SPINS.with(|n| n.set(n.get() + 1))));
+            while ready.load(Acquire) == 0 {
+                wait(&ready, 0);
+            }
+            assert_eq!(receiver.receive(), Ok(1));
+            assert!(SPINS.with(|n| n.get()) > 0);
+        });
+        thread::sleep(Duration::from_millis(10));
+        ready.store(1, Release);
+        thread::sleep(Duration::from_millis(10));
+        sender.send(1).unwrap();
+    });
+}
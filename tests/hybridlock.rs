@@ -0,0 +1,63 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_atomic_locks::hybridlock::{HybridLock, LockMode};
+use rust_atomic_locks::waitstrategy::cpu_budget;
+
+#[test]
+fn hybrid_lock() {
+    let lock = HybridLock::new(0);
+    let (threads, per_thread) = if cfg!(miri) { (2, 50) } else { (4, 10_000) };
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                for _ in 0..per_thread {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), threads * per_thread);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn long_critical_sections_switch_to_parking() {
+    let lock = HybridLock::new(());
+    assert_eq!(lock.mode(), LockMode::Spinning);
+    // held for far longer than the spin lasts, so nearly every contended lock has to sleep
+    contend_until(&lock, 4, Duration::from_micros(200), LockMode::Parking);
+    assert_eq!(lock.mode(), LockMode::Parking);
+    assert!(lock.try_lock().is_some());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn short_critical_sections_switch_back_to_spinning() {
+    let lock = HybridLock::new(());
+    contend_until(&lock, 4, Duration::from_micros(200), LockMode::Parking);
+    assert_eq!(lock.mode(), LockMode::Parking);
+    // on a single core the spin is skipped and every contended lock sleeps, so it stays parking there
+    if !cpu_budget().spinning_helps() {
+        return;
+    }
+    // let go straight away, so even the short spin while parking gets the lock nearly every time
+    contend_until(&lock, 2, Duration::ZERO, LockMode::Spinning);
+    assert_eq!(lock.mode(), LockMode::Spinning);
+}
+
+// Has threads lock it over and over, holding it for hold each time, until it's in mode - or a good while has gone
+// by, for the assert after to fail rather than the test hanging
+fn contend_until(lock: &HybridLock<()>, threads: usize, hold: Duration, mode: LockMode) {
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                while lock.mode() != mode && start.elapsed() < Duration::from_secs(30) {
+                    let _guard = lock.lock();
+                    thread::sleep(hold);
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,56 @@
+use std::sync::mpsc;
+use std::thread;
+
+use rust_atomic_locks::boundedchannel::{sync_channel, Receiver, RecvError, Sender};
+
+// An old producer on std's channel, a new consumer on the crate's, and the end of the stream getting through
+#[test]
+fn std_receiver_into_receiver() {
+    let (sender, receiver) = mpsc::channel();
+    let receiver: Receiver<u32> = receiver.into();
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    thread::scope(|s| {
+        s.spawn(move || {
+            for i in 0..n {
+                sender.send(i).unwrap();
+            }
+        });
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), (0..n).collect::<Vec<_>>());
+    });
+    assert_eq!(receiver.receive(), Err(RecvError));
+}
+
+#[test]
+fn each_way() {
+    // the crate's Sender feeding an mpsc consumer
+    let (sender, receiver) = mpsc::channel();
+    let sender: Sender<&str> = sender.into();
+    sender.send("to std").unwrap();
+    assert_eq!(receiver.recv(), Ok("to std"));
+    drop(sender);
+    assert!(receiver.recv().is_err());
+
+    // mpsc producers feeding the crate's channel, and its Receiver handed to an mpsc consumer
+    let (sender, receiver) = sync_channel(4);
+    let std_sender: mpsc::SyncSender<&str> = sender.clone().into();
+    let std_receiver: mpsc::Receiver<&str> = receiver.into();
+    std_sender.send("from std").unwrap();
+    sender.send("from the crate").unwrap();
+    let mut received = vec![std_receiver.recv().unwrap(), std_receiver.recv().unwrap()];
+    received.sort();
+    assert_eq!(received, ["from std", "from the crate"]);
+    drop((sender, std_sender));
+    assert!(std_receiver.recv().is_err());
+}
+
+#[cfg(feature = "crossbeam")]
+#[test]
+fn crossbeam_each_way() {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let receiver: Receiver<u32> = receiver.into();
+    let sender: crossbeam_channel::Sender<u32> = Sender::from(sender).into();
+    sender.send(7).unwrap();
+    assert_eq!(receiver.receive(), Ok(7));
+    drop(sender);
+    assert_eq!(receiver.receive(), Err(RecvError));
+}
@@ -0,0 +1,82 @@
+use std::cell::Cell;
+use std::thread;
+
+use rust_atomic_locks::irqspinlock::{InterruptController, IrqSpinLock};
+
+// Pretends to be an interrupt controller by keeping an "interrupts enabled" flag per thread, so the
+// save/restore behaviour can be checked without any hardware
+struct FakeInterrupts;
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(true) };
+}
+
+unsafe impl InterruptController for FakeInterrupts {
+    type State = bool;
+
+    fn disable() -> bool {
+        ENABLED.with(|e| e.replace(false))
+    }
+
+    unsafe fn restore(state: bool) {
+        ENABLED.with(|e| e.set(state));
+    }
+}
+
+fn enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+#[test]
+fn irq_spinlock() {
+    let iters = if cfg!(miri) { 20 } else { 1000 };
+    let shared: IrqSpinLock<u32, FakeInterrupts> = IrqSpinLock::new(0);
+    let other: IrqSpinLock<u32, FakeInterrupts> = IrqSpinLock::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..iters {
+                    *shared.lock() += 1;
+                }
+                // every thread gets its interrupts back once it's done
+                assert!(enabled());
+            });
+        }
+    });
+    assert_eq!(*shared.lock(), 4 * iters);
+
+    // nested locks only turn interrupts back on once the outer one is unlocked
+    let outer = shared.lock();
+    assert!(!enabled());
+    let inner = other.lock();
+    assert!(shared.try_lock().is_none());
+    drop(inner);
+    assert!(!enabled());
+    drop(outer);
+    assert!(enabled());
+
+    // exclusive access doesn't go near interrupts
+    let mut other = other;
+    *other.get_mut() = 1;
+    assert!(enabled());
+    assert_eq!(other.into_inner(), 1);
+}
+
+#[test]
+fn failed_try_lock_restores_interrupts() {
+    let lock: IrqSpinLock<(), FakeInterrupts> = IrqSpinLock::new(());
+    let guard = lock.lock();
+    drop(guard);
+    thread::scope(|s| {
+        let _guard = lock.lock();
+        // another thread that can't get the lock doesn't come away with its interrupts off
+        s.spawn(|| {
+            assert!(lock.try_lock().is_none());
+            assert!(enabled());
+        })
+        .join()
+        .unwrap();
+    });
+    assert!(enabled());
+}
@@ -0,0 +1,38 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::latch::Latch;
+
+#[test]
+fn latch() {
+    // a start gate: none of the workers start until the main thread opens the gate
+    let start = Latch::new(1);
+    let done = Latch::new(4);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                start.wait();
+                done.count_down();
+            });
+        }
+        assert_eq!(done.count(), 4);
+        start.count_down();
+        done.wait();
+        assert_eq!(done.count(), 0);
+    });
+    // counting down an open latch does nothing
+    done.count_down();
+    assert_eq!(done.count(), 0);
+}
+
+#[test]
+fn wait_timeout() {
+    let latch = Latch::new(2);
+    latch.count_down();
+    // one short, so it stays shut
+    assert!(!latch.wait_timeout(Duration::from_millis(10)));
+    latch.count_down();
+    assert!(latch.wait_timeout(Duration::from_millis(10)));
+    // and a latch made open doesn't wait at all
+    Latch::new(0).wait();
+}
@@ -0,0 +1,99 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::mutex::{Mutex, MutexGuard, Timeout};
+
+const ITERS: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+#[test]
+fn mutex() {
+    let counter = Mutex::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    *counter.lock() += 1;
+                }
+            });
+        }
+    });
+    let guard = counter.lock();
+    assert!(counter.try_lock().is_none());
+    drop(guard);
+    assert!(counter.try_lock().is_some());
+    #[cfg(feature = "metrics")]
+    {
+        let metrics = counter.metrics();
+        assert_eq!(metrics.acquisitions as usize, 4 * ITERS + 2);
+        assert!(metrics.max_wait <= metrics.total_wait);
+    }
+    assert_eq!(counter.into_inner(), 4 * ITERS);
+}
+
+#[test]
+fn sleeping_waiter_is_woken() {
+    // held long enough that the other thread gives up spinning and goes to sleep, so unlocking has to wake it
+    let value = Mutex::new(0);
+    thread::scope(|s| {
+        let mut guard = value.lock();
+        let waiter = s.spawn(|| *value.lock());
+        thread::sleep(Duration::from_millis(10));
+        *guard = 5;
+        drop(guard);
+        assert_eq!(waiter.join().unwrap(), 5);
+    });
+    #[cfg(feature = "metrics")]
+    assert!(value.metrics().max_hold >= Duration::from_millis(10));
+}
+
+#[test]
+fn unlocked_lets_others_in() {
+    let lock = Mutex::new(Vec::new());
+    let mut guard = lock.lock();
+    guard.push(1);
+    let seen = MutexGuard::unlocked(&mut guard, || {
+        // another thread can take the lock while it's let go
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut v = lock.lock();
+                v.push(2);
+                v.len()
+            })
+            .join()
+            .unwrap()
+        })
+    });
+    assert_eq!(seen, 2);
+    // and it's locked again afterwards, with whatever the other thread did
+    assert_eq!(*guard, [1, 2]);
+    assert!(lock.try_lock().is_none());
+    drop(guard);
+
+    // a panic inside still locks it again, so the guard can unlock it as normal on the way out
+    let result = thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.lock();
+            MutexGuard::unlocked(&mut guard, || panic!("callback failed"));
+        })
+        .join()
+    });
+    assert!(result.is_err());
+    lock.lock().push(3);
+    assert_eq!(*lock.lock(), [1, 2, 3]);
+}
+
+#[test]
+fn lock_timeout_map() {
+    let mutex = Mutex::new(vec![1]);
+    assert_eq!(mutex.lock_timeout_map(Duration::from_millis(10), |v| v.len()), Ok(1));
+    thread::scope(|s| {
+        let guard = mutex.lock();
+        // held on another thread, so this one gives up and f never runs
+        let timed_out = s.spawn(|| mutex.lock_timeout_map(Duration::from_millis(10), |_| unreachable!()));
+        assert_eq!(timed_out.join().unwrap(), Err::<(), _>(Timeout));
+        drop(guard);
+    });
+    // a waiter that gave up doesn't leave the mutex stuck
+    mutex.lock_timeout_map(Duration::from_secs(1), |v| v.push(2)).unwrap();
+    assert_eq!(*mutex.lock_timeout(Duration::ZERO).unwrap(), [1, 2]);
+}
@@ -0,0 +1,143 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::mutexchannel::{Fairness, MutexChannel};
+
+#[test]
+fn mutex_channel() {
+    let channel = MutexChannel::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            channel.send(0);
+            // a burst of messages goes in under one lock
+            channel.send_all(1..10);
+        });
+        while channel.is_empty() {
+            thread::yield_now();
+        }
+        // the first message can be looked at without taking it
+        assert_eq!(channel.peek_with(|m| *m), Some(0));
+        assert_eq!(channel.peek().as_deref(), Some(&0));
+        let mut received = vec![channel.receive()];
+        while received.len() < 10 {
+            received.extend(channel.receive_up_to(4));
+        }
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    });
+    assert!(channel.is_empty());
+    assert!(channel.drain().is_empty());
+
+    // picking messages out of order: the even one is taken and the odd ones before it stay queued
+    channel.send_all([1, 3, 4, 5]);
+    assert_eq!(channel.receive_if(|m| m % 2 == 0), 4);
+    assert_eq!(channel.drain(), [1, 3, 5]);
+    #[cfg(feature = "metrics")]
+    {
+        let stats = channel.stats();
+        assert_eq!((stats.sends, stats.receives), (14, 14));
+        assert!(stats.max_depth >= 4);
+    }
+}
+
+#[test]
+fn try_send_and_try_receive() {
+    let channel = MutexChannel::new();
+    assert_eq!(channel.try_receive(), None);
+    assert_eq!(channel.try_send(1), Ok(()));
+    assert_eq!(channel.try_receive(), Some(1));
+
+    // only a byte budget can make it full, and then the message comes back rather than waiting for room
+    let channel = MutexChannel::with_byte_budget(4, |m: &&str| m.len());
+    assert_eq!(channel.try_send("abc"), Ok(()));
+    assert_eq!(channel.try_send("de"), Err("de"));
+    assert_eq!(channel.try_receive(), Some("abc"));
+    assert_eq!(channel.try_send("de"), Ok(()));
+    assert_eq!(channel.queued_bytes(), Some(2));
+}
+
+#[test]
+fn many_senders_and_receivers() {
+    let per_sender = if cfg!(miri) { 10 } else { 1000 };
+    for fairness in [Fairness::Unfair, Fairness::Fifo] {
+        let channel = MutexChannel::with_fairness(fairness);
+        let mut received = thread::scope(|s| {
+            for t in 0..2 {
+                let channel = &channel;
+                s.spawn(move || (0..per_sender).for_each(|i| channel.send(t * per_sender + i)));
+            }
+            let receivers: Vec<_> =
+                (0..2).map(|_| s.spawn(|| (0..per_sender).map(|_| channel.receive()).collect::<Vec<_>>())).collect();
+            receivers.into_iter().flat_map(|r| r.join().unwrap()).collect::<Vec<_>>()
+        });
+        // every message went to exactly one receiver
+        received.sort();
+        assert_eq!(received, (0..2 * per_sender).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn fifo_receivers_served_in_order() {
+    let channel = MutexChannel::with_fairness(Fairness::Fifo);
+    thread::scope(|s| {
+        // the receivers start waiting one after another, so they're in line in that order
+        let receivers: Vec<_> = (0..3)
+            .map(|_| {
+                let receiver = s.spawn(|| channel.receive());
+                thread::sleep(Duration::from_millis(20));
+                receiver
+            })
+            .collect();
+        channel.send_all(0..3);
+        let received: Vec<_> = receivers.into_iter().map(|r| r.join().unwrap()).collect();
+        assert_eq!(received, [0, 1, 2]);
+    });
+
+    // and with nobody in line, a receive takes what's there straight away
+    channel.send(3);
+    assert_eq!(channel.receive_up_to(4), [3]);
+}
+
+#[test]
+fn byte_budget() {
+    let channel = MutexChannel::with_byte_budget(10, |m: &String| m.len());
+    channel.send("hello".to_string());
+    channel.send("world".to_string());
+    assert_eq!(channel.queued_bytes(), Some(10));
+    thread::scope(|s| {
+        // the budget's used up, so this waits until a receive makes room for it
+        let blocked = s.spawn(|| channel.send("!".to_string()));
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(channel.len(), 2);
+        assert_eq!(channel.receive(), "hello");
+        blocked.join().unwrap();
+    });
+    assert_eq!(channel.queued_bytes(), Some(6));
+    assert_eq!(channel.drain(), ["world", "!"]);
+
+    // a message bigger than the whole budget still gets through once the queue is empty
+    channel.send("a long message".to_string());
+    assert_eq!(channel.receive_up_to(5), ["a long message"]);
+    assert_eq!(channel.queued_bytes(), Some(0));
+    assert_eq!(MutexChannel::<String>::new().queued_bytes(), None);
+}
+
+#[test]
+fn acked_redelivery() {
+    let channel = MutexChannel::with_byte_budget(100, |_: &u32| 10);
+    channel.send(1);
+    channel.send(2);
+    thread::scope(|s| {
+        let worker = s.spawn(|| {
+            let delivery = channel.receive_acked();
+            assert_eq!(*delivery, 1);
+            panic!("the worker died before it acked");
+        });
+        assert!(worker.join().is_err());
+    });
+    // back at the front, ahead of 2, with its bytes counted again
+    assert_eq!(channel.queued_bytes(), Some(20));
+    assert_eq!(channel.receive_acked().ack(), 1);
+    // dropped without a panic, which is as good as an ack
+    drop(channel.receive_acked());
+    assert!(channel.is_empty());
+}
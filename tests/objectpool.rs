@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::objectpool::ObjectPool;
+
+#[test]
+fn object_pool() {
+    let pool = ObjectPool::with_cap(2, || Vec::<u8>::with_capacity(1024));
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for i in 0..100 {
+                    let mut buffer = pool.checkout();
+                    buffer.clear();
+                    buffer.push(i);
+                }
+            });
+        }
+    });
+    // the four threads shared (at most) two buffers between them
+    assert!(pool.created() <= 2);
+    assert_eq!(pool.idle(), pool.created());
+
+    let a = pool.try_checkout().unwrap();
+    let _b = pool.try_checkout().unwrap();
+    assert!(pool.try_checkout().is_none());
+    // detaching one makes room for the factory to make a new one
+    let a = a.detach();
+    assert!(a.capacity() >= 1024);
+    assert!(pool.try_checkout().is_some());
+}
+
+#[test]
+fn checkout_waits_for_a_return() {
+    let pool = ObjectPool::with_cap(1, || 0);
+    let mut only = pool.checkout();
+    *only = 7;
+    thread::scope(|s| {
+        // blocks until the main thread hands the object back, and gets the same one
+        let waiter = s.spawn(|| *pool.checkout());
+        drop(only);
+        assert_eq!(waiter.join().unwrap(), 7);
+    });
+    assert_eq!(pool.created(), 1);
+}
+
+#[test]
+fn reused_in_the_order_returned() {
+    let made = AtomicUsize::new(0);
+    let pool = ObjectPool::new(move || made.fetch_add(1, Relaxed));
+    let mut objects: Vec<_> = (0..3).map(|_| Some(pool.checkout())).collect();
+    // handed back 2, 0, 1, and handed out again in that order
+    for i in [2, 0, 1] {
+        objects[i] = None;
+    }
+    let reused: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    assert_eq!(reused.iter().map(|object| **object).collect::<Vec<_>>(), [2, 0, 1]);
+    assert_eq!(pool.created(), 3);
+}
@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::oneshotchannel::{Channel, OneshotChannel};
+
+#[test]
+fn oneshot_channel() {
+    let channel = OneshotChannel::new();
+    let t = thread::current();
+    thread::scope(|s| {
+        s.spawn(|| {
+            channel.send("hello world!");
+            t.unpark();
+        });
+        while !channel.is_ready() {
+            thread::park();
+        }
+        assert_eq!(channel.len(), 1);
+        assert_eq!(channel.receive(), "hello world!");
+        assert!(channel.is_empty());
+    })
+}
+
+#[test]
+fn oneshot_channel_with_sender_and_receiver() {
+    let mut channel = Channel::new();
+    thread::scope(|s| {
+        let (sender, receiver) = channel.split();
+        s.spawn(move || {
+            sender.send("hello world!");
+        });
+        assert_eq!(receiver.receive(), "hello world!");
+        #[cfg(feature = "metrics")]
+        assert_eq!((receiver.stats().sends, receiver.stats().receives), (1, 1));
+    })
+}
+
+#[test]
+#[should_panic(expected = "Can't send more than one message!")]
+fn double_send() {
+    let channel = OneshotChannel::new();
+    channel.send(1);
+    channel.send(2);
+}
+
+#[test]
+#[should_panic(expected = "No message available!")]
+fn receive_before_send() {
+    OneshotChannel::<i32>::new().receive();
+}
+
+#[test]
+fn drop_without_receive() {
+    static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct DetectDrop;
+
+    impl Drop for DetectDrop {
+        fn drop(&mut self) {
+            NUM_DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    // a message nobody received is dropped with the channel, from either kind
+    let channel = OneshotChannel::new();
+    channel.send(DetectDrop);
+    drop(channel);
+    assert_eq!(NUM_DROPS.load(Relaxed), 1);
+
+    let mut channel = Channel::new();
+    {
+        let (sender, _receiver) = channel.split();
+        sender.send(DetectDrop);
+    }
+    drop(channel);
+    assert_eq!(NUM_DROPS.load(Relaxed), 2);
+
+    // and one that was received isn't dropped a second time
+    let channel = OneshotChannel::new();
+    channel.send(DetectDrop);
+    drop(channel.receive());
+    drop(channel);
+    assert_eq!(NUM_DROPS.load(Relaxed), 3);
+}
+
+#[test]
+fn sender_notices_receiver_gone() {
+    let mut channel = Channel::<u64>::new();
+    thread::scope(|s| {
+        let (sender, receiver) = channel.split();
+        assert!(!sender.is_closed());
+        s.spawn(move || {
+            // stands in for a long computation, which gives up once the result isn't wanted
+            sender.closed();
+            assert!(sender.is_closed());
+        });
+        // give the sender a chance to go to sleep first, though it works either way round
+        thread::sleep(Duration::from_millis(10));
+        drop(receiver);
+    });
+
+    // already gone before the sender asks
+    let (sender, receiver) = channel.split();
+    drop(receiver);
+    assert!(sender.is_closed());
+    sender.closed();
+}
@@ -0,0 +1,349 @@
+// The ordering claims the primitives make in their comments ("Release matches the Acquire load in wait, so
+// anything written before set is visible to the waiters"), one test each, so weakening one of those orderings
+// fails a test rather than quietly turning into a data race.
+//
+// Each test writes plain, non-atomic memory on one thread and reads it on another, with nothing but the primitive
+// in between to order the two - no joins or channels of std's in the way. Run natively they only check the values;
+// it's under Miri that they mean something, as its race detector tracks happens-before exactly, and flags the
+// read (or the free) as a data race as soon as the Release or Acquire it relied on isn't there. Miri also picks
+// a different interleaving and different stale values for Relaxed loads on every seed, so it's worth going
+// through a few:
+//
+//     MIRIFLAGS="-Zmiri-many-seeds=0..16" cargo +nightly miri test --test orderings
+use std::cell::UnsafeCell;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+
+use rust_atomic_locks::arc::Arc;
+use rust_atomic_locks::atomicoption::AtomicOption;
+use rust_atomic_locks::boundedchannel::sync_channel;
+use rust_atomic_locks::boundedqueue::BoundedQueue;
+use rust_atomic_locks::event::Event;
+use rust_atomic_locks::frozencell::FrozenCell;
+use rust_atomic_locks::hybridlock::HybridLock;
+use rust_atomic_locks::latch::Latch;
+use rust_atomic_locks::mutex::Mutex;
+use rust_atomic_locks::oneshotchannel::Channel;
+use rust_atomic_locks::parker::Parker;
+use rust_atomic_locks::racecell::RaceCell;
+use rust_atomic_locks::rwspinlock::RwSpinLock;
+use rust_atomic_locks::spinlock::SpinLock;
+use rust_atomic_locks::triplebuffer::triple_buffer;
+use rust_atomic_locks::waitstrategy::SpinThenYield;
+
+// Memory the test orders by hand, through the primitive under test and nothing else
+struct Unsynced<T>(UnsafeCell<T>);
+
+unsafe impl<T: Send> Sync for Unsynced<T> {}
+
+impl<T: Copy> Unsynced<T> {
+    fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    fn write(&self, value: T) {
+        unsafe { *self.0.get() = value }
+    }
+
+    fn read(&self) -> T {
+        unsafe { *self.0.get() }
+    }
+}
+
+// Waits for the other thread to be done on a Relaxed flag, which orders nothing. For the primitives that park:
+// the wake on the way to a parked thread orders things by itself, so the tests have them find the other side
+// already done instead, with nothing but the ordering under test between the two
+fn after(done: &AtomicBool) {
+    while !done.load(Relaxed) {
+        thread::yield_now();
+    }
+}
+
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 1000 };
+
+// SpinLock: unlock's Release store matches the next lock's Acquire swap
+#[test]
+fn spinlock_unlock_to_lock() {
+    let lock = SpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), 2 * ROUNDS);
+}
+
+// Mutex: the same, through the futex slow path too
+#[test]
+fn mutex_unlock_to_lock() {
+    let lock = Mutex::new(0);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), 2 * ROUNDS);
+}
+
+// HybridLock: the same again, whichever mode it's in
+#[test]
+fn hybridlock_unlock_to_lock() {
+    let lock = HybridLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), 2 * ROUNDS);
+}
+
+// RwSpinLock: a write guard's Release on drop matches a reader's Acquire, and the readers' Release on the way
+// out matches the next writer's Acquire (or the writer would be writing while they read)
+#[test]
+fn rwspinlock_writer_to_readers() {
+    let lock = RwSpinLock::new(0);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..ROUNDS {
+                *lock.write() += 1;
+            }
+        });
+        for _ in 0..2 {
+            s.spawn(|| {
+                let mut last = 0;
+                for _ in 0..ROUNDS {
+                    let now = *lock.read();
+                    assert!(now >= last);
+                    last = now;
+                }
+            });
+        }
+    });
+}
+
+// BoundedQueue: push's Release publishes the value to pop's Acquire, and pop's Release hands the slot back to
+// the push that reuses it - with one slot, every push reuses it
+#[test]
+fn boundedqueue_push_to_pop_and_back() {
+    let queue = BoundedQueue::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for n in 0..ROUNDS {
+                let mut message = vec![n];
+                while let Err(back) = queue.push(message) {
+                    message = back;
+                    thread::yield_now();
+                }
+            }
+        });
+        for n in 0..ROUNDS {
+            let message = loop {
+                match queue.pop() {
+                    Some(message) => break message,
+                    None => thread::yield_now(),
+                }
+            };
+            assert_eq!(message, [n]);
+        }
+    });
+}
+
+// The bounded channel: what the sender wrote before send is there after receive. Each message gets its own
+// cell, as the sender's a message ahead and would be writing the one the receiver's reading otherwise
+#[test]
+fn boundedchannel_send_to_receive() {
+    let (sender, receiver) = sync_channel(1);
+    let written: Vec<_> = (0..ROUNDS).map(|_| Unsynced::new(0)).collect();
+    thread::scope(|s| {
+        s.spawn(|| {
+            for (n, cell) in written.iter().enumerate() {
+                cell.write(n + 1);
+                sender.send(n).unwrap();
+            }
+        });
+        for (n, cell) in written.iter().enumerate() {
+            assert_eq!(receiver.receive(), Ok(n));
+            assert_eq!(cell.read(), n + 1);
+        }
+    });
+}
+
+// oneshotchannel: send's Release store of ready matches receive's Acquire swap. receive_with and yielding rather
+// than receive, as the unpark on the way to a parked receiver would order things by itself
+#[test]
+fn oneshot_send_to_receive() {
+    for n in 0..ROUNDS {
+        let mut channel = Channel::new();
+        let written = Unsynced::new(0);
+        thread::scope(|s| {
+            let (sender, receiver) = channel.split();
+            s.spawn(|| {
+                written.write(n);
+                sender.send(vec![n]);
+            });
+            assert_eq!(receiver.receive_with(&SpinThenYield { spins: 0 }), [n]);
+            assert_eq!(written.read(), n);
+        });
+    }
+}
+
+// Event: "Release matches the Acquire load in wait, so anything written before set is visible to the waiters"
+#[test]
+fn event_set_to_wait() {
+    let event = Event::new();
+    let written = Unsynced::new(0);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                event.wait();
+                assert_eq!(written.read(), 1);
+            });
+        }
+        written.write(1);
+        event.set();
+    });
+}
+
+// Latch: every count_down's writes are visible once wait returns
+#[test]
+fn latch_count_down_to_wait() {
+    let latch = Latch::new(2);
+    let written = [Unsynced::new(0), Unsynced::new(0)];
+    thread::scope(|s| {
+        for (i, slot) in written.iter().enumerate() {
+            let latch = &latch;
+            s.spawn(move || {
+                slot.write(i + 1);
+                latch.count_down();
+            });
+        }
+        latch.wait();
+        assert_eq!([written[0].read(), written[1].read()], [1, 2]);
+    });
+}
+
+// Parker: "Acquire, so whatever the unparking thread did before unpark is visible after"
+#[test]
+fn parker_unpark_to_park() {
+    let parker = Parker::new();
+    let written = Unsynced::new(0);
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            written.write(1);
+            parker.unpark();
+            done.store(true, Relaxed);
+        });
+        after(&done);
+        parker.park();
+        assert_eq!(written.read(), 1);
+    });
+}
+
+// RaceCell: "Release, so whoever sees SET sees the value too"
+#[test]
+fn racecell_set_to_get() {
+    let cell = RaceCell::new();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            cell.try_set(vec![1, 2, 3]).unwrap();
+            done.store(true, Relaxed);
+        });
+        after(&done);
+        assert_eq!(cell.wait(), &[1, 2, 3]);
+    });
+}
+
+// FrozenCell: "Acquire, so the last writer's writes are all visible to this thread" in freeze, and get's Acquire
+// sees them too. The writer's a different thread to the one freezing, or it'd be in order anyway
+#[test]
+fn frozencell_write_to_freeze_to_get() {
+    let cell = FrozenCell::new(Vec::new());
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            cell.write().unwrap().push(1);
+            done.store(true, Relaxed);
+        });
+        s.spawn(|| {
+            let value = loop {
+                match cell.try_get() {
+                    Some(value) => break value,
+                    None => thread::yield_now(),
+                }
+            };
+            assert_eq!(value, &[1]);
+        });
+        after(&done);
+        assert_eq!(cell.freeze(), &[1]);
+    });
+}
+
+// AtomicOption: "Release for the value going in, Acquire for the one coming out"
+#[test]
+fn atomicoption_swap_to_take() {
+    let slot = AtomicOption::none();
+    thread::scope(|s| {
+        s.spawn(|| slot.swap(Some(Box::new(vec![7]))));
+        let taken = loop {
+            match slot.take() {
+                Some(taken) => break taken,
+                None => thread::yield_now(),
+            }
+        };
+        assert_eq!(*taken, [7]);
+    });
+}
+
+// TripleBuffer: publish's Release (of its AcqRel) matches read's Acquire
+#[test]
+fn triplebuffer_publish_to_read() {
+    let (mut input, mut output) = triple_buffer(Vec::new());
+    thread::scope(|s| {
+        s.spawn(move || {
+            for n in 1..=ROUNDS {
+                input.write(vec![n; 4]);
+            }
+        });
+        let mut last = 0;
+        while last < ROUNDS {
+            let value = output.read();
+            if let Some(&n) = value.first() {
+                assert!(value.iter().all(|&m| m == n) && n >= last);
+                last = n;
+            }
+            thread::yield_now();
+        }
+    });
+}
+
+// Arc: every drop but the last is a Release decrement, and the last one's Acquire fence sees all of them before
+// it frees the data - so a clone read on another thread can't race with the free
+#[test]
+fn arc_drop_to_free() {
+    for _ in 0..ROUNDS {
+        let data = Arc::new(vec![1, 2, 3]);
+        let clone = data.clone();
+        thread::scope(|s| {
+            s.spawn(move || {
+                assert_eq!(clone.iter().sum::<i32>(), 6);
+                drop(clone);
+            });
+            drop(data);
+        });
+    }
+}
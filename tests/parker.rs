@@ -0,0 +1,30 @@
+use std::sync::atomic::{AtomicBool, Ordering::{Acquire, Release}};
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::parker::Parker;
+
+#[test]
+fn parker() {
+    let parker = Parker::new();
+    // the token's kept, so a park after an unpark doesn't sleep (and only one is kept)
+    parker.unpark();
+    parker.unpark();
+    parker.park();
+
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(10));
+            done.store(true, Release);
+            parker.unpark();
+        });
+        // a loop, like thread::park, as there's nothing else to say it was this unpark that woke it
+        while !done.load(Acquire) {
+            parker.park();
+        }
+    });
+
+    // with nobody to unpark it, only the timeout gets it out
+    parker.park_timeout(Duration::from_millis(5));
+}
@@ -0,0 +1,80 @@
+// PiMutex is Linux only, and Miri doesn't know the PI futex operations
+#![cfg(all(target_os = "linux", feature = "pi_mutex", not(miri)))]
+
+use std::fmt::{self, Write};
+use std::thread;
+
+use rust_atomic_locks::pimutex::PiMutex;
+
+#[test]
+fn pi_mutex() {
+    let counter = PiMutex::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..1000 {
+                    *counter.lock() += 1;
+                }
+            });
+        }
+    });
+    let guard = counter.lock();
+    assert!(counter.try_lock().is_none());
+    drop(guard);
+    let mut counter = counter;
+    *counter.get_mut() += 1;
+    assert_eq!(counter.into_inner(), 4001);
+}
+
+#[test]
+fn handed_over_to_a_waiter() {
+    // a thread blocked in the kernel gets the lock when it's unlocked, with the value the holder left
+    let value = PiMutex::new(0);
+    thread::scope(|s| {
+        let mut guard = value.lock();
+        let waiter = s.spawn(|| *value.lock());
+        thread::sleep(std::time::Duration::from_millis(10));
+        *guard = 5;
+        drop(guard);
+        assert_eq!(waiter.join().unwrap(), 5);
+    });
+}
+
+// Formats into a buffer on the stack - a forked child of a process with other threads can't allocate safely
+struct StackString {
+    buf: [u8; 64],
+    len: usize,
+}
+
+impl Write for StackString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+// a child forked by a thread that's used a PiMutex locks with its own thread id, not the parent's
+#[test]
+fn owner_after_fork() {
+    drop(PiMutex::new(()).lock());
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed: {}", std::io::Error::last_os_error()),
+        0 => {
+            let mutex = PiMutex::new(());
+            let _guard = mutex.lock();
+            let mut owner = StackString { buf: [0; 64], len: 0 };
+            let mut expected = StackString { buf: [0; 64], len: 0 };
+            let _ = write!(owner, "{mutex:?}");
+            let _ = write!(expected, "PiMutex {{ owner: Some({}), .. }}", unsafe { libc::gettid() });
+            let same = owner.buf[..owner.len] == expected.buf[..expected.len];
+            unsafe { libc::_exit(if same { 0 } else { 1 }) };
+        }
+        child => {
+            let mut status = 0;
+            unsafe { libc::waitpid(child, &mut status, 0) };
+            assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0, "the child saw the parent's tid");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::pipeline::{Cancelled, Pipeline};
+
+const ITEMS: u64 = if cfg!(miri) { 50 } else { 10_000 };
+
+#[test]
+fn stages_in_parallel() {
+    let mut squares = Pipeline::source(4, 0..ITEMS)
+        .map(3, |n| n * n)
+        .map(2, |n| n + 1)
+        .collect()
+        .unwrap();
+    // spread over several threads, so the order's gone
+    squares.sort();
+    assert_eq!(squares, (0..ITEMS).map(|n| n * n + 1).collect::<Vec<_>>());
+}
+
+#[test]
+fn error_stops_everything_upstream() {
+    let produced = Arc::new(AtomicUsize::new(0));
+    let counting = {
+        let produced = produced.clone();
+        (0..).inspect(move |_| {
+            produced.fetch_add(1, Relaxed);
+        })
+    };
+    // an endless source, which only stops because the stage after it fails
+    let result = Pipeline::source(2, counting)
+        .try_map(2, |n: u64| if n == 10 { Err(format!("bad item {n}")) } else { Ok(n) })
+        .run(|_| {});
+    assert_eq!(result.unwrap_err().to_string(), "bad item 10");
+    // the channels are small, so the source didn't get far past the failing item
+    assert!(produced.load(Relaxed) < 100, "{} produced", produced.load(Relaxed));
+}
+
+#[test]
+fn sink_error_and_cancel() {
+    let result = Pipeline::source(2, 0..ITEMS).map(2, |n| n).try_run(|n| if n > 5 { Err("too big") } else { Ok(()) });
+    assert_eq!(result.unwrap_err().to_string(), "too big");
+
+    // cancelled from another thread while the sink's waiting on a stage that's taking forever
+    let pipeline = Pipeline::source(2, 0..).map(1, |n: u64| {
+        thread::sleep(Duration::from_millis(1));
+        n
+    });
+    let canceller = pipeline.canceller();
+    let result = thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            canceller.cancel();
+        });
+        pipeline.run(|_| {})
+    });
+    assert!(result.unwrap_err().is::<Cancelled>());
+}
+
+#[test]
+#[should_panic(expected = "stage panicked")]
+fn panic_is_passed_on() {
+    let _ = Pipeline::source(2, 0..10).map(1, |n: i32| assert!(n < 5, "stage panicked")).collect();
+}
+
+#[test]
+fn map_ordered_keeps_the_order() {
+    // the odd items take longer, so they finish after the even ones that came in behind them
+    let items = Pipeline::source(4, 0..ITEMS.min(200))
+        .map_ordered(4, |n| {
+            if n % 2 == 1 {
+                thread::sleep(Duration::from_micros(100));
+            }
+            n * 10
+        })
+        .collect()
+        .unwrap();
+    assert_eq!(items, (0..ITEMS.min(200)).map(|n| n * 10).collect::<Vec<_>>());
+}
@@ -0,0 +1,73 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::boundedchannel::{RecvError, RecvTimeoutError, SendError};
+use rust_atomic_locks::prioritychannel::{priority_channel, priority_channel_with_burst};
+
+#[test]
+fn priority_jumps_the_queue() {
+    let (sender, receiver) = priority_channel();
+    for n in 0..100 {
+        sender.send(n).unwrap();
+    }
+    sender.send_priority(-1).unwrap();
+    assert_eq!(receiver.priority_len(), 1);
+    assert_eq!(receiver.receive(), Ok(-1));
+    assert_eq!(receiver.receive(), Ok(0));
+    assert_eq!(receiver.len(), 99);
+}
+
+// A normal message gets through after every burst of priority ones, and the rest in order once the priority
+// lane's empty
+#[test]
+fn burst_lets_normal_through() {
+    let (sender, receiver) = priority_channel_with_burst(2);
+    for n in 1..=5 {
+        sender.send(n).unwrap();
+    }
+    for n in 1..=6 {
+        sender.send_priority(-n).unwrap();
+    }
+    drop(sender);
+    let received: Vec<i32> = receiver.iter().collect();
+    assert_eq!(received, [-1, -2, 1, -3, -4, 2, -5, -6, 3, 4, 5]);
+}
+
+#[test]
+fn disconnects() {
+    let (sender, receiver) = priority_channel();
+    assert_eq!(receiver.receive_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Timeout));
+    sender.send_priority("left behind").unwrap();
+    drop(sender);
+    assert_eq!(receiver.receive(), Ok("left behind"));
+    assert_eq!(receiver.receive(), Err(RecvError));
+
+    let (sender, receiver) = priority_channel();
+    drop(receiver);
+    assert_eq!(sender.send(1), Err(SendError(1)));
+}
+
+#[test]
+fn senders_on_other_threads() {
+    let (sender, receiver) = priority_channel();
+    let per_sender = if cfg!(miri) { 20 } else { 1000 };
+    thread::scope(|s| {
+        for _ in 0..2 {
+            let sender = sender.clone();
+            s.spawn(move || {
+                for n in 0..per_sender {
+                    if n % 10 == 0 {
+                        sender.send_priority(n).unwrap();
+                    } else {
+                        sender.send(n).unwrap();
+                    }
+                }
+            });
+        }
+        drop(sender);
+        let mut received = receiver.iter().collect::<Vec<_>>();
+        assert_eq!(received.len(), 2 * per_sender);
+        received.sort();
+        assert!(received.chunks(2).all(|pair| pair[0] == pair[1]));
+    });
+}
@@ -0,0 +1,66 @@
+use std::thread;
+
+use rust_atomic_locks::boundedchannel::TryRecvError;
+use rust_atomic_locks::pubsub::{Bus, SlowSubscriber};
+
+#[test]
+fn publish_and_unsubscribe() {
+    let bus = Bus::new();
+    let a = bus.subscribe("prices", 4);
+    let b = bus.subscribe("prices", 4);
+    let news = bus.subscribe("news", 4);
+    assert_eq!(bus.subscribers("prices"), 2);
+
+    assert_eq!(bus.publish("prices", 1), 2);
+    assert_eq!(bus.publish("weather", 2), 0);
+    assert_eq!(a.try_receive(), Ok(1));
+    assert_eq!(b.try_receive(), Ok(1));
+    assert_eq!(news.try_receive(), Err(TryRecvError::Empty));
+
+    drop(a);
+    assert_eq!(bus.subscribers("prices"), 1);
+    assert_eq!(bus.publish("prices", 3), 1);
+    assert_eq!(b.try_receive(), Ok(3));
+    drop(b);
+    assert_eq!(bus.topics(), ["news"]);
+
+    // with the bus gone, subscribers find their channels disconnected
+    drop(bus);
+    assert_eq!(news.try_receive(), Err(TryRecvError::Disconnected));
+}
+
+#[test]
+fn slow_subscribers() {
+    let bus = Bus::new();
+    let newest = bus.subscribe_with("t", 2, SlowSubscriber::DropNewest);
+    let oldest = bus.subscribe_with("t", 2, SlowSubscriber::DropOldest);
+    let cut_off = bus.subscribe_with("t", 2, SlowSubscriber::Disconnect);
+    for i in 0..3 {
+        bus.publish("t", i);
+    }
+    assert_eq!(newest.iter().take(2).collect::<Vec<_>>(), [0, 1]);
+    assert_eq!(newest.dropped(), 1);
+    assert_eq!(oldest.iter().take(2).collect::<Vec<_>>(), [1, 2]);
+    // it gets what made it in before it was cut off
+    assert_eq!(bus.subscribers("t"), 2);
+    assert_eq!(cut_off.iter().collect::<Vec<_>>(), [0, 1]);
+}
+
+#[test]
+fn blocking_subscribers() {
+    let n = if cfg!(miri) { 20 } else { 2000 };
+    let bus = Bus::new();
+    let subscriptions: Vec<_> = (0..3).map(|_| bus.subscribe(0, 4)).collect();
+    thread::scope(|s| {
+        for subscription in &subscriptions {
+            s.spawn(move || {
+                for i in 0..n {
+                    assert_eq!(subscription.receive(), Ok(i));
+                }
+            });
+        }
+        for i in 0..n {
+            assert_eq!(bus.publish(&0, i), 3);
+        }
+    });
+}
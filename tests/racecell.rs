@@ -0,0 +1,35 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::racecell::RaceCell;
+
+#[test]
+fn first_writer_wins() {
+    let cell = RaceCell::new();
+    let won = thread::scope(|s| {
+        let cell = &cell;
+        let waiters: Vec<_> = (0..2).map(|_| s.spawn(|| *cell.wait())).collect();
+        let racers: Vec<_> = (0..4).map(|i| s.spawn(move || cell.try_set(i).is_ok())).collect();
+        let winners = racers.into_iter().filter_map(|racer| racer.join().unwrap().then_some(())).count();
+        assert_eq!(winners, 1);
+        // the waiters all see the one winning value
+        let seen: Vec<_> = waiters.into_iter().map(|waiter| waiter.join().unwrap()).collect();
+        assert_eq!(seen[0], seen[1]);
+        seen[0]
+    });
+    assert_eq!(cell.get(), Some(&won));
+    assert_eq!(cell.try_set(10), Err(10));
+    assert_eq!(cell.into_inner(), Some(won));
+}
+
+#[test]
+fn wait_timeout() {
+    let mut cell = RaceCell::new();
+    assert_eq!(cell.wait_timeout(Duration::from_millis(5)), None);
+    cell.try_set(String::from("done")).unwrap();
+    assert_eq!(cell.wait_timeout(Duration::from_millis(5)).map(String::as_str), Some("done"));
+    assert_eq!(format!("{cell:?}"), "RaceCell(\"done\")");
+    // taken out, the cell can be raced for again
+    assert_eq!(cell.take().as_deref(), Some("done"));
+    assert!(!cell.is_set());
+}
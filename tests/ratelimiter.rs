@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_atomic_locks::ratelimiter::RateLimiter;
+
+const ATTEMPTS: usize = if cfg!(miri) { 50 } else { 100_000 };
+
+#[test]
+fn burst_then_refill() {
+    // one token every 10ms, up to 5 at once. Miri's clock moves on a few ms per call, so it gets 100ms
+    let scale = if cfg!(miri) { 10 } else { 1 };
+    let limiter = RateLimiter::per_second(100 / scale, 5);
+    assert_eq!(limiter.available(), 5);
+    assert!(limiter.try_acquire(3));
+    assert!(limiter.try_acquire(2));
+    // empty now, and asking for more than it can ever hold never works
+    assert!(!limiter.try_acquire(1));
+    assert!(!limiter.try_acquire(6));
+
+    thread::sleep(Duration::from_millis(25 * scale));
+    // two tokens back (and most of the way to a third)
+    assert!(limiter.available() >= 2);
+    assert!(limiter.try_acquire(2));
+}
+
+#[test]
+fn acquire_waits_for_tokens() {
+    let limiter = RateLimiter::per_second(100, 1);
+    let start = Instant::now();
+    // one straight away, then one every 10ms
+    for _ in 0..4 {
+        limiter.acquire(1);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test]
+fn threads_share_the_rate() {
+    // however many threads are trying, no more than burst plus what refilled gets through
+    let limiter = RateLimiter::per_second(1000, 10);
+    let taken = AtomicU64::new(0);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..ATTEMPTS {
+                    if limiter.try_acquire(1) {
+                        taken.fetch_add(1, Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    // the bucket starts full, so the first ten always get through
+    let allowed = 10 + start.elapsed().as_millis() as u64;
+    assert!(taken.load(Relaxed) <= allowed, "{} taken, only {allowed} allowed", taken.load(Relaxed));
+    assert!(taken.load(Relaxed) >= 10);
+}
+
+#[test]
+#[should_panic(expected = "only holds 2")]
+fn acquire_more_than_burst() {
+    RateLimiter::per_second(10, 2).acquire(3);
+}
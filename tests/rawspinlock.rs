@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::rawspinlock::RawSpinLock;
+
+#[test]
+fn raw_spinlock() {
+    let iters = if cfg!(miri) { 20 } else { 1000 };
+    // the lock lives in a plain buffer of memory here, which stands in for a region shared between processes
+    let mut region = [0u32; 4];
+    let lock = unsafe { RawSpinLock::init_at(region.as_mut_ptr().cast()) };
+    let counter = AtomicU32::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..iters {
+                    let _guard = lock.lock();
+                    // a load then a store is only safe because the lock is held
+                    counter.store(counter.load(Relaxed) + 1, Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.load(Relaxed), 4 * iters);
+    let guard = lock.try_lock().unwrap();
+    assert!(lock.try_lock().is_none());
+    assert_eq!(lock.owner(), Some(std::process::id()));
+    drop(guard);
+    assert!(!lock.is_locked());
+}
+
+#[test]
+fn failed_try_lock_leaves_the_lock_alone() {
+    // a try_lock that loses mustn't unlock the lock for whoever won it
+    let lock = RawSpinLock::new();
+    let guard = lock.lock();
+    for _ in 0..3 {
+        assert!(lock.try_lock().is_none());
+    }
+    assert!(lock.is_locked());
+    drop(guard);
+    assert!(!lock.is_locked());
+}
+
+// kill and fork are beyond Miri
+#[cfg(unix)]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn lock_robust() {
+    let lock = RawSpinLock::new();
+    assert!(lock.lock_robust().is_ok());
+}
+
+// a child process takes the lock and dies without unlocking it, and the parent takes it over
+#[cfg(all(unix, feature = "shared_memory"))]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn dead_owner_recovered() {
+    let region = rust_atomic_locks::sharedmem::SharedRegion::anonymous(4).unwrap();
+    let lock = unsafe { RawSpinLock::init_at(region.as_ptr().cast()) };
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed: {}", std::io::Error::last_os_error()),
+        0 => {
+            std::mem::forget(lock.lock());
+            unsafe { libc::_exit(0) };
+        }
+        child => {
+            // reap the child first, a zombie still counts as alive
+            unsafe { libc::waitpid(child, std::ptr::null_mut(), 0) };
+            assert_eq!(lock.owner(), Some(child as u32));
+            let Err(recovered) = lock.lock_robust() else { panic!("the dead owner wasn't noticed") };
+            assert_eq!(recovered.dead_owner, child as u32);
+            drop(recovered.into_guard());
+        }
+    }
+    assert!(lock.lock_robust().is_ok());
+}
@@ -0,0 +1,58 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::boundedchannel::{sync_channel, RecvError, RecvTimeoutError, TryRecvError};
+use rust_atomic_locks::receive::Receive;
+
+#[test]
+fn map_and_filter() {
+    let (sender, receiver) = sync_channel(16);
+    let evens = receiver.filter(|n: &i32| n % 2 == 0).map(|n| n * 10);
+    for n in 0..6 {
+        sender.send(n).unwrap();
+    }
+    assert_eq!(Receive::try_receive(&evens), Ok(0));
+    assert_eq!(Receive::try_receive(&evens), Ok(20));
+    assert_eq!(Receive::try_receive(&evens), Ok(40));
+    // 5 was taken out and turned down on the way
+    assert_eq!(Receive::try_receive(&evens), Err(TryRecvError::Empty));
+    drop(sender);
+    assert_eq!(evens.receive(), Err(RecvError));
+}
+
+#[test]
+fn merge_blocks_on_both() {
+    let (numbers, number_receiver) = sync_channel(4);
+    let (words, word_receiver) = sync_channel(4);
+    let merged = number_receiver.map(|n: u32| n.to_string()).merge(word_receiver);
+    let count = if cfg!(miri) { 10 } else { 1000 };
+    thread::scope(|s| {
+        s.spawn(move || {
+            for n in 0..count {
+                numbers.send(n).unwrap();
+            }
+        });
+        s.spawn(move || {
+            for _ in 0..count {
+                words.send("word".to_string()).unwrap();
+            }
+        });
+        // disconnected only once both senders are gone, with everything received
+        let received: Vec<String> = merged.iter().collect();
+        assert_eq!(received.len(), 2 * count as usize);
+        assert_eq!(received.iter().filter(|m| *m == "word").count(), count as usize);
+    });
+}
+
+#[test]
+fn merge_timeout() {
+    let (_first_sender, first) = sync_channel::<i32>(1);
+    let (second_sender, second) = sync_channel(1);
+    let merged = first.merge(second);
+    assert_eq!(merged.receive_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Timeout));
+    second_sender.send(7).unwrap();
+    assert_eq!(merged.receive_timeout(Duration::from_millis(10)), Ok(7));
+    // one side gone isn't a disconnect
+    drop(second_sender);
+    assert_eq!(merged.receive_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Timeout));
+}
@@ -0,0 +1,47 @@
+use std::thread;
+
+use rust_atomic_locks::registry::{self, LockRegistry};
+
+const ITERS: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+#[test]
+fn same_name_same_lock() {
+    let locks = LockRegistry::new();
+    let a = locks.lock("a");
+    // held, so nobody else gets it, but other names are separate locks
+    assert!(locks.try_lock("a").is_none());
+    let b = locks.try_lock("b").unwrap();
+    drop(a);
+    assert!(locks.try_lock("a").is_some());
+    drop(b);
+
+    // with nobody holding them, the locks are gone and only the dead entries are left to prune
+    assert!(locks.len() <= 2);
+}
+
+#[test]
+fn serialises_threads() {
+    // a non-atomic read-modify-write, which loses counts if two threads are ever in at once
+    static mut COUNTER: usize = 0;
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    let _guard = registry::lock("counter");
+                    unsafe { COUNTER += 1 };
+                }
+            });
+        }
+    });
+    let _guard = registry::lock("counter");
+    assert_eq!(unsafe { COUNTER }, 4 * ITERS);
+}
+
+#[test]
+fn unused_locks_are_pruned() {
+    let locks = LockRegistry::new();
+    for i in 0..1000 {
+        drop(locks.lock(&format!("resource-{i}")));
+    }
+    assert!(locks.len() < 100, "{} entries left", locks.len());
+}
@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use rust_atomic_locks::relax::{cpu_relax, set_spin_hint, spin_hint, SpinHint, MAX_REPEAT};
+
+// The hint's global, so it's all one test rather than several racing to set it
+#[test]
+fn spin_hints() {
+    static RELAXED: AtomicUsize = AtomicUsize::new(0);
+    assert!(matches!(spin_hint(), SpinHint::Default));
+
+    set_spin_hint(SpinHint::Custom(|| {
+        RELAXED.fetch_add(1, Relaxed);
+    }));
+    cpu_relax();
+    cpu_relax();
+    assert_eq!(RELAXED.load(Relaxed), 2);
+
+    // Repeat always spins at least once, and no more than MAX_REPEAT times
+    set_spin_hint(SpinHint::Repeat(0));
+    assert!(matches!(spin_hint(), SpinHint::Repeat(1)));
+    set_spin_hint(SpinHint::Repeat(u32::MAX));
+    assert!(matches!(spin_hint(), SpinHint::Repeat(MAX_REPEAT)));
+    set_spin_hint(SpinHint::Repeat(8));
+    cpu_relax();
+
+    for hint in [SpinHint::Yield, SpinHint::Isb, SpinHint::Default] {
+        set_spin_hint(hint);
+        cpu_relax();
+    }
+    // the custom function's no longer called
+    assert_eq!(RELAXED.load(Relaxed), 2);
+}
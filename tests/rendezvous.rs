@@ -0,0 +1,70 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::rendezvous::{self, CallError, Disconnected};
+
+#[test]
+fn request_and_response() {
+    let (caller, callee) = rendezvous::call();
+    let worker = thread::spawn(move || {
+        let (request, responder) = callee.request().unwrap();
+        responder.respond(format!("hello {request}")).unwrap();
+    });
+    assert_eq!(caller.call("world").as_deref(), Ok("hello world"));
+    worker.join().unwrap();
+
+    // the callee ready and waiting before the call is made
+    let (caller, callee) = rendezvous::call::<u32, u32>();
+    let worker = thread::spawn(move || {
+        let (request, responder) = callee.request().unwrap();
+        responder.respond(request + 1).unwrap();
+    });
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(caller.call(1), Ok(2));
+    worker.join().unwrap();
+}
+
+#[test]
+fn disconnects() {
+    // callee dropped before it got the request
+    let (caller, callee) = rendezvous::call::<i32, i32>();
+    drop(callee);
+    assert_eq!(caller.call(1), Err(CallError::Disconnected));
+
+    // the responder dropped without responding, while the caller's waiting
+    let (caller, callee) = rendezvous::call::<i32, i32>();
+    let worker = thread::spawn(move || drop(callee.request().unwrap()));
+    assert_eq!(caller.call(1), Err(CallError::Disconnected));
+    worker.join().unwrap();
+
+    // the caller dropped without calling
+    let (caller, callee) = rendezvous::call::<i32, i32>();
+    drop(caller);
+    assert_eq!(callee.request().err(), Some(Disconnected));
+}
+
+#[test]
+fn caller_times_out() {
+    let (caller, callee) = rendezvous::call::<i32, String>();
+    thread::scope(|s| {
+        s.spawn(move || {
+            let (_, responder) = callee.request().unwrap();
+            // too slow, so the response comes back unwanted
+            while !responder.is_closed() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(responder.respond(String::from("late")), Err(String::from("late")));
+        });
+        assert_eq!(caller.call_timeout(1, Duration::from_millis(20)), Err(CallError::Timeout));
+    });
+
+    // and one that's answered in time
+    let (caller, callee) = rendezvous::call::<i32, i32>();
+    thread::scope(|s| {
+        s.spawn(move || {
+            let (request, responder) = callee.request().unwrap();
+            responder.respond(-request).unwrap();
+        });
+        assert_eq!(caller.call_timeout(7, Duration::from_secs(10)), Ok(-7));
+    });
+}
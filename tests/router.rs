@@ -0,0 +1,64 @@
+use std::thread;
+
+use rust_atomic_locks::boundedchannel::{SendError, TryRecvError, TrySendError};
+use rust_atomic_locks::router::Router;
+
+#[test]
+fn routes_by_key() {
+    let (router, dead_letters) = Router::new(4);
+    let orders = router.add_route(String::from("orders"), 2);
+    let refunds = router.add_route(String::from("refunds"), 2);
+    assert!(router.has_route("orders"));
+
+    router.send("orders", 1).unwrap();
+    router.send("refunds", 2).unwrap();
+    router.send("returns", 3).unwrap();
+    assert_eq!(orders.try_receive(), Ok(1));
+    assert_eq!(refunds.try_receive(), Ok(2));
+    assert_eq!(dead_letters.try_receive(), Ok(3));
+
+    // a full route hands the message back to try_send rather than dead lettering it
+    router.try_send("orders", 4).unwrap();
+    router.try_send("orders", 5).unwrap();
+    assert_eq!(router.try_send("orders", 6), Err(TrySendError::Full(6)));
+    assert_eq!(dead_letters.try_receive(), Err(TryRecvError::Empty));
+
+    // a route nobody receives on any more dead letters too
+    drop(refunds);
+    router.send("refunds", 7).unwrap();
+    assert_eq!(dead_letters.try_receive(), Ok(7));
+
+    // removing a route leaves what was already sent for its receivers
+    assert!(router.remove_route("orders"));
+    assert!(!router.remove_route("orders"));
+    router.send("orders", 8).unwrap();
+    assert_eq!(orders.iter().collect::<Vec<_>>(), [4, 5]);
+    assert_eq!(dead_letters.try_receive(), Ok(8));
+
+    drop(dead_letters);
+    assert_eq!(router.send("orders", 9), Err(SendError(9)));
+}
+
+#[test]
+fn senders_across_threads() {
+    let (router, _dead_letters) = Router::new(1);
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    let receivers: Vec<_> = (0..4).map(|key| router.add_route(key, 8)).collect();
+    thread::scope(|s| {
+        for key in 0..4 {
+            let router = &router;
+            s.spawn(move || {
+                for i in 0..n {
+                    router.send(&key, i).unwrap();
+                }
+            });
+        }
+        for receiver in &receivers {
+            s.spawn(move || {
+                for i in 0..n {
+                    assert_eq!(receiver.receive(), Ok(i));
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,98 @@
+use std::thread;
+
+use rust_atomic_locks::rwspinlock::RwSpinLock;
+
+// try_read and try_upgradable_read use a weak compare exchange, which is allowed to fail now and then even
+// when the lock is free (and under Miri it does), so a failure only counts after a few tries
+fn retry<T>(mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    (0..100).find_map(|_| f())
+}
+
+#[test]
+fn rwspinlock() {
+    let x = RwSpinLock::new(Vec::new());
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                // only push if the vec is still short. Checking with an upgradable read and then upgrading means
+                // the length can't change between the check and the push, which a read then a write couldn't promise
+                let g = x.upgradable_read();
+                if g.len() < 2 {
+                    g.upgrade().push(1);
+                }
+            });
+            s.spawn(|| {
+                let g = x.read();
+                assert!(g.len() <= 2);
+            });
+        }
+    });
+    assert_eq!(*x.read(), [1, 1]);
+    x.write().push(2);
+    let g = x.upgradable_read();
+    // plain readers can share the lock with an upgradable reader, but writers can't
+    assert!(retry(|| x.try_read()).is_some());
+    assert!(x.try_write().is_none());
+    assert!(x.try_upgradable_read().is_none());
+    let g = g.downgrade();
+    assert!(retry(|| x.try_upgradable_read()).is_some());
+    assert_eq!(g.len(), 3);
+    drop(g);
+
+    let mut x = x;
+    x.get_mut().push(4);
+    assert_eq!(x.into_inner(), [1, 1, 2, 4]);
+}
+
+#[test]
+fn readers_never_see_a_half_write() {
+    // the writers keep both halves equal, so a reader that sees them differ got in during a write
+    let iters = if cfg!(miri) { 10 } else { 1000 };
+    let x = RwSpinLock::new((0, 0));
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..iters {
+                    let mut g = x.write();
+                    g.0 += 1;
+                    g.1 += 1;
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..iters {
+                    let g = x.read();
+                    assert_eq!(g.0, g.1);
+                }
+            });
+        }
+    });
+    assert_eq!(*x.read(), (2 * iters, 2 * iters));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "recursive read of an RwSpinLock")]
+fn recursive_read() {
+    let x = RwSpinLock::new(0);
+    // reading again after the first guard is gone is fine, and so is a try_read on top of a read
+    drop(x.read());
+    let _first = x.read();
+    assert!(retry(|| x.try_read()).is_some());
+    // but a second blocking read would wait forever if a writer had started waiting in between
+    let _second = x.read();
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn guard_dropped_on_another_thread() {
+    // the guard is taken off the readers list wherever it's dropped, so this thread can read again after
+    let x = RwSpinLock::new(0);
+    let g = x.read();
+    thread::scope(|s| {
+        s.spawn(move || drop(g));
+    });
+    drop(x.read());
+    // and a downgraded guard is still on the list, dropping it takes it off
+    drop(x.upgradable_read().downgrade());
+    let _g = x.upgradable_read();
+}
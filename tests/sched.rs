@@ -0,0 +1,47 @@
+// The pause points are only compiled into debug builds
+#![cfg(debug_assertions)]
+
+use std::thread;
+
+use rust_atomic_locks::oneshotchannel::OneshotChannel;
+use rust_atomic_locks::rawspinlock::RawSpinLock;
+use rust_atomic_locks::sched::{Scheduler, Until};
+
+#[test]
+fn scheduler() {
+    // thread 0 is stopped while it holds the lock, so thread 1 always finds it locked
+    let lock = RawSpinLock::new();
+    let steps = [(0, Until::Point("RawSpinLock::locked")), (1, Until::Done), (0, Until::Done)];
+    let scheduler = Scheduler::new(steps);
+    thread::scope(|s| {
+        s.spawn(scheduler.thread(0, || drop(lock.lock())));
+        let contended = s.spawn(scheduler.thread(1, || lock.try_lock().is_none())).join().unwrap();
+        assert!(contended);
+    });
+    assert!(!lock.is_locked());
+}
+
+#[test]
+fn message_not_ready_until_published() {
+    // the sender has written the message but not yet said it's ready, so the receiver can't see it
+    let channel = OneshotChannel::new();
+    let scheduler = Scheduler::new([(0, Until::Point("OneshotChannel::send written")), (1, Until::Done), (0, Until::Done)]);
+    thread::scope(|s| {
+        s.spawn(scheduler.thread(0, || channel.send(1)));
+        let ready = s.spawn(scheduler.thread(1, || channel.is_ready())).join().unwrap();
+        assert!(!ready);
+    });
+    assert_eq!(channel.receive(), 1);
+}
+
+#[test]
+fn panicking_thread_doesnt_hold_up_the_rest() {
+    // thread 0 panics before it reaches its pause point, and thread 1 still gets to run
+    let scheduler = Scheduler::new([(0, Until::Point("never reached")), (1, Until::Done)]);
+    thread::scope(|s| {
+        let panicked = s.spawn(scheduler.thread(0, || panic!("on purpose")));
+        let ran = s.spawn(scheduler.thread(1, || true));
+        assert!(panicked.join().is_err());
+        assert!(ran.join().unwrap());
+    });
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::segqueue::SegQueue;
+
+const PER_THREAD: usize = if cfg!(miri) { 100 } else { 100_000 };
+
+#[test]
+fn fifo_across_blocks() {
+    let queue = SegQueue::new();
+    assert!(queue.is_empty());
+    assert_eq!(queue.pop(), None);
+    // enough to go through a few blocks, and to leave the head and tail in different ones
+    for i in 0..100 {
+        queue.push(i);
+    }
+    assert_eq!(queue.len(), 100);
+    for i in 0..70 {
+        assert_eq!(queue.pop(), Some(i));
+    }
+    assert_eq!(queue.len(), 30);
+    for i in 100..150 {
+        queue.push(i);
+    }
+    assert_eq!((70..150).map(|_| queue.pop().unwrap()).collect::<Vec<_>>(), (70..150).collect::<Vec<_>>());
+    assert_eq!(queue.pop(), None);
+    assert!(queue.is_empty());
+    assert_eq!(queue.capacity(), None);
+}
+
+#[test]
+fn many_producers_and_consumers() {
+    let queue = SegQueue::new();
+    let popped = AtomicUsize::new(0);
+    let sum = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for t in 0..2 {
+            let queue = &queue;
+            s.spawn(move || {
+                for i in 0..PER_THREAD {
+                    queue.push(t * PER_THREAD + i);
+                }
+            });
+        }
+        for _ in 0..2 {
+            s.spawn(|| {
+                while popped.load(Relaxed) < 2 * PER_THREAD {
+                    if let Some(n) = queue.pop() {
+                        sum.fetch_add(n, Relaxed);
+                        popped.fetch_add(1, Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    // every value came out exactly once
+    let n = 2 * PER_THREAD;
+    assert_eq!(sum.load(Relaxed), n * (n - 1) / 2);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn order_per_producer() {
+    // values from one producer come out in the order it pushed them, whatever the other one's doing
+    let queue = SegQueue::new();
+    thread::scope(|s| {
+        for t in 0..2 {
+            let queue = &queue;
+            s.spawn(move || {
+                for i in 0..PER_THREAD {
+                    queue.push((t, i));
+                }
+            });
+        }
+        let mut next = [0, 0];
+        while next != [PER_THREAD, PER_THREAD] {
+            if let Some((t, i)) = queue.pop() {
+                assert_eq!(i, next[t]);
+                next[t] += 1;
+            }
+        }
+    });
+}
+
+#[test]
+fn drops_whats_left() {
+    static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct DetectDrop;
+
+    impl Drop for DetectDrop {
+        fn drop(&mut self) {
+            NUM_DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    let queue = SegQueue::new();
+    for _ in 0..50 {
+        queue.push(DetectDrop);
+    }
+    for _ in 0..40 {
+        drop(queue.pop());
+    }
+    assert_eq!(NUM_DROPS.load(Relaxed), 40);
+    drop(queue);
+    assert_eq!(NUM_DROPS.load(Relaxed), 50);
+}
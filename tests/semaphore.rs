@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::semaphore::Semaphore;
+use rust_atomic_locks::waitstrategy::SpinThenYield;
+
+#[test]
+fn semaphore() {
+    let iters = if cfg!(miri) { 10 } else { 1000 };
+    let semaphore = Semaphore::new(3);
+    let inside = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for t in 0..6 {
+            let (semaphore, inside) = (&semaphore, &inside);
+            s.spawn(move || {
+                for _ in 0..iters {
+                    // some threads take one permit and some take two, and there's never more than three out
+                    let n = t % 2 + 1;
+                    let permit = semaphore.acquire_many(n);
+                    assert!(inside.fetch_add(n, Relaxed) + n <= 3);
+                    inside.fetch_sub(permit.permits(), Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(semaphore.available_permits(), 3);
+}
+
+#[test]
+fn large_waiter_not_starved() {
+    let semaphore = Semaphore::new(4);
+    let order = Mutex::new(Vec::new());
+    thread::scope(|s| {
+        let small = semaphore.acquire();
+        // the big request can't go yet, as one permit is out
+        s.spawn(|| {
+            let _all = semaphore.acquire_many(4);
+            order.lock().unwrap().push("large");
+        });
+        thread::sleep(Duration::from_millis(20));
+
+        // three permits are free, but the big request is waiting, so small ones can't get in ahead of it
+        assert_eq!(semaphore.available_permits(), 3);
+        assert!(semaphore.try_acquire().is_none());
+        s.spawn(|| {
+            let _one = semaphore.acquire();
+            order.lock().unwrap().push("small");
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(order.lock().unwrap().is_empty());
+
+        // once the first permit comes back, the big request goes first and then the small one behind it
+        drop(small);
+    });
+    assert_eq!(*order.lock().unwrap(), ["large", "small"]);
+    assert_eq!(semaphore.available_permits(), 4);
+}
+
+#[test]
+fn large_waiter_gets_through_a_stream_of_small_ones() {
+    // small acquires keep the permits busy the whole time, and the big one still gets its turn - whether it
+    // sleeps in line or waits there with a strategy
+    let iters = if cfg!(miri) { 10 } else { 2000 };
+    for with_strategy in [false, true] {
+        let semaphore = Semaphore::new(4);
+        let large_done = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    while large_done.load(Relaxed) == 0 {
+                        let _one = semaphore.acquire();
+                        std::hint::spin_loop();
+                    }
+                });
+            }
+            s.spawn(|| {
+                for _ in 0..iters {
+                    match with_strategy {
+                        false => drop(semaphore.acquire_many(4)),
+                        true => drop(semaphore.acquire_many_with(4, &SpinThenYield::default())),
+                    }
+                }
+                large_done.store(1, Relaxed);
+            });
+        });
+    }
+}
+
+#[test]
+fn acquire_timeout_gives_up_its_place() {
+    let semaphore = Semaphore::new(1);
+    let held = semaphore.acquire();
+    assert!(semaphore.acquire_timeout(Duration::from_millis(10)).is_none());
+    thread::scope(|s| {
+        // one waiter ahead and one behind a timed out acquire, and the one behind isn't stuck waiting on it
+        let first = s.spawn(|| drop(semaphore.acquire()));
+        thread::sleep(Duration::from_millis(20));
+        assert!(semaphore.acquire_timeout(Duration::from_millis(10)).is_none());
+        let last = s.spawn(|| drop(semaphore.acquire()));
+        thread::sleep(Duration::from_millis(20));
+        drop(held);
+        first.join().unwrap();
+        last.join().unwrap();
+    });
+    assert!(semaphore.acquire_timeout(Duration::from_millis(10)).is_some());
+}
+
+#[test]
+fn resize() {
+    let semaphore = Semaphore::new(2);
+    semaphore.add_permits(2);
+    assert_eq!(semaphore.available_permits(), 4);
+    // forgotten permits never come back
+    semaphore.acquire_many(3).forget();
+    assert_eq!(semaphore.available_permits(), 1);
+    assert!(semaphore.try_acquire_many(2).is_none());
+    // and added ones wake up a waiter that needs them
+    thread::scope(|s| {
+        s.spawn(|| drop(semaphore.acquire_many(3)));
+        thread::sleep(Duration::from_millis(10));
+        semaphore.add_permits(2);
+    });
+    assert_eq!(semaphore.available_permits(), 3);
+}
@@ -0,0 +1,52 @@
+use std::cell::Cell;
+
+use rust_atomic_locks::boundedchannel::{Receiver, Sender};
+use rust_atomic_locks::mutex::MutexGuard;
+use rust_atomic_locks::rawspinlock::RawGuard;
+use rust_atomic_locks::registry::NamedGuard;
+use rust_atomic_locks::spinlock::Guard;
+use rust_atomic_locks::{rwspinlock, shardedrwlock, stampedlock};
+
+fn send<T: Send>() {}
+fn sync<T: Sync>() {}
+
+// What every guard is meant to be. The other half - the combinations that mustn't compile - are the
+// trybuild cases in tests/ui
+#[test]
+fn guards() {
+    // a guard whose T is Send but not Sync can move between threads, but not be shared
+    send::<Guard<'_, Cell<i32>>>();
+    send::<MutexGuard<'_, Cell<i32>>>();
+    send::<rwspinlock::WriteGuard<'_, Cell<i32>>>();
+    send::<shardedrwlock::WriteGuard<'_, Cell<i32>>>();
+    send::<stampedlock::WriteGuard<'_, Cell<i32>>>();
+
+    // and one whose T is Sync can be shared, even if T isn't Send
+    sync::<Guard<'_, std::sync::MutexGuard<'_, i32>>>();
+    sync::<MutexGuard<'_, std::sync::MutexGuard<'_, i32>>>();
+    send::<rwspinlock::ReadGuard<'_, std::sync::MutexGuard<'_, i32>>>();
+    sync::<rwspinlock::UpgradableGuard<'_, std::sync::MutexGuard<'_, i32>>>();
+    send::<stampedlock::ReadGuard<'_, std::sync::MutexGuard<'_, i32>>>();
+
+    // nothing inside them to worry about
+    send::<RawGuard<'_>>();
+    sync::<RawGuard<'_>>();
+    send::<NamedGuard>();
+    sync::<NamedGuard>();
+}
+
+#[test]
+fn channel_ends() {
+    send::<Sender<i32>>();
+    sync::<Sender<i32>>();
+    send::<Receiver<Cell<i32>>>();
+    sync::<Receiver<Cell<i32>>>();
+    send::<rust_atomic_locks::oneshotchannel::Sender<'_, i32>>();
+    sync::<rust_atomic_locks::arc::Arc<i32>>();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
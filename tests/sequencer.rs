@@ -0,0 +1,50 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::sequencer::Sequencer;
+
+const ITEMS: u64 = if cfg!(miri) { 50 } else { 10_000 };
+
+#[test]
+fn back_in_order() {
+    let sequencer = Sequencer::new(8);
+    thread::scope(|s| {
+        // each worker takes every third item, and they race each other
+        for t in 0..3 {
+            let sequencer = &sequencer;
+            s.spawn(move || {
+                for seq in (t..ITEMS).step_by(3) {
+                    sequencer.push(seq, seq * 2).unwrap();
+                }
+            });
+        }
+        for seq in 0..ITEMS {
+            assert_eq!(sequencer.pop(), Some(seq * 2));
+        }
+    });
+    assert_eq!(sequencer.try_pop(), None);
+}
+
+#[test]
+fn window_holds_workers_back() {
+    let sequencer = Sequencer::new(2);
+    sequencer.push(1, "b").unwrap();
+    thread::scope(|s| {
+        // 2 is a whole window past 0, which hasn't turned up yet
+        let blocked = s.spawn(|| sequencer.push(2, "c"));
+        thread::sleep(Duration::from_millis(10));
+        assert!(!blocked.is_finished());
+        assert_eq!(sequencer.try_pop(), None);
+        sequencer.push(0, "a").unwrap();
+        assert_eq!(sequencer.pop(), Some("a"));
+        blocked.join().unwrap().unwrap();
+    });
+    assert_eq!(sequencer.pop(), Some("b"));
+    assert_eq!(sequencer.pop(), Some("c"));
+
+    // after a close, what's in order still comes out, then None at the gap
+    sequencer.push(4, "e").unwrap();
+    sequencer.close();
+    assert_eq!(sequencer.pop(), None);
+    assert_eq!(sequencer.push(3, "d"), Err("d"));
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "serde")]
+
+use std::io::Cursor;
+use std::thread;
+
+use rust_atomic_locks::arc::Arc;
+use rust_atomic_locks::serialized::{Error, SerializedReceiver, SerializedSender, MAX_FRAME};
+use rust_atomic_locks::spinlock::SpinLock;
+
+#[test]
+fn round_trip() {
+    let sender = SerializedSender::new(Vec::new());
+    sender.send((1u32, String::from("one"))).unwrap();
+    sender.send((2, String::from("two"))).unwrap();
+    let bytes = sender.into_inner();
+
+    let receiver = SerializedReceiver::<(u32, String), _>::new(Cursor::new(bytes));
+    assert_eq!(receiver.receive().unwrap(), (1, String::from("one")));
+    assert_eq!(receiver.receive().unwrap(), (2, String::from("two")));
+    // the end of the stream between messages is the sender going away
+    assert!(matches!(receiver.receive(), Err(Error::Disconnected)));
+}
+
+#[test]
+fn arcs_and_spinlocks_in_messages() {
+    // serialized as the values inside, so they can go in a message without unwrapping them first
+    let sender = SerializedSender::new(Vec::new());
+    sender.send((Arc::new(String::from("config")), SpinLock::new(vec![1u32, 2]))).unwrap();
+    let receiver = SerializedReceiver::<(Arc<String>, SpinLock<Vec<u32>>), _>::new(Cursor::new(sender.into_inner()));
+    let (name, values) = receiver.receive().unwrap();
+    assert_eq!(*name, "config");
+    assert_eq!(values.into_inner(), [1, 2]);
+}
+
+#[test]
+fn broken_frames() {
+    let sender = SerializedSender::new(Vec::new());
+    sender.send(vec![0u8; 16]).unwrap();
+    let mut bytes = sender.into_inner();
+
+    // cut off partway through a message, which isn't a clean disconnect
+    bytes.pop();
+    let receiver = SerializedReceiver::<Vec<u8>, _>::new(Cursor::new(bytes));
+    assert!(matches!(receiver.receive(), Err(Error::Io(_))));
+
+    // a length that's too big is refused before anything's allocated for it
+    let len = (MAX_FRAME as u32 + 1).to_le_bytes();
+    let receiver = SerializedReceiver::<Vec<u8>, _>::new(Cursor::new(len));
+    assert!(matches!(receiver.receive(), Err(Error::TooBig(_))));
+}
+
+#[cfg(unix)]
+#[test]
+// no sockets under Miri
+#[cfg_attr(miri, ignore)]
+fn between_threads_over_a_socket() {
+    use std::os::unix::net::UnixStream;
+
+    const MESSAGES: u64 = 1000;
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let sender = SerializedSender::new(a);
+    let receiver = SerializedReceiver::<u64, _>::new(b);
+    thread::scope(|s| {
+        // two threads sharing the sender, so the frames have to stay whole
+        for _ in 0..2 {
+            s.spawn(|| {
+                for i in 0..MESSAGES {
+                    sender.send(i).unwrap();
+                }
+            });
+        }
+        let total: u64 = (0..2 * MESSAGES).map(|_| receiver.receive().unwrap()).sum();
+        assert_eq!(total, MESSAGES * (MESSAGES - 1));
+    });
+
+    // closing the sender's side disconnects the receiver
+    drop(sender);
+    assert!(matches!(receiver.receive(), Err(Error::Disconnected)));
+}
@@ -0,0 +1,21 @@
+use std::thread;
+
+use rust_atomic_locks::shardedcounter::ShardedCounter;
+
+#[test]
+fn sharded_counter() {
+    let iters = if cfg!(miri) { 20 } else { 1000 };
+    let counter = ShardedCounter::new();
+    thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for _ in 0..iters {
+                    counter.increment();
+                }
+            });
+        }
+    });
+    assert_eq!(counter.sum(), 8 * iters);
+    counter.reset();
+    assert_eq!(counter.sum(), 0);
+}
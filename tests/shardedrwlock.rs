@@ -0,0 +1,59 @@
+use std::thread;
+
+use rust_atomic_locks::shardedrwlock::ShardedRwLock;
+
+const ITERS: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+// try_read and try_write_all use a weak compare exchange underneath, which Miri makes fail now and then even
+// when the lock is free, so a failure only counts after a few tries
+fn retry<T>(mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    (0..100).find_map(|_| f())
+}
+
+#[test]
+fn readers_and_writers() {
+    // the two halves are always written together, so a reader seeing them differ got in during a write
+    let x = ShardedRwLock::with_shards((0, 0), 4);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    let mut g = x.write_all();
+                    g.0 += 1;
+                    g.1 += 1;
+                }
+            });
+        }
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    let g = x.read();
+                    assert_eq!(g.0, g.1);
+                }
+            });
+        }
+    });
+    assert_eq!(x.into_inner(), (2 * ITERS, 2 * ITERS));
+}
+
+#[test]
+fn writer_shuts_out_every_shard() {
+    let x = ShardedRwLock::with_shards(1, 3);
+    assert_eq!(x.shards(), 3);
+    let g = x.write_all();
+    // whichever shard each thread lands on, it's locked
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| assert!(x.try_read().is_none()));
+        }
+    });
+    drop(g);
+
+    let r = x.read();
+    // a reader on any shard keeps writers out
+    assert!(x.try_write_all().is_none());
+    assert_eq!(*r, 1);
+    drop(r);
+    *retry(|| x.try_write_all()).unwrap() += 1;
+    assert_eq!(*retry(|| x.try_read()).unwrap(), 2);
+}
@@ -0,0 +1,63 @@
+use std::thread;
+
+use rust_atomic_locks::sharedmem::SharedMemChannel;
+
+#[test]
+fn shared_mem_channel() {
+    // a plain buffer stands in for shared memory here, with threads in place of processes.
+    // u64s so it's aligned for the header
+    let messages = if cfg!(miri) { 20 } else { 100 };
+    let mut region = vec![0u64; SharedMemChannel::<u32>::size_for(8).div_ceil(8)];
+    let len = region.len() * 8;
+    let sender = unsafe { SharedMemChannel::<u32>::init(region.as_mut_ptr().cast(), len, 8) };
+    let receiver = unsafe { SharedMemChannel::<u32>::attach(region.as_mut_ptr().cast(), len) };
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..messages {
+                sender.send(i);
+            }
+        });
+        let received: Vec<_> = (0..messages).map(|_| receiver.receive()).collect();
+        assert_eq!(received, (0..messages).collect::<Vec<_>>());
+    });
+    assert!(receiver.is_empty());
+}
+
+#[test]
+fn full_and_empty() {
+    let mut region = vec![0u64; SharedMemChannel::<u32>::size_for(2).div_ceil(8)];
+    let len = region.len() * 8;
+    let channel = unsafe { SharedMemChannel::<u32>::init(region.as_mut_ptr().cast(), len, 2) };
+    assert_eq!(channel.try_receive(), None);
+    assert_eq!(channel.try_send(1), Ok(()));
+    assert_eq!(channel.try_send(2), Ok(()));
+    assert_eq!(channel.try_send(3), Err(3));
+    assert_eq!(channel.try_receive(), Some(1));
+    assert_eq!(channel.len(), 1);
+}
+
+// with real shared memory, a child process sends and the parent receives
+#[cfg(all(unix, feature = "shared_memory"))]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn across_processes() {
+    use rust_atomic_locks::sharedmem::SharedRegion;
+
+    let region = SharedRegion::anonymous(SharedMemChannel::<u32>::size_for(8)).unwrap();
+    let channel = unsafe { SharedMemChannel::<u32>::init(region.as_ptr(), region.len(), 8) };
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed: {}", std::io::Error::last_os_error()),
+        0 => {
+            for i in 0..100 {
+                channel.send(i);
+            }
+            // straight out, without running anything the parent set up to run at exit
+            unsafe { libc::_exit(0) };
+        }
+        child => {
+            let received: Vec<_> = (0..100).map(|_| channel.receive()).collect();
+            assert_eq!(received, (0..100).collect::<Vec<_>>());
+            unsafe { libc::waitpid(child, std::ptr::null_mut(), 0) };
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::PathBuf;
+use std::thread;
+
+use memmap2::{MmapMut, MmapOptions};
+use rust_atomic_locks::shmring::{RingError, ShmRing};
+
+// A file in the temp dir, mapped as many times as a test needs. Every mapping of it sees the same memory, the
+// same as separate processes mapping it would
+struct RingFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl RingFile {
+    fn new(name: &str, len: usize) -> Self {
+        let path = std::env::temp_dir().join(format!("shmring-{name}-{}", std::process::id()));
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        file.set_len(len as u64).unwrap();
+        Self { path, file }
+    }
+
+    fn map(&self) -> MmapMut {
+        unsafe { MmapMut::map_mut(&self.file).unwrap() }
+    }
+}
+
+impl Drop for RingFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[test]
+fn send_and_receive() {
+    let mut region = MmapOptions::new().len(ShmRing::size_for(8, 4)).map_anon().unwrap();
+    let (mut producer, mut consumer) = ShmRing::init(&mut region, 8, 4).unwrap().split().unwrap();
+    assert_eq!(consumer.try_receive(), None);
+
+    for i in 0..4u8 {
+        // messages can be anything up to the slot size, including nothing
+        producer.try_send(&vec![i; i as usize]).unwrap();
+    }
+    assert_eq!(producer.try_send(b"full"), Err(&b"full"[..]));
+    assert_eq!(consumer.len(), 4);
+
+    assert_eq!(consumer.try_receive_with(|m| m.len()), Some(0));
+    assert_eq!(consumer.try_receive(), Some(vec![1]));
+    producer.send(b"12345678");
+    assert_eq!(consumer.receive(), [2, 2]);
+    assert_eq!(consumer.receive(), [3, 3, 3]);
+    assert_eq!(consumer.receive(), b"12345678");
+    assert!(producer.is_empty());
+}
+
+#[test]
+fn bad_regions() {
+    let mut region = MmapOptions::new().len(ShmRing::size_for(8, 4) + 8).map_anon().unwrap();
+    // all zeroes, so nothing's been set up there
+    assert_eq!(ShmRing::attach(&mut region).err(), Some(RingError::NotInitialised));
+    assert_eq!(ShmRing::init(&mut region[1..], 8, 4).err(), Some(RingError::Misaligned));
+    assert_eq!(ShmRing::init(&mut region, 8, 5).err(), Some(RingError::TooSmall));
+    assert_eq!(ShmRing::init(&mut region, 0, 4).err(), Some(RingError::Empty));
+
+    // a header that says the ring is bigger than the region is caught on attach
+    ShmRing::init(&mut region, 8, 4).unwrap();
+    let len = region.len();
+    assert_eq!(ShmRing::attach(&mut region[..len - 16]).err(), Some(RingError::TooSmall));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn one_producer_and_one_consumer() {
+    let file = RingFile::new("claims", ShmRing::size_for(16, 4));
+    let (mut a, mut b, mut c) = (file.map(), file.map(), file.map());
+    let producer = ShmRing::init(&mut a, 16, 4).unwrap().into_producer().unwrap();
+    let consumer = ShmRing::attach(&mut b).unwrap().into_consumer().unwrap();
+    assert_eq!(ShmRing::attach(&mut c).unwrap().into_producer().err(), Some(RingError::Claimed));
+    assert_eq!(ShmRing::attach(&mut c).unwrap().into_consumer().err(), Some(RingError::Claimed));
+
+    // gone, so someone else can be the producer now
+    drop(producer);
+    assert!(ShmRing::attach(&mut c).unwrap().into_producer().is_ok());
+    drop(consumer);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn across_mappings() {
+    // the ring wraps round plenty of times, with a thread on each mapping
+    let file = RingFile::new("mappings", ShmRing::size_for(8, 4));
+    let (mut a, mut b) = (file.map(), file.map());
+    let mut producer = ShmRing::init(&mut a, 8, 4).unwrap().into_producer().unwrap();
+    let mut consumer = ShmRing::attach(&mut b).unwrap().into_consumer().unwrap();
+    thread::scope(|s| {
+        s.spawn(move || {
+            for i in 0..10_000u64 {
+                producer.send(&i.to_le_bytes());
+            }
+        });
+        for i in 0..10_000u64 {
+            assert_eq!(consumer.receive(), i.to_le_bytes());
+        }
+    });
+}
+
+// a forked child sends and the parent receives, through the file mapped before the fork
+#[cfg(unix)]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn across_processes() {
+    let file = RingFile::new("processes", ShmRing::size_for(8, 4));
+    let mut region = file.map();
+    let ring = ShmRing::init(&mut region, 8, 4).unwrap();
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed: {}", std::io::Error::last_os_error()),
+        0 => {
+            let mut producer = ring.into_producer().unwrap();
+            for i in 0..1000u64 {
+                producer.send(&i.to_le_bytes());
+            }
+            // straight out, without running anything the parent set up to run at exit
+            unsafe { libc::_exit(0) };
+        }
+        child => {
+            let mut consumer = ring.into_consumer().unwrap();
+            for i in 0..1000u64 {
+                assert_eq!(consumer.receive(), i.to_le_bytes());
+            }
+            unsafe { libc::waitpid(child, std::ptr::null_mut(), 0) };
+        }
+    }
+}
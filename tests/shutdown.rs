@@ -0,0 +1,61 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::boundedchannel::sync_channel;
+use rust_atomic_locks::shutdown::ShutdownController;
+
+#[test]
+fn workers_stop_and_finish() {
+    let shutdown = ShutdownController::new();
+    let (sender, receiver) = sync_channel(4);
+    let handled = thread::scope(|s| {
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let signal = shutdown.signal();
+                let receiver = &receiver;
+                s.spawn(move || {
+                    let mut handled = 0;
+                    // blocked in here when shutdown comes, with the sender still around
+                    while let Some(()) = signal.receive(receiver) {
+                        handled += 1;
+                    }
+                    handled
+                })
+            })
+            .collect();
+        for _ in 0..10 {
+            sender.send(()).unwrap();
+        }
+        // not finished on their own, they're waiting for more
+        assert!(!shutdown.wait_for_completion_timeout(Duration::from_millis(10)));
+        assert_eq!(shutdown.workers(), 3);
+        while !receiver.is_empty() {
+            thread::yield_now();
+        }
+        shutdown.shutdown();
+        shutdown.wait_for_completion();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum::<usize>()
+    });
+    // the channel was emptied before the shutdown, so every message was handled
+    assert_eq!(handled, 10);
+    assert_eq!(shutdown.workers(), 0);
+}
+
+#[test]
+fn polling_and_parking() {
+    let shutdown = ShutdownController::new();
+    let signal = shutdown.signal();
+    let parked = signal.clone();
+    assert!(!signal.wait_timeout(Duration::from_millis(5)));
+    thread::scope(|s| {
+        s.spawn(move || {
+            parked.wait();
+            assert!(parked.is_shutdown());
+        });
+        shutdown.shutdown();
+    });
+    assert!(signal.is_shutdown());
+    assert!(shutdown.signal().is_shutdown());
+    signal.ack();
+    assert!(shutdown.wait_for_completion_timeout(Duration::ZERO));
+}
@@ -0,0 +1,214 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::arc::Arc;
+use rust_atomic_locks::mutex::Timeout;
+use rust_atomic_locks::spinlock::{lock_all, lock_both, Guard, SpinLock};
+use rust_atomic_locks::waitstrategy::SpinThenYield;
+
+const ITERS: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+#[test]
+fn spinlock() {
+    // create a new Spinlock with a vec inside of the spinlock
+    let x = SpinLock::new(Vec::new());
+    thread::scope(|s| {
+        // create a new thread that will lock the spinlock to that thread
+        // after done, the spinlock is free so it can be locked again in another thread
+        s.spawn(|| x.lock().push(1));
+        s.spawn(|| {
+            // this thread yields its time slice if it has to wait a while, rather than spinning the whole time
+            let mut g = x.lock_with(&SpinThenYield::default());
+            g.push(2);
+            g.push(2);
+        });
+    });
+    let g = x.lock();
+    assert!(g.as_slice() == [1, 2, 2] || g.as_slice() == [2, 2, 1]);
+    #[cfg(feature = "metrics")]
+    {
+        let metrics = x.metrics();
+        assert_eq!(metrics.acquisitions, 3);
+        assert!(metrics.max_wait <= metrics.total_wait);
+    }
+    drop(g);
+    // after leaking the guard the vec can be used as long as x is around, but x can never be locked again
+    let v = Guard::leak(x.lock());
+    v.push(3);
+    assert_eq!(v.len(), 4);
+}
+
+#[test]
+fn contended() {
+    // a non-atomic read-modify-write under the lock, which loses counts (or trips Miri) if two threads get in
+    let counter = SpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    *counter.lock() += 1;
+                }
+            });
+        }
+    });
+    let mut counter = counter;
+    // the threads are done with it, so it's ours alone
+    *counter.get_mut() += 1;
+    assert_eq!(counter.into_inner(), 4 * ITERS + 1);
+}
+
+#[test]
+fn lock_both_either_way_round() {
+    // taking a then b on one thread and b then a on the other deadlocks sooner or later if they're locked
+    // in the order they're passed
+    let a = SpinLock::new(0);
+    let b = SpinLock::new(String::new());
+    thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..ITERS {
+                let (mut a, mut b) = lock_both(&a, &b);
+                *a += 1;
+                b.push('a');
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..ITERS {
+                let (mut b, mut a) = lock_both(&b, &a);
+                b.push('b');
+                *a += 1;
+            }
+        });
+    });
+    assert_eq!(*a.lock(), 2 * ITERS);
+    assert_eq!(b.lock().len(), 2 * ITERS);
+}
+
+#[test]
+fn lock_all_in_any_order() {
+    let locks = [SpinLock::new(0), SpinLock::new(0), SpinLock::new(0)];
+    let [a, b, c] = &locks;
+    thread::scope(|s| {
+        for order in [[a, b, c], [c, b, a], [b, c, a]] {
+            s.spawn(move || {
+                for _ in 0..ITERS {
+                    // the guards line up with the locks as they were passed
+                    let [mut x, mut y, z] = lock_all(order);
+                    *x += 1;
+                    *y += 2;
+                    drop(z);
+                }
+            });
+        }
+    });
+    assert_eq!(*a.lock(), ITERS);
+    assert_eq!(*b.lock(), 5 * ITERS);
+    assert_eq!(*c.lock(), 3 * ITERS);
+}
+
+#[test]
+#[should_panic(expected = "the same lock twice")]
+fn lock_all_same_lock_twice() {
+    let a = SpinLock::new(0);
+    let b = SpinLock::new(0);
+    lock_all([&a, &b, &a]);
+}
+
+#[test]
+fn unlocked_lets_others_in() {
+    let lock = SpinLock::new(Vec::new());
+    let mut guard = lock.lock();
+    guard.push(1);
+    let seen = Guard::unlocked(&mut guard, || {
+        // another thread can take the lock while it's let go
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut v = lock.lock();
+                v.push(2);
+                v.len()
+            })
+            .join()
+            .unwrap()
+        })
+    });
+    assert_eq!(seen, 2);
+    // and it's locked again afterwards, with whatever the other thread did
+    assert_eq!(*guard, [1, 2]);
+    drop(guard);
+
+    // a panic inside still locks it again, so the guard can unlock it as normal on the way out
+    let result = thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.lock();
+            Guard::unlocked(&mut guard, || panic!("callback failed"));
+        })
+        .join()
+    });
+    assert!(result.is_err());
+    lock.lock().push(3);
+    assert_eq!(*lock.lock(), [1, 2, 3]);
+}
+
+#[test]
+fn debug_doesnt_block() {
+    // a struct that derives Debug can hold a lock, and formatting it while it's locked just says so
+    #[derive(Debug, Default)]
+    struct Config {
+        #[allow(dead_code)]
+        retries: SpinLock<u32>,
+    }
+    let config = Config::default();
+    let guard = config.retries.lock();
+    assert_eq!(format!("{config:?}"), "Config { retries: SpinLock { locked: true, .. } }");
+    drop(guard);
+    assert_eq!(format!("{:?}", SpinLock::from(5)), "SpinLock { locked: false, .. }");
+}
+
+#[test]
+fn with_closure() {
+    let lock = SpinLock::new(Vec::new());
+    let len = lock.with(|v| {
+        v.push(1);
+        v.len()
+    });
+    assert_eq!(len, 1);
+    // unlocked again as soon as the closure's done
+    assert_eq!(lock.try_with(|v| v.pop()), Some(Some(1)));
+
+    // while something else holds it, try_with gives up without running the closure
+    let guard = lock.lock();
+    assert_eq!(lock.try_with(|_| unreachable!()), None::<()>);
+    drop(guard);
+
+    // a panic in the closure still unlocks
+    let result = thread::scope(|s| s.spawn(|| lock.with(|_| panic!("closure failed"))).join());
+    assert!(result.is_err());
+    assert_eq!(lock.try_with(|v| v.len()), Some(0));
+}
+
+#[test]
+fn lock_timeout_map() {
+    let lock = SpinLock::new(0);
+    assert_eq!(lock.lock_timeout_map(Duration::from_millis(10), |n| *n += 1), Ok(()));
+    let guard = lock.lock();
+    assert_eq!(lock.lock_timeout_map(Duration::from_millis(10), |n| *n), Err(Timeout));
+    drop(guard);
+    assert_eq!(*lock.lock_timeout(Duration::ZERO).unwrap(), 1);
+}
+
+#[test]
+fn with_owned_on_detached_threads() {
+    let mut counter = Arc::new(SpinLock::new(0));
+    let threads = if cfg!(miri) { 4 } else { 16 };
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let counter = counter.clone();
+            thread::spawn(move || counter.with_owned(|n| *n += 1))
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    // each thread's Arc went with it
+    assert!(Arc::get_mut(&mut counter).is_some());
+    assert_eq!(counter.with_owned(|n| *n), threads);
+}
@@ -0,0 +1,56 @@
+use std::thread;
+
+use rust_atomic_locks::stampedlock::StampedLock;
+
+const ITERS: u64 = if cfg!(miri) { 20 } else { 10_000 };
+
+#[test]
+fn stamps() {
+    let lock = StampedLock::new(1);
+    let stamp = lock.optimistic_read().unwrap();
+    assert_eq!(lock.peek(stamp), Some(1));
+    // reading doesn't get in the way of a stamp, writing does
+    drop(lock.read());
+    assert!(lock.validate(stamp));
+    let mut guard = lock.write();
+    assert_eq!(lock.optimistic_read(), None);
+    assert!(!lock.validate(stamp));
+    *guard = 2;
+    drop(guard);
+    assert!(!lock.validate(stamp));
+    assert_eq!(lock.peek(stamp), None);
+    assert!(lock.try_write_at(stamp).is_none());
+
+    let stamp = lock.read().stamp();
+    *lock.try_write_at(stamp).unwrap() += 1;
+    assert_eq!(lock.get(), 3);
+
+    let reading = lock.write().downgrade();
+    assert!(lock.try_write().is_none());
+    assert!(!lock.validate(stamp));
+    drop(reading);
+    assert_eq!(lock.into_inner(), 3);
+}
+
+#[test]
+fn reads_never_tear() {
+    // the two halves are always written together, so an optimistic read that saw one write's a and another's b
+    // would show up as a mismatch
+    let lock = StampedLock::new((0u64, 0u64));
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 1..=ITERS {
+                *lock.write() = (i, i);
+            }
+        });
+        for _ in 0..2 {
+            s.spawn(|| loop {
+                let (a, b) = lock.get();
+                assert_eq!(a, b);
+                if a == ITERS {
+                    break;
+                }
+            });
+        }
+    });
+}
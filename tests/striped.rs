@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::thread;
+
+use rust_atomic_locks::mutex::Mutex;
+use rust_atomic_locks::rwspinlock::RwSpinLock;
+use rust_atomic_locks::striped::Striped;
+
+#[test]
+fn striped() {
+    let map: Striped<HashMap<usize, usize>> = Striped::new(8, HashMap::new);
+    thread::scope(|s| {
+        for t in 0..4 {
+            let map = &map;
+            s.spawn(move || {
+                for i in 0..100 {
+                    let key = t * 100 + i;
+                    map.lock(&key).insert(key, i);
+                }
+            });
+        }
+    });
+    let total: usize = map.lock_all().iter().map(|stripe| stripe.len()).sum();
+    assert_eq!(total, 400);
+    assert_eq!(map.lock(&150usize).get(&150), Some(&50));
+}
+
+#[test]
+fn same_key_same_stripe() {
+    // every thread hammers one key, so they all fight over the one stripe
+    let iters = if cfg!(miri) { 10 } else { 1000 };
+    let map: Striped<HashMap<&str, usize>> = Striped::new(8, HashMap::new);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..iters {
+                    *map.lock(&"hot").entry("hot").or_default() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(map.lock(&"hot")["hot"], 4 * iters);
+}
+
+#[test]
+fn other_lock_types() {
+    let iters = if cfg!(miri) { 10 } else { 1000 };
+    let mutexes: Striped<Vec<usize>, Mutex<_>> = Striped::with_lock_type(4, Vec::new);
+    let rwlocks: Striped<Vec<usize>, RwSpinLock<_>> = Striped::with_lock_type(4, Vec::new);
+    thread::scope(|s| {
+        for t in 0..4 {
+            let (mutexes, rwlocks) = (&mutexes, &rwlocks);
+            s.spawn(move || {
+                for i in 0..iters {
+                    mutexes.lock(&(t, i)).push(i);
+                    rwlocks.lock(&(t, i)).push(i);
+                }
+            });
+        }
+    });
+    assert_eq!(mutexes.lock_all().iter().map(|stripe| stripe.len()).sum::<usize>(), 4 * iters);
+    assert_eq!((0..4).map(|i| rwlocks.lock_index(i).len()).sum::<usize>(), 4 * iters);
+}
@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rust_atomic_locks::event::Event;
+use rust_atomic_locks::taskgroup::{TaskError, TaskGroup};
+use rust_atomic_locks::threadpool::ThreadPool;
+
+#[test]
+fn all_succeed() {
+    let pool = ThreadPool::new(2).unwrap();
+    let done = Arc::new(AtomicUsize::new(0));
+    let group = TaskGroup::<()>::new(&pool);
+    let n = if cfg!(miri) { 10 } else { 100 };
+    for _ in 0..n {
+        let done = done.clone();
+        group.spawn(move |_| {
+            done.fetch_add(1, Relaxed);
+            Ok(())
+        });
+    }
+    assert!(group.join().is_ok());
+    assert_eq!(done.load(Relaxed), n);
+}
+
+// The waiters would sleep for a minute without the cancel
+#[test]
+fn first_error_cancels_the_rest() {
+    let pool = ThreadPool::new(3).unwrap();
+    let cancelled = Arc::new(AtomicUsize::new(0));
+    let group = TaskGroup::new(&pool);
+    for _ in 0..2 {
+        let cancelled = cancelled.clone();
+        group.spawn(move |token| {
+            if token.wait_timeout(Duration::from_secs(60)) {
+                cancelled.fetch_add(1, Relaxed);
+            }
+            Err("cancelled")
+        });
+    }
+    group.spawn(|_| Err("first"));
+    match group.join() {
+        Err(TaskError::Failed(error)) => assert_eq!(error, "first"),
+        other => panic!("expected the first task's error, got {other:?}"),
+    }
+    assert_eq!(cancelled.load(Relaxed), 2);
+}
+
+#[test]
+fn panics_and_drops() {
+    let pool = ThreadPool::new(2).unwrap();
+    let group = TaskGroup::<String>::new(&pool);
+    let token = group.token();
+    group.spawn(|_| panic!("task blew up"));
+    let error = group.join().unwrap_err();
+    assert_eq!(error.to_string(), "task panicked: task blew up");
+    assert!(token.is_cancelled());
+    assert_eq!(pool.panicked(), 0);
+
+    // dropping a group cancels whatever's still going, and waits for it. The task has to be running first, or
+    // the cancel skips it
+    let stopped = Arc::new(AtomicUsize::new(0));
+    let started = Arc::new(Event::new());
+    let group = TaskGroup::<()>::new(&pool);
+    let (waiting, running) = (stopped.clone(), started.clone());
+    group.spawn(move |token| {
+        running.set();
+        token.wait();
+        waiting.fetch_add(1, Relaxed);
+        Ok(())
+    });
+    started.wait();
+    drop(group);
+    assert_eq!(stopped.load(Relaxed), 1);
+}
@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::threadlocal::ThreadLocal;
+
+#[test]
+fn thread_local() {
+    let mut counts = ThreadLocal::new();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                // each thread counts into its own cell, with no contention at all
+                for _ in 0..100 {
+                    let count = counts.get_or(|| AtomicUsize::new(0));
+                    count.fetch_add(1, Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(counts.iter().count(), 4);
+    assert_eq!(counts.iter().map(|c| c.load(Relaxed)).sum::<usize>(), 400);
+    // the main thread hasn't used it
+    assert!(counts.get().is_none());
+    counts.clear();
+    assert_eq!(counts.iter().count(), 0);
+}
+
+#[test]
+fn values_dropped_once() {
+    static NUM_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct DetectDrop;
+
+    impl Drop for DetectDrop {
+        fn drop(&mut self) {
+            NUM_DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    // the threads are gone by the time the ThreadLocal is, but their values are still dropped with it
+    let values = ThreadLocal::new();
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| {
+                values.get_or(|| DetectDrop);
+            });
+        }
+    });
+    assert_eq!(NUM_DROPS.load(Relaxed), 0);
+    drop(values);
+    assert_eq!(NUM_DROPS.load(Relaxed), 3);
+}
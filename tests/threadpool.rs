@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use rust_atomic_locks::threadpool::ThreadPool;
+
+#[test]
+fn runs_every_job() {
+    let n = if cfg!(miri) { 20 } else { 1000 };
+    let done = Arc::new(AtomicUsize::new(0));
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let pool = ThreadPool::builder().threads(3).name("worker").queue_capacity(4).stack_size(256 * 1024).build().unwrap();
+    assert_eq!(pool.threads(), 3);
+    for _ in 0..n {
+        let (done, names) = (done.clone(), names.clone());
+        pool.execute(move || {
+            done.fetch_add(1, Relaxed);
+            names.lock().unwrap().push(thread::current().name().unwrap().to_owned());
+        });
+    }
+    // join waits for what's queued
+    pool.join();
+    assert_eq!(done.load(Relaxed), n);
+    assert!(names.lock().unwrap().iter().all(|name| ["worker-0", "worker-1", "worker-2"].contains(&name.as_str())));
+}
+
+#[test]
+fn panicking_jobs_are_counted() {
+    let pool = ThreadPool::new(1).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    pool.execute(|| panic!("job failed"));
+    // one worker takes them in order, so by the time this one runs the panic's been counted
+    pool.execute(move || sender.send(()).unwrap());
+    receiver.recv().unwrap();
+    assert_eq!(pool.panicked(), 1);
+}
+
+#[cfg(all(target_os = "linux", feature = "thread_tuning"))]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn pinned_and_niced() {
+    use rust_atomic_locks::threadpool::ThreadPriority;
+
+    let pool = ThreadPool::builder().threads(2).pin_to_cores([0]).priority(ThreadPriority::Nice(5)).build().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..2 {
+        let sender = sender.clone();
+        pool.execute(move || {
+            let core = unsafe { libc::sched_getcpu() };
+            let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) };
+            sender.send((core, nice)).unwrap();
+        });
+    }
+    assert_eq!(receiver.iter().take(2).collect::<Vec<_>>(), [(0, 5), (0, 5)]);
+
+    let error = ThreadPool::builder().threads(1).pin_to_cores([libc::CPU_SETSIZE as usize]).build().err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "timed_guard")]
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::mutex::Mutex;
+use rust_atomic_locks::spinlock::SpinLock;
+use rust_atomic_locks::timedguard::{self, HeldTooLong, TimedGuard};
+
+// One test for the whole file, as the threshold and callback are global and the tests in a file run in parallel
+#[test]
+fn reports_long_holds() {
+    let reports = Arc::new(StdMutex::new(Vec::<HeldTooLong>::new()));
+    let sink = reports.clone();
+    timedguard::set_threshold(
+        Duration::from_millis(20),
+        move |report: &HeldTooLong| sink.lock().unwrap().push(report.clone()),
+    );
+
+    // held briefly, never reported
+    let lock = SpinLock::new(0);
+    for _ in 0..10 {
+        *lock.lock_timed() += 1;
+    }
+    assert!(reports.lock().unwrap().is_empty());
+
+    // held over the threshold, reported with where it was taken
+    let mutex = Mutex::new(0);
+    let line = line!() + 1;
+    let mut guard = mutex.lock_timed();
+    *guard += 1;
+    thread::sleep(Duration::from_millis(40));
+    drop(guard);
+    {
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].held >= Duration::from_millis(40));
+        assert_eq!(reports[0].location.file(), file!());
+        assert_eq!(reports[0].location.line(), line);
+    }
+    assert_eq!(*mutex.lock(), 1);
+
+    // a threshold of its own wins over the global one
+    let guard = TimedGuard::with_threshold(lock.lock(), Duration::from_secs(60));
+    thread::sleep(Duration::from_millis(40));
+    drop(guard);
+    let guard = TimedGuard::with_threshold(lock.lock(), Duration::ZERO);
+    thread::sleep(Duration::from_millis(1));
+    drop(guard);
+    assert_eq!(reports.lock().unwrap().len(), 2);
+
+    // nothing once it's disabled
+    timedguard::disable();
+    let guard = lock.lock_timed();
+    thread::sleep(Duration::from_millis(40));
+    drop(guard);
+    assert_eq!(reports.lock().unwrap().len(), 2);
+}
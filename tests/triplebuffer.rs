@@ -0,0 +1,38 @@
+use std::thread;
+
+use rust_atomic_locks::triplebuffer::triple_buffer;
+
+#[test]
+fn triple_buffer_frames() {
+    let frames = if cfg!(miri) { 50 } else { 1000 };
+    let (mut input, mut output) = triple_buffer(0);
+    thread::scope(|s| {
+        s.spawn(move || {
+            for frame in 1..=frames {
+                input.write(frame);
+            }
+        });
+        // the reader never blocks, and the frames it sees only ever go forward
+        let mut last = 0;
+        while last < frames {
+            let frame = *output.read();
+            assert!(frame >= last);
+            last = frame;
+        }
+    });
+    assert!(!output.updated());
+    assert_eq!(*output.read(), frames);
+}
+
+#[test]
+fn writes_in_place() {
+    let (mut input, mut output) = triple_buffer(vec![0u8; 4]);
+    // nothing the writer does shows up until it's published, and then all of it does at once
+    input.input_buffer()[0] = 1;
+    input.input_buffer()[3] = 1;
+    assert!(!output.updated());
+    assert_eq!(*output.read(), [0, 0, 0, 0]);
+    input.publish();
+    assert!(output.updated());
+    assert_eq!(*output.read(), [1, 0, 0, 1]);
+}
@@ -0,0 +1,8 @@
+// a pin belongs to the thread that made it
+use rust_atomic_locks::epoch::Guard;
+
+fn send<T: Send>() {}
+
+fn main() {
+    send::<Guard>();
+}
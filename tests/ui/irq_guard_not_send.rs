@@ -0,0 +1,16 @@
+// the interrupt state belongs to the core that took the lock
+use rust_atomic_locks::irqspinlock::{InterruptController, IrqGuard};
+
+struct NoInterrupts;
+
+unsafe impl InterruptController for NoInterrupts {
+    type State = ();
+    fn disable() {}
+    unsafe fn restore(_state: ()) {}
+}
+
+fn send<T: Send>() {}
+
+fn main() {
+    send::<IrqGuard<'_, i32, NoInterrupts>>();
+}
@@ -0,0 +1,9 @@
+use std::cell::Cell;
+
+use rust_atomic_locks::mutex::MutexGuard;
+
+fn sync<T: Sync>() {}
+
+fn main() {
+    sync::<MutexGuard<'_, Cell<i32>>>();
+}
@@ -0,0 +1,8 @@
+// the sender unparks the thread the receiver was made on
+use rust_atomic_locks::oneshotchannel::Receiver;
+
+fn send<T: Send>() {}
+
+fn main() {
+    send::<Receiver<'_, i32>>();
+}
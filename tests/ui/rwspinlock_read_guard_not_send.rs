@@ -0,0 +1,10 @@
+// a read guard on another thread is &T on another thread, which a Cell isn't allowed
+use std::cell::Cell;
+
+use rust_atomic_locks::rwspinlock::ReadGuard;
+
+fn send<T: Send>() {}
+
+fn main() {
+    send::<ReadGuard<'_, Cell<i32>>>();
+}
@@ -0,0 +1,10 @@
+// an Rc sent down a channel would end up on the receiving thread
+use std::rc::Rc;
+
+use rust_atomic_locks::boundedchannel::Sender;
+
+fn send<T: Send>() {}
+
+fn main() {
+    send::<Sender<Rc<i32>>>();
+}
@@ -0,0 +1,10 @@
+// &Guard hands out &T, so a guard over a Cell can't be shared between threads
+use std::cell::Cell;
+
+use rust_atomic_locks::spinlock::Guard;
+
+fn sync<T: Sync>() {}
+
+fn main() {
+    sync::<Guard<'_, Cell<i32>>>();
+}
@@ -0,0 +1,10 @@
+// a write guard on another thread could move the Rc there with mem::swap
+use std::rc::Rc;
+
+use rust_atomic_locks::stampedlock::WriteGuard;
+
+fn send<T: Send>() {}
+
+fn main() {
+    send::<WriteGuard<'_, Rc<i32>>>();
+}
@@ -0,0 +1,44 @@
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::versioned::Versioned;
+
+const FRAMES: u64 = if cfg!(miri) { 10 } else { 1_000 };
+
+#[test]
+fn readers_follow_the_writer() {
+    let state = Versioned::new(0u64);
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| {
+                let mut seen = 0;
+                while seen < FRAMES {
+                    let frame = state.wait_newer_than(seen);
+                    // versions only go forward, and the value is always the one published with it
+                    assert!(frame.version > seen);
+                    assert_eq!(*frame.value, frame.version * 10);
+                    seen = frame.version;
+                }
+            });
+        }
+        for i in 1..=FRAMES {
+            assert_eq!(state.publish(i * 10), i);
+        }
+    });
+    assert_eq!(state.version(), FRAMES);
+}
+
+#[test]
+fn wait_for_version() {
+    let state = Versioned::new("start");
+    assert_eq!(*state.wait_for_version(0).value, "start");
+    assert!(state.wait_for_version_timeout(1, Duration::from_millis(10)).is_none());
+    thread::scope(|s| {
+        let waiter = s.spawn(|| state.wait_for_version(2));
+        state.publish("one");
+        state.publish("two");
+        let snapshot = waiter.join().unwrap();
+        assert_eq!((snapshot.version, *snapshot.value), (2, "two"));
+    });
+    assert_eq!(state.latest().version, 2);
+}
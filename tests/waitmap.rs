@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::mutex::Mutex;
+use rust_atomic_locks::waitmap::WaitMap;
+
+#[test]
+fn wakes_only_the_key() {
+    let responses = WaitMap::new();
+    let done = Mutex::new(HashSet::new());
+    let ids = if cfg!(miri) { 4 } else { 16 };
+    thread::scope(|s| {
+        for id in 0..ids {
+            let (responses, done) = (&responses, &done);
+            s.spawn(move || responses.wait_while(id, || !done.lock().contains(&id)));
+        }
+        // answered backwards, so each waiter has to sleep through the others' wakes
+        for id in (0..ids).rev() {
+            done.lock().insert(id);
+            responses.wake(&id);
+        }
+    });
+    // the keys go once nothing waits on them
+    assert!(responses.is_empty());
+}
+
+#[test]
+fn wake_all_matching() {
+    let waits: WaitMap<(u32, u32)> = WaitMap::new();
+    thread::scope(|s| {
+        for request in 0..4 {
+            let waits = &waits;
+            s.spawn(move || waits.wait((request % 2, request)));
+        }
+        while waits.len() < 4 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        // everything for connection 1
+        let mut woken = 0;
+        while woken < 2 {
+            woken += waits.wake_all_matching(|&(connection, _)| connection == 1);
+        }
+        while waits.len() > 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(waits.is_waiting(&(0, 0)) && waits.is_waiting(&(0, 2)));
+        let mut woken = 0;
+        while woken < 2 {
+            woken += waits.wake_all_matching(|_| true);
+        }
+    });
+}
+
+#[test]
+fn wait_timeout_while() {
+    let waits = WaitMap::new();
+    assert!(!waits.wait_timeout_while("never", Duration::from_millis(5), || true));
+    assert!(waits.wait_timeout_while("already", Duration::from_millis(5), || false));
+    assert!(!waits.is_waiting("never"));
+}
@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::{Acquire, Release}};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_atomic_locks::waitqueue::WaitQueue;
+
+#[test]
+fn wake_one_and_all() {
+    let queue = WaitQueue::new();
+    let ready = AtomicBool::new(false);
+    let woken = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| {
+                while !ready.load(Acquire) {
+                    queue.wait(|| !ready.load(Acquire));
+                }
+                woken.fetch_add(1, Release);
+            });
+        }
+        thread::sleep(Duration::from_millis(10));
+        ready.store(true, Release);
+        // a wake_one per waiter, or one wake_all for whoever's still parked
+        queue.wake_one();
+        queue.wake_all();
+    });
+    assert_eq!(woken.load(Acquire), 3);
+    assert!(queue.is_empty());
+    assert!(!queue.wake_one());
+}
+
+#[test]
+fn timeout_leaves_the_queue() {
+    let queue = WaitQueue::new();
+    let start = Instant::now();
+    assert!(!queue.wait_until(Some(start + Duration::from_millis(10)), || true));
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    // and not parking at all never joins it for long either
+    assert!(!queue.wait(|| false));
+    assert!(queue.is_empty());
+    assert_eq!(queue.wake_all(), 0);
+}
+
+#[test]
+fn wake_key_wakes_only_that_waiter() {
+    let queue = WaitQueue::new();
+    let turn = AtomicUsize::new(0);
+    let order = std::sync::Mutex::new(Vec::new());
+    thread::scope(|s| {
+        for key in [2, 1] {
+            let (queue, turn, order) = (&queue, &turn, &order);
+            s.spawn(move || {
+                while turn.load(Acquire) != key {
+                    queue.wait_keyed_until(key as u64, None, || turn.load(Acquire) != key);
+                }
+                order.lock().unwrap().push(key);
+            });
+        }
+        thread::sleep(Duration::from_millis(10));
+        assert!(!queue.wake_key(3));
+        for key in [1, 2] {
+            turn.store(key, Release);
+            // leaves the other waiter parked, if it's got that far
+            queue.wake_key(key as u64);
+            while order.lock().unwrap().len() < key {
+                thread::yield_now();
+            }
+        }
+    });
+    assert_eq!(*order.lock().unwrap(), [1, 2]);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,53 @@
+use std::thread;
+
+use rust_atomic_locks::mutexchannel::{Fairness, MutexChannel};
+use rust_atomic_locks::semaphore::Semaphore;
+use rust_atomic_locks::spinlock::SpinLock;
+use rust_atomic_locks::waitstrategy::{cpu_budget, CpuBudget, SpinThenPark, SpinThenYield};
+
+#[test]
+fn budget_matches_the_machine() {
+    let budget = cpu_budget();
+    // a one core machine (or container) never spins by default
+    if thread::available_parallelism().map_or(1, |n| n.get()) == 1 {
+        assert_eq!(budget, CpuBudget::SingleCore);
+    }
+    if !budget.spinning_helps() {
+        assert_eq!(SpinThenYield::default().spins, 0);
+        assert_eq!(SpinThenPark::default().spins, 0);
+    }
+
+    // and a contended lock still works whichever way lock waits
+    let counter = SpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..if cfg!(miri) { 20 } else { 10_000 } {
+                    *counter.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(*counter.lock(), if cfg!(miri) { 80 } else { 40_000 });
+}
+
+// The primitives that sleep on a condvar by default can be told to poll instead
+#[test]
+fn channel_and_semaphore() {
+    let n = if cfg!(miri) { 10 } else { 1000 };
+    let channel = MutexChannel::with_fairness(Fairness::Fifo);
+    let semaphore = Semaphore::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..n {
+                let _permit = semaphore.acquire_with(&SpinThenYield::default());
+                channel.send(i);
+            }
+        });
+        for i in 0..n {
+            assert_eq!(channel.receive_with(&SpinThenPark::default()), i);
+            drop(semaphore.acquire_with(&SpinThenYield { spins: 0 }));
+        }
+    });
+    assert_eq!(semaphore.available_permits(), 1);
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "watchdog")]
+
+use std::thread;
+use std::time::Duration;
+
+use rust_atomic_locks::spinlock::{Guard, SpinLock};
+use rust_atomic_locks::watchdog::{self, OnStarve};
+
+#[inline(never)]
+fn take_and_forget(lock: &SpinLock<i32>) {
+    Guard::leak(lock.lock());
+}
+
+// One test for the whole file, as the watchdog settings are global and the tests in a file run in parallel
+#[test]
+fn reports_the_holder() {
+    watchdog::enable(Duration::from_millis(50), OnStarve::Panic);
+
+    // a guard leaked on another thread, so the lock is never unlocked again
+    let lock = SpinLock::new(0);
+    thread::scope(|s| {
+        thread::Builder::new().name("forgetful".into()).spawn_scoped(s, || take_and_forget(&lock)).unwrap();
+    });
+    let report = thread::scope(|s| s.spawn(|| *lock.lock() += 1).join()).unwrap_err();
+    let report = report.downcast_ref::<String>().unwrap();
+    assert!(report.contains("held by thread forgetful"), "{report}");
+    // the backtrace says where it was locked
+    assert!(report.contains("take_and_forget"), "{report}");
+
+    // a lock that's only held briefly never gets reported
+    let lock = SpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..100 {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(*lock.lock(), 400);
+
+    watchdog::disable();
+}
@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use rust_atomic_locks::arc::Arc;
+use rust_atomic_locks::weakregistry::WeakRegistry;
+
+#[test]
+fn shared_while_alive() {
+    let registry = WeakRegistry::new();
+    let a = registry.get_or_create("a", || String::from("first"));
+    // while there's an Arc around, everyone gets the same value and create isn't called
+    let again = registry.get_or_create("a", || unreachable!());
+    assert!(std::ptr::eq(&*a, &*again));
+    assert_eq!(registry.get("a").as_deref().map(String::as_str), Some("first"));
+    assert!(registry.get("b").is_none());
+
+    // with the last Arc gone, the value's gone and the next call makes a new one
+    drop((a, again));
+    assert!(registry.get("a").is_none());
+    let a = registry.get_or_create("a", || String::from("second"));
+    assert_eq!(*a, "second");
+    assert_eq!(registry.len(), 1);
+}
+
+#[test]
+fn dead_entries_get_pruned() {
+    let registry = WeakRegistry::new();
+    // none of these are kept, so the map would grow forever without pruning
+    for i in 0..1000 {
+        registry.get_or_create(i, || i);
+    }
+    assert!(registry.len() < 100, "{} entries left", registry.len());
+
+    let kept = registry.get_or_create(-1, || -1);
+    registry.prune();
+    assert_eq!(registry.len(), 1);
+    assert_eq!(*registry.get(&-1).unwrap(), *kept);
+}
+
+#[test]
+fn one_value_per_key_across_threads() {
+    let registry = WeakRegistry::new();
+    let created = AtomicUsize::new(0);
+    let values: Vec<Arc<usize>> = thread::scope(|s| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| s.spawn(|| registry.get_or_create("key", || created.fetch_add(1, Relaxed))))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    // they all held on to their Arc until after the others had asked, so there was only ever one value
+    assert_eq!(created.load(Relaxed), 1);
+    assert!(values.iter().all(|v| **v == 0));
+}